@@ -41,7 +41,7 @@ impl<T: Directory + 'static> CachedDirectory<T> {
                 .property((prefix, "cache.ttl.positive"))?
                 .unwrap_or(Duration::from_secs(86400));
             let cache_ttl_negative = config
-                .property((prefix, "cache.ttl.positive"))?
+                .property((prefix, "cache.ttl.negative"))?
                 .unwrap_or_else(|| Duration::from_secs(3600));
 
             Ok(Arc::new(CachedDirectory {
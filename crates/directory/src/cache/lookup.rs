@@ -23,7 +23,9 @@
 
 use mail_send::Credentials;
 
-use crate::{DatabaseColumn, Directory, Principal};
+use crate::{
+    DatabaseColumn, Directory, Domain, DomainStatus, GroupPolicy, Principal, RecipientRoute,
+};
 
 use super::CachedDirectory;
 
@@ -40,6 +42,13 @@ impl<T: Directory> Directory for CachedDirectory<T> {
         self.inner.principal(name).await
     }
 
+    async fn principal_by_external_id(
+        &self,
+        external_id: &str,
+    ) -> crate::Result<Option<Principal>> {
+        self.inner.principal_by_external_id(external_id).await
+    }
+
     async fn emails_by_name(&self, name: &str) -> crate::Result<Vec<String>> {
         self.inner.emails_by_name(name).await
     }
@@ -63,6 +72,10 @@ impl<T: Directory> Directory for CachedDirectory<T> {
         }
     }
 
+    async fn route(&self, address: &str) -> crate::Result<RecipientRoute> {
+        self.inner.route(address).await
+    }
+
     async fn vrfy(&self, address: &str) -> crate::Result<Vec<String>> {
         self.inner.vrfy(address).await
     }
@@ -97,4 +110,48 @@ impl<T: Directory> Directory for CachedDirectory<T> {
             Ok(false)
         }
     }
+
+    async fn domain(&self, domain: &str) -> crate::Result<Option<Domain>> {
+        self.inner.domain(domain).await
+    }
+
+    async fn domains_status(&self, domains: &[&str]) -> crate::Result<Vec<DomainStatus>> {
+        // Known unknown domains are answered from the cache, the rest are
+        // resolved with a single lookup.
+        let mut result = Vec::with_capacity(domains.len());
+        let mut pending = Vec::new();
+        {
+            let mut cached_domains = self.cached_domains.lock();
+            for domain in domains {
+                if cached_domains.get(*domain) == Some(false) {
+                    result.push(DomainStatus::Unknown);
+                } else {
+                    pending.push(result.len());
+                    result.push(DomainStatus::Unknown);
+                }
+            }
+        }
+
+        if !pending.is_empty() {
+            let statuses = self
+                .inner
+                .domains_status(&pending.iter().map(|idx| domains[*idx]).collect::<Vec<_>>())
+                .await?;
+            let mut cached_domains = self.cached_domains.lock();
+            for (idx, status) in pending.into_iter().zip(statuses) {
+                if status != DomainStatus::Unknown {
+                    cached_domains.insert_pos(domains[idx].to_string());
+                } else {
+                    cached_domains.insert_neg(domains[idx].to_string());
+                }
+                result[idx] = status;
+            }
+        }
+
+        Ok(result)
+    }
+
+    async fn delivery_policy(&self, address: &str) -> crate::Result<Option<GroupPolicy>> {
+        self.inner.delivery_policy(address).await
+    }
 }
@@ -65,7 +65,7 @@ impl<T: Hash + Eq> LookupCache<T> {
         if *valid_until >= Instant::now() {
             Some(false)
         } else {
-            self.cache_pos.remove(name);
+            self.cache_neg.remove(name);
             None
         }
     }
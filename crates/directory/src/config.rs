@@ -39,9 +39,10 @@ use utils::config::{
 use ahash::AHashMap;
 
 use crate::{
-    imap::ImapDirectory, ldap::LdapDirectory, memory::MemoryDirectory, smtp::SmtpDirectory,
-    sql::SqlDirectory, AddressMapping, DirectoryConfig, DirectoryOptions, DirectorySchedule,
-    Lookup, LookupList, MatchType,
+    imap::ImapDirectory, ldap::LdapDirectory, memory::MemoryDirectory, oidc::OidcDirectory,
+    smtp::SmtpDirectory, sql::SqlDirectory, webhook::WebhookDirectory, AddressMapping,
+    DeliveryPolicy, DirectoryConfig, DirectoryOptions, DirectorySchedule, Lookup, LookupList,
+    MatchType,
 };
 
 pub trait ConfigDirectory {
@@ -75,6 +76,8 @@ impl ConfigDirectory for Config {
                     return Err(format!("Unknown directory type: {unknown:?}"));
                 }
             };
+            let directory = OidcDirectory::try_from_config(self, prefix, directory)?;
+            let directory = WebhookDirectory::try_from_config(self, prefix, directory)?;
 
             // Add queries/filters as lookups
             let is_directory = ["sql", "ldap"].contains(&protocol);
@@ -367,6 +370,11 @@ impl DirectoryOptions {
                 .value("options.superuser-group")
                 .unwrap_or("superusers")
                 .to_string(),
+            default_domain: config
+                .value((&key, "options.default-domain"))
+                .map(|domain| domain.to_lowercase()),
+            display_name_from_email: config
+                .property_or_static((&key, "options.display-name-from-email"), "false")?,
         })
     }
 }
@@ -394,6 +402,27 @@ impl AddressMapping {
                 })?,
                 mapping: config.property_require((key.as_str(), "to"))?,
             })
+        } else if config.value((key.as_str(), "delimiter")).is_some()
+            || config.values((key.as_str(), "domains")).next().is_some()
+        {
+            let delimiters = config
+                .value((key.as_str(), "delimiter"))
+                .unwrap_or("+")
+                .chars()
+                .collect::<Vec<_>>();
+            if delimiters.is_empty() {
+                return Err(format!(
+                    "Empty delimiter for address mapping {:?}.",
+                    (&key, "delimiter").as_key()
+                ));
+            }
+            Ok(AddressMapping::Delimiter {
+                delimiters,
+                domains: config
+                    .values((key.as_str(), "domains"))
+                    .map(|(_, domain)| domain.to_lowercase())
+                    .collect(),
+            })
         } else {
             Ok(AddressMapping::Disable)
         }
@@ -452,6 +481,18 @@ impl Default for LookupFormat {
     }
 }
 
+impl ParseValue for DeliveryPolicy {
+    fn parse_value(key: impl AsKey, value: &str) -> utils::config::Result<Self> {
+        DeliveryPolicy::parse(value).ok_or_else(|| {
+            format!(
+                "Invalid value for delivery policy {key:?}: {value:?}",
+                key = key.as_key(),
+                value = value
+            )
+        })
+    }
+}
+
 impl ParseValue for LookupType {
     fn parse_value(key: impl AsKey, value: &str) -> utils::config::Result<Self> {
         match value {
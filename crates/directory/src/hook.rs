@@ -0,0 +1,129 @@
+/*
+ * Copyright (c) 2023 Stalwart Labs Ltd.
+ *
+ * This file is part of Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use std::sync::Arc;
+
+use mail_send::Credentials;
+
+use crate::{
+    DatabaseColumn, Directory, Domain, DomainStatus, GroupPolicy, Principal, RecipientRoute,
+};
+
+// Intercepts authentication requests made to a directory, every other lookup
+// is forwarded unchanged to the wrapped directory.
+#[async_trait::async_trait]
+pub trait AuthenticationHook: Sync + Send {
+    async fn authenticate(
+        &self,
+        inner: &dyn Directory,
+        credentials: &Credentials<String>,
+    ) -> crate::Result<Option<Principal>>;
+
+    fn verifies_bearer_tokens(&self) -> bool {
+        false
+    }
+}
+
+pub struct HookedDirectory<T: AuthenticationHook> {
+    pub(crate) inner: Arc<dyn Directory>,
+    pub(crate) hook: T,
+}
+
+#[async_trait::async_trait]
+impl<T: AuthenticationHook> Directory for HookedDirectory<T> {
+    async fn authenticate(
+        &self,
+        credentials: &Credentials<String>,
+    ) -> crate::Result<Option<Principal>> {
+        self.hook
+            .authenticate(self.inner.as_ref(), credentials)
+            .await
+    }
+
+    async fn principal(&self, name: &str) -> crate::Result<Option<Principal>> {
+        self.inner.principal(name).await
+    }
+
+    async fn principal_by_external_id(
+        &self,
+        external_id: &str,
+    ) -> crate::Result<Option<Principal>> {
+        self.inner.principal_by_external_id(external_id).await
+    }
+
+    async fn emails_by_name(&self, name: &str) -> crate::Result<Vec<String>> {
+        self.inner.emails_by_name(name).await
+    }
+
+    async fn names_by_email(&self, address: &str) -> crate::Result<Vec<String>> {
+        self.inner.names_by_email(address).await
+    }
+
+    async fn rcpt(&self, address: &str) -> crate::Result<bool> {
+        self.inner.rcpt(address).await
+    }
+
+    async fn route(&self, address: &str) -> crate::Result<RecipientRoute> {
+        self.inner.route(address).await
+    }
+
+    async fn vrfy(&self, address: &str) -> crate::Result<Vec<String>> {
+        self.inner.vrfy(address).await
+    }
+
+    async fn expn(&self, address: &str) -> crate::Result<Vec<String>> {
+        self.inner.expn(address).await
+    }
+
+    async fn lookup(&self, query: &str, params: &[DatabaseColumn<'_>]) -> crate::Result<bool> {
+        self.inner.lookup(query, params).await
+    }
+
+    async fn query(
+        &self,
+        query: &str,
+        params: &[DatabaseColumn<'_>],
+    ) -> crate::Result<Vec<DatabaseColumn<'static>>> {
+        self.inner.query(query, params).await
+    }
+
+    async fn is_local_domain(&self, domain: &str) -> crate::Result<bool> {
+        self.inner.is_local_domain(domain).await
+    }
+
+    async fn domain(&self, domain: &str) -> crate::Result<Option<Domain>> {
+        self.inner.domain(domain).await
+    }
+
+    async fn domains_status(&self, domains: &[&str]) -> crate::Result<Vec<DomainStatus>> {
+        self.inner.domains_status(domains).await
+    }
+
+    async fn delivery_policy(&self, address: &str) -> crate::Result<Option<GroupPolicy>> {
+        self.inner.delivery_policy(address).await
+    }
+
+    fn verifies_bearer_tokens(&self) -> bool {
+        self.hook.verifies_bearer_tokens() || self.inner.verifies_bearer_tokens()
+    }
+}
@@ -24,7 +24,10 @@
 use mail_send::Credentials;
 use smtp_proto::{AUTH_CRAM_MD5, AUTH_LOGIN, AUTH_OAUTHBEARER, AUTH_PLAIN, AUTH_XOAUTH2};
 
-use crate::{DatabaseColumn, Directory, DirectoryError, Principal};
+use crate::{
+    DatabaseColumn, Directory, DirectoryError, Domain, DomainStatus, GroupPolicy, Principal,
+    RecipientRoute,
+};
 
 use super::{ImapDirectory, ImapError};
 
@@ -78,6 +81,13 @@ impl Directory for ImapDirectory {
         Err(DirectoryError::unsupported("imap", "principal"))
     }
 
+    async fn principal_by_external_id(&self, _: &str) -> crate::Result<Option<Principal>> {
+        Err(DirectoryError::unsupported(
+            "imap",
+            "principal_by_external_id",
+        ))
+    }
+
     async fn emails_by_name(&self, _: &str) -> crate::Result<Vec<String>> {
         Err(DirectoryError::unsupported("imap", "emails_by_name"))
     }
@@ -90,6 +100,10 @@ impl Directory for ImapDirectory {
         Err(DirectoryError::unsupported("imap", "rcpt"))
     }
 
+    async fn route(&self, _address: &str) -> crate::Result<RecipientRoute> {
+        Err(DirectoryError::unsupported("imap", "route"))
+    }
+
     async fn vrfy(&self, _address: &str) -> crate::Result<Vec<String>> {
         Err(DirectoryError::unsupported("imap", "vrfy"))
     }
@@ -113,4 +127,29 @@ impl Directory for ImapDirectory {
     async fn is_local_domain(&self, domain: &str) -> crate::Result<bool> {
         Ok(self.domains.contains(domain))
     }
+
+    async fn domain(&self, domain: &str) -> crate::Result<Option<Domain>> {
+        Ok(if self.domains.contains(domain) {
+            Some(Domain::new(domain))
+        } else {
+            None
+        })
+    }
+
+    async fn domains_status(&self, domains: &[&str]) -> crate::Result<Vec<DomainStatus>> {
+        Ok(domains
+            .iter()
+            .map(|domain| {
+                if self.domains.contains(*domain) {
+                    DomainStatus::Local
+                } else {
+                    DomainStatus::Unknown
+                }
+            })
+            .collect())
+    }
+
+    async fn delivery_policy(&self, _: &str) -> crate::Result<Option<GroupPolicy>> {
+        Ok(None)
+    }
 }
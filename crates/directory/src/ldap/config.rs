@@ -115,13 +115,20 @@ impl LdapDirectory {
             .attrs_email
             .extend(mappings.attr_email_address.iter().cloned());
 
+        let opt = DirectoryOptions::from_config(config, prefix.as_str())?;
+        if opt.display_name_from_email {
+            mappings
+                .attrs_principal
+                .extend(mappings.attr_email_address.iter().cloned());
+        }
+
         CachedDirectory::try_from_config(
             config,
             &prefix,
             LdapDirectory {
                 mappings,
                 pool: build_pool(config, &prefix, manager)?,
-                opt: DirectoryOptions::from_config(config, prefix.as_str())?,
+                opt,
             },
         )
     }
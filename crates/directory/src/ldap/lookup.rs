@@ -24,7 +24,10 @@
 use ldap3::{ResultEntry, Scope, SearchEntry};
 use mail_send::Credentials;
 
-use crate::{DatabaseColumn, Directory, Principal, Type};
+use crate::{
+    DatabaseColumn, Directory, DirectoryError, Domain, DomainStatus, GroupPolicy, Principal,
+    RecipientRoute, Type,
+};
 
 use super::{LdapDirectory, LdapMappings};
 
@@ -39,10 +42,15 @@ impl Directory for LdapDirectory {
             Credentials::OAuthBearer { token } => (token, token),
             Credentials::XOauth2 { username, secret } => (username, secret),
         };
-        match self
+        let mut result = self
             .find_principal(&self.mappings.filter_name.build(username))
-            .await
-        {
+            .await;
+        if let (Ok(None), Some(username)) = (&result, self.opt.qualify_login(username)) {
+            result = self
+                .find_principal(&self.mappings.filter_name.build(&username))
+                .await;
+        }
+        match result {
             Ok(Some(principal)) => {
                 if principal.verify_secret(secret).await {
                     Ok(Some(principal))
@@ -59,6 +67,13 @@ impl Directory for LdapDirectory {
             .await
     }
 
+    async fn principal_by_external_id(&self, _: &str) -> crate::Result<Option<Principal>> {
+        Err(DirectoryError::unsupported(
+            "ldap",
+            "principal_by_external_id",
+        ))
+    }
+
     async fn emails_by_name(&self, name: &str) -> crate::Result<Vec<String>> {
         let (rs, _res) = self
             .pool
@@ -173,6 +188,14 @@ impl Directory for LdapDirectory {
         }
     }
 
+    async fn route(&self, address: &str) -> crate::Result<RecipientRoute> {
+        Ok(if self.rcpt(address).await? {
+            RecipientRoute::Local
+        } else {
+            RecipientRoute::Reject
+        })
+    }
+
     async fn vrfy(&self, address: &str) -> crate::Result<Vec<String>> {
         let mut stream = self
             .pool
@@ -284,6 +307,28 @@ impl Directory for LdapDirectory {
             .map(|entry| entry.is_some())
             .map_err(|e| e.into())
     }
+
+    async fn domain(&self, domain: &str) -> crate::Result<Option<Domain>> {
+        self.is_local_domain(domain)
+            .await
+            .map(|is_local| is_local.then(|| Domain::new(domain)))
+    }
+
+    async fn domains_status(&self, domains: &[&str]) -> crate::Result<Vec<DomainStatus>> {
+        let mut result = Vec::with_capacity(domains.len());
+        for domain in domains {
+            result.push(if self.is_local_domain(domain).await? {
+                DomainStatus::Local
+            } else {
+                DomainStatus::Unknown
+            });
+        }
+        Ok(result)
+    }
+
+    async fn delivery_policy(&self, _: &str) -> crate::Result<Option<GroupPolicy>> {
+        Ok(None)
+    }
 }
 
 impl LdapDirectory {
@@ -411,15 +456,23 @@ impl LdapDirectory {
 impl LdapMappings {
     pub fn entry_to_principal(&self, entry: SearchEntry) -> Principal {
         let mut principal = Principal::default();
+        let mut description_idx = usize::MAX;
+        let mut emails = Vec::new();
         for (attr, value) in entry.attrs {
             if self.attr_name.contains(&attr) {
                 principal.name = value.into_iter().next().unwrap_or_default();
             } else if self.attr_secret.contains(&attr) {
                 principal.secrets.extend(value);
             } else if let Some(idx) = self.attr_description.iter().position(|a| a == &attr) {
-                if principal.description.is_none() || idx == 0 {
-                    principal.description = value.into_iter().next();
+                // Attributes are a fallback chain, the first non-empty one wins
+                if idx < description_idx {
+                    if let Some(description) = value.into_iter().find(|v| !v.is_empty()) {
+                        principal.description = Some(description);
+                        description_idx = idx;
+                    }
                 }
+            } else if self.attr_email_address.contains(&attr) {
+                emails.extend(value);
             } else if self.attr_groups.contains(&attr) {
                 principal.member_of.extend(value);
             } else if self.attr_quota.contains(&attr) {
@@ -435,6 +488,15 @@ impl LdapMappings {
             }
         }
 
+        // Email attributes are only requested when deriving display names from addresses
+        if principal.description.is_none() && !emails.is_empty() {
+            principal.description = emails
+                .iter()
+                .find_map(|email| email.split_once('@'))
+                .map(|(local_part, _)| local_part.to_string())
+                .filter(|local_part| !local_part.is_empty());
+        }
+
         principal
     }
 }
@@ -34,17 +34,20 @@ use ldap3::LdapError;
 use mail_send::Credentials;
 use sieve::runtime::{tests::glob::GlobPattern, Variable};
 use smtp_proto::IntoString;
-use utils::config::{cron::SimpleCron, DynValue};
+use utils::config::{cron::SimpleCron, DynValue, Rate};
 
 pub mod cache;
 pub mod config;
+pub mod hook;
 pub mod imap;
 pub mod ldap;
 pub mod memory;
+pub mod oidc;
 pub mod scheduled;
 pub mod secret;
 pub mod smtp;
 pub mod sql;
+pub mod webhook;
 
 #[derive(Debug, Default, Clone, PartialEq, Eq)]
 pub struct Principal {
@@ -54,6 +57,100 @@ pub struct Principal {
     pub description: Option<String>,
     pub quota: u32,
     pub member_of: Vec<String>,
+    pub max_message_size: Option<usize>,
+    pub max_recipients: Option<usize>,
+    pub forward_to: Vec<String>,
+    pub vacation: Option<Vacation>,
+    pub password_expired: bool,
+    pub created_at: Option<u64>,
+    pub spam_policy: Option<String>,
+    pub protocols: Option<u32>,
+    pub shard: Option<String>,
+    // Out of band contact address, never a deliverable address of the account
+    pub recovery_email: Option<String>,
+    pub identities: Vec<Identity>,
+    // Reference to the TOTP secret of accounts that must present a second
+    // factor once their password has been verified
+    pub totp_secret: Option<String>,
+}
+
+// Protocols an account may authenticate with, stored as a bitmask where
+// bit N enables the protocol with discriminant N. Accounts without a mask
+// may use all protocols.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Protocol {
+    Imap = 0,
+    Pop3 = 1,
+    Smtp = 2,
+    Jmap = 3,
+    ManageSieve = 4,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Vacation {
+    pub subject: Option<String>,
+    pub message: String,
+    pub from_date: Option<u64>,
+    pub to_date: Option<u64>,
+    pub rate: Option<Rate>,
+}
+
+// Sending identity provisioned by the directory, the primary identity is the
+// one clients should select by default.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Identity {
+    pub email: String,
+    pub name: Option<String>,
+    pub reply_to: Option<String>,
+    pub text_signature: Option<String>,
+    pub html_signature: Option<String>,
+    pub is_primary: bool,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Domain {
+    pub name: String,
+    pub dkim_selector: Option<String>,
+    pub dkim_key: Option<String>,
+    pub sender_rewrite: Option<SenderRewrite>,
+    pub spam_policy: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SenderRewrite {
+    Address(String),
+    Srs,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct GroupPolicy {
+    pub policy: DeliveryPolicy,
+    pub moderators: Vec<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DomainStatus {
+    Local,
+    Relay,
+    Unknown,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RecipientRoute {
+    Local,
+    Relay(String),
+    Reject,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum DeliveryPolicy {
+    #[default]
+    Open,
+    MembersOnly,
+    /// Messages from non-moderators are forwarded to the moderators instead
+    /// of the list members. Nothing is held for approval.
+    ForwardToModerators,
+    AnnouncementOnly,
 }
 
 #[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
@@ -73,6 +170,7 @@ pub enum DirectoryError {
     Sql(sqlx::Error),
     Imap(ImapError),
     Smtp(mail_send::Error),
+    Http(reqwest::Error),
     TimedOut,
     Unsupported,
 }
@@ -81,10 +179,15 @@ pub enum DirectoryError {
 pub trait Directory: Sync + Send {
     async fn authenticate(&self, credentials: &Credentials<String>) -> Result<Option<Principal>>;
     async fn principal(&self, name: &str) -> Result<Option<Principal>>;
+    async fn principal_by_external_id(&self, external_id: &str) -> Result<Option<Principal>>;
     async fn emails_by_name(&self, name: &str) -> Result<Vec<String>>;
     async fn names_by_email(&self, email: &str) -> Result<Vec<String>>;
     async fn is_local_domain(&self, domain: &str) -> crate::Result<bool>;
+    async fn domain(&self, domain: &str) -> crate::Result<Option<Domain>>;
+    async fn domains_status(&self, domains: &[&str]) -> crate::Result<Vec<DomainStatus>>;
+    async fn delivery_policy(&self, address: &str) -> crate::Result<Option<GroupPolicy>>;
     async fn rcpt(&self, address: &str) -> crate::Result<bool>;
+    async fn route(&self, address: &str) -> crate::Result<RecipientRoute>;
     async fn vrfy(&self, address: &str) -> Result<Vec<String>>;
     async fn expn(&self, address: &str) -> Result<Vec<String>>;
     async fn lookup(&self, query: &str, params: &[DatabaseColumn<'_>]) -> Result<bool>;
@@ -94,6 +197,12 @@ pub trait Directory: Sync + Send {
         params: &[DatabaseColumn<'_>],
     ) -> Result<Vec<DatabaseColumn<'static>>>;
 
+    // Whether bearer tokens issued by an external identity provider are verified
+    // by this directory, as opposed to being passed through to a remote server.
+    fn verifies_bearer_tokens(&self) -> bool {
+        false
+    }
+
     fn type_name(&self) -> &'static str {
         std::any::type_name::<Self>()
     }
@@ -270,6 +379,48 @@ impl Principal {
     pub fn description(&self) -> Option<&str> {
         self.description.as_deref()
     }
+
+    pub fn is_protocol_enabled(&self, protocol: Protocol) -> bool {
+        self.protocols
+            .map_or(true, |protocols| protocols & protocol.mask() != 0)
+    }
+
+    pub fn requires_second_factor(&self) -> bool {
+        self.totp_secret.is_some()
+    }
+}
+
+impl Protocol {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "imap" => Some(Protocol::Imap),
+            "pop3" => Some(Protocol::Pop3),
+            "smtp" => Some(Protocol::Smtp),
+            "jmap" => Some(Protocol::Jmap),
+            "managesieve" => Some(Protocol::ManageSieve),
+            _ => None,
+        }
+    }
+
+    pub fn mask(&self) -> u32 {
+        1 << (*self as u32)
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Protocol::Imap => "imap",
+            Protocol::Pop3 => "pop3",
+            Protocol::Smtp => "smtp",
+            Protocol::Jmap => "jmap",
+            Protocol::ManageSieve => "managesieve",
+        }
+    }
+}
+
+impl Vacation {
+    pub fn is_active(&self, now: u64) -> bool {
+        self.from_date.map_or(true, |from| now >= from) && self.to_date.map_or(true, |to| now <= to)
+    }
 }
 
 impl Debug for dyn Directory {
@@ -292,6 +443,48 @@ impl Debug for Lookup {
     }
 }
 
+impl Domain {
+    pub fn new(name: impl Into<String>) -> Self {
+        Domain {
+            name: name.into(),
+            ..Default::default()
+        }
+    }
+}
+
+impl SenderRewrite {
+    pub fn parse(value: &str) -> Option<Self> {
+        let value = value.trim();
+        if value.eq_ignore_ascii_case("srs") {
+            Some(SenderRewrite::Srs)
+        } else if value.contains('@') {
+            Some(SenderRewrite::Address(value.to_lowercase()))
+        } else {
+            None
+        }
+    }
+}
+
+impl GroupPolicy {
+    pub fn is_moderator(&self, address: &str) -> bool {
+        self.moderators
+            .iter()
+            .any(|moderator| moderator.eq_ignore_ascii_case(address))
+    }
+}
+
+impl DeliveryPolicy {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "open" => Some(DeliveryPolicy::Open),
+            "members-only" | "members" => Some(DeliveryPolicy::MembersOnly),
+            "forward-to-moderators" => Some(DeliveryPolicy::ForwardToModerators),
+            "announcement-only" | "announcement" => Some(DeliveryPolicy::AnnouncementOnly),
+            _ => None,
+        }
+    }
+}
+
 impl Type {
     pub fn to_jmap(&self) -> &'static str {
         match self {
@@ -309,6 +502,8 @@ struct DirectoryOptions {
     catch_all: AddressMapping,
     subaddressing: AddressMapping,
     superuser_group: String,
+    default_domain: Option<String>,
+    display_name_from_email: bool,
 }
 
 #[derive(Debug, Default)]
@@ -318,6 +513,10 @@ pub enum AddressMapping {
         regex: regex::Regex,
         mapping: DynValue<String>,
     },
+    Delimiter {
+        delimiters: Vec<char>,
+        domains: AHashSet<String>,
+    },
     #[default]
     Disable,
 }
@@ -421,6 +620,20 @@ impl From<mail_send::Error> for DirectoryError {
     }
 }
 
+impl From<reqwest::Error> for DirectoryError {
+    fn from(error: reqwest::Error) -> Self {
+        tracing::warn!(
+            context = "directory",
+            event = "error",
+            protocol = "http",
+            reason = %error,
+            "HTTP directory error"
+        );
+
+        DirectoryError::Http(error)
+    }
+}
+
 impl DirectoryError {
     pub fn unsupported(protocol: &str, method: &str) -> Self {
         tracing::warn!(
@@ -444,6 +657,34 @@ impl DirectoryError {
     }
 }
 
+impl DirectoryOptions {
+    // Unqualified logins are only qualified with the default domain when no
+    // principal matches the login as typed, so accounts without a domain keep working.
+    pub fn qualify_login(&self, username: &str) -> Option<String> {
+        self.default_domain
+            .as_ref()
+            .filter(|_| !username.contains('@'))
+            .map(|domain| format!("{username}@{domain}"))
+    }
+
+    // Last resort of the display name fallback chain, used when none of the
+    // configured name columns or attributes are set.
+    pub fn display_name_from<'x>(
+        &self,
+        addresses: impl IntoIterator<Item = &'x str>,
+    ) -> Option<String> {
+        if self.display_name_from_email {
+            addresses
+                .into_iter()
+                .find_map(|address| address.split_once('@'))
+                .map(|(local_part, _)| local_part.to_string())
+                .filter(|local_part| !local_part.is_empty())
+        } else {
+            None
+        }
+    }
+}
+
 impl AddressMapping {
     pub fn to_subaddress<'x, 'y: 'x>(&'x self, address: &'y str) -> Cow<'x, str> {
         match self {
@@ -466,6 +707,23 @@ impl AddressMapping {
                     return mapping.apply(regex_capture, &());
                 }
             }
+            AddressMapping::Delimiter {
+                delimiters,
+                domains,
+            } => {
+                // The detail is only stripped for directory lookups, the original
+                // recipient is still handed to Sieve so that tag-based rules work.
+                if let Some((local_part, domain_part)) = address.rsplit_once('@') {
+                    if domains.is_empty() || domains.contains(&domain_part.to_lowercase()) {
+                        if let Some((local_part, _)) = local_part.split_once(delimiters.as_slice())
+                        {
+                            if !local_part.is_empty() {
+                                return format!("{}@{}", local_part, domain_part).into();
+                            }
+                        }
+                    }
+                }
+            }
             AddressMapping::Disable => (),
         }
 
@@ -478,6 +736,15 @@ impl AddressMapping {
                 .rsplit_once('@')
                 .map(|(_, domain_part)| format!("@{}", domain_part))
                 .map(Cow::Owned),
+            // Delimiters have no meaning for catch-all addresses, only the
+            // domain restriction applies
+            AddressMapping::Delimiter { domains, .. } => address
+                .rsplit_once('@')
+                .filter(|(_, domain_part)| {
+                    domains.is_empty() || domains.contains(&domain_part.to_lowercase())
+                })
+                .map(|(_, domain_part)| format!("@{}", domain_part))
+                .map(Cow::Owned),
             AddressMapping::Custom { regex, mapping } => {
                 let mut regex_capture = Vec::new();
                 for captures in regex.captures_iter(address) {
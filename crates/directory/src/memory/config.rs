@@ -27,7 +27,7 @@ use utils::config::{utils::AsKey, Config};
 
 use crate::{
     config::{ConfigDirectory, LookupFormat},
-    Directory, DirectoryOptions, Principal, Type,
+    Directory, DirectoryOptions, GroupPolicy, Identity, Principal, Protocol, Type,
 };
 
 use super::{EmailType, MemoryDirectory};
@@ -74,6 +74,52 @@ impl MemoryDirectory {
                         .property((prefix.as_str(), "users", lookup_id, "quota"))?
                         .unwrap_or(0),
                     member_of,
+                    max_message_size: config.property((
+                        prefix.as_str(),
+                        "users",
+                        lookup_id,
+                        "max-message-size",
+                    ))?,
+                    max_recipients: config.property((
+                        prefix.as_str(),
+                        "users",
+                        lookup_id,
+                        "max-recipients",
+                    ))?,
+                    forward_to: config
+                        .values((prefix.as_str(), "users", lookup_id, "forward-to"))
+                        .map(|(_, v)| v.to_string())
+                        .collect(),
+                    vacation: None,
+                    password_expired: config
+                        .property((prefix.as_str(), "users", lookup_id, "password-expired"))?
+                        .unwrap_or(false),
+                    created_at: None,
+                    spam_policy: config
+                        .value((prefix.as_str(), "users", lookup_id, "spam-policy"))
+                        .map(|v| v.to_string()),
+                    protocols: {
+                        let mut protocols = None;
+                        for (key, value) in
+                            config.values((prefix.as_str(), "users", lookup_id, "protocols"))
+                        {
+                            let protocol = Protocol::parse(value).ok_or_else(|| {
+                                format!("Invalid protocol {value:?} for property {key:?}.")
+                            })?;
+                            protocols = Some(protocols.unwrap_or(0) | protocol.mask());
+                        }
+                        protocols
+                    },
+                    shard: config
+                        .value((prefix.as_str(), "users", lookup_id, "shard"))
+                        .map(|v| v.to_string()),
+                    recovery_email: config
+                        .value((prefix.as_str(), "users", lookup_id, "recovery-email"))
+                        .map(|v| v.to_string()),
+                    identities: parse_identities(config, (prefix.as_str(), "users", lookup_id))?,
+                    totp_secret: config
+                        .value((prefix.as_str(), "users", lookup_id, "totp-secret"))
+                        .map(|v| v.to_string()),
                 },
             );
 
@@ -100,12 +146,42 @@ impl MemoryDirectory {
                         .values((prefix.as_str(), "groups", lookup_id, "member-of"))
                         .map(|(_, v)| v.to_string())
                         .collect(),
+                    max_message_size: None,
+                    max_recipients: None,
+                    forward_to: vec![],
+                    vacation: None,
+                    password_expired: false,
+                    created_at: None,
+                    spam_policy: None,
+                    protocols: None,
+                    shard: None,
+                    recovery_email: None,
+                    identities: vec![],
+                    totp_secret: None,
                 },
             );
 
             directory.parse_emails(config, (prefix.as_str(), "groups", lookup_id), name)?;
         }
 
+        for lookup_id in config.sub_keys((prefix.as_str(), "lists")) {
+            let address = config
+                .value_require((prefix.as_str(), "lists", lookup_id, "address"))?
+                .to_lowercase();
+            directory.policies.insert(
+                address,
+                GroupPolicy {
+                    policy: config
+                        .property((prefix.as_str(), "lists", lookup_id, "policy"))?
+                        .unwrap_or_default(),
+                    moderators: config
+                        .values((prefix.as_str(), "lists", lookup_id, "moderators"))
+                        .map(|(_, v)| v.to_lowercase())
+                        .collect(),
+                },
+            );
+        }
+
         directory.domains.extend(
             config.parse_lookup_list((&prefix, "lookup.domains"), LookupFormat::default())?,
         );
@@ -159,3 +235,33 @@ impl MemoryDirectory {
         Ok(())
     }
 }
+
+fn parse_identities(config: &Config, prefix: impl AsKey) -> utils::config::Result<Vec<Identity>> {
+    let prefix = prefix.as_key();
+    let mut identities = Vec::new();
+
+    for id in config.sub_keys((prefix.as_str(), "identities")) {
+        identities.push(Identity {
+            email: config
+                .value_require((prefix.as_str(), "identities", id, "email"))?
+                .to_lowercase(),
+            name: config
+                .value((prefix.as_str(), "identities", id, "name"))
+                .map(|v| v.to_string()),
+            reply_to: config
+                .value((prefix.as_str(), "identities", id, "reply-to"))
+                .map(|v| v.to_lowercase()),
+            text_signature: config
+                .value((prefix.as_str(), "identities", id, "text-signature"))
+                .map(|v| v.to_string()),
+            html_signature: config
+                .value((prefix.as_str(), "identities", id, "html-signature"))
+                .map(|v| v.to_string()),
+            is_primary: config
+                .property((prefix.as_str(), "identities", id, "primary"))?
+                .unwrap_or(false),
+        });
+    }
+
+    Ok(identities)
+}
@@ -23,7 +23,10 @@
 
 use mail_send::Credentials;
 
-use crate::{DatabaseColumn, Directory, DirectoryError, Principal};
+use crate::{
+    DatabaseColumn, Directory, DirectoryError, Domain, DomainStatus, GroupPolicy, Principal,
+    RecipientRoute,
+};
 
 use super::{EmailType, MemoryDirectory};
 
@@ -38,7 +41,14 @@ impl Directory for MemoryDirectory {
             Credentials::OAuthBearer { token } => (token, token),
             Credentials::XOauth2 { username, secret } => (username, secret),
         };
-        match self.principals.get(username) {
+        let principal = match self.principals.get(username) {
+            Some(principal) => Some(principal),
+            None => self
+                .opt
+                .qualify_login(username)
+                .and_then(|username| self.principals.get(&username)),
+        };
+        match principal {
             Some(principal) if principal.verify_secret(secret).await => Ok(Some(principal.clone())),
             _ => Ok(None),
         }
@@ -48,6 +58,13 @@ impl Directory for MemoryDirectory {
         Ok(self.principals.get(name).cloned())
     }
 
+    async fn principal_by_external_id(&self, _: &str) -> crate::Result<Option<Principal>> {
+        Err(DirectoryError::unsupported(
+            "memory",
+            "principal_by_external_id",
+        ))
+    }
+
     async fn emails_by_name(&self, name: &str) -> crate::Result<Vec<String>> {
         let mut result = Vec::new();
         if let Some(emails) = self.names_to_email.get(name) {
@@ -100,6 +117,14 @@ impl Directory for MemoryDirectory {
                 }))
     }
 
+    async fn route(&self, address: &str) -> crate::Result<RecipientRoute> {
+        Ok(if self.rcpt(address).await? {
+            RecipientRoute::Local
+        } else {
+            RecipientRoute::Reject
+        })
+    }
+
     async fn vrfy(&self, address: &str) -> crate::Result<Vec<String>> {
         let mut result = Vec::new();
         let address = self.opt.subaddressing.to_subaddress(address);
@@ -147,4 +172,29 @@ impl Directory for MemoryDirectory {
     async fn is_local_domain(&self, domain: &str) -> crate::Result<bool> {
         Ok(self.domains.contains(domain))
     }
+
+    async fn domain(&self, domain: &str) -> crate::Result<Option<Domain>> {
+        Ok(if self.domains.contains(domain) {
+            Some(Domain::new(domain))
+        } else {
+            None
+        })
+    }
+
+    async fn domains_status(&self, domains: &[&str]) -> crate::Result<Vec<DomainStatus>> {
+        Ok(domains
+            .iter()
+            .map(|domain| {
+                if self.domains.contains(*domain) {
+                    DomainStatus::Local
+                } else {
+                    DomainStatus::Unknown
+                }
+            })
+            .collect())
+    }
+
+    async fn delivery_policy(&self, address: &str) -> crate::Result<Option<GroupPolicy>> {
+        Ok(self.policies.get(address).cloned())
+    }
 }
@@ -23,7 +23,7 @@
 
 use ahash::AHashMap;
 
-use crate::{DirectoryOptions, LookupList, Principal};
+use crate::{DirectoryOptions, GroupPolicy, LookupList, Principal};
 
 pub mod config;
 pub mod lookup;
@@ -33,6 +33,7 @@ pub struct MemoryDirectory {
     principals: AHashMap<String, Principal>,
     emails_to_names: AHashMap<String, Vec<EmailType>>,
     names_to_email: AHashMap<String, Vec<EmailType>>,
+    policies: AHashMap<String, GroupPolicy>,
     domains: LookupList,
     opt: DirectoryOptions,
 }
@@ -0,0 +1,98 @@
+/*
+ * Copyright (c) 2023 Stalwart Labs Ltd.
+ *
+ * This file is part of Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use std::{sync::Arc, time::Duration};
+
+use parking_lot::Mutex;
+use utils::config::{
+    utils::{AsKey, ParseValue},
+    Config,
+};
+
+use crate::Directory;
+
+use super::{OidcDirectory, TokenClaim, TokenVerifier};
+
+impl OidcDirectory {
+    pub fn try_from_config(
+        config: &Config,
+        prefix: impl AsKey,
+        inner: Arc<dyn Directory>,
+    ) -> utils::config::Result<Arc<dyn Directory>> {
+        let prefix = prefix.as_key();
+        if let Some(jwks_url) = config.value((&prefix, "oidc.jwks-url")) {
+            let audience = config
+                .values((&prefix, "oidc.audience"))
+                .map(|(_, s)| s.to_string())
+                .collect::<Vec<_>>();
+            if audience.is_empty() {
+                return Err(format!(
+                    "Missing audience for OIDC verifier {prefix:?}.oidc."
+                ));
+            }
+
+            Ok(Arc::new(OidcDirectory {
+                inner,
+                hook: TokenVerifier {
+                    jwks_url: jwks_url.to_string(),
+                    issuer: config
+                        .value((&prefix, "oidc.issuer"))
+                        .map(|s| s.to_string()),
+                    audience,
+                    claim: config.property_or_static((&prefix, "oidc.claim"), "email")?,
+                    leeway: config
+                        .property_or_static::<Duration>((&prefix, "oidc.leeway"), "1m")?
+                        .as_secs(),
+                    jwks_refresh: config
+                        .property_or_static((&prefix, "oidc.jwks-refresh"), "1h")?,
+                    jwks_min_refresh: config
+                        .property_or_static((&prefix, "oidc.jwks-min-refresh"), "1m")?,
+                    client: reqwest::Client::builder()
+                        .timeout(config.property_or_static((&prefix, "oidc.timeout"), "10s")?)
+                        .build()
+                        .map_err(|err| {
+                            format!("Failed to build HTTP client for {prefix:?}: {err}")
+                        })?,
+                    jwks: Mutex::new(None),
+                },
+            }))
+        } else {
+            Ok(inner)
+        }
+    }
+}
+
+impl ParseValue for TokenClaim {
+    fn parse_value(key: impl AsKey, value: &str) -> utils::config::Result<Self> {
+        match value {
+            "email" => Ok(TokenClaim::Email),
+            "sub" => Ok(TokenClaim::Subject),
+            "external-id" => Ok(TokenClaim::ExternalId),
+            _ => Err(format!(
+                "Invalid value for token claim {key:?}: {value:?}",
+                key = key.as_key(),
+                value = value
+            )),
+        }
+    }
+}
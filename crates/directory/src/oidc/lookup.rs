@@ -0,0 +1,216 @@
+/*
+ * Copyright (c) 2023 Stalwart Labs Ltd.
+ *
+ * This file is part of Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use std::{sync::Arc, time::Instant};
+
+use jsonwebtoken::{
+    jwk::{Jwk, JwkSet},
+    Algorithm, DecodingKey, Validation,
+};
+use mail_send::Credentials;
+
+use crate::{hook::AuthenticationHook, Directory, DirectoryError, Principal};
+
+use super::{Claims, TokenClaim, TokenVerifier};
+
+#[async_trait::async_trait]
+impl AuthenticationHook for TokenVerifier {
+    async fn authenticate(
+        &self,
+        inner: &dyn Directory,
+        credentials: &Credentials<String>,
+    ) -> crate::Result<Option<Principal>> {
+        let (username, token) = match credentials {
+            Credentials::OAuthBearer { token } => (None, token),
+            Credentials::XOauth2 { username, secret } => (Some(username), secret),
+            Credentials::Plain { .. } => return inner.authenticate(credentials).await,
+        };
+
+        let account = match self.verify(token).await? {
+            Some(account) => account,
+            None => return Ok(None),
+        };
+
+        let principal = match self.claim {
+            TokenClaim::Email => {
+                if let Some(name) = inner.names_by_email(&account).await?.into_iter().next() {
+                    inner.principal(&name).await?
+                } else {
+                    None
+                }
+            }
+            TokenClaim::Subject => inner.principal(&account).await?,
+            TokenClaim::ExternalId => inner.principal_by_external_id(&account).await?,
+        };
+
+        match (principal, username) {
+            (Some(principal), Some(username))
+                if !username.eq_ignore_ascii_case(&principal.name)
+                    && !username.eq_ignore_ascii_case(&account) =>
+            {
+                tracing::debug!(
+                    context = "directory",
+                    event = "invalid",
+                    protocol = "oidc",
+                    username = username.as_str(),
+                    account = account.as_str(),
+                    "Bearer token was issued for a different account"
+                );
+                Ok(None)
+            }
+            (principal, _) => Ok(principal),
+        }
+    }
+
+    fn verifies_bearer_tokens(&self) -> bool {
+        true
+    }
+}
+
+impl TokenVerifier {
+    // Validates the token signature and claims, returning the value of the
+    // configured claim used to locate the local account.
+    pub async fn verify(&self, token: &str) -> crate::Result<Option<String>> {
+        let header = match jsonwebtoken::decode_header(token) {
+            Ok(header) => header,
+            Err(err) => {
+                tracing::debug!(
+                    context = "directory",
+                    event = "invalid",
+                    protocol = "oidc",
+                    reason = %err,
+                    "Failed to decode bearer token header"
+                );
+                return Ok(None);
+            }
+        };
+
+        // Only accept asymmetric algorithms, the JWKS never contains shared secrets
+        if matches!(
+            header.alg,
+            Algorithm::HS256 | Algorithm::HS384 | Algorithm::HS512
+        ) {
+            return Ok(None);
+        }
+
+        let mut jwks = self.jwks(false).await?;
+        if header
+            .kid
+            .as_ref()
+            .map_or(false, |kid| jwks.find(kid).is_none())
+        {
+            jwks = self.jwks(true).await?;
+        }
+        let keys: Vec<&Jwk> = if let Some(kid) = &header.kid {
+            jwks.find(kid).into_iter().collect()
+        } else {
+            jwks.keys.iter().collect()
+        };
+
+        let mut validation = Validation::new(header.alg);
+        validation.leeway = self.leeway;
+        validation.set_audience(&self.audience);
+        if let Some(issuer) = &self.issuer {
+            validation.set_issuer(&[issuer]);
+            validation.set_required_spec_claims(&["exp", "aud", "iss"]);
+        } else {
+            validation.set_required_spec_claims(&["exp", "aud"]);
+        }
+
+        for key in keys {
+            let key = match DecodingKey::from_jwk(key) {
+                Ok(key) => key,
+                Err(_) => continue,
+            };
+            match jsonwebtoken::decode::<Claims>(token, &key, &validation) {
+                Ok(token) => {
+                    let claims = token.claims;
+                    return Ok(match self.claim {
+                        TokenClaim::Email if claims.email_verified == Some(true) => {
+                            claims.email.map(|email| email.to_lowercase())
+                        }
+                        TokenClaim::Email => None,
+                        TokenClaim::Subject | TokenClaim::ExternalId => claims.sub,
+                    });
+                }
+                Err(err) => {
+                    tracing::debug!(
+                        context = "directory",
+                        event = "invalid",
+                        protocol = "oidc",
+                        reason = %err,
+                        "Bearer token validation failed"
+                    );
+                }
+            }
+        }
+
+        Ok(None)
+    }
+
+    async fn jwks(&self, force_refresh: bool) -> crate::Result<Arc<JwkSet>> {
+        let cached = self.jwks.lock().clone();
+        if let Some((jwks, fetched_at)) = &cached {
+            let elapsed = fetched_at.elapsed();
+            if (!force_refresh && elapsed < self.jwks_refresh)
+                || (force_refresh && elapsed < self.jwks_min_refresh)
+            {
+                return Ok(jwks.clone());
+            }
+        }
+
+        match self.fetch_jwks().await {
+            Ok(jwks) => {
+                let jwks = Arc::new(jwks);
+                *self.jwks.lock() = Some((jwks.clone(), Instant::now()));
+                Ok(jwks)
+            }
+            Err(err) => {
+                tracing::warn!(
+                    context = "directory",
+                    event = "error",
+                    protocol = "oidc",
+                    reason = %err,
+                    "Failed to fetch JSON Web Key Set"
+                );
+
+                // Keep using the previous keys if the IdP is temporarily unreachable
+                if let Some((jwks, _)) = cached {
+                    Ok(jwks)
+                } else {
+                    Err(DirectoryError::Http(err))
+                }
+            }
+        }
+    }
+
+    async fn fetch_jwks(&self) -> reqwest::Result<JwkSet> {
+        self.client
+            .get(&self.jwks_url)
+            .send()
+            .await?
+            .error_for_status()?
+            .json::<JwkSet>()
+            .await
+    }
+}
@@ -0,0 +1,63 @@
+/*
+ * Copyright (c) 2023 Stalwart Labs Ltd.
+ *
+ * This file is part of Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use jsonwebtoken::jwk::JwkSet;
+use parking_lot::Mutex;
+
+use crate::hook::HookedDirectory;
+
+pub mod config;
+pub mod lookup;
+
+pub type OidcDirectory = HookedDirectory<TokenVerifier>;
+
+pub struct TokenVerifier {
+    jwks_url: String,
+    issuer: Option<String>,
+    audience: Vec<String>,
+    claim: TokenClaim,
+    leeway: u64,
+    jwks_refresh: Duration,
+    jwks_min_refresh: Duration,
+    client: reqwest::Client,
+    jwks: Mutex<Option<(Arc<JwkSet>, Instant)>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TokenClaim {
+    Email,
+    Subject,
+    ExternalId,
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct Claims {
+    sub: Option<String>,
+    email: Option<String>,
+    email_verified: Option<bool>,
+}
@@ -38,8 +38,14 @@ use crate::Principal;
 
 impl Principal {
     pub async fn verify_secret(&self, secret: &str) -> bool {
+        self.verify_secret_(secret, true).await
+    }
+
+    /// Verifies the secret against the stored hashes, comparing against
+    /// secrets without a recognized scheme prefix only if `allow_plaintext` is set.
+    pub(crate) async fn verify_secret_(&self, secret: &str, allow_plaintext: bool) -> bool {
         for hashed_secret in &self.secrets {
-            if verify_secret_hash(hashed_secret, secret).await {
+            if verify_secret_hash(hashed_secret, secret, allow_plaintext).await {
                 return true;
             }
         }
@@ -47,6 +53,19 @@ impl Principal {
     }
 }
 
+pub(crate) async fn verify_recovery_secret(
+    recovery_secrets: &[String],
+    secret: &str,
+    allow_plaintext: bool,
+) -> bool {
+    for hashed_secret in recovery_secrets {
+        if verify_secret_hash(hashed_secret, secret, allow_plaintext).await {
+            return true;
+        }
+    }
+    false
+}
+
 async fn verify_hash_prefix(hashed_secret: &str, secret: &str) -> bool {
     if hashed_secret.starts_with("$argon2")
         || hashed_secret.starts_with("$pbkdf2")
@@ -109,7 +128,7 @@ async fn verify_hash_prefix(hashed_secret: &str, secret: &str) -> bool {
     }
 }
 
-async fn verify_secret_hash(hashed_secret: &str, secret: &str) -> bool {
+async fn verify_secret_hash(hashed_secret: &str, secret: &str, allow_plaintext: bool) -> bool {
     if hashed_secret.starts_with('$') {
         verify_hash_prefix(hashed_secret, secret).await
     } else if hashed_secret.starts_with('_') {
@@ -209,7 +228,54 @@ async fn verify_secret_hash(hashed_secret: &str, secret: &str) -> bool {
             );
             false
         }
-    } else {
+    } else if allow_plaintext {
         hashed_secret == secret
+    } else {
+        tracing::debug!(
+            context = "directory",
+            event = "error",
+            "Plaintext password comparison is disabled"
+        );
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::verify_secret_hash;
+
+    #[tokio::test]
+    async fn verify_hash_schemes() {
+        for hashed_secret in [
+            // bcrypt
+            "$2b$05$abcdefghijklmnopqrstuuWG29KuyeAicPCJODk1zjyGvyQUU2awu",
+            "{CRYPT}$2b$05$abcdefghijklmnopqrstuuWG29KuyeAicPCJODk1zjyGvyQUU2awu",
+            // argon2id
+            "$argon2id$v=19$m=19456,t=2,p=1$c29tZXNhbHQxNmJ5dGVzIQ$tPXA8KRaMxUDqoVFOkz5qceiysamiMSLxBrcC5sAtmo",
+            "{ARGON2ID}$argon2id$v=19$m=19456,t=2,p=1$c29tZXNhbHQxNmJ5dGVzIQ$tPXA8KRaMxUDqoVFOkz5qceiysamiMSLxBrcC5sAtmo",
+            // SHA-512 crypt
+            "$6$saltsalt$qFmFH.bQmmtXzyBY0s9v7Oicd2z4XSIecDzlB5KiA2/jctKu9YterLp8wwnSq.qc.eoxqOmSuNp2xS0ktL3nh/",
+            "{CRYPT}$6$saltsalt$qFmFH.bQmmtXzyBY0s9v7Oicd2z4XSIecDzlB5KiA2/jctKu9YterLp8wwnSq.qc.eoxqOmSuNp2xS0ktL3nh/",
+        ] {
+            assert!(
+                verify_secret_hash(hashed_secret, "password", false).await,
+                "{hashed_secret}"
+            );
+            assert!(
+                !verify_secret_hash(hashed_secret, "passw0rd", true).await,
+                "{hashed_secret}"
+            );
+            assert!(
+                !verify_secret_hash(hashed_secret, hashed_secret, true).await,
+                "{hashed_secret}"
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn verify_plaintext() {
+        assert!(verify_secret_hash("password", "password", true).await);
+        assert!(!verify_secret_hash("password", "password", false).await);
+        assert!(verify_secret_hash("{PLAIN}password", "password", false).await);
     }
 }
@@ -24,7 +24,10 @@
 use mail_send::{smtp::AssertReply, Credentials};
 use smtp_proto::Severity;
 
-use crate::{DatabaseColumn, Directory, DirectoryError, Principal};
+use crate::{
+    DatabaseColumn, Directory, DirectoryError, Domain, DomainStatus, GroupPolicy, Principal,
+    RecipientRoute,
+};
 
 use super::{SmtpClient, SmtpDirectory};
 
@@ -41,6 +44,13 @@ impl Directory for SmtpDirectory {
         Err(DirectoryError::unsupported("smtp", "principal"))
     }
 
+    async fn principal_by_external_id(&self, _: &str) -> crate::Result<Option<Principal>> {
+        Err(DirectoryError::unsupported(
+            "smtp",
+            "principal_by_external_id",
+        ))
+    }
+
     async fn emails_by_name(&self, _: &str) -> crate::Result<Vec<String>> {
         Err(DirectoryError::unsupported("smtp", "emails_by_name"))
     }
@@ -77,6 +87,14 @@ impl Directory for SmtpDirectory {
         }
     }
 
+    async fn route(&self, address: &str) -> crate::Result<RecipientRoute> {
+        Ok(if self.rcpt(address).await? {
+            RecipientRoute::Local
+        } else {
+            RecipientRoute::Reject
+        })
+    }
+
     async fn vrfy(&self, address: &str) -> crate::Result<Vec<String>> {
         self.pool
             .get()
@@ -108,6 +126,31 @@ impl Directory for SmtpDirectory {
     async fn is_local_domain(&self, domain: &str) -> crate::Result<bool> {
         Ok(self.domains.contains(domain))
     }
+
+    async fn domain(&self, domain: &str) -> crate::Result<Option<Domain>> {
+        Ok(if self.domains.contains(domain) {
+            Some(Domain::new(domain))
+        } else {
+            None
+        })
+    }
+
+    async fn domains_status(&self, domains: &[&str]) -> crate::Result<Vec<DomainStatus>> {
+        Ok(domains
+            .iter()
+            .map(|domain| {
+                if self.domains.contains(*domain) {
+                    DomainStatus::Local
+                } else {
+                    DomainStatus::Unknown
+                }
+            })
+            .collect())
+    }
+
+    async fn delivery_policy(&self, _: &str) -> crate::Result<Option<GroupPolicy>> {
+        Ok(None)
+    }
 }
 
 impl SmtpClient {
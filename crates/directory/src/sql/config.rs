@@ -21,14 +21,24 @@
  * for more details.
 */
 
-use std::sync::Arc;
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
 
-use sqlx::any::{install_default_drivers, AnyPoolOptions};
+use hickory_resolver::TokioAsyncResolver;
+use sqlx::{
+    any::{install_default_drivers, AnyPoolOptions},
+    Any, Pool,
+};
 use utils::config::{utils::AsKey, Config};
 
 use crate::{cache::CachedDirectory, Directory, DirectoryOptions};
 
-use super::{SqlDirectory, SqlMappings};
+use super::{
+    srv::SrvDiscovery, tls::TlsVerifier, AddressNormalization, SqlDialect, SqlDirectory,
+    SqlMappings,
+};
 
 impl SqlDirectory {
     pub fn from_config(
@@ -36,82 +46,286 @@ impl SqlDirectory {
         prefix: impl AsKey,
     ) -> utils::config::Result<Arc<dyn Directory>> {
         let prefix = prefix.as_key();
-        let address = config.value_require((&prefix, "address"))?;
+        CachedDirectory::try_from_config(config, &prefix, SqlDirectory::open(config, &prefix)?)
+    }
+
+    // Builds the directory without the lookup cache in front of it
+    pub fn open(config: &Config, prefix: &str) -> utils::config::Result<SqlDirectory> {
+        let address = config.value_require((prefix, "address"))?;
         install_default_drivers();
-        let pool = AnyPoolOptions::new()
-            .max_connections(
-                config
-                    .property((&prefix, "pool.max-connections"))?
-                    .unwrap_or(10),
-            )
-            .min_connections(
-                config
-                    .property((&prefix, "pool.min-connections"))?
-                    .unwrap_or(0),
-            )
-            .idle_timeout(config.property((&prefix, "pool.idle-timeout"))?)
-            .connect_lazy(address)
-            .map_err(|err| format!("Failed to create connection pool for {address:?}: {err}"))?;
+        let pool = build_pool(config, prefix, address)?;
 
+        let srv = if let Some(record) = config.value((prefix, "srv")) {
+            Some(SrvDiscovery {
+                record: record.to_string(),
+                address: address.to_string(),
+                resolver: TokioAsyncResolver::tokio_from_system_conf().map_err(|err| {
+                    format!("Failed to create DNS resolver for SRV record {record:?}: {err}")
+                })?,
+                valid_until: Instant::now().into(),
+            })
+        } else {
+            None
+        };
+
+        // Queries are written with '?' placeholders and rewritten for the backend
+        let dialect = SqlDialect::from_address(address);
         let mappings = SqlMappings {
-            query_name: config
-                .value((&prefix, "query.name"))
+            query_name: dialect
+                .placeholders(config.value((prefix, "query.name")).unwrap_or_default()),
+            query_members: dialect
+                .placeholders(config.value((prefix, "query.members")).unwrap_or_default()),
+            query_recipients: dialect.placeholders(
+                config
+                    .value((prefix, "query.recipients"))
+                    .unwrap_or_default(),
+            ),
+            query_emails: dialect
+                .placeholders(config.value((prefix, "query.emails")).unwrap_or_default()),
+            query_verify: dialect
+                .placeholders(config.value((prefix, "query.verify")).unwrap_or_default()),
+            query_expand: dialect
+                .placeholders(config.value((prefix, "query.expand")).unwrap_or_default()),
+            query_delivery_policy: dialect.placeholders(
+                config
+                    .value((prefix, "query.delivery-policy"))
+                    .unwrap_or_default(),
+            ),
+            query_forwards: dialect
+                .placeholders(config.value((prefix, "query.forwards")).unwrap_or_default()),
+            query_vacation: dialect
+                .placeholders(config.value((prefix, "query.vacation")).unwrap_or_default()),
+            query_identities: dialect.placeholders(
+                config
+                    .value((prefix, "query.identities"))
+                    .unwrap_or_default(),
+            ),
+            query_by_external_id: dialect.placeholders(
+                config
+                    .value((prefix, "query.by-external-id"))
+                    .unwrap_or_default(),
+            ),
+            query_domains: dialect
+                .placeholders(config.value((prefix, "query.domains")).unwrap_or_default()),
+            query_domains_batch: config
+                .value((prefix, "query.domains-batch"))
                 .unwrap_or_default()
                 .to_string(),
-            query_members: config
-                .value((&prefix, "query.members"))
+            column_name: config
+                .value((prefix, "columns.name"))
                 .unwrap_or_default()
                 .to_string(),
-            query_recipients: config
-                .value((&prefix, "query.recipients"))
+            column_description: config
+                .values((prefix, "columns.description"))
+                .map(|(_, v)| v.to_string())
+                .collect(),
+            column_secret: config
+                .value((prefix, "columns.secret"))
                 .unwrap_or_default()
                 .to_string(),
-            query_emails: config
-                .value((&prefix, "query.emails"))
+            column_recovery_secret: config
+                .value((prefix, "columns.recovery-secret"))
                 .unwrap_or_default()
                 .to_string(),
-            query_verify: config
-                .value((&prefix, "query.verify"))
+            column_quota: config
+                .value((prefix, "columns.quota"))
                 .unwrap_or_default()
                 .to_string(),
-            query_expand: config
-                .value((&prefix, "query.expand"))
+            column_type: config
+                .value((prefix, "columns.type"))
                 .unwrap_or_default()
                 .to_string(),
-            query_domains: config
-                .value((&prefix, "query.domains"))
+            column_max_message_size: config
+                .value((prefix, "columns.max-message-size"))
                 .unwrap_or_default()
                 .to_string(),
-            column_name: config
-                .value((&prefix, "columns.name"))
+            column_max_recipients: config
+                .value((prefix, "columns.max-recipients"))
                 .unwrap_or_default()
                 .to_string(),
-            column_description: config
-                .value((&prefix, "columns.description"))
+            column_password_expired: config
+                .value((prefix, "columns.password-expired"))
                 .unwrap_or_default()
                 .to_string(),
-            column_secret: config
-                .value((&prefix, "columns.secret"))
+            column_created_at: config
+                .value((prefix, "columns.created-at"))
                 .unwrap_or_default()
                 .to_string(),
-            column_quota: config
-                .value((&prefix, "columns.quota"))
+            column_protocols: config
+                .value((prefix, "columns.protocols"))
                 .unwrap_or_default()
                 .to_string(),
-            column_type: config
-                .value((&prefix, "columns.type"))
+            column_shard: config
+                .value((prefix, "columns.shard"))
+                .unwrap_or_default()
+                .to_string(),
+            column_recovery_email: config
+                .value((prefix, "columns.recovery-email"))
+                .unwrap_or_default()
+                .to_string(),
+            column_totp_secret: config
+                .value((prefix, "columns.totp-secret"))
+                .unwrap_or_default()
+                .to_string(),
+            column_dkim_selector: config
+                .value((prefix, "columns.dkim-selector"))
+                .unwrap_or_default()
+                .to_string(),
+            column_dkim_key: config
+                .value((prefix, "columns.dkim-key"))
+                .unwrap_or_default()
+                .to_string(),
+            column_spam_policy: config
+                .value((prefix, "columns.spam-policy"))
+                .unwrap_or_default()
+                .to_string(),
+            column_sender_rewrite: config
+                .value((prefix, "columns.sender-rewrite"))
+                .unwrap_or_default()
+                .to_string(),
+            column_delivery_policy: config
+                .value((prefix, "columns.delivery-policy"))
+                .unwrap_or_default()
+                .to_string(),
+            column_moderator: config
+                .value((prefix, "columns.moderator"))
+                .unwrap_or_default()
+                .to_string(),
+            column_relay_host: config
+                .value((prefix, "columns.relay-host"))
+                .unwrap_or_default()
+                .to_string(),
+            column_vacation_subject: config
+                .value((prefix, "columns.vacation-subject"))
+                .unwrap_or_default()
+                .to_string(),
+            column_vacation_message: config
+                .value((prefix, "columns.vacation-message"))
+                .unwrap_or_default()
+                .to_string(),
+            column_vacation_from: config
+                .value((prefix, "columns.vacation-from"))
+                .unwrap_or_default()
+                .to_string(),
+            column_vacation_to: config
+                .value((prefix, "columns.vacation-to"))
+                .unwrap_or_default()
+                .to_string(),
+            column_vacation_rate: config
+                .value((prefix, "columns.vacation-rate"))
+                .unwrap_or_default()
+                .to_string(),
+            column_identity_email: config
+                .value((prefix, "columns.identity-email"))
+                .unwrap_or_default()
+                .to_string(),
+            column_identity_name: config
+                .value((prefix, "columns.identity-name"))
+                .unwrap_or_default()
+                .to_string(),
+            column_identity_reply_to: config
+                .value((prefix, "columns.identity-reply-to"))
+                .unwrap_or_default()
+                .to_string(),
+            column_identity_text_signature: config
+                .value((prefix, "columns.identity-text-signature"))
+                .unwrap_or_default()
+                .to_string(),
+            column_identity_html_signature: config
+                .value((prefix, "columns.identity-html-signature"))
+                .unwrap_or_default()
+                .to_string(),
+            column_identity_primary: config
+                .value((prefix, "columns.identity-primary"))
                 .unwrap_or_default()
                 .to_string(),
         };
 
-        CachedDirectory::try_from_config(
-            config,
-            &prefix,
-            SqlDirectory {
-                pool,
-                mappings,
-                opt: DirectoryOptions::from_config(config, prefix.as_str())?,
+        Ok(SqlDirectory {
+            pool,
+            mappings,
+            dialect,
+            opt: DirectoryOptions::from_config(config, prefix)?,
+            normalize: AddressNormalization {
+                lowercase_local_part: config
+                    .property((prefix, "options.lowercase-local-part"))?
+                    .unwrap_or(false),
+                idn_to_ascii: config
+                    .property((prefix, "options.idn-to-ascii"))?
+                    .unwrap_or(false),
             },
+            srv,
+            allow_recovery: config
+                .property((prefix, "options.allow-recovery"))?
+                .unwrap_or(false),
+            allow_plaintext: config
+                .property((prefix, "options.allow-plaintext"))?
+                .unwrap_or(false),
+            max_group_depth: config
+                .property::<usize>((prefix, "options.max-group-depth"))?
+                .unwrap_or(5)
+                .max(1),
+        })
+    }
+}
+
+// Pool defaults match the sqlx defaults
+fn build_pool(config: &Config, prefix: &str, address: &str) -> utils::config::Result<Pool<Any>> {
+    let tls = Arc::new(TlsVerifier::new(
+        config.property_or_static((prefix, "tls.require"), "false")?,
+    ));
+    AnyPoolOptions::new()
+        .max_connections(config.property_or_static((prefix, "pool.max-connections"), "10")?)
+        .min_connections(config.property_or_static((prefix, "pool.min-connections"), "0")?)
+        .acquire_timeout(
+            config.property_or_static::<Duration>((prefix, "pool.acquire-timeout"), "30s")?,
         )
+        .idle_timeout(config.property_or_static::<Duration>((prefix, "pool.idle-timeout"), "10m")?)
+        .after_connect(move |conn, _| {
+            let tls = tls.clone();
+            Box::pin(async move { tls.verify(conn).await })
+        })
+        .connect_lazy(address)
+        .map_err(|err| format!("Failed to create connection pool for {address:?}: {err}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use sqlx::any::install_default_drivers;
+    use utils::config::Config;
+
+    use super::build_pool;
+
+    #[tokio::test]
+    async fn pool_options() {
+        install_default_drivers();
+
+        let config = Config::new(concat!(
+            "[directory.\"sql\".pool]\n",
+            "max-connections = 25\n",
+            "min-connections = 2\n",
+            "acquire-timeout = \"5s\"\n",
+            "idle-timeout = \"1m\"\n",
+        ))
+        .unwrap();
+        let options = build_pool(&config, "directory.sql", "sqlite::memory:")
+            .unwrap()
+            .options()
+            .clone();
+        assert_eq!(options.get_max_connections(), 25);
+        assert_eq!(options.get_min_connections(), 2);
+        assert_eq!(options.get_acquire_timeout(), Duration::from_secs(5));
+        assert_eq!(options.get_idle_timeout(), Some(Duration::from_secs(60)));
+
+        // Unset options keep the sqlx defaults
+        let config = Config::new("").unwrap();
+        let pool = build_pool(&config, "directory.sql", "sqlite::memory:").unwrap();
+        let options = pool.options();
+        assert_eq!(options.get_max_connections(), 10);
+        assert_eq!(options.get_min_connections(), 0);
+        assert_eq!(options.get_acquire_timeout(), Duration::from_secs(30));
+        assert_eq!(options.get_idle_timeout(), Some(Duration::from_secs(600)));
     }
 }
@@ -0,0 +1,110 @@
+/*
+ * Copyright (c) 2023 Stalwart Labs Ltd.
+ *
+ * This file is part of Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use std::time::{Duration, Instant};
+
+use super::SqlDirectory;
+
+const PING_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SqlHealth {
+    pub status: SqlHealthStatus,
+    pub latency: Duration,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SqlHealthStatus {
+    Healthy,
+    Timeout,
+    Unavailable(String),
+}
+
+impl SqlDirectory {
+    // Runs a trivial query on a pooled connection to check that the server is reachable.
+    // 'SELECT 1' is understood by all supported dialects and takes no parameters.
+    pub async fn ping(&self) -> SqlHealth {
+        let time = Instant::now();
+        let result = tokio::time::timeout(
+            PING_TIMEOUT,
+            sqlx::query("SELECT 1").execute(self.pool().await),
+        )
+        .await;
+        let latency = time.elapsed();
+
+        let status = match result {
+            Ok(Ok(_)) => SqlHealthStatus::Healthy,
+            Ok(Err(err)) => SqlHealthStatus::Unavailable(err.to_string()),
+            Err(_) => SqlHealthStatus::Timeout,
+        };
+        if status != SqlHealthStatus::Healthy {
+            tracing::debug!(
+                context = "directory",
+                event = "ping-failed",
+                status = ?status,
+                latency = latency.as_millis() as u64,
+                "SQL directory health check failed"
+            );
+        }
+
+        SqlHealth { status, latency }
+    }
+}
+
+impl SqlHealth {
+    pub fn is_healthy(&self) -> bool {
+        self.status == SqlHealthStatus::Healthy
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use utils::config::Config;
+
+    use crate::sql::SqlDirectory;
+
+    use super::SqlHealthStatus;
+
+    #[tokio::test]
+    async fn ping() {
+        let config = Config::new(concat!(
+            "[directory.\"sql\"]\n",
+            "type = \"sql\"\n",
+            "address = \"sqlite::memory:\"\n",
+        ))
+        .unwrap();
+        let directory = SqlDirectory::open(&config, "directory.sql").unwrap();
+
+        let health = directory.ping().await;
+        assert!(health.is_healthy(), "{health:?}");
+
+        // A closed pool reports the failure instead of erroring out
+        directory.pool.close().await;
+        let health = directory.ping().await;
+        assert!(!health.is_healthy());
+        assert!(
+            matches!(health.status, SqlHealthStatus::Unavailable(_)),
+            "{health:?}"
+        );
+    }
+}
@@ -21,11 +21,17 @@
  * for more details.
 */
 
+use ahash::{AHashMap, AHashSet};
 use futures::TryStreamExt;
 use mail_send::Credentials;
 use sqlx::{any::AnyRow, postgres::any::AnyTypeInfoKind, Column, Row};
+use utils::config::{utils::ParseValue, Rate};
 
-use crate::{DatabaseColumn, Directory, Principal, Type};
+use crate::{
+    secret::verify_recovery_secret, DatabaseColumn, DeliveryPolicy, Directory, Domain,
+    DomainStatus, GroupPolicy, Identity, Principal, Protocol, RecipientRoute, SenderRewrite, Type,
+    Vacation,
+};
 
 use super::{SqlDirectory, SqlMappings};
 
@@ -41,41 +47,58 @@ impl Directory for SqlDirectory {
             Credentials::XOauth2 { username, secret } => (username, secret),
         };
 
-        match self.principal(username).await {
-            Ok(Some(principal)) if principal.verify_secret(secret).await => Ok(Some(principal)),
-            Ok(_) => Ok(None),
-            Err(err) => Err(err),
+        let principal = match self.principal_(username).await? {
+            Some(principal) => Some(principal),
+            None => match self.opt.qualify_login(username) {
+                Some(username) => self.principal_(&username).await?,
+                None => None,
+            },
+        };
+
+        match principal {
+            Some((principal, _))
+                if principal.verify_secret_(secret, self.allow_plaintext).await =>
+            {
+                Ok(Some(principal))
+            }
+            Some((principal, recovery_secrets))
+                if self.allow_recovery
+                    && verify_recovery_secret(&recovery_secrets, secret, self.allow_plaintext)
+                        .await =>
+            {
+                tracing::warn!(
+                    context = "directory",
+                    event = "recovery-secret-used",
+                    account = principal.name.as_str(),
+                    "Account accessed using its recovery credential"
+                );
+                Ok(Some(principal))
+            }
+            _ => Ok(None),
         }
     }
 
     async fn principal(&self, name: &str) -> crate::Result<Option<Principal>> {
-        let result = sqlx::query(&self.mappings.query_name)
-            .bind(name)
-            .fetch(&self.pool)
-            .try_next()
-            .await?;
-        if let Some(row) = result {
-            // Map row to principal
-            let mut principal = self.mappings.row_to_principal(row)?;
-
-            // Obtain members
-            principal.member_of = sqlx::query_scalar::<_, String>(&self.mappings.query_members)
-                .bind(name)
-                .fetch(&self.pool)
-                .try_collect::<Vec<_>>()
-                .await?;
+        self.principal_(name)
+            .await
+            .map(|principal| principal.map(|(principal, _)| principal))
+    }
 
-            // Check whether the user is a superuser
-            if let Some(idx) = principal
-                .member_of
-                .iter()
-                .position(|group| group.eq_ignore_ascii_case(&self.opt.superuser_group))
-            {
-                principal.member_of.swap_remove(idx);
-                principal.typ = Type::Superuser;
-            }
+    async fn principal_by_external_id(
+        &self,
+        external_id: &str,
+    ) -> crate::Result<Option<Principal>> {
+        if self.mappings.query_by_external_id.is_empty() {
+            return Ok(None);
+        }
 
-            Ok(Some(principal))
+        if let Some(name) = sqlx::query_scalar::<_, String>(&self.mappings.query_by_external_id)
+            .bind(external_id)
+            .fetch(self.pool().await)
+            .try_next()
+            .await?
+        {
+            self.principal(&name).await
         } else {
             Ok(None)
         }
@@ -83,17 +106,19 @@ impl Directory for SqlDirectory {
 
     async fn emails_by_name(&self, name: &str) -> crate::Result<Vec<String>> {
         sqlx::query_scalar::<_, String>(&self.mappings.query_emails)
-            .bind(name)
-            .fetch(&self.pool)
+            .bind(self.normalize.login(name).as_ref())
+            .fetch(self.pool().await)
             .try_collect::<Vec<_>>()
             .await
             .map_err(Into::into)
     }
 
     async fn names_by_email(&self, address: &str) -> crate::Result<Vec<String>> {
+        let address = self.normalize.address(address);
+        let address = address.as_ref();
         let ids = sqlx::query_scalar::<_, String>(&self.mappings.query_recipients)
             .bind(self.opt.subaddressing.to_subaddress(address).as_ref())
-            .fetch(&self.pool)
+            .fetch(self.pool().await)
             .try_collect::<Vec<_>>()
             .await?;
         if !ids.is_empty() {
@@ -101,7 +126,7 @@ impl Directory for SqlDirectory {
         } else if let Some(address) = self.opt.catch_all.to_catch_all(address) {
             sqlx::query_scalar::<_, String>(&self.mappings.query_recipients)
                 .bind(address.as_ref())
-                .fetch(&self.pool)
+                .fetch(self.pool().await)
                 .try_collect::<Vec<_>>()
                 .await
                 .map_err(Into::into)
@@ -111,9 +136,11 @@ impl Directory for SqlDirectory {
     }
 
     async fn rcpt(&self, address: &str) -> crate::Result<bool> {
+        let address = self.normalize.address(address);
+        let address = address.as_ref();
         let result = sqlx::query(&self.mappings.query_recipients)
             .bind(self.opt.subaddressing.to_subaddress(address).as_ref())
-            .fetch(&self.pool)
+            .fetch(self.pool().await)
             .try_next()
             .await;
         match result {
@@ -122,7 +149,7 @@ impl Directory for SqlDirectory {
                 if let Some(address) = self.opt.catch_all.to_catch_all(address) {
                     sqlx::query(&self.mappings.query_recipients)
                         .bind(address.as_ref())
-                        .fetch(&self.pool)
+                        .fetch(self.pool().await)
                         .try_next()
                         .await
                         .map(|id| id.is_some())
@@ -136,22 +163,50 @@ impl Directory for SqlDirectory {
         }
     }
 
+    async fn route(&self, address: &str) -> crate::Result<RecipientRoute> {
+        let address = self.normalize.address(address);
+        let address = address.as_ref();
+        if let Some(row) = sqlx::query(&self.mappings.query_recipients)
+            .bind(self.opt.subaddressing.to_subaddress(address).as_ref())
+            .fetch(self.pool().await)
+            .try_next()
+            .await?
+        {
+            Ok(self.mappings.row_to_route(row))
+        } else if let Some(address) = self.opt.catch_all.to_catch_all(address) {
+            Ok(sqlx::query(&self.mappings.query_recipients)
+                .bind(address.as_ref())
+                .fetch(self.pool().await)
+                .try_next()
+                .await?
+                .map_or(RecipientRoute::Reject, |row| {
+                    self.mappings.row_to_route(row)
+                }))
+        } else {
+            Ok(RecipientRoute::Reject)
+        }
+    }
+
     async fn vrfy(&self, address: &str) -> crate::Result<Vec<String>> {
+        let address = self.normalize.address(address);
+        let address = address.as_ref();
         sqlx::query_scalar::<_, String>(&self.mappings.query_verify)
             .bind(self.opt.subaddressing.to_subaddress(address).as_ref())
-            .fetch(&self.pool)
+            .fetch(self.pool().await)
             .try_collect::<Vec<_>>()
             .await
             .map_err(Into::into)
     }
 
     async fn expn(&self, address: &str) -> crate::Result<Vec<String>> {
-        sqlx::query_scalar::<_, String>(&self.mappings.query_expand)
-            .bind(self.opt.subaddressing.to_subaddress(address).as_ref())
-            .fetch(&self.pool)
-            .try_collect::<Vec<_>>()
-            .await
-            .map_err(Into::into)
+        let address = self.normalize.address(address);
+        let address = address.as_ref();
+        self.expand_nested(
+            &self.mappings.query_expand,
+            self.opt.subaddressing.to_subaddress(address).as_ref(),
+            false,
+        )
+        .await
     }
 
     async fn lookup(&self, query: &str, params: &[DatabaseColumn<'_>]) -> crate::Result<bool> {
@@ -200,16 +255,258 @@ impl Directory for SqlDirectory {
 
     async fn is_local_domain(&self, domain: &str) -> crate::Result<bool> {
         sqlx::query(&self.mappings.query_domains)
-            .bind(domain)
-            .fetch(&self.pool)
+            .bind(self.normalize.domain(domain).as_ref())
+            .fetch(self.pool().await)
             .try_next()
             .await
             .map(|id| id.is_some())
             .map_err(Into::into)
     }
+
+    async fn domain(&self, domain: &str) -> crate::Result<Option<Domain>> {
+        let domain = self.normalize.domain(domain);
+        let domain = domain.as_ref();
+        if let Some(row) = sqlx::query(&self.mappings.query_domains)
+            .bind(domain)
+            .fetch(self.pool().await)
+            .try_next()
+            .await?
+        {
+            Ok(Some(self.mappings.row_to_domain(domain, row)))
+        } else {
+            Ok(None)
+        }
+    }
+
+    async fn domains_status(&self, domains: &[&str]) -> crate::Result<Vec<DomainStatus>> {
+        let domains = domains
+            .iter()
+            .map(|domain| self.normalize.domain(domain))
+            .collect::<Vec<_>>();
+        if self.mappings.query_domains_batch.is_empty() {
+            let mut result = Vec::with_capacity(domains.len());
+            for domain in domains {
+                result.push(
+                    if let Some(row) = sqlx::query(&self.mappings.query_domains)
+                        .bind(domain.as_ref())
+                        .fetch(self.pool().await)
+                        .try_next()
+                        .await?
+                    {
+                        self.mappings.row_to_domain_status(&row)
+                    } else {
+                        DomainStatus::Unknown
+                    },
+                );
+            }
+            return Ok(result);
+        } else if domains.is_empty() {
+            return Ok(vec![]);
+        }
+
+        // Expand the placeholder to one parameter per domain
+        let query = self
+            .dialect
+            .placeholders(&self.mappings.query_domains_batch.replacen(
+                '?',
+                &vec!["?"; domains.len()].join(", "),
+                1,
+            ));
+        let mut query = sqlx::query(&query);
+        for domain in &domains {
+            query = query.bind(domain.as_ref());
+        }
+
+        let mut found = AHashMap::new();
+        let mut rows = query.fetch(self.pool().await);
+        while let Some(row) = rows.try_next().await? {
+            if let Ok(name) = row.try_get::<String, _>(0) {
+                found.insert(
+                    name.to_lowercase(),
+                    self.mappings.row_to_domain_status(&row),
+                );
+            }
+        }
+
+        Ok(domains
+            .iter()
+            .map(|domain| {
+                found
+                    .get(domain.as_ref())
+                    .copied()
+                    .unwrap_or(DomainStatus::Unknown)
+            })
+            .collect())
+    }
+
+    async fn delivery_policy(&self, address: &str) -> crate::Result<Option<GroupPolicy>> {
+        if self.mappings.query_delivery_policy.is_empty() {
+            return Ok(None);
+        }
+
+        let rows = sqlx::query(&self.mappings.query_delivery_policy)
+            .bind(self.normalize.address(address).as_ref())
+            .fetch(self.pool().await)
+            .try_collect::<Vec<_>>()
+            .await?;
+        if !rows.is_empty() {
+            let mut policy = GroupPolicy::default();
+            for row in rows {
+                self.mappings.row_to_policy(row, &mut policy);
+            }
+            Ok(Some(policy))
+        } else {
+            Ok(None)
+        }
+    }
 }
 
 impl SqlDirectory {
+    async fn principal_(&self, name: &str) -> crate::Result<Option<(Principal, Vec<String>)>> {
+        let name = self.normalize.login(name);
+        let name = name.as_ref();
+        let result = sqlx::query(&self.mappings.query_name)
+            .bind(name)
+            .fetch(self.pool().await)
+            .try_next()
+            .await?;
+        if let Some(row) = result {
+            // Recovery secrets are kept apart from the principal so they are never cached
+            let recovery_secrets = self.mappings.row_to_recovery_secrets(&row);
+
+            // Map row to principal
+            let mut principal = self.mappings.row_to_principal(row)?;
+
+            // Obtain members
+            principal.member_of = self
+                .expand_nested(&self.mappings.query_members, name, true)
+                .await?;
+
+            // Check whether the user is a superuser
+            if let Some(idx) = principal
+                .member_of
+                .iter()
+                .position(|group| group.eq_ignore_ascii_case(&self.opt.superuser_group))
+            {
+                principal.member_of.swap_remove(idx);
+                principal.typ = Type::Superuser;
+            }
+
+            // Derive the display name from the account's address
+            if principal.description.is_none() {
+                principal.description = if principal.name.contains('@') {
+                    self.opt.display_name_from([principal.name.as_str()])
+                } else if self.opt.display_name_from_email && !self.mappings.query_emails.is_empty()
+                {
+                    self.opt.display_name_from(
+                        self.emails_by_name(name).await?.iter().map(|e| e.as_str()),
+                    )
+                } else {
+                    None
+                };
+            }
+
+            // Obtain forwarding addresses
+            if !self.mappings.query_forwards.is_empty() {
+                principal.forward_to =
+                    sqlx::query_scalar::<_, String>(&self.mappings.query_forwards)
+                        .bind(name)
+                        .fetch(self.pool().await)
+                        .try_collect::<Vec<_>>()
+                        .await?;
+            }
+
+            // Obtain vacation settings
+            if !self.mappings.query_vacation.is_empty() {
+                if let Some(row) = sqlx::query(&self.mappings.query_vacation)
+                    .bind(name)
+                    .fetch(self.pool().await)
+                    .try_next()
+                    .await?
+                {
+                    principal.vacation = self.mappings.row_to_vacation(row);
+                }
+            }
+
+            // Obtain sending identities
+            if !self.mappings.query_identities.is_empty() {
+                principal.identities = sqlx::query(&self.mappings.query_identities)
+                    .bind(name)
+                    .fetch(self.pool().await)
+                    .try_collect::<Vec<_>>()
+                    .await?
+                    .into_iter()
+                    .filter_map(|row| self.mappings.row_to_identity(row))
+                    .collect();
+            }
+
+            Ok(Some((principal, recovery_secrets)))
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Resolves `root` with `query` and keeps expanding the results for up to
+    /// `max_group_depth` levels, so that groups nested inside groups (or lists
+    /// inside lists) are followed. When the query also returns the type column,
+    /// only entries of type group or list are expanded further; otherwise an
+    /// entry is treated as a group when expanding it yields members. Entries
+    /// already visited are skipped, which breaks membership cycles.
+    ///
+    /// When `keep_groups` is set every entry found is returned (transitive group
+    /// membership), otherwise nested groups are replaced by their members.
+    async fn expand_nested(
+        &self,
+        query: &str,
+        root: &str,
+        keep_groups: bool,
+    ) -> crate::Result<Vec<String>> {
+        let mut visited = AHashSet::from_iter([root.to_string()]);
+        let mut results = Vec::new();
+        let mut pending = vec![(root.to_string(), None)];
+
+        for depth in 0..self.max_group_depth {
+            let mut next = Vec::new();
+
+            for (entry, is_group) in pending {
+                // Entries known not to be groups have no members to look up
+                if is_group == Some(false) {
+                    results.push(entry);
+                    continue;
+                }
+
+                let members = sqlx::query(query)
+                    .bind(entry.as_str())
+                    .fetch(self.pool().await)
+                    .try_collect::<Vec<_>>()
+                    .await?
+                    .into_iter()
+                    .map(|row| self.mappings.row_to_member(row))
+                    .collect::<crate::Result<Vec<_>>>()?;
+
+                if depth > 0 && (keep_groups || members.is_empty()) {
+                    results.push(entry);
+                }
+
+                for (member, is_group) in members {
+                    if visited.insert(member.clone()) {
+                        next.push((member, is_group));
+                    }
+                }
+            }
+
+            if next.is_empty() {
+                return Ok(results);
+            }
+            pending = next;
+        }
+
+        // Maximum depth reached, the remaining entries are returned unexpanded
+        results.extend(pending.into_iter().map(|(entry, _)| entry));
+
+        Ok(results)
+    }
+
     async fn query_(
         &self,
         query: &str,
@@ -230,13 +527,17 @@ impl SqlDirectory {
             }
         }
 
-        q.fetch(&self.pool).try_next().await.map_err(Into::into)
+        q.fetch(self.pool().await)
+            .try_next()
+            .await
+            .map_err(Into::into)
     }
 }
 
 impl SqlMappings {
     pub fn row_to_principal(&self, row: AnyRow) -> crate::Result<Principal> {
         let mut principal = Principal::default();
+        let mut description_idx = usize::MAX;
         for col in row.columns() {
             let idx = col.ordinal();
             let name = col.name();
@@ -253,13 +554,281 @@ impl SqlMappings {
                     "group" => principal.typ = Type::Group,
                     _ => (),
                 }
-            } else if name.eq_ignore_ascii_case(&self.column_description) {
-                principal.description = row.try_get::<String, _>(idx).ok();
+            } else if let Some(column_idx) = self
+                .column_description
+                .iter()
+                .position(|column| name.eq_ignore_ascii_case(column))
+            {
+                // Columns are a fallback chain, the first non-empty one wins
+                if column_idx < description_idx {
+                    if let Some(description) = row
+                        .try_get::<String, _>(idx)
+                        .ok()
+                        .filter(|description| !description.is_empty())
+                    {
+                        principal.description = Some(description);
+                        description_idx = column_idx;
+                    }
+                }
             } else if name.eq_ignore_ascii_case(&self.column_quota) {
                 principal.quota = row.try_get::<i64, _>(idx).unwrap_or_default() as u32;
+            } else if name.eq_ignore_ascii_case(&self.column_max_message_size) {
+                principal.max_message_size = row
+                    .try_get::<i64, _>(idx)
+                    .ok()
+                    .filter(|size| *size > 0)
+                    .map(|size| size as usize);
+            } else if name.eq_ignore_ascii_case(&self.column_max_recipients) {
+                principal.max_recipients = row
+                    .try_get::<i64, _>(idx)
+                    .ok()
+                    .filter(|rcpts| *rcpts > 0)
+                    .map(|rcpts| rcpts as usize);
+            } else if name.eq_ignore_ascii_case(&self.column_password_expired) {
+                principal.password_expired = row
+                    .try_get::<bool, _>(idx)
+                    .or_else(|_| row.try_get::<i64, _>(idx).map(|expired| expired != 0))
+                    .unwrap_or_default();
+            } else if name.eq_ignore_ascii_case(&self.column_created_at) {
+                principal.created_at = row
+                    .try_get::<i64, _>(idx)
+                    .ok()
+                    .filter(|date| *date > 0)
+                    .map(|date| date as u64);
+            } else if name.eq_ignore_ascii_case(&self.column_spam_policy) {
+                principal.spam_policy = row
+                    .try_get::<String, _>(idx)
+                    .ok()
+                    .filter(|policy| !policy.is_empty());
+            } else if name.eq_ignore_ascii_case(&self.column_protocols) {
+                // Either a bitmask or a comma separated list of protocol names
+                principal.protocols = row
+                    .try_get::<i64, _>(idx)
+                    .map(|mask| mask as u32)
+                    .or_else(|_| {
+                        row.try_get::<String, _>(idx).map(|list| {
+                            list.split(',')
+                                .filter_map(Protocol::parse)
+                                .fold(0, |mask, protocol| mask | protocol.mask())
+                        })
+                    })
+                    .ok();
+            } else if name.eq_ignore_ascii_case(&self.column_shard) {
+                principal.shard = row
+                    .try_get::<String, _>(idx)
+                    .ok()
+                    .filter(|shard| !shard.is_empty());
+            } else if name.eq_ignore_ascii_case(&self.column_recovery_email) {
+                principal.recovery_email = row
+                    .try_get::<String, _>(idx)
+                    .ok()
+                    .filter(|email| !email.is_empty());
+            } else if name.eq_ignore_ascii_case(&self.column_totp_secret) {
+                principal.totp_secret = row
+                    .try_get::<String, _>(idx)
+                    .ok()
+                    .filter(|secret| !secret.is_empty());
             }
         }
 
         Ok(principal)
     }
+
+    pub fn row_to_vacation(&self, row: AnyRow) -> Option<Vacation> {
+        let mut vacation = Vacation::default();
+        for col in row.columns() {
+            let idx = col.ordinal();
+            let name = col.name();
+
+            if name.eq_ignore_ascii_case(&self.column_vacation_message) {
+                vacation.message = row.try_get::<String, _>(idx).unwrap_or_default();
+            } else if name.eq_ignore_ascii_case(&self.column_vacation_subject) {
+                vacation.subject = row
+                    .try_get::<String, _>(idx)
+                    .ok()
+                    .filter(|subject| !subject.is_empty());
+            } else if name.eq_ignore_ascii_case(&self.column_vacation_from) {
+                vacation.from_date = row
+                    .try_get::<i64, _>(idx)
+                    .ok()
+                    .filter(|date| *date > 0)
+                    .map(|date| date as u64);
+            } else if name.eq_ignore_ascii_case(&self.column_vacation_to) {
+                vacation.to_date = row
+                    .try_get::<i64, _>(idx)
+                    .ok()
+                    .filter(|date| *date > 0)
+                    .map(|date| date as u64);
+            } else if name.eq_ignore_ascii_case(&self.column_vacation_rate) {
+                vacation.rate = row
+                    .try_get::<String, _>(idx)
+                    .ok()
+                    .and_then(|rate| Rate::parse_value(name, &rate).ok());
+            }
+        }
+
+        if !vacation.message.is_empty() {
+            Some(vacation)
+        } else {
+            None
+        }
+    }
+
+    pub fn row_to_identity(&self, row: AnyRow) -> Option<Identity> {
+        let mut identity = Identity::default();
+        for col in row.columns() {
+            let idx = col.ordinal();
+            let name = col.name();
+
+            if name.eq_ignore_ascii_case(&self.column_identity_email) {
+                identity.email = row
+                    .try_get::<String, _>(idx)
+                    .unwrap_or_default()
+                    .to_lowercase();
+            } else if name.eq_ignore_ascii_case(&self.column_identity_name) {
+                identity.name = row
+                    .try_get::<String, _>(idx)
+                    .ok()
+                    .filter(|name| !name.is_empty());
+            } else if name.eq_ignore_ascii_case(&self.column_identity_reply_to) {
+                identity.reply_to = row
+                    .try_get::<String, _>(idx)
+                    .ok()
+                    .filter(|reply_to| !reply_to.is_empty());
+            } else if name.eq_ignore_ascii_case(&self.column_identity_text_signature) {
+                identity.text_signature = row
+                    .try_get::<String, _>(idx)
+                    .ok()
+                    .filter(|signature| !signature.is_empty());
+            } else if name.eq_ignore_ascii_case(&self.column_identity_html_signature) {
+                identity.html_signature = row
+                    .try_get::<String, _>(idx)
+                    .ok()
+                    .filter(|signature| !signature.is_empty());
+            } else if name.eq_ignore_ascii_case(&self.column_identity_primary) {
+                identity.is_primary = row
+                    .try_get::<bool, _>(idx)
+                    .or_else(|_| row.try_get::<i64, _>(idx).map(|primary| primary != 0))
+                    .unwrap_or_default();
+            }
+        }
+
+        if !identity.email.is_empty() {
+            Some(identity)
+        } else {
+            None
+        }
+    }
+
+    // The first column holds the member, an optional type column tells whether
+    // it is a group or list that can be expanded further
+    pub fn row_to_member(&self, row: AnyRow) -> crate::Result<(String, Option<bool>)> {
+        let member = row.try_get::<String, _>(0)?;
+        let is_group = row
+            .columns()
+            .iter()
+            .skip(1)
+            .find(|col| col.name().eq_ignore_ascii_case(&self.column_type))
+            .and_then(|col| row.try_get::<String, _>(col.ordinal()).ok())
+            .map(|typ| matches!(typ.as_str(), "group" | "list"));
+
+        Ok((member, is_group))
+    }
+
+    pub fn row_to_recovery_secrets(&self, row: &AnyRow) -> Vec<String> {
+        let mut secrets = Vec::new();
+        if !self.column_recovery_secret.is_empty() {
+            for col in row.columns() {
+                if col
+                    .name()
+                    .eq_ignore_ascii_case(&self.column_recovery_secret)
+                {
+                    if let Ok(secret) = row.try_get::<String, _>(col.ordinal()) {
+                        secrets.push(secret);
+                    }
+                }
+            }
+        }
+        secrets
+    }
+
+    pub fn row_to_policy(&self, row: AnyRow, policy: &mut GroupPolicy) {
+        for col in row.columns() {
+            let idx = col.ordinal();
+            let name = col.name();
+
+            if name.eq_ignore_ascii_case(&self.column_delivery_policy) {
+                if let Some(value) = row
+                    .try_get::<String, _>(idx)
+                    .ok()
+                    .and_then(|value| DeliveryPolicy::parse(&value))
+                {
+                    policy.policy = value;
+                }
+            } else if name.eq_ignore_ascii_case(&self.column_moderator) {
+                if let Ok(moderator) = row.try_get::<String, _>(idx) {
+                    if !moderator.is_empty() && !policy.is_moderator(&moderator) {
+                        policy.moderators.push(moderator.to_lowercase());
+                    }
+                }
+            }
+        }
+    }
+
+    pub fn row_to_route(&self, row: AnyRow) -> RecipientRoute {
+        if let Some(host) = self.row_to_relay_host(&row) {
+            RecipientRoute::Relay(host)
+        } else {
+            RecipientRoute::Local
+        }
+    }
+
+    pub fn row_to_domain_status(&self, row: &AnyRow) -> DomainStatus {
+        if self.row_to_relay_host(row).is_some() {
+            DomainStatus::Relay
+        } else {
+            DomainStatus::Local
+        }
+    }
+
+    fn row_to_relay_host(&self, row: &AnyRow) -> Option<String> {
+        if !self.column_relay_host.is_empty() {
+            for col in row.columns() {
+                if col.name().eq_ignore_ascii_case(&self.column_relay_host) {
+                    if let Ok(host) = row.try_get::<String, _>(col.ordinal()) {
+                        if !host.is_empty() {
+                            return Some(host);
+                        }
+                    }
+                }
+            }
+        }
+        None
+    }
+
+    pub fn row_to_domain(&self, name: &str, row: AnyRow) -> Domain {
+        let mut domain = Domain::new(name);
+        for col in row.columns() {
+            let idx = col.ordinal();
+            let name = col.name();
+
+            if name.eq_ignore_ascii_case(&self.column_dkim_selector) {
+                domain.dkim_selector = row.try_get::<String, _>(idx).ok();
+            } else if name.eq_ignore_ascii_case(&self.column_dkim_key) {
+                domain.dkim_key = row.try_get::<String, _>(idx).ok();
+            } else if name.eq_ignore_ascii_case(&self.column_sender_rewrite) {
+                domain.sender_rewrite = row
+                    .try_get::<String, _>(idx)
+                    .ok()
+                    .and_then(|value| SenderRewrite::parse(&value));
+            } else if name.eq_ignore_ascii_case(&self.column_spam_policy) {
+                domain.spam_policy = row
+                    .try_get::<String, _>(idx)
+                    .ok()
+                    .filter(|policy| !policy.is_empty());
+            }
+        }
+
+        domain
+    }
 }
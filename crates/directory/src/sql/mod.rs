@@ -21,17 +21,42 @@
  * for more details.
 */
 
+use std::borrow::Cow;
+
 use sqlx::{Any, Pool};
+use unicode_normalization::{is_nfc, UnicodeNormalization};
 
 use crate::DirectoryOptions;
 
 pub mod config;
+pub mod health;
 pub mod lookup;
+pub mod srv;
+pub mod tls;
 
 pub struct SqlDirectory {
     pool: Pool<Any>,
     mappings: SqlMappings,
+    dialect: SqlDialect,
     opt: DirectoryOptions,
+    normalize: AddressNormalization,
+    allow_recovery: bool,
+    allow_plaintext: bool,
+    max_group_depth: usize,
+    srv: Option<srv::SrvDiscovery>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum SqlDialect {
+    Postgres,
+    MySql,
+    Sqlite,
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+pub(crate) struct AddressNormalization {
+    pub lowercase_local_part: bool,
+    pub idn_to_ascii: bool,
 }
 
 #[derive(Debug)]
@@ -41,11 +66,240 @@ pub(crate) struct SqlMappings {
     query_recipients: String,
     query_emails: String,
     query_domains: String,
+    query_domains_batch: String,
     query_verify: String,
     query_expand: String,
+    query_delivery_policy: String,
+    query_forwards: String,
+    query_vacation: String,
+    query_identities: String,
+    query_by_external_id: String,
     column_name: String,
-    column_description: String,
+    column_description: Vec<String>,
     column_secret: String,
+    column_recovery_secret: String,
     column_quota: String,
     column_type: String,
+    column_max_message_size: String,
+    column_max_recipients: String,
+    column_password_expired: String,
+    column_created_at: String,
+    column_protocols: String,
+    column_shard: String,
+    column_recovery_email: String,
+    column_totp_secret: String,
+    column_dkim_selector: String,
+    column_dkim_key: String,
+    column_sender_rewrite: String,
+    column_spam_policy: String,
+    column_delivery_policy: String,
+    column_moderator: String,
+    column_relay_host: String,
+    column_vacation_subject: String,
+    column_vacation_message: String,
+    column_vacation_from: String,
+    column_vacation_to: String,
+    column_vacation_rate: String,
+    column_identity_email: String,
+    column_identity_name: String,
+    column_identity_reply_to: String,
+    column_identity_text_signature: String,
+    column_identity_html_signature: String,
+    column_identity_primary: String,
+}
+
+impl SqlDialect {
+    pub fn from_address(address: &str) -> Self {
+        if address.starts_with("postgres:") || address.starts_with("postgresql:") {
+            SqlDialect::Postgres
+        } else if address.starts_with("mysql:") || address.starts_with("mariadb:") {
+            SqlDialect::MySql
+        } else {
+            SqlDialect::Sqlite
+        }
+    }
+
+    // Rewrites '?' placeholders outside of quoted strings and identifiers into
+    // the backend's native syntax. MySQL and SQLite take '?' as is, Postgres
+    // expects numbered '$n' parameters.
+    pub fn placeholders(&self, query: &str) -> String {
+        if *self != SqlDialect::Postgres {
+            return query.to_string();
+        }
+
+        let mut result = String::with_capacity(query.len() + 8);
+        let mut param_num = 0;
+        let mut quote = None;
+        for ch in query.chars() {
+            match ch {
+                '\'' | '"' | '`' => {
+                    match quote {
+                        None => quote = Some(ch),
+                        Some(quote_ch) if quote_ch == ch => quote = None,
+                        _ => (),
+                    }
+                    result.push(ch);
+                }
+                '?' if quote.is_none() => {
+                    param_num += 1;
+                    result.push('$');
+                    result.push_str(&param_num.to_string());
+                }
+                _ => result.push(ch),
+            }
+        }
+        result
+    }
+}
+
+impl AddressNormalization {
+    // Addresses are NFC normalized and their domain lowercased before being bound
+    // to a query. Local parts are only lowercased when configured to do so, as some
+    // databases store them in mixed case on purpose.
+    pub fn address<'x>(&self, address: &'x str) -> Cow<'x, str> {
+        if let Some((local_part, domain)) = address.rsplit_once('@') {
+            match (self.local_part(local_part), self.domain(domain)) {
+                (Cow::Borrowed(_), Cow::Borrowed(_)) => address.into(),
+                (local_part, domain) => format!("{local_part}@{domain}").into(),
+            }
+        } else {
+            self.local_part(address)
+        }
+    }
+
+    // Login names are only normalized when they are addresses
+    pub fn login<'x>(&self, name: &'x str) -> Cow<'x, str> {
+        if name.contains('@') {
+            self.address(name)
+        } else {
+            name.into()
+        }
+    }
+
+    pub fn local_part<'x>(&self, local_part: &'x str) -> Cow<'x, str> {
+        let local_part = nfc(local_part);
+        if self.lowercase_local_part && local_part.chars().any(char::is_uppercase) {
+            local_part.to_lowercase().into()
+        } else {
+            local_part
+        }
+    }
+
+    pub fn domain<'x>(&self, domain: &'x str) -> Cow<'x, str> {
+        if self.idn_to_ascii && !domain.is_ascii() {
+            if let Ok(domain) = idna::domain_to_ascii(domain) {
+                return domain.into();
+            }
+        }
+
+        let domain = nfc(domain);
+        if domain.chars().any(char::is_uppercase) {
+            domain.to_lowercase().into()
+        } else {
+            domain
+        }
+    }
+}
+
+fn nfc(value: &str) -> Cow<'_, str> {
+    if value.is_ascii() || is_nfc(value) {
+        value.into()
+    } else {
+        value.nfc().collect::<String>().into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{AddressNormalization, SqlDialect};
+
+    #[test]
+    fn placeholder_syntax() {
+        for (address, dialect) in [
+            ("postgres://localhost/stalwart", SqlDialect::Postgres),
+            ("postgresql://localhost/stalwart", SqlDialect::Postgres),
+            ("mysql://localhost:3306/stalwart", SqlDialect::MySql),
+            ("mariadb://localhost:3306/stalwart", SqlDialect::MySql),
+            ("sqlite::memory:", SqlDialect::Sqlite),
+            ("sqlite:///var/lib/stalwart.db", SqlDialect::Sqlite),
+        ] {
+            assert_eq!(SqlDialect::from_address(address), dialect, "{address}");
+        }
+
+        let query = concat!(
+            "SELECT p.address FROM emails AS p WHERE p.name = ? AND p.type != 'list?' ",
+            "AND \"odd?col\" LIKE '%' || ? || '%' AND p.domain IN (?, ?)"
+        );
+        for (dialect, expected) in [
+            (
+                SqlDialect::Postgres,
+                concat!(
+                    "SELECT p.address FROM emails AS p WHERE p.name = $1 AND p.type != 'list?' ",
+                    "AND \"odd?col\" LIKE '%' || $2 || '%' AND p.domain IN ($3, $4)"
+                ),
+            ),
+            (SqlDialect::MySql, query),
+            (SqlDialect::Sqlite, query),
+        ] {
+            assert_eq!(dialect.placeholders(query), expected, "{dialect:?}");
+        }
+
+        // Queries already written in the native syntax are left unchanged
+        assert_eq!(
+            SqlDialect::Postgres.placeholders("SELECT name FROM emails WHERE address = $1"),
+            "SELECT name FROM emails WHERE address = $1"
+        );
+    }
+
+    #[test]
+    fn address_normalization() {
+        let default = AddressNormalization::default();
+        let lowercase = AddressNormalization {
+            lowercase_local_part: true,
+            idn_to_ascii: false,
+        };
+        let idn = AddressNormalization {
+            lowercase_local_part: true,
+            idn_to_ascii: true,
+        };
+
+        for (address, normalize, expected) in [
+            ("john@example.org", default, "john@example.org"),
+            ("John@Example.ORG", default, "John@example.org"),
+            ("John@Example.ORG", lowercase, "john@example.org"),
+            ("John", default, "John"),
+            ("John", lowercase, "john"),
+            ("@Example.org", default, "@example.org"),
+            // Decomposed characters are composed (NFC)
+            ("jose\u{301}@example.org", default, "jos\u{e9}@example.org"),
+            (
+                "Jose\u{301}@B\u{fc}cher.example",
+                lowercase,
+                "jos\u{e9}@b\u{fc}cher.example",
+            ),
+            // IDN domains are converted to A-labels when enabled
+            (
+                "Jane@B\u{fc}cher.Example",
+                idn,
+                "jane@xn--bcher-kva.example",
+            ),
+            (
+                "jane@Bu\u{308}cher.example",
+                idn,
+                "jane@xn--bcher-kva.example",
+            ),
+            (
+                "jane@xn--bcher-kva.example",
+                idn,
+                "jane@xn--bcher-kva.example",
+            ),
+        ] {
+            assert_eq!(normalize.address(address), expected, "{address}");
+        }
+
+        assert_eq!(lowercase.login("John@Example.ORG"), "john@example.org");
+        assert_eq!(lowercase.login("John"), "John");
+        assert_eq!(idn.domain("B\u{fc}cher.example"), "xn--bcher-kva.example");
+        assert_eq!(default.domain("B\u{fc}cher.Example"), "b\u{fc}cher.example");
+    }
 }
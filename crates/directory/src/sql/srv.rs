@@ -0,0 +1,150 @@
+/*
+ * Copyright (c) 2023 Stalwart Labs Ltd.
+ *
+ * This file is part of Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use std::{
+    str::FromStr,
+    time::{Duration, Instant},
+};
+
+use hickory_resolver::{proto::rr::rdata::SRV, TokioAsyncResolver};
+use rand::Rng;
+use sqlx::{any::AnyConnectOptions, Any, Pool};
+
+use super::SqlDirectory;
+
+const RETRY_INTERVAL: Duration = Duration::from_secs(30);
+
+pub(crate) struct SrvDiscovery {
+    pub record: String,
+    pub address: String,
+    pub resolver: TokioAsyncResolver,
+    pub valid_until: parking_lot::Mutex<Instant>,
+}
+
+impl SqlDirectory {
+    pub(crate) async fn pool(&self) -> &Pool<Any> {
+        if let Some(srv) = &self.srv {
+            let now = Instant::now();
+            if *srv.valid_until.lock() <= now {
+                // Avoid concurrent lookups while this one is in progress
+                *srv.valid_until.lock() = now + RETRY_INTERVAL;
+
+                match srv.resolve().await {
+                    Ok((address, valid_until)) => match AnyConnectOptions::from_str(&address) {
+                        Ok(options) => {
+                            tracing::debug!(
+                                context = "directory",
+                                event = "srv-resolved",
+                                record = srv.record.as_str(),
+                                "Resolved SQL server from SRV record"
+                            );
+                            self.pool.set_connect_options(options);
+                            *srv.valid_until.lock() = valid_until;
+                        }
+                        Err(err) => {
+                            tracing::warn!(
+                                context = "directory",
+                                event = "error",
+                                record = srv.record.as_str(),
+                                reason = %err,
+                                "Invalid SQL address obtained from SRV record"
+                            );
+                        }
+                    },
+                    Err(err) => {
+                        tracing::warn!(
+                            context = "directory",
+                            event = "error",
+                            record = srv.record.as_str(),
+                            reason = %err,
+                            "Failed to resolve SQL server SRV record"
+                        );
+                    }
+                }
+            }
+        }
+
+        &self.pool
+    }
+}
+
+impl SrvDiscovery {
+    async fn resolve(&self) -> Result<(String, Instant), String> {
+        let lookup = self
+            .resolver
+            .srv_lookup(self.record.as_str())
+            .await
+            .map_err(|err| err.to_string())?;
+        let target = select_target(lookup.iter().collect())
+            .ok_or_else(|| "No SRV targets available".to_string())?;
+        let host = target.target().to_utf8();
+        Ok((
+            replace_host(
+                &self.address,
+                host.strip_suffix('.').unwrap_or(&host),
+                target.port(),
+            ),
+            lookup.as_lookup().valid_until(),
+        ))
+    }
+}
+
+fn select_target(mut targets: Vec<&SRV>) -> Option<&SRV> {
+    // Pick among the targets with the lowest priority, weighted as described in RFC 2782
+    let priority = targets.iter().map(|target| target.priority()).min()?;
+    targets.retain(|target| target.priority() == priority);
+    let total_weight = targets
+        .iter()
+        .map(|target| target.weight() as u32)
+        .sum::<u32>();
+    if total_weight > 0 {
+        let mut choice = rand::thread_rng().gen_range(0..total_weight);
+        for target in &targets {
+            if choice < target.weight() as u32 {
+                return Some(*target);
+            }
+            choice -= target.weight() as u32;
+        }
+    }
+    targets
+        .get(rand::thread_rng().gen_range(0..targets.len()))
+        .copied()
+}
+
+fn replace_host(address: &str, host: &str, port: u16) -> String {
+    let authority_start = address.find("://").map_or(0, |pos| pos + 3);
+    let authority_end = address[authority_start..]
+        .find(|c| c == '/' || c == '?')
+        .map_or(address.len(), |pos| authority_start + pos);
+    let host_start = address[authority_start..authority_end]
+        .rfind('@')
+        .map_or(authority_start, |pos| authority_start + pos + 1);
+
+    format!(
+        "{}{}:{}{}",
+        &address[..host_start],
+        host,
+        port,
+        &address[authority_end..]
+    )
+}
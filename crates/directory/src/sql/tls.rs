@@ -0,0 +1,152 @@
+/*
+ * Copyright (c) 2023 Stalwart Labs Ltd.
+ *
+ * This file is part of Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use futures::TryStreamExt;
+use sqlx::{any::AnyConnection, Row};
+
+pub(crate) struct TlsVerifier {
+    require: bool,
+    logged: AtomicBool,
+}
+
+#[derive(Debug, Default)]
+struct TlsStatus {
+    encrypted: bool,
+    protocol: Option<String>,
+    cipher: Option<String>,
+}
+
+impl TlsVerifier {
+    pub fn new(require: bool) -> Self {
+        TlsVerifier {
+            require,
+            logged: AtomicBool::new(false),
+        }
+    }
+
+    pub async fn verify(&self, conn: &mut AnyConnection) -> Result<(), sqlx::Error> {
+        let backend = conn.backend_name().to_string();
+        let status = match backend.as_str() {
+            "PostgreSQL" => postgres_tls_status(conn).await?,
+            "MySQL" => mysql_tls_status(conn).await?,
+            _ => {
+                // Embedded databases such as SQLite have no network connection
+                return Ok(());
+            }
+        };
+
+        // Log the first connection, subsequent reconnections are logged at debug level
+        let is_first = !self.logged.swap(true, Ordering::Relaxed);
+        if status.encrypted {
+            if is_first {
+                tracing::info!(
+                    context = "directory",
+                    event = "tls",
+                    backend = backend.as_str(),
+                    protocol = status.protocol.as_deref().unwrap_or_default(),
+                    cipher = status.cipher.as_deref().unwrap_or_default(),
+                    "SQL connection is encrypted"
+                );
+            } else {
+                tracing::debug!(
+                    context = "directory",
+                    event = "tls",
+                    backend = backend.as_str(),
+                    protocol = status.protocol.as_deref().unwrap_or_default(),
+                    cipher = status.cipher.as_deref().unwrap_or_default(),
+                    "SQL connection is encrypted"
+                );
+            }
+            Ok(())
+        } else if self.require {
+            tracing::error!(
+                context = "directory",
+                event = "tls-required",
+                backend = backend.as_str(),
+                "SQL server accepted an unencrypted connection but TLS is required, closing connection"
+            );
+            Err(sqlx::Error::Configuration(
+                "TLS is required but the SQL connection is not encrypted".into(),
+            ))
+        } else {
+            if is_first {
+                tracing::warn!(
+                    context = "directory",
+                    event = "tls",
+                    backend = backend.as_str(),
+                    "SQL connection is not encrypted"
+                );
+            } else {
+                tracing::debug!(
+                    context = "directory",
+                    event = "tls",
+                    backend = backend.as_str(),
+                    "SQL connection is not encrypted"
+                );
+            }
+            Ok(())
+        }
+    }
+}
+
+async fn postgres_tls_status(conn: &mut AnyConnection) -> Result<TlsStatus, sqlx::Error> {
+    Ok(
+        if let Some(row) =
+            sqlx::query("SELECT ssl, version, cipher FROM pg_stat_ssl WHERE pid = pg_backend_pid()")
+                .fetch_optional(&mut *conn)
+                .await?
+        {
+            TlsStatus {
+                encrypted: row.try_get::<bool, _>(0)?,
+                protocol: row.try_get::<Option<String>, _>(1)?,
+                cipher: row.try_get::<Option<String>, _>(2)?,
+            }
+        } else {
+            TlsStatus::default()
+        },
+    )
+}
+
+async fn mysql_tls_status(conn: &mut AnyConnection) -> Result<TlsStatus, sqlx::Error> {
+    let mut status = TlsStatus::default();
+    let mut rows =
+        sqlx::query("SHOW SESSION STATUS WHERE Variable_name IN ('Ssl_version', 'Ssl_cipher')")
+            .fetch(&mut *conn);
+    while let Some(row) = rows.try_next().await? {
+        let value = row.try_get::<String, _>(1)?;
+        if value.is_empty() {
+            continue;
+        }
+        match row.try_get::<String, _>(0)?.as_str() {
+            "Ssl_version" => status.protocol = value.into(),
+            "Ssl_cipher" => {
+                status.encrypted = true;
+                status.cipher = value.into();
+            }
+            _ => (),
+        }
+    }
+    Ok(status)
+}
@@ -0,0 +1,80 @@
+/*
+ * Copyright (c) 2023 Stalwart Labs Ltd.
+ *
+ * This file is part of Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use std::sync::Arc;
+
+use parking_lot::Mutex;
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+use utils::{
+    config::{utils::AsKey, Config, Rate},
+    listener::limiter::RateLimiter,
+};
+
+use crate::Directory;
+
+use super::{AuthWebhook, WebhookDirectory};
+
+impl WebhookDirectory {
+    pub fn try_from_config(
+        config: &Config,
+        prefix: impl AsKey,
+        inner: Arc<dyn Directory>,
+    ) -> utils::config::Result<Arc<dyn Directory>> {
+        let prefix = prefix.as_key();
+        if let Some(url) = config.value((&prefix, "webhook.url")) {
+            let mut headers = HeaderMap::new();
+            for (_, header) in config.values((&prefix, "webhook.headers")) {
+                let (name, value) = header
+                    .split_once(':')
+                    .and_then(|(name, value)| {
+                        Some((
+                            HeaderName::from_bytes(name.trim().as_bytes()).ok()?,
+                            HeaderValue::from_str(value.trim()).ok()?,
+                        ))
+                    })
+                    .ok_or_else(|| format!("Invalid webhook header {header:?} for {prefix:?}"))?;
+                headers.insert(name, value);
+            }
+
+            Ok(Arc::new(WebhookDirectory {
+                inner,
+                hook: AuthWebhook {
+                    url: url.to_string(),
+                    source: prefix.rsplit('.').next().unwrap_or_default().to_string(),
+                    client: reqwest::Client::builder()
+                        .timeout(config.property_or_static((&prefix, "webhook.timeout"), "10s")?)
+                        .default_headers(headers)
+                        .build()
+                        .map_err(|err| {
+                            format!("Failed to build HTTP client for {prefix:?}: {err}")
+                        })?,
+                    limiter: config
+                        .property::<Rate>((&prefix, "webhook.rate"))?
+                        .map(|rate| Mutex::new(RateLimiter::new(rate.requests, rate.period))),
+                },
+            }))
+        } else {
+            Ok(inner)
+        }
+    }
+}
@@ -0,0 +1,123 @@
+/*
+ * Copyright (c) 2023 Stalwart Labs Ltd.
+ *
+ * This file is part of Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use std::time::SystemTime;
+
+use mail_send::Credentials;
+
+use crate::{hook::AuthenticationHook, Directory, DirectoryError, Principal};
+
+use super::{AuthEvent, AuthOutcome, AuthWebhook};
+
+#[async_trait::async_trait]
+impl AuthenticationHook for AuthWebhook {
+    async fn authenticate(
+        &self,
+        inner: &dyn Directory,
+        credentials: &Credentials<String>,
+    ) -> crate::Result<Option<Principal>> {
+        let result = inner.authenticate(credentials).await;
+        let (username, mechanism) = match credentials {
+            Credentials::Plain { username, .. } => (Some(username), "plain"),
+            Credentials::XOauth2 { username, .. } => (Some(username), "xoauth2"),
+            Credentials::OAuthBearer { .. } => (None, "oauthbearer"),
+        };
+        let (principal, outcome, reason) = match &result {
+            Ok(Some(principal)) => (
+                Some(principal.name.clone())
+                    .filter(|name| !name.is_empty())
+                    .or_else(|| username.cloned()),
+                AuthOutcome::Success,
+                None,
+            ),
+            Ok(None) => (
+                username.cloned(),
+                AuthOutcome::Failure,
+                Some("Invalid credentials".to_string()),
+            ),
+            Err(err) => (
+                username.cloned(),
+                AuthOutcome::Error,
+                Some(
+                    match err {
+                        DirectoryError::Ldap(_) => "LDAP error",
+                        DirectoryError::Sql(_) => "SQL error",
+                        DirectoryError::Imap(_) => "IMAP error",
+                        DirectoryError::Smtp(_) => "SMTP error",
+                        DirectoryError::Http(_) => "HTTP error",
+                        DirectoryError::TimedOut => "Directory timed out",
+                        DirectoryError::Unsupported => "Unsupported",
+                    }
+                    .to_string(),
+                ),
+            ),
+        };
+        self.notify(AuthEvent {
+            principal,
+            source: self.source.clone(),
+            mechanism,
+            outcome,
+            reason,
+            timestamp: SystemTime::now()
+                .duration_since(SystemTime::UNIX_EPOCH)
+                .map_or(0, |d| d.as_secs()),
+        });
+
+        result
+    }
+}
+
+impl AuthWebhook {
+    // Delivery is best-effort, events are dropped when rate limited and the
+    // request runs in the background so it never delays authentication.
+    pub fn notify(&self, event: AuthEvent) {
+        if let Some(limiter) = &self.limiter {
+            if !limiter.lock().is_allowed() {
+                tracing::debug!(
+                    context = "directory",
+                    event = "webhook-rate-limited",
+                    source = self.source,
+                    "Authentication webhook rate limit exceeded, event dropped"
+                );
+                return;
+            }
+        }
+
+        let request = self.client.post(&self.url).json(&event);
+        tokio::spawn(async move {
+            if let Err(err) = request
+                .send()
+                .await
+                .and_then(|response| response.error_for_status())
+            {
+                tracing::debug!(
+                    context = "directory",
+                    event = "webhook-error",
+                    source = event.source,
+                    reason = %err,
+                    "Failed to deliver authentication event"
+                );
+            }
+        });
+    }
+}
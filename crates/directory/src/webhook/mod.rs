@@ -0,0 +1,57 @@
+/*
+ * Copyright (c) 2023 Stalwart Labs Ltd.
+ *
+ * This file is part of Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use parking_lot::Mutex;
+use utils::listener::limiter::RateLimiter;
+
+use crate::hook::HookedDirectory;
+
+pub mod config;
+pub mod lookup;
+
+pub type WebhookDirectory = HookedDirectory<AuthWebhook>;
+
+pub struct AuthWebhook {
+    url: String,
+    source: String,
+    client: reqwest::Client,
+    limiter: Option<Mutex<RateLimiter>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AuthOutcome {
+    Success,
+    Failure,
+    Error,
+}
+
+#[derive(Debug, serde::Serialize)]
+pub struct AuthEvent {
+    pub principal: Option<String>,
+    pub source: String,
+    pub mechanism: &'static str,
+    pub outcome: AuthOutcome,
+    pub reason: Option<String>,
+    pub timestamp: u64,
+}
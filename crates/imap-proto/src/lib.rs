@@ -89,6 +89,9 @@ pub enum Command {
 
     // RFC 2971
     Id,
+
+    // RFC 7377
+    ESearch,
 }
 
 impl Command {
@@ -117,7 +120,9 @@ pub enum ResponseCode {
     },
     AuthenticationFailed,
     AuthorizationFailed,
-    BadCharset,
+    BadCharset {
+        charsets: Vec<String>,
+    },
     Cannot,
     Capability {
         capabilities: Vec<Capability>,
@@ -0,0 +1,107 @@
+/*
+ * Copyright (c) 2020-2022, Stalwart Labs Ltd.
+ *
+ * This file is part of Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use crate::{
+    protocol::id,
+    receiver::{Request, Token},
+    Command,
+};
+
+impl Request<Command> {
+    pub fn parse_id(self) -> crate::Result<id::Arguments> {
+        let mut tokens = self.tokens.into_iter();
+        let mut parameters = Vec::new();
+
+        match tokens.next() {
+            Some(Token::Nil) | None => (),
+            Some(Token::ParenthesisOpen) => loop {
+                let field = match tokens.next() {
+                    Some(Token::ParenthesisClose) => break,
+                    Some(token) => token.unwrap_string().map_err(|v| (self.tag.as_str(), v))?,
+                    None => return Err((self.tag.as_str(), "Missing closing parenthesis.").into()),
+                };
+                let value = match tokens.next() {
+                    Some(Token::Nil) => None,
+                    Some(Token::ParenthesisClose) | None => {
+                        return Err((self.tag, format!("Missing value for field {field:?}.")).into())
+                    }
+                    Some(token) => token
+                        .unwrap_string()
+                        .map_err(|v| (self.tag.as_str(), v))?
+                        .into(),
+                };
+                parameters.push((field, value));
+            },
+            Some(_) => return Err((self.tag.as_str(), "Expected parameter list or NIL.").into()),
+        }
+
+        if tokens.next().is_none() {
+            Ok(id::Arguments {
+                tag: self.tag,
+                parameters,
+            })
+        } else {
+            Err((self.tag.as_str(), "Too many arguments.").into())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{protocol::id, receiver::Receiver};
+
+    #[test]
+    fn parse_id() {
+        let mut receiver = Receiver::new();
+
+        for (command, arguments) in [
+            (
+                "a023 ID (\"name\" \"sodr\" \"version\" NIL \"timezone\" \"+02:00\")\r\n",
+                id::Arguments {
+                    tag: "a023".to_string(),
+                    parameters: vec![
+                        ("name".to_string(), Some("sodr".to_string())),
+                        ("version".to_string(), None),
+                        ("timezone".to_string(), Some("+02:00".to_string())),
+                    ],
+                },
+            ),
+            (
+                "a024 ID NIL\r\n",
+                id::Arguments {
+                    tag: "a024".to_string(),
+                    parameters: vec![],
+                },
+            ),
+        ] {
+            assert_eq!(
+                receiver
+                    .parse(&mut command.as_bytes().iter())
+                    .unwrap()
+                    .parse_id()
+                    .unwrap(),
+                arguments
+            );
+        }
+    }
+}
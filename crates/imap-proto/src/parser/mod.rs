@@ -29,6 +29,7 @@ pub mod create;
 pub mod delete;
 pub mod enable;
 pub mod fetch;
+pub mod id;
 pub mod list;
 pub mod login;
 pub mod lsub;
@@ -94,6 +95,7 @@ impl CommandParser for Command {
             b"MYRIGHTS" => Some(Command::MyRights),
             b"UNAUTHENTICATE" => Some(Command::Unauthenticate),
             b"ID" => Some(Command::Id),
+            b"ESEARCH" => Some(Command::ESearch),
             _ => None,
         }
     }
@@ -187,6 +189,48 @@ impl Flag {
             Flag::Keyword(keyword)
         }
     }
+
+    // Gmail labels migrated to keywords may have been stored with or without
+    // the `$` prefix and in any case, this returns the flags a search for the
+    // label has to match.
+    pub fn label_variants(&self) -> Vec<Flag> {
+        let name = match self {
+            Flag::Keyword(keyword) => keyword.trim_start_matches('$'),
+            Flag::Important => "Important",
+            Flag::Phishing => "Phishing",
+            Flag::Junk => "Junk",
+            Flag::NotJunk => "NotJunk",
+            Flag::Forwarded => "Forwarded",
+            Flag::MDNSent => "MDNSent",
+            _ => "",
+        };
+        if name.is_empty() {
+            return vec![self.clone()];
+        }
+
+        let lowercase = name.to_lowercase();
+        let mut capitalized = String::with_capacity(name.len());
+        let mut chars = lowercase.chars();
+        if let Some(first) = chars.next() {
+            capitalized.extend(first.to_uppercase());
+            capitalized.push_str(chars.as_str());
+        }
+
+        let mut variants = vec![self.clone()];
+        for name in [
+            name.to_string(),
+            name.to_uppercase(),
+            capitalized,
+            lowercase,
+        ] {
+            for flag in [Flag::parse_jmap(format!("${name}")), Flag::Keyword(name)] {
+                if !variants.contains(&flag) {
+                    variants.push(flag);
+                }
+            }
+        }
+        variants
+    }
 }
 
 pub fn parse_datetime(value: &[u8]) -> Result<i64> {
@@ -408,7 +452,45 @@ impl<T: PartialEq> PushUnique<T> for Vec<T> {
 
 #[cfg(test)]
 mod tests {
-    use crate::protocol::Sequence;
+    use crate::protocol::{Flag, Sequence};
+
+    #[test]
+    fn label_variants() {
+        for (flag, expected) in [
+            (
+                Flag::Keyword("Important".to_string()),
+                vec![
+                    Flag::Keyword("Important".to_string()),
+                    Flag::Important,
+                    Flag::Keyword("IMPORTANT".to_string()),
+                    Flag::Keyword("important".to_string()),
+                ],
+            ),
+            (
+                Flag::Important,
+                vec![
+                    Flag::Important,
+                    Flag::Keyword("Important".to_string()),
+                    Flag::Keyword("IMPORTANT".to_string()),
+                    Flag::Keyword("important".to_string()),
+                ],
+            ),
+            (
+                Flag::Keyword("$work".to_string()),
+                vec![
+                    Flag::Keyword("$work".to_string()),
+                    Flag::Keyword("work".to_string()),
+                    Flag::Keyword("$WORK".to_string()),
+                    Flag::Keyword("WORK".to_string()),
+                    Flag::Keyword("$Work".to_string()),
+                    Flag::Keyword("Work".to_string()),
+                ],
+            ),
+            (Flag::Seen, vec![Flag::Seen]),
+        ] {
+            assert_eq!(flag.label_variants(), expected, "failed for {flag:?}");
+        }
+    }
 
     #[test]
     fn parse_sequence_set() {
@@ -32,10 +32,57 @@ use crate::protocol::search::{self, Filter};
 use crate::protocol::search::{ModSeqEntry, ResultOption};
 use crate::protocol::{Flag, ProtocolVersion};
 use crate::receiver::{Request, Token};
-use crate::Command;
+use crate::utf7::utf7_maybe_decode;
+use crate::{Command, ResponseCode, StatusResponse};
 
 use super::{parse_date, parse_number, parse_sequence_set};
 
+// Canonical names of the charsets known to mail-parser. The decoder table
+// can't be enumerated, so BADCHARSET advertises the names from this list that
+// `charset_decoder` resolves, which keeps it in sync with the decoders.
+static CHARSET_NAMES: &[&str] = &[
+    "ISO-8859-1",
+    "ISO-8859-2",
+    "ISO-8859-3",
+    "ISO-8859-4",
+    "ISO-8859-5",
+    "ISO-8859-6",
+    "ISO-8859-7",
+    "ISO-8859-8",
+    "ISO-8859-9",
+    "ISO-8859-10",
+    "ISO-8859-13",
+    "ISO-8859-14",
+    "ISO-8859-15",
+    "ISO-8859-16",
+    "WINDOWS-1250",
+    "WINDOWS-1251",
+    "WINDOWS-1252",
+    "WINDOWS-1253",
+    "WINDOWS-1254",
+    "WINDOWS-1255",
+    "WINDOWS-1256",
+    "WINDOWS-1257",
+    "WINDOWS-1258",
+    "KOI8-R",
+    "KOI8-U",
+    "IBM850",
+    "IBM866",
+    "TIS-620",
+    "UTF-7",
+    "UTF-16",
+    "UTF-16BE",
+    "UTF-16LE",
+    "SHIFT_JIS",
+    "EUC-JP",
+    "ISO-2022-JP",
+    "EUC-KR",
+    "BIG5",
+    "GB2312",
+    "GBK",
+    "GB18030",
+];
+
 impl Request<Command> {
     #[allow(clippy::while_let_on_iterator)]
     pub fn parse_search(self, version: ProtocolVersion) -> crate::Result<search::Arguments> {
@@ -58,12 +105,11 @@ impl Request<Command> {
                 }
                 Some(Token::Argument(value)) if value.eq_ignore_ascii_case(b"charset") => {
                     tokens.next();
-                    decoder = charset_decoder(
-                        &tokens
-                            .next()
-                            .ok_or((self.tag.as_str(), "Missing charset."))?
-                            .unwrap_bytes(),
-                    );
+                    let value = tokens
+                        .next()
+                        .ok_or((self.tag.as_str(), "Missing charset."))?
+                        .unwrap_bytes();
+                    decoder = parse_charset(&self.tag, &value)?;
                 }
                 _ => break,
             }
@@ -79,11 +125,135 @@ impl Request<Command> {
                 filter,
                 sort: None,
                 is_esearch,
+                timezone: 0,
             }),
         }
     }
 }
 
+impl Request<Command> {
+    pub fn parse_esearch(
+        mut self,
+        version: ProtocolVersion,
+    ) -> crate::Result<search::MultiArguments> {
+        let sources = if self
+            .tokens
+            .first()
+            .map_or(false, |token| token.eq_ignore_ascii_case(b"in"))
+        {
+            let mut tokens = std::mem::take(&mut self.tokens).into_iter().peekable();
+            tokens.next();
+            let sources =
+                parse_sources(&mut tokens, version).map_err(|v| (self.tag.as_str(), v))?;
+            self.tokens = tokens.collect();
+            sources
+        } else {
+            vec![search::Source::Selected]
+        };
+
+        Ok(search::MultiArguments {
+            sources,
+            arguments: self.parse_search(ProtocolVersion::Rev2)?,
+        })
+    }
+}
+
+fn parse_sources(
+    tokens: &mut Peekable<IntoIter<Token>>,
+    version: ProtocolVersion,
+) -> super::Result<Vec<search::Source>> {
+    if tokens
+        .next()
+        .map_or(true, |token| !token.is_parenthesis_open())
+    {
+        return Err(Cow::from("Expected parenthesis after IN."));
+    }
+
+    let mut sources = Vec::new();
+    while let Some(token) = tokens.next() {
+        match token {
+            Token::ParenthesisClose => break,
+            Token::Argument(value) if value.eq_ignore_ascii_case(b"selected") => {
+                sources.push(search::Source::Selected);
+            }
+            Token::Argument(value) => {
+                let names = parse_source_mailboxes(tokens, version)?;
+                sources.push(if value.eq_ignore_ascii_case(b"mailboxes") {
+                    search::Source::Mailboxes(names)
+                } else if value.eq_ignore_ascii_case(b"subtree") {
+                    search::Source::Subtree(names)
+                } else if value.eq_ignore_ascii_case(b"subtree-one") {
+                    search::Source::SubtreeOne(names)
+                } else {
+                    return Err(format!(
+                        "Unsupported search source {:?}.",
+                        String::from_utf8_lossy(&value)
+                    )
+                    .into());
+                });
+            }
+            _ => return Err(Cow::from("Invalid search source.")),
+        }
+    }
+
+    if !sources.is_empty() {
+        Ok(sources)
+    } else {
+        Err(Cow::from("Missing search sources."))
+    }
+}
+
+fn parse_source_mailboxes(
+    tokens: &mut Peekable<IntoIter<Token>>,
+    version: ProtocolVersion,
+) -> super::Result<Vec<String>> {
+    let mut names = Vec::new();
+    match tokens.next() {
+        Some(Token::ParenthesisOpen) => {
+            for token in tokens {
+                match token {
+                    Token::ParenthesisClose => break,
+                    token => names.push(utf7_maybe_decode(token.unwrap_string()?, version)),
+                }
+            }
+        }
+        Some(token) => names.push(utf7_maybe_decode(token.unwrap_string()?, version)),
+        None => (),
+    }
+
+    if !names.is_empty() {
+        Ok(names)
+    } else {
+        Err(Cow::from("Missing mailbox names."))
+    }
+}
+
+pub fn parse_charset(tag: &str, value: &[u8]) -> crate::Result<Option<DecoderFnc>> {
+    if let Some(decoder) = charset_decoder(value) {
+        Ok(Some(decoder))
+    } else if value.eq_ignore_ascii_case(b"UTF-8") || value.eq_ignore_ascii_case(b"US-ASCII") {
+        Ok(None)
+    } else {
+        Err(StatusResponse::bad(format!(
+            "Unsupported charset {:?}.",
+            String::from_utf8_lossy(value)
+        ))
+        .with_tag(tag)
+        .with_code(ResponseCode::BadCharset {
+            charsets: ["UTF-8", "US-ASCII"]
+                .into_iter()
+                .chain(
+                    CHARSET_NAMES
+                        .iter()
+                        .copied()
+                        .filter(|charset| charset_decoder(charset.as_bytes()).is_some()),
+                )
+                .map(|charset| charset.to_string())
+                .collect(),
+        }))
+    }
+}
+
 pub fn parse_result_options(
     tokens: &mut Peekable<IntoIter<Token>>,
 ) -> super::Result<Vec<ResultOption>> {
@@ -95,8 +265,33 @@ pub fn parse_result_options(
         return Err(Cow::from("Invalid result option, expected parenthesis."));
     }
 
-    for token in tokens {
+    while let Some(token) = tokens.next() {
         match token {
+            Token::Argument(value) if value.eq_ignore_ascii_case(b"partial") => {
+                let (start, end) = parse_partial_range(
+                    &tokens
+                        .next()
+                        .ok_or_else(|| Cow::from("Missing partial range."))?
+                        .unwrap_bytes(),
+                )?;
+                result_options.push(ResultOption::Partial { start, end });
+            }
+            Token::Argument(value) if value.eq_ignore_ascii_case(b"x-deadline") => {
+                let value = tokens
+                    .next()
+                    .ok_or_else(|| Cow::from("Missing deadline."))?
+                    .unwrap_bytes();
+                let millis = std::str::from_utf8(&value)
+                    .ok()
+                    .and_then(|value| value.parse::<u32>().ok())
+                    .ok_or_else(|| {
+                        Cow::from(format!(
+                            "Invalid deadline {:?}",
+                            String::from_utf8_lossy(&value)
+                        ))
+                    })?;
+                result_options.push(ResultOption::Deadline { millis });
+            }
             Token::ParenthesisClose => break,
             Token::Argument(value) => {
                 result_options.push(ResultOption::parse(&value)?);
@@ -108,6 +303,16 @@ pub fn parse_result_options(
     Ok(result_options)
 }
 
+fn parse_partial_range(value: &[u8]) -> super::Result<(i32, i32)> {
+    // Both ends must be non-zero and count from the same side of the results
+    std::str::from_utf8(value)
+        .ok()
+        .and_then(|value| value.split_once(':'))
+        .and_then(|(start, end)| Some((start.parse::<i32>().ok()?, end.parse::<i32>().ok()?)))
+        .filter(|(start, end)| *start != 0 && *end != 0 && (*start > 0) == (*end > 0))
+        .ok_or_else(|| format!("Invalid partial range {:?}", String::from_utf8_lossy(value)).into())
+}
+
 pub fn parse_filters(
     tokens: &mut Peekable<IntoIter<Token>>,
     decoder: Option<DecoderFnc>,
@@ -323,6 +528,86 @@ pub fn parse_filters(
                             .ok_or_else(|| Cow::from("Expected an THREADID value."))?
                             .unwrap_string()?,
                     ));
+                } else if value.eq_ignore_ascii_case(b"X-ALLMAILBOXES") {
+                    filters.push(Filter::AllMailboxes);
+                } else if value.eq_ignore_ascii_case(b"X-WITHIN") {
+                    let mut mailboxes = Vec::new();
+                    match tokens
+                        .next()
+                        .ok_or_else(|| Cow::from("Expected a mailbox name or list."))?
+                    {
+                        Token::ParenthesisOpen => loop {
+                            match tokens
+                                .next()
+                                .ok_or_else(|| Cow::from("Unterminated mailbox list."))?
+                            {
+                                Token::ParenthesisClose => break,
+                                token => mailboxes.push(token.unwrap_string()?),
+                            }
+                        },
+                        token => mailboxes.push(token.unwrap_string()?),
+                    }
+                    if mailboxes.is_empty() {
+                        return Err(Cow::from("Expected at least one mailbox name."));
+                    }
+                    filters.push(Filter::Within(mailboxes));
+                } else if value.eq_ignore_ascii_case(b"X-EXACT") {
+                    filters.push(Filter::Exact(
+                        decode_argument(tokens, decoder)?,
+                        decode_argument(tokens, decoder)?,
+                    ));
+                } else if value.eq_ignore_ascii_case(b"X-EXCLUDE") {
+                    filters.push(Filter::Exclude(parse_sequence_set(
+                        &tokens
+                            .next()
+                            .ok_or_else(|| Cow::from("Missing sequence set."))?
+                            .unwrap_bytes(),
+                    )?));
+                } else if value.eq_ignore_ascii_case(b"X-THREADROOT") {
+                    filters.push(Filter::ThreadRoot);
+                } else if value.eq_ignore_ascii_case(b"X-SPAMSCORE-MIN")
+                    || value.eq_ignore_ascii_case(b"X-SPAMSCORE-MAX")
+                {
+                    let score = (parse_number::<f64>(
+                        &tokens
+                            .next()
+                            .ok_or_else(|| Cow::from("Expected a spam score"))?
+                            .unwrap_bytes(),
+                    )? * 10.0)
+                        .round() as i32;
+                    filters.push(if value.eq_ignore_ascii_case(b"X-SPAMSCORE-MIN") {
+                        Filter::SpamScoreMin(score)
+                    } else {
+                        Filter::SpamScoreMax(score)
+                    });
+                } else if value.eq_ignore_ascii_case(b"X-ATTACHMENTS-MIN") {
+                    filters.push(Filter::AttachmentsMin(parse_number::<u32>(
+                        &tokens
+                            .next()
+                            .ok_or_else(|| Cow::from("Expected integer"))?
+                            .unwrap_bytes(),
+                    )?));
+                } else if value.eq_ignore_ascii_case(b"X-ATTACHMENTS-MAX") {
+                    filters.push(Filter::AttachmentsMax(parse_number::<u32>(
+                        &tokens
+                            .next()
+                            .ok_or_else(|| Cow::from("Expected integer"))?
+                            .unwrap_bytes(),
+                    )?));
+                } else if value.eq_ignore_ascii_case(b"X-THREADSIZE-MIN") {
+                    filters.push(Filter::ThreadSizeMin(parse_number::<u32>(
+                        &tokens
+                            .next()
+                            .ok_or_else(|| Cow::from("Expected integer"))?
+                            .unwrap_bytes(),
+                    )?));
+                } else if value.eq_ignore_ascii_case(b"X-THREADSIZE-MAX") {
+                    filters.push(Filter::ThreadSizeMax(parse_number::<u32>(
+                        &tokens
+                            .next()
+                            .ok_or_else(|| Cow::from("Expected integer"))?
+                            .unwrap_bytes(),
+                    )?));
                 } else if value.eq_ignore_ascii_case(b"OR") {
                     if filters_stack.len() > 10 {
                         return Err(Cow::from("Too many nested filters"));
@@ -430,6 +715,14 @@ impl ResultOption {
             Ok(Self::Save)
         } else if value.eq_ignore_ascii_case(b"context") {
             Ok(Self::Context)
+        } else if value.eq_ignore_ascii_case(b"x-matches") {
+            Ok(Self::Matches)
+        } else if value.eq_ignore_ascii_case(b"x-snippets") {
+            Ok(Self::Snippets)
+        } else if value.eq_ignore_ascii_case(b"x-changebuckets") {
+            Ok(Self::ChangeBuckets)
+        } else if value.eq_ignore_ascii_case(b"x-groupbymailbox") {
+            Ok(Self::GroupByMailbox)
         } else {
             Err(format!("Invalid result option {:?}", String::from_utf8_lossy(value)).into())
         }
@@ -444,6 +737,7 @@ mod tests {
             Flag, ProtocolVersion, Sequence,
         },
         receiver::Receiver,
+        ResponseCode, ResponseType,
     };
 
     #[test]
@@ -466,6 +760,7 @@ mod tests {
                     ],
                     is_esearch: true,
                     sort: None,
+                    timezone: 0,
                 },
             ),
             (
@@ -482,6 +777,7 @@ mod tests {
                     ],
                     is_esearch: true,
                     sort: None,
+                    timezone: 0,
                 },
             ),
             (
@@ -492,6 +788,7 @@ mod tests {
                     filter: vec![Filter::seq_saved_search(), Filter::Smaller(4096)],
                     is_esearch: true,
                     sort: None,
+                    timezone: 0,
                 },
             ),
             (
@@ -518,6 +815,7 @@ mod tests {
                     ],
                     is_esearch: true,
                     sort: None,
+                    timezone: 0,
                 },
             ),
             (
@@ -528,6 +826,7 @@ mod tests {
                     filter: vec![Filter::Keyword(Flag::Junk)],
                     is_esearch: true,
                     sort: None,
+                    timezone: 0,
                 },
             ),
             (
@@ -554,6 +853,7 @@ mod tests {
                     ],
                     is_esearch: true,
                     sort: None,
+                    timezone: 0,
                 },
             ),
             (
@@ -577,6 +877,7 @@ mod tests {
                     ],
                     is_esearch: true,
                     sort: None,
+                    timezone: 0,
                 },
             ),
             (
@@ -619,6 +920,7 @@ mod tests {
                     ],
                     is_esearch: true,
                     sort: None,
+                    timezone: 0,
                 },
             ),
             (
@@ -653,6 +955,7 @@ mod tests {
                     ],
                     is_esearch: true,
                     sort: None,
+                    timezone: 0,
                 },
             ),
             (
@@ -689,6 +992,7 @@ mod tests {
                     ],
                     is_esearch: true,
                     sort: None,
+                    timezone: 0,
                 },
             ),
             (
@@ -703,6 +1007,7 @@ mod tests {
                     filter: vec![Filter::Text("Привет, мир".to_string())],
                     is_esearch: true,
                     sort: None,
+                    timezone: 0,
                 },
             ),
             (
@@ -713,6 +1018,46 @@ mod tests {
                     filter: vec![Filter::From("你好，世界".to_string())],
                     is_esearch: true,
                     sort: None,
+                    timezone: 0,
+                },
+            ),
+            (
+                b"C283 SEARCH CHARSET ISO-8859-1 SUBJECT \"caf\xe9\"\r\n".to_vec(),
+                search::Arguments {
+                    tag: "C283".to_string(),
+                    result_options: vec![],
+                    filter: vec![Filter::Subject("café".to_string())],
+                    is_esearch: true,
+                    sort: None,
+                    timezone: 0,
+                },
+            ),
+            (
+                b"E283 SEARCH RETURN (COUNT PARTIAL -1:-100) UNSEEN\r\n".to_vec(),
+                search::Arguments {
+                    tag: "E283".to_string(),
+                    result_options: vec![
+                        ResultOption::Count,
+                        ResultOption::Partial {
+                            start: -1,
+                            end: -100,
+                        },
+                    ],
+                    filter: vec![Filter::Unseen],
+                    is_esearch: true,
+                    sort: None,
+                    timezone: 0,
+                },
+            ),
+            (
+                b"E284 SEARCH RETURN (ALL X-DEADLINE 250) UNSEEN\r\n".to_vec(),
+                search::Arguments {
+                    tag: "E284".to_string(),
+                    result_options: vec![ResultOption::All, ResultOption::Deadline { millis: 250 }],
+                    filter: vec![Filter::Unseen],
+                    is_esearch: true,
+                    sort: None,
+                    timezone: 0,
                 },
             ),
             (
@@ -723,6 +1068,7 @@ mod tests {
                     filter: vec![Filter::ModSeq((620162338, ModSeqEntry::All(Flag::Draft)))],
                     is_esearch: true,
                     sort: None,
+                    timezone: 0,
                 },
             ),
             (
@@ -740,6 +1086,95 @@ mod tests {
                     ],
                     is_esearch: true,
                     sort: None,
+                    timezone: 0,
+                },
+            ),
+            (
+                b"t SEARCH X-THREADSIZE-MIN 3 X-THREADSIZE-MAX 10\r\n".to_vec(),
+                search::Arguments {
+                    tag: "t".to_string(),
+                    result_options: vec![],
+                    filter: vec![Filter::ThreadSizeMin(3), Filter::ThreadSizeMax(10)],
+                    is_esearch: true,
+                    sort: None,
+                    timezone: 0,
+                },
+            ),
+            (
+                b"t SEARCH X-THREADROOT UNSEEN\r\n".to_vec(),
+                search::Arguments {
+                    tag: "t".to_string(),
+                    result_options: vec![],
+                    filter: vec![Filter::ThreadRoot, Filter::Unseen],
+                    is_esearch: true,
+                    sort: None,
+                    timezone: 0,
+                },
+            ),
+            (
+                b"t SEARCH X-SPAMSCORE-MIN 3 X-SPAMSCORE-MAX 5.5\r\n".to_vec(),
+                search::Arguments {
+                    tag: "t".to_string(),
+                    result_options: vec![],
+                    filter: vec![Filter::SpamScoreMin(30), Filter::SpamScoreMax(55)],
+                    is_esearch: true,
+                    sort: None,
+                    timezone: 0,
+                },
+            ),
+            (
+                b"t SEARCH X-ATTACHMENTS-MIN 4 X-ATTACHMENTS-MAX 10\r\n".to_vec(),
+                search::Arguments {
+                    tag: "t".to_string(),
+                    result_options: vec![],
+                    filter: vec![Filter::AttachmentsMin(4), Filter::AttachmentsMax(10)],
+                    is_esearch: true,
+                    sort: None,
+                    timezone: 0,
+                },
+            ),
+            (
+                b"t SEARCH X-WITHIN (INBOX \"Sent Items\") FROM jane\r\n".to_vec(),
+                search::Arguments {
+                    tag: "t".to_string(),
+                    result_options: vec![],
+                    filter: vec![
+                        Filter::Within(vec!["INBOX".to_string(), "Sent Items".to_string()]),
+                        Filter::From("jane".to_string()),
+                    ],
+                    is_esearch: true,
+                    sort: None,
+                    timezone: 0,
+                },
+            ),
+            (
+                b"t SEARCH X-EXACT Message-ID \"AbC.123@\" SEEN\r\n".to_vec(),
+                search::Arguments {
+                    tag: "t".to_string(),
+                    result_options: vec![],
+                    filter: vec![
+                        Filter::Exact("Message-ID".to_string(), "AbC.123@".to_string()),
+                        Filter::Seen,
+                    ],
+                    is_esearch: true,
+                    sort: None,
+                    timezone: 0,
+                },
+            ),
+            (
+                b"t SEARCH SUBJECT hello X-EXCLUDE 1:5,9\r\n".to_vec(),
+                search::Arguments {
+                    tag: "t".to_string(),
+                    result_options: vec![],
+                    filter: vec![
+                        Filter::Subject("hello".to_string()),
+                        Filter::Exclude(Sequence::List {
+                            items: vec![Sequence::range(1.into(), 5.into()), Sequence::number(9)],
+                        }),
+                    ],
+                    is_esearch: true,
+                    sort: None,
+                    timezone: 0,
                 },
             ),
         ] {
@@ -755,5 +1190,52 @@ mod tests {
                 command_str
             );
         }
+
+        let err = receiver
+            .parse(&mut b"D283 SEARCH CHARSET X-UNKNOWN SUBJECT hello\r\n".iter())
+            .unwrap()
+            .parse_search(ProtocolVersion::Rev2)
+            .unwrap_err();
+        assert_eq!(err.tag.as_deref(), Some("D283"));
+        assert_eq!(err.rtype, ResponseType::Bad);
+        assert!(
+            matches!(err.code, Some(ResponseCode::BadCharset { ref charsets }) if charsets.contains(&"ISO-8859-1".to_string()))
+        );
+    }
+
+    #[test]
+    fn parse_esearch() {
+        let mut receiver = Receiver::new();
+
+        for (command, sources) in [
+            (
+                b"t1 ESEARCH IN (mailboxes \"Drafts\" subtree-one (\"Work\" INBOX)) RETURN (COUNT) SEEN\r\n".to_vec(),
+                vec![
+                    search::Source::Mailboxes(vec!["Drafts".to_string()]),
+                    search::Source::SubtreeOne(vec!["Work".to_string(), "INBOX".to_string()]),
+                ],
+            ),
+            (
+                b"t2 ESEARCH IN (selected subtree Archive) RETURN (COUNT) SEEN\r\n".to_vec(),
+                vec![
+                    search::Source::Selected,
+                    search::Source::Subtree(vec!["Archive".to_string()]),
+                ],
+            ),
+            (
+                b"t3 ESEARCH RETURN (COUNT) SEEN\r\n".to_vec(),
+                vec![search::Source::Selected],
+            ),
+        ] {
+            let command_str = String::from_utf8_lossy(&command).into_owned();
+            let arguments = receiver
+                .parse(&mut command.iter())
+                .unwrap()
+                .parse_esearch(ProtocolVersion::Rev2)
+                .expect(&command_str);
+            assert_eq!(arguments.sources, sources, "{}", command_str);
+            assert_eq!(arguments.arguments.result_options, vec![ResultOption::Count]);
+            assert_eq!(arguments.arguments.filter, vec![Filter::Seen]);
+        }
     }
 }
@@ -21,15 +21,13 @@
  * for more details.
 */
 
-use mail_parser::decoders::charsets::map::charset_decoder;
-
 use crate::{
     protocol::search::{Arguments, Comparator, Sort},
     receiver::{Request, Token},
     Command,
 };
 
-use super::search::{parse_filters, parse_result_options};
+use super::search::{parse_charset, parse_filters, parse_result_options};
 
 impl Request<Command> {
     #[allow(clippy::while_let_on_iterator)]
@@ -86,12 +84,13 @@ impl Request<Command> {
             return Err((self.tag.as_str(), "Missing sort criteria.").into());
         }
 
-        let decoder = charset_decoder(
+        let decoder = parse_charset(
+            &self.tag,
             &tokens
                 .next()
                 .ok_or((self.tag.as_str(), "Missing charset."))?
                 .unwrap_bytes(),
-        );
+        )?;
 
         let filter = parse_filters(&mut tokens, decoder).map_err(|v| (self.tag.as_str(), v))?;
         match filter.len() {
@@ -101,6 +100,7 @@ impl Request<Command> {
                 result_options,
                 filter,
                 is_esearch,
+                timezone: 0,
                 tag: self.tag,
             }),
         }
@@ -127,6 +127,8 @@ impl Sort {
             Ok(Self::DisplayFrom)
         } else if value.eq_ignore_ascii_case(b"DISPLAYTO") {
             Ok(Self::DisplayTo)
+        } else if value.eq_ignore_ascii_case(b"X-RELEVANCE") {
+            Ok(Self::Relevance)
         } else {
             Err(format!("Invalid sort criteria {:?}", String::from_utf8_lossy(value)).into())
         }
@@ -160,6 +162,7 @@ mod tests {
                     filter: vec![Filter::Since(760060800)],
                     result_options: Vec::new(),
                     is_esearch: false,
+                    timezone: 0,
                     tag: "A282".to_string(),
                 },
             ),
@@ -180,6 +183,7 @@ mod tests {
                     filter: vec![Filter::All],
                     result_options: Vec::new(),
                     is_esearch: false,
+                    timezone: 0,
                     tag: "A283".to_string(),
                 },
             ),
@@ -194,6 +198,7 @@ mod tests {
                     filter: vec![Filter::Text("not in mailbox".to_string())],
                     result_options: Vec::new(),
                     is_esearch: false,
+                    timezone: 0,
                     tag: "A284".to_string(),
                 },
             ),
@@ -218,6 +223,7 @@ mod tests {
                     filter: vec![Filter::Subject("مرحبا بالعالم".to_string())],
                     result_options: Vec::new(),
                     is_esearch: false,
+                    timezone: 0,
                     tag: "A284".to_string(),
                 },
             ),
@@ -236,6 +242,7 @@ mod tests {
                     filter: vec![Filter::Undeleted, Filter::Unkeyword(Flag::Junk)],
                     result_options: vec![ResultOption::Count],
                     is_esearch: true,
+                    timezone: 0,
                     tag: "E01".to_string(),
                 },
             ),
@@ -21,15 +21,13 @@
  * for more details.
 */
 
-use mail_parser::decoders::charsets::map::charset_decoder;
-
 use crate::{
     protocol::thread::{self, Algorithm},
     receiver::Request,
     Command,
 };
 
-use super::search::parse_filters;
+use super::search::{parse_charset, parse_filters};
 
 impl Request<Command> {
     #[allow(clippy::while_let_on_iterator)]
@@ -47,12 +45,11 @@ impl Request<Command> {
         )
         .map_err(|v| (self.tag.as_str(), v))?;
 
-        let decoder = charset_decoder(
-            &tokens
-                .next()
-                .ok_or((self.tag.as_str(), "Missing charset."))?
-                .unwrap_bytes(),
-        );
+        let charset = tokens
+            .next()
+            .ok_or((self.tag.as_str(), "Missing charset."))?
+            .unwrap_bytes();
+        let decoder = parse_charset(&self.tag, &charset)?;
 
         let filter = parse_filters(&mut tokens, decoder).map_err(|v| (self.tag.as_str(), v))?;
         match filter.len() {
@@ -64,6 +64,8 @@ pub enum Capability {
     ObjectId,
     Preview,
     Utf8Accept,
+    Partial,
+    MultiSearch,
     Auth(Mechanism),
 }
 
@@ -109,6 +111,8 @@ impl Capability {
             Capability::CreateSpecialUse => b"CREATE-SPECIAL-USE",
             Capability::Move => b"MOVE",
             Capability::Utf8Accept => b"UTF8=ACCEPT",
+            Capability::Partial => b"PARTIAL",
+            Capability::MultiSearch => b"MULTISEARCH",
         });
     }
 
@@ -140,6 +144,8 @@ impl Capability {
                 Capability::Thread,
                 Capability::ListExtended,
                 Capability::ESort,
+                Capability::Partial,
+                Capability::MultiSearch,
                 Capability::SortDisplay,
                 Capability::SpecialUse,
                 Capability::CreateSpecialUse,
@@ -39,6 +39,7 @@ pub mod delete;
 pub mod enable;
 pub mod expunge;
 pub mod fetch;
+pub mod id;
 pub mod list;
 pub mod login;
 pub mod namespace;
@@ -313,7 +314,20 @@ impl ResponseCode {
             }
             ResponseCode::AuthenticationFailed => b"AUTHENTICATIONFAILED",
             ResponseCode::AuthorizationFailed => b"AUTHORIZATIONFAILED",
-            ResponseCode::BadCharset => b"BADCHARSET",
+            ResponseCode::BadCharset { charsets } => {
+                buf.extend_from_slice(b"BADCHARSET");
+                if !charsets.is_empty() {
+                    buf.extend_from_slice(b" (");
+                    for (pos, charset) in charsets.iter().enumerate() {
+                        if pos > 0 {
+                            buf.push(b' ');
+                        }
+                        buf.extend_from_slice(charset.as_bytes());
+                    }
+                    buf.push(b')');
+                }
+                return;
+            }
             ResponseCode::Cannot => b"CANNOT",
             ResponseCode::Capability { capabilities } => {
                 buf.extend_from_slice(b"CAPABILITY");
@@ -505,6 +519,7 @@ impl Display for Command {
             Command::MyRights => write!(f, "MYRIGHTS"),
             Command::Unauthenticate => write!(f, "UNAUTHENTICATE"),
             Command::Id => write!(f, "ID"),
+            Command::ESearch => write!(f, "ESEARCH"),
         }
     }
 }
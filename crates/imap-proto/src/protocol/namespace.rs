@@ -23,20 +23,78 @@
 
 use super::{quoted_string, ImapResponse};
 
+// Namespaces as defined in RFC 2342, each group lists the prefixes of the
+// namespaces it contains. All namespaces use the same hierarchy delimiter as LIST.
 pub struct Response {
-    pub shared_prefix: Option<String>,
+    pub personal: Vec<String>,
+    pub other_users: Vec<String>,
+    pub shared: Vec<String>,
 }
 
 impl ImapResponse for Response {
     fn serialize(self) -> Vec<u8> {
         let mut buf = Vec::with_capacity(64);
-        if let Some(shared_prefix) = &self.shared_prefix {
-            buf.extend_from_slice(b"* NAMESPACE ((\"\" \"/\")) ((");
-            quoted_string(&mut buf, shared_prefix);
-            buf.extend_from_slice(b" \"/\")) NIL\r\n");
-        } else {
-            buf.extend_from_slice(b"* NAMESPACE ((\"\" \"/\")) NIL NIL\r\n");
-        }
+        buf.extend_from_slice(b"* NAMESPACE ");
+        serialize_namespaces(&mut buf, &self.personal);
+        buf.push(b' ');
+        serialize_namespaces(&mut buf, &self.other_users);
+        buf.push(b' ');
+        serialize_namespaces(&mut buf, &self.shared);
+        buf.extend_from_slice(b"\r\n");
         buf
     }
 }
+
+fn serialize_namespaces(buf: &mut Vec<u8>, prefixes: &[String]) {
+    if !prefixes.is_empty() {
+        buf.push(b'(');
+        for prefix in prefixes {
+            buf.push(b'(');
+            quoted_string(buf, prefix);
+            buf.extend_from_slice(b" \"/\")");
+        }
+        buf.push(b')');
+    } else {
+        buf.extend_from_slice(b"NIL");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::protocol::ImapResponse;
+
+    #[test]
+    fn serialize_namespace() {
+        for (response, expected) in [
+            (
+                super::Response {
+                    personal: vec!["".to_string()],
+                    other_users: vec![],
+                    shared: vec![],
+                },
+                "* NAMESPACE ((\"\" \"/\")) NIL NIL\r\n",
+            ),
+            (
+                super::Response {
+                    personal: vec!["".to_string()],
+                    other_users: vec!["Shared Folders/".to_string()],
+                    shared: vec![],
+                },
+                "* NAMESPACE ((\"\" \"/\")) ((\"Shared Folders/\" \"/\")) NIL\r\n",
+            ),
+            (
+                super::Response {
+                    personal: vec!["".to_string()],
+                    other_users: vec!["Other \"Users\"/".to_string()],
+                    shared: vec!["Public/".to_string(), "Archive/".to_string()],
+                },
+                concat!(
+                    "* NAMESPACE ((\"\" \"/\")) ((\"Other \\\"Users\\\"/\" \"/\")) ",
+                    "((\"Public/\" \"/\")(\"Archive/\" \"/\"))\r\n"
+                ),
+            ),
+        ] {
+            assert_eq!(String::from_utf8(response.serialize()).unwrap(), expected);
+        }
+    }
+}
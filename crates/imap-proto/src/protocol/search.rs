@@ -30,6 +30,22 @@ pub struct Arguments {
     pub sort: Option<Vec<Comparator>>,
     pub result_options: Vec<ResultOption>,
     pub filter: Vec<Filter>,
+    pub timezone: i32,
+}
+
+// Multi-mailbox search, as in RFC 7377
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MultiArguments {
+    pub sources: Vec<Source>,
+    pub arguments: Arguments,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Source {
+    Selected,
+    Mailboxes(Vec<String>),
+    Subtree(Vec<String>),
+    SubtreeOne(Vec<String>),
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -43,6 +59,7 @@ pub enum Sort {
     Subject,
     To,
     DisplayTo,
+    Relevance,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -61,6 +78,60 @@ pub struct Response {
     pub max: Option<u32>,
     pub count: Option<u32>,
     pub highest_modseq: Option<u64>,
+    pub matches: Vec<Match>,
+    pub change_buckets: Option<ChangeBuckets>,
+    pub groups: Vec<MailboxGroup>,
+    pub partial: Option<PartialResult>,
+    pub snippets: Vec<Snippet>,
+}
+
+// Window of the sorted results requested with PARTIAL, as in RFC 9394
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PartialResult {
+    pub start: i32,
+    pub end: i32,
+    pub ids: Vec<u32>,
+}
+
+// Number of matching messages by the time the searched keyword was set, each
+// message is counted in exactly one bucket.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ChangeBuckets {
+    pub day: u32,
+    pub week: u32,
+    pub month: u32,
+    pub older: u32,
+}
+
+// Matching UIDs of a single mailbox, for searches spanning several mailboxes
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MailboxGroup {
+    pub mailbox_name: String,
+    pub uid_validity: u32,
+    pub uids: Vec<u32>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Match {
+    pub id: u32,
+    pub terms: Vec<(MatchProperty, String)>,
+}
+
+// Excerpt of the body text around the first matched term
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Snippet {
+    pub id: u32,
+    pub text: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MatchProperty {
+    From,
+    To,
+    Cc,
+    Bcc,
+    Subject,
+    Body,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -71,6 +142,12 @@ pub enum ResultOption {
     Count,
     Save,
     Context,
+    Matches,
+    ChangeBuckets,
+    GroupByMailbox,
+    Snippets,
+    Partial { start: i32, end: i32 },
+    Deadline { millis: u32 },
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -127,6 +204,33 @@ pub enum Filter {
     // RFC 8474 - ObjectID
     EmailId(String),
     ThreadId(String),
+
+    // Thread size
+    ThreadSizeMin(u32),
+    ThreadSizeMax(u32),
+
+    // Messages without In-Reply-To or References headers
+    ThreadRoot,
+
+    // Spam score, in tenths of a point
+    SpamScoreMin(i32),
+    SpamScoreMax(i32),
+
+    // Number of attachments
+    AttachmentsMin(u32),
+    AttachmentsMax(u32),
+
+    // UIDs already returned to the client, removed from the results
+    Exclude(Sequence),
+
+    // Include the mailboxes excluded from All Mail searches
+    AllMailboxes,
+
+    // Restrict All Mail searches to the listed mailboxes
+    Within(Vec<String>),
+
+    // Case-sensitive substring match on a header, without tokenization
+    Exact(String, String),
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -147,36 +251,71 @@ impl Filter {
     }
 }
 
+impl ResultOption {
+    pub fn partial(&self) -> Option<(i32, i32)> {
+        match self {
+            ResultOption::Partial { start, end } => Some((*start, *end)),
+            _ => None,
+        }
+    }
+
+    pub fn deadline(&self) -> Option<std::time::Duration> {
+        match self {
+            ResultOption::Deadline { millis } => {
+                Some(std::time::Duration::from_millis(*millis as u64))
+            }
+            _ => None,
+        }
+    }
+}
+
+impl PartialResult {
+    pub fn new(start: i32, end: i32, ids: &[u32]) -> Self {
+        // Positive ranges count from the first result, negative ones from the last
+        let (from, to) = (
+            start.unsigned_abs().min(end.unsigned_abs()) as usize,
+            start.unsigned_abs().max(end.unsigned_abs()) as usize,
+        );
+        let range = if start > 0 {
+            (from - 1).min(ids.len())..to.min(ids.len())
+        } else {
+            ids.len().saturating_sub(to)..ids.len().saturating_sub(from - 1)
+        };
+
+        PartialResult {
+            start,
+            end,
+            ids: ids[range].to_vec(),
+        }
+    }
+}
+
 impl Response {
     pub fn serialize(self, tag: &str) -> Vec<u8> {
         let mut buf = Vec::with_capacity(64);
-        if self.is_esearch {
+        if self.is_esearch && !self.groups.is_empty() {
+            // One ESEARCH response per mailbox, as in RFC 7377
+            for group in &self.groups {
+                buf.extend_from_slice(b"* ESEARCH (TAG ");
+                quoted_string(&mut buf, tag);
+                buf.extend_from_slice(b" MAILBOX ");
+                quoted_string(&mut buf, &group.mailbox_name);
+                buf.extend_from_slice(b" UIDVALIDITY ");
+                buf.extend_from_slice(group.uid_validity.to_string().as_bytes());
+                buf.extend_from_slice(b") UID COUNT ");
+                buf.extend_from_slice(group.uids.len().to_string().as_bytes());
+                if !group.uids.is_empty() {
+                    buf.extend_from_slice(b" ALL ");
+                    serialize_sequence(&mut buf, &group.uids);
+                }
+                buf.extend_from_slice(b"\r\n");
+            }
+            return buf;
+        } else if self.is_esearch {
             buf.extend_from_slice(b"* ESEARCH (TAG ");
             quoted_string(&mut buf, tag);
             buf.extend_from_slice(b")");
-            if self.is_uid {
-                buf.extend_from_slice(b" UID");
-            }
-            if let Some(count) = &self.count {
-                buf.extend_from_slice(b" COUNT ");
-                buf.extend_from_slice(count.to_string().as_bytes());
-            }
-            if let Some(min) = &self.min {
-                buf.extend_from_slice(b" MIN ");
-                buf.extend_from_slice(min.to_string().as_bytes());
-            }
-            if let Some(max) = &self.max {
-                buf.extend_from_slice(b" MAX ");
-                buf.extend_from_slice(max.to_string().as_bytes());
-            }
-            if !self.ids.is_empty() {
-                buf.extend_from_slice(b" ALL ");
-                serialize_sequence(&mut buf, &self.ids);
-            }
-            if let Some(highest_modseq) = self.highest_modseq {
-                buf.extend_from_slice(b" MODSEQ ");
-                buf.extend_from_slice(highest_modseq.to_string().as_bytes());
-            }
+            self.serialize_esearch(&mut buf);
         } else {
             if !self.is_sort {
                 buf.extend_from_slice(b"* SEARCH");
@@ -198,6 +337,121 @@ impl Response {
         buf.extend_from_slice(b"\r\n");
         buf
     }
+
+    // One ESEARCH response for a mailbox of a multi-mailbox search, as in RFC 7377
+    pub fn serialize_mailbox(&self, tag: &str, mailbox_name: &str, uid_validity: u32) -> Vec<u8> {
+        let mut buf = Vec::with_capacity(64);
+        buf.extend_from_slice(b"* ESEARCH (TAG ");
+        quoted_string(&mut buf, tag);
+        buf.extend_from_slice(b" MAILBOX ");
+        quoted_string(&mut buf, mailbox_name);
+        buf.extend_from_slice(b" UIDVALIDITY ");
+        buf.extend_from_slice(uid_validity.to_string().as_bytes());
+        buf.push(b')');
+        self.serialize_esearch(&mut buf);
+        buf.extend_from_slice(b"\r\n");
+        buf
+    }
+
+    pub fn has_results(&self) -> bool {
+        self.count.map_or_else(
+            || !self.ids.is_empty() || self.min.is_some() || self.max.is_some(),
+            |count| count > 0,
+        )
+    }
+
+    fn serialize_esearch(&self, buf: &mut Vec<u8>) {
+        if self.is_uid {
+            buf.extend_from_slice(b" UID");
+        }
+        if let Some(count) = &self.count {
+            buf.extend_from_slice(b" COUNT ");
+            buf.extend_from_slice(count.to_string().as_bytes());
+        }
+        if let Some(min) = &self.min {
+            buf.extend_from_slice(b" MIN ");
+            buf.extend_from_slice(min.to_string().as_bytes());
+        }
+        if let Some(max) = &self.max {
+            buf.extend_from_slice(b" MAX ");
+            buf.extend_from_slice(max.to_string().as_bytes());
+        }
+        if !self.ids.is_empty() {
+            buf.extend_from_slice(b" ALL ");
+            serialize_sequence(buf, &self.ids);
+        }
+        if let Some(partial) = &self.partial {
+            buf.extend_from_slice(b" PARTIAL (");
+            buf.extend_from_slice(partial.start.to_string().as_bytes());
+            buf.push(b':');
+            buf.extend_from_slice(partial.end.to_string().as_bytes());
+            if !partial.ids.is_empty() {
+                buf.push(b' ');
+                serialize_sequence(buf, &partial.ids);
+            } else {
+                buf.extend_from_slice(b" NIL");
+            }
+            buf.push(b')');
+        }
+        if let Some(highest_modseq) = self.highest_modseq {
+            buf.extend_from_slice(b" MODSEQ ");
+            buf.extend_from_slice(highest_modseq.to_string().as_bytes());
+        }
+        if !self.matches.is_empty() {
+            buf.extend_from_slice(b" X-MATCHES (");
+            for (pos, item) in self.matches.iter().enumerate() {
+                if pos > 0 {
+                    buf.push(b' ');
+                }
+                buf.extend_from_slice(item.id.to_string().as_bytes());
+                buf.extend_from_slice(b" (");
+                for (pos, (property, term)) in item.terms.iter().enumerate() {
+                    if pos > 0 {
+                        buf.push(b' ');
+                    }
+                    buf.extend_from_slice(property.as_str().as_bytes());
+                    buf.push(b' ');
+                    quoted_string(buf, term);
+                }
+                buf.push(b')');
+            }
+            buf.push(b')');
+        }
+        if !self.snippets.is_empty() {
+            buf.extend_from_slice(b" X-SNIPPETS (");
+            for (pos, snippet) in self.snippets.iter().enumerate() {
+                if pos > 0 {
+                    buf.push(b' ');
+                }
+                buf.extend_from_slice(snippet.id.to_string().as_bytes());
+                buf.push(b' ');
+                quoted_string(buf, &snippet.text);
+            }
+            buf.push(b')');
+        }
+        if let Some(buckets) = &self.change_buckets {
+            buf.extend_from_slice(
+                format!(
+                    " X-CHANGEBUCKETS (DAY {} WEEK {} MONTH {} OLDER {})",
+                    buckets.day, buckets.week, buckets.month, buckets.older
+                )
+                .as_bytes(),
+            );
+        }
+    }
+}
+
+impl MatchProperty {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            MatchProperty::From => "FROM",
+            MatchProperty::To => "TO",
+            MatchProperty::Cc => "CC",
+            MatchProperty::Bcc => "BCC",
+            MatchProperty::Subject => "SUBJECT",
+            MatchProperty::Body => "BODY",
+        }
+    }
 }
 
 #[cfg(test)]
@@ -216,6 +470,11 @@ mod tests {
                     max: 11.into(),
                     count: 3.into(),
                     highest_modseq: None,
+                    matches: vec![],
+                    change_buckets: None,
+                    groups: vec![],
+                    partial: None,
+                    snippets: vec![],
                 },
                 "A283",
                 concat!("* ESEARCH (TAG \"A283\") COUNT 3 MIN 2 MAX 11 ALL 2,10:11\r\n",),
@@ -233,6 +492,11 @@ mod tests {
                     max: None,
                     count: None,
                     highest_modseq: None,
+                    matches: vec![],
+                    change_buckets: None,
+                    groups: vec![],
+                    partial: None,
+                    snippets: vec![],
                 },
                 "A283",
                 concat!("* ESEARCH (TAG \"A283\") ALL 1:3,5,10:13,90,92:99\r\n",),
@@ -248,6 +512,11 @@ mod tests {
                     max: None,
                     count: None,
                     highest_modseq: None,
+                    matches: vec![],
+                    change_buckets: None,
+                    groups: vec![],
+                    partial: None,
+                    snippets: vec![],
                 },
                 "A283",
                 concat!("* ESEARCH (TAG \"A283\")\r\n",),
@@ -263,11 +532,209 @@ mod tests {
                     max: None,
                     count: None,
                     highest_modseq: 12345.into(),
+                    matches: vec![],
+                    change_buckets: None,
+                    groups: vec![],
+                    partial: None,
+                    snippets: vec![],
                 },
                 "A283",
                 concat!("* ESEARCH (TAG \"A283\") ALL 10:13,21 MODSEQ 12345\r\n",),
                 concat!("* SEARCH 10 11 12 13 21 (MODSEQ 12345)\r\n",),
             ),
+            (
+                super::Response {
+                    is_uid: true,
+                    is_esearch: true,
+                    is_sort: false,
+                    ids: vec![4, 9],
+                    min: None,
+                    max: None,
+                    count: None,
+                    highest_modseq: None,
+                    matches: vec![
+                        super::Match {
+                            id: 4,
+                            terms: vec![
+                                (super::MatchProperty::Subject, "hello".to_string()),
+                                (super::MatchProperty::Body, "hello".to_string()),
+                            ],
+                        },
+                        super::Match {
+                            id: 9,
+                            terms: vec![(super::MatchProperty::From, "jane".to_string())],
+                        },
+                    ],
+                    change_buckets: None,
+                    groups: vec![],
+                    partial: None,
+                    snippets: vec![],
+                },
+                "A284",
+                concat!(
+                    "* ESEARCH (TAG \"A284\") UID ALL 4,9 X-MATCHES ",
+                    "(4 (SUBJECT \"hello\" BODY \"hello\") 9 (FROM \"jane\"))\r\n",
+                ),
+                concat!("* SEARCH 4 9\r\n",),
+            ),
+            (
+                super::Response {
+                    is_uid: true,
+                    is_esearch: true,
+                    is_sort: false,
+                    ids: vec![],
+                    min: None,
+                    max: None,
+                    count: 12.into(),
+                    highest_modseq: None,
+                    matches: vec![],
+                    change_buckets: Some(super::ChangeBuckets {
+                        day: 2,
+                        week: 3,
+                        month: 0,
+                        older: 7,
+                    }),
+                    groups: vec![],
+                    partial: None,
+                    snippets: vec![],
+                },
+                "A285",
+                concat!(
+                    "* ESEARCH (TAG \"A285\") UID COUNT 12 ",
+                    "X-CHANGEBUCKETS (DAY 2 WEEK 3 MONTH 0 OLDER 7)\r\n",
+                ),
+                concat!("* SEARCH\r\n",),
+            ),
+            (
+                super::Response {
+                    is_uid: true,
+                    is_esearch: true,
+                    is_sort: false,
+                    ids: vec![3, 7, 8],
+                    min: None,
+                    max: None,
+                    count: None,
+                    highest_modseq: None,
+                    matches: vec![],
+                    change_buckets: None,
+                    groups: vec![
+                        super::MailboxGroup {
+                            mailbox_name: "INBOX".to_string(),
+                            uid_validity: 1234,
+                            uids: vec![1, 2, 3],
+                        },
+                        super::MailboxGroup {
+                            mailbox_name: "Sent Items".to_string(),
+                            uid_validity: 5678,
+                            uids: vec![10],
+                        },
+                    ],
+                    partial: None,
+                    snippets: vec![],
+                },
+                "A286",
+                concat!(
+                    "* ESEARCH (TAG \"A286\" MAILBOX \"INBOX\" UIDVALIDITY 1234) UID COUNT 3 ALL 1:3\r\n",
+                    "* ESEARCH (TAG \"A286\" MAILBOX \"Sent Items\" UIDVALIDITY 5678) UID COUNT 1 ALL 10\r\n",
+                ),
+                concat!("* SEARCH 3 7 8\r\n",),
+            ),
+            (
+                super::Response {
+                    is_uid: true,
+                    is_esearch: true,
+                    is_sort: true,
+                    ids: vec![],
+                    min: None,
+                    max: None,
+                    count: 10.into(),
+                    highest_modseq: None,
+                    matches: vec![],
+                    change_buckets: None,
+                    groups: vec![],
+                    partial: Some(super::PartialResult::new(
+                        2,
+                        4,
+                        &[6, 4, 5, 1, 10, 9, 3, 7, 8, 2],
+                    )),
+                    snippets: vec![],
+                },
+                "A287",
+                concat!("* ESEARCH (TAG \"A287\") UID COUNT 10 PARTIAL (2:4 4:5,1)\r\n",),
+                concat!("* SORT\r\n",),
+            ),
+            (
+                super::Response {
+                    is_uid: true,
+                    is_esearch: true,
+                    is_sort: false,
+                    ids: vec![],
+                    min: None,
+                    max: None,
+                    count: 3.into(),
+                    highest_modseq: None,
+                    matches: vec![],
+                    change_buckets: None,
+                    groups: vec![],
+                    partial: Some(super::PartialResult::new(-1, -2, &[1, 5, 9])),
+                    snippets: vec![],
+                },
+                "A288",
+                concat!("* ESEARCH (TAG \"A288\") UID COUNT 3 PARTIAL (-1:-2 5,9)\r\n",),
+                concat!("* SEARCH\r\n",),
+            ),
+            (
+                super::Response {
+                    is_uid: true,
+                    is_esearch: true,
+                    is_sort: false,
+                    ids: vec![],
+                    min: None,
+                    max: None,
+                    count: 3.into(),
+                    highest_modseq: None,
+                    matches: vec![],
+                    change_buckets: None,
+                    groups: vec![],
+                    partial: Some(super::PartialResult::new(20, 30, &[1, 5, 9])),
+                    snippets: vec![],
+                },
+                "A289",
+                concat!("* ESEARCH (TAG \"A289\") UID COUNT 3 PARTIAL (20:30 NIL)\r\n",),
+                concat!("* SEARCH\r\n",),
+            ),
+            (
+                super::Response {
+                    is_uid: true,
+                    is_esearch: true,
+                    is_sort: false,
+                    ids: vec![],
+                    min: None,
+                    max: None,
+                    count: None,
+                    highest_modseq: None,
+                    matches: vec![],
+                    change_buckets: None,
+                    groups: vec![],
+                    partial: None,
+                    snippets: vec![
+                        super::Snippet {
+                            id: 4,
+                            text: "over the <mark>lazy</mark> dog".to_string(),
+                        },
+                        super::Snippet {
+                            id: 7,
+                            text: "a \"quoted\" <mark>lazy</mark> cat".to_string(),
+                        },
+                    ],
+                },
+                "A290",
+                concat!(
+                    "* ESEARCH (TAG \"A290\") UID X-SNIPPETS (4 \"over the <mark>lazy</mark> dog\" ",
+                    "7 \"a \\\"quoted\\\" <mark>lazy</mark> cat\")\r\n",
+                ),
+                concat!("* SEARCH\r\n",),
+            ),
         ] {
             let response_v2 = String::from_utf8(response.clone().serialize(tag)).unwrap();
             response.is_esearch = false;
@@ -39,7 +39,58 @@ pub enum Algorithm {
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Response {
     pub is_uid: bool,
-    pub threads: Vec<Vec<u32>>,
+    pub threads: Vec<Thread>,
+}
+
+// A node without an id is a placeholder parent for sibling roots
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Thread {
+    pub id: Option<u32>,
+    pub children: Vec<Thread>,
+}
+
+impl Thread {
+    pub fn new(id: u32, children: Vec<Thread>) -> Self {
+        Thread {
+            id: Some(id),
+            children,
+        }
+    }
+
+    pub fn placeholder(children: Vec<Thread>) -> Self {
+        Thread { id: None, children }
+    }
+
+    fn serialize(&self, buf: &mut Vec<u8>) {
+        buf.push(b'(');
+        let mut node = self;
+        let mut is_first = true;
+        loop {
+            if let Some(id) = node.id {
+                if !is_first {
+                    buf.push(b' ');
+                }
+                buf.extend_from_slice(id.to_string().as_bytes());
+                is_first = false;
+            }
+            match node.children.len() {
+                0 => break,
+                1 => {
+                    node = &node.children[0];
+                }
+                _ => {
+                    if !is_first {
+                        buf.push(b' ');
+                    }
+                    for child in &node.children {
+                        child.serialize(buf);
+                    }
+                    break;
+                }
+            }
+        }
+        buf.push(b')');
+    }
 }
 
 impl ImapResponse for Response {
@@ -47,14 +98,7 @@ impl ImapResponse for Response {
         let mut buf = Vec::with_capacity(64);
         buf.extend_from_slice(b"* THREAD ");
         for thread in &self.threads {
-            buf.push(b'(');
-            for (pos, id) in thread.iter().enumerate() {
-                if pos > 0 {
-                    buf.push(b' ');
-                }
-                buf.extend_from_slice(id.to_string().as_bytes());
-            }
-            buf.push(b')');
+            thread.serialize(&mut buf);
         }
         buf.extend_from_slice(b"\r\n");
         buf
@@ -63,6 +107,7 @@ impl ImapResponse for Response {
 
 #[cfg(test)]
 mod tests {
+    use super::Thread;
     use crate::protocol::ImapResponse;
 
     #[test]
@@ -71,12 +116,20 @@ mod tests {
             String::from_utf8(
                 super::Response {
                     is_uid: true,
-                    threads: vec![vec![2, 10, 11], vec![49], vec![1, 3]],
+                    threads: vec![
+                        Thread::new(2, vec![Thread::new(10, vec![Thread::new(11, vec![])])]),
+                        Thread::new(49, vec![]),
+                        Thread::new(1, vec![Thread::new(3, vec![]), Thread::new(4, vec![])]),
+                        Thread::placeholder(vec![
+                            Thread::new(5, vec![]),
+                            Thread::new(6, vec![Thread::new(7, vec![]), Thread::new(8, vec![])]),
+                        ]),
+                    ],
                 }
                 .serialize()
             )
             .unwrap(),
-            concat!("* THREAD (2 10 11)(49)(1 3)\r\n",)
+            concat!("* THREAD (2 10 11)(49)(1 (3)(4))((5)(6 (7)(8)))\r\n",)
         );
     }
 }
@@ -194,6 +194,9 @@ impl<T: AsyncRead> Session<T> {
                 Command::Id => {
                     self.handle_id(request).await?;
                 }
+                Command::ESearch => {
+                    self.handle_esearch(request).await?;
+                }
             }
         }
 
@@ -238,6 +241,31 @@ impl<T: AsyncRead> Session<T> {
                     .with_tag(request.tag)
                     .with_code(ResponseCode::Limit));
             }
+
+            // Accounts with an expired password are limited to read-only access
+            if data.is_read_only
+                && matches!(
+                    request.command,
+                    Command::Create
+                        | Command::Delete
+                        | Command::Rename
+                        | Command::Subscribe
+                        | Command::Unsubscribe
+                        | Command::Append
+                        | Command::Store(_)
+                        | Command::Copy(_)
+                        | Command::Move(_)
+                        | Command::Expunge(_)
+                        | Command::SetAcl
+                        | Command::DeleteAcl
+                )
+            {
+                return Err(StatusResponse::no(
+                    "Password expired, the account is read-only until it is changed.",
+                )
+                .with_tag(request.tag)
+                .with_code(ResponseCode::NoPerm));
+            }
         }
 
         match &request.command {
@@ -289,7 +317,8 @@ impl<T: AsyncRead> Session<T> {
             | Command::GetAcl
             | Command::ListRights
             | Command::MyRights
-            | Command::Unauthenticate => {
+            | Command::Unauthenticate
+            | Command::ESearch => {
                 if let State::Authenticated { .. } | State::Selected { .. } = state {
                     Ok(request)
                 } else {
@@ -33,6 +33,7 @@ impl SessionData {
             state: access_token.state().into(),
             mailbox_locks: MutexMap::with_capacity(5),
             in_flight,
+            is_read_only: access_token.password_expired,
         };
 
         // Fetch mailboxes for the main account
@@ -149,6 +150,7 @@ impl SessionData {
             prefix: mailbox_prefix,
             mailbox_names: BTreeMap::new(),
             mailbox_state: AHashMap::with_capacity(mailboxes.len()),
+            saved_queries: BTreeMap::new(),
             state_mailbox: self
                 .jmap
                 .store
@@ -161,8 +163,23 @@ impl SessionData {
                 .get_last_change_id(account_id, Collection::Email)
                 .await
                 .map_err(|_| {})?,
+            state_saved_query: self
+                .jmap
+                .store
+                .get_last_change_id(account_id, Collection::SavedQuery)
+                .await
+                .map_err(|_| {})?,
         };
 
+        // Saved queries of the primary account are listed as virtual mailboxes
+        if account.prefix.is_none() {
+            for (query_id, name) in self.jmap.saved_queries(account_id).await.map_err(|_| {})? {
+                account
+                    .saved_queries
+                    .insert(format!("{}/{}", self.imap.name_saved, name), query_id);
+            }
+        }
+
         loop {
             while let Some((mailbox_id, mailbox_parent_id, mailbox)) = iter.next() {
                 if *mailbox_parent_id == parent_id {
@@ -345,9 +362,9 @@ impl SessionData {
             .mailboxes
             .lock()
             .iter()
-            .map(|m| (m.account_id, m.state_mailbox))
+            .map(|m| (m.account_id, m.state_mailbox, m.state_saved_query))
             .collect::<Vec<_>>();
-        for (account_id, last_state) in account_states {
+        for (account_id, last_state, last_saved_query_state) in account_states {
             let changelog = self
                 .jmap
                 .changes_(
@@ -356,8 +373,22 @@ impl SessionData {
                     last_state.map(Query::Since).unwrap_or(Query::All),
                 )
                 .await?;
-            if !changelog.changes.is_empty() {
-                let mut has_changes = false;
+
+            // Saved queries are only listed for the primary account
+            let has_saved_query_changes = access_token.is_primary_id(account_id)
+                && self
+                    .jmap
+                    .store
+                    .get_last_change_id(account_id, Collection::SavedQuery)
+                    .await
+                    .map_err(|e| {
+                        tracing::warn!(parent: &self.span, "Failed to get last change id for saved query collection: {}", e);
+                        StatusResponse::database_failure()
+                    })?
+                    != last_saved_query_state;
+
+            if !changelog.changes.is_empty() || has_saved_query_changes {
+                let mut has_changes = has_saved_query_changes;
                 let mut has_child_changes = false;
 
                 for change in changelog.changes {
@@ -463,6 +494,18 @@ impl SessionData {
                                 changes.deleted.push(mailbox_name.to_string());
                             }
                         }
+
+                        // Add new and deleted saved queries
+                        for mailbox_name in new_account.saved_queries.keys() {
+                            if !old_account.saved_queries.contains_key(mailbox_name) {
+                                changes.added.push(mailbox_name.to_string());
+                            }
+                        }
+                        for mailbox_name in old_account.saved_queries.keys() {
+                            if !new_account.saved_queries.contains_key(mailbox_name) {
+                                changes.deleted.push(mailbox_name.to_string());
+                            }
+                        }
                     }
 
                     mailboxes[pos] = changed_account;
@@ -508,10 +551,19 @@ impl SessionData {
                             return MailboxId {
                                 account_id: account.account_id,
                                 mailbox_id: Some(*mailbox_id_),
+                                query_id: None,
                             }
                             .into();
                         }
                     }
+                    if let Some(query_id) = account.saved_queries.get(mailbox_name) {
+                        return MailboxId {
+                            account_id: account.account_id,
+                            mailbox_id: None,
+                            query_id: Some(*query_id),
+                        }
+                        .into();
+                    }
                 }
             }
             None
@@ -519,6 +571,7 @@ impl SessionData {
             MailboxId {
                 account_id: self.account_id,
                 mailbox_id: None,
+                query_id: None,
             }
             .into()
         }
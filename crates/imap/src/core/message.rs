@@ -72,12 +72,13 @@ impl SessionData {
             let _guard = self.mailbox_locks.lock_hash(mailbox).await;
 
             // Deserialize mailbox data
+            let (uid_map_collection, uid_map_id) = mailbox.uid_map_id();
             let uid_map = self
                 .jmap
                 .get_property::<HashedValue<UidMap>>(
                     mailbox.account_id,
-                    Collection::Mailbox,
-                    mailbox.mailbox_id.unwrap_or(u32::MAX),
+                    uid_map_collection,
+                    uid_map_id,
                     Property::EmailIds,
                 )
                 .await?;
@@ -109,6 +110,11 @@ impl SessionData {
                     )
                     .await?
                     .unwrap_or_default()
+            } else if let Some(query_id) = mailbox.query_id {
+                self.jmap
+                    .saved_query_ids(mailbox.account_id, query_id)
+                    .await?
+                    .ok_or_else(|| StatusResponse::no("Saved search no longer exists."))?
             } else {
                 self.jmap
                     .get_document_ids(mailbox.account_id, Collection::Email)
@@ -193,8 +199,8 @@ impl SessionData {
                     let mut batch = BatchBuilder::new();
                     batch
                         .with_account_id(mailbox.account_id)
-                        .with_collection(Collection::Mailbox)
-                        .update_document(mailbox.mailbox_id.unwrap_or(u32::MAX))
+                        .with_collection(uid_map_collection)
+                        .update_document(uid_map_id)
                         .assert_value(Property::EmailIds, &uid_map)
                         .value(Property::EmailIds, &uid_map.inner, F_VALUE);
 
@@ -208,7 +214,7 @@ impl SessionData {
                             tracing::error!(event = "error",
                                             context = "store",
                                             account_id = mailbox.account_id,
-                                            collection = ?Collection::Mailbox,
+                                            collection = ?uid_map_collection,
                                             error = ?err,
                                             "Failed to update uid map");
                             return Err(StatusResponse::database_failure());
@@ -245,7 +251,8 @@ impl SessionData {
                 });
             } else {
                 let uid_next = (id_list.len() + 1) as u32;
-                let uid_validity = now() as u32 ^ mailbox.mailbox_id.unwrap_or(0);
+                let uid_validity =
+                    now() as u32 ^ mailbox.mailbox_id.or(mailbox.query_id).unwrap_or(0);
                 let mut id_to_imap = AHashMap::with_capacity(id_list.len());
                 let mut uid_to_id = AHashMap::with_capacity(id_list.len());
                 let mut uids = Vec::with_capacity(id_list.len());
@@ -268,8 +275,8 @@ impl SessionData {
                 let mut batch = BatchBuilder::new();
                 batch
                     .with_account_id(mailbox.account_id)
-                    .with_collection(Collection::Mailbox)
-                    .update_document(mailbox.mailbox_id.unwrap_or(u32::MAX))
+                    .with_collection(uid_map_collection)
+                    .update_document(uid_map_id)
                     .assert_value(Property::EmailIds, ())
                     .value(Property::EmailIds, &uid_map, F_VALUE);
 
@@ -283,7 +290,7 @@ impl SessionData {
                         tracing::error!(event = "error",
                                             context = "store",
                                             account_id = mailbox.account_id,
-                                            collection = ?Collection::Mailbox,
+                                            collection = ?uid_map_collection,
                                             error = ?err,
                                             "Failed to store new uid map");
                         return Err(StatusResponse::database_failure());
@@ -445,9 +452,11 @@ impl SelectedMailbox {
             let mut ids = AHashMap::with_capacity(saved_ids.len());
             let state = self.state.lock();
 
+            // Resolve to the current sequence numbers, skipping messages
+            // expunged after the search was saved
             for imap_id in saved_ids.iter() {
                 if let Some(id) = state.uid_to_id.get(&imap_id.uid) {
-                    ids.insert(*id, *imap_id);
+                    ids.insert(*id, state.id_to_imap.get(id).copied().unwrap_or(*imap_id));
                 }
             }
 
@@ -41,6 +41,7 @@ use jmap::{
     },
     JMAP,
 };
+use jmap_proto::types::collection::Collection;
 use parking_lot::Mutex;
 use tokio::{
     io::{AsyncRead, ReadHalf},
@@ -76,12 +77,22 @@ pub struct IMAP {
     pub name_shared: String,
     pub name_all: String,
     pub name_all_enable: bool,
+    pub name_saved: String,
     pub allow_plain_auth: bool,
     pub enable_uidplus: bool,
 
     pub timeout_auth: Duration,
     pub timeout_unauth: Duration,
     pub timeout_idle: Duration,
+    pub search_deadline: Option<Duration>,
+    pub search_all_exclude: Vec<String>,
+    pub search_label_keywords: bool,
+    pub search_any_header: bool,
+    pub search_timezone: i32,
+    pub search_thread_max: usize,
+    pub snippet_length: usize,
+    pub snippet_max_results: usize,
+    pub snippet_highlight: (String, String),
 
     pub greeting_plain: Vec<u8>,
     pub greeting_tls: Vec<u8>,
@@ -101,6 +112,7 @@ pub struct Session<T: AsyncRead> {
     pub is_tls: bool,
     pub is_condstore: bool,
     pub is_qresync: bool,
+    pub timezone: i32,
     pub writer: mpsc::Sender<writer::Event>,
     pub stream_rx: ReadHalf<T>,
     pub in_flight: InFlight,
@@ -118,6 +130,7 @@ pub struct SessionData {
     pub writer: mpsc::Sender<writer::Event>,
     pub state: AtomicU32,
     pub in_flight: InFlight,
+    pub is_read_only: bool,
 }
 
 #[derive(Debug, Default)]
@@ -139,8 +152,10 @@ pub struct Account {
     pub prefix: Option<String>,
     pub mailbox_names: BTreeMap<String, u32>,
     pub mailbox_state: AHashMap<u32, Mailbox>,
+    pub saved_queries: BTreeMap<String, u32>,
     pub state_email: Option<u64>,
     pub state_mailbox: Option<u64>,
+    pub state_saved_query: Option<u64>,
 }
 
 pub struct SelectedMailbox {
@@ -155,6 +170,7 @@ pub struct SelectedMailbox {
 pub struct MailboxId {
     pub account_id: u32,
     pub mailbox_id: Option<u32>,
+    pub query_id: Option<u32>,
 }
 
 #[derive(Debug)]
@@ -222,3 +238,24 @@ impl SessionData {
             })
     }
 }
+
+impl MailboxId {
+    // Key used for the status cache and MAILBOXID, saved queries are numbered
+    // downwards from the "All Mail" folder.
+    pub fn state_id(&self) -> u32 {
+        match (self.mailbox_id, self.query_id) {
+            (Some(mailbox_id), _) => mailbox_id,
+            (None, Some(query_id)) => u32::MAX - 1 - query_id,
+            (None, None) => u32::MAX,
+        }
+    }
+
+    // Saved queries store their UID map in the saved query document, so that
+    // it is removed when the query is destroyed.
+    pub fn uid_map_id(&self) -> (Collection, u32) {
+        match (self.mailbox_id, self.query_id) {
+            (None, Some(query_id)) => (Collection::SavedQuery, query_id),
+            (mailbox_id, _) => (Collection::Mailbox, mailbox_id.unwrap_or(u32::MAX)),
+        }
+    }
+}
@@ -131,6 +131,7 @@ impl Session<TcpStream> {
             is_tls: false,
             is_condstore: false,
             is_qresync: false,
+            timezone: manager.imap.search_timezone,
             imap: manager.imap,
             jmap: manager.jmap,
             instance: session.instance,
@@ -181,6 +182,7 @@ impl Session<TcpStream> {
             is_tls: true,
             is_condstore: self.is_condstore,
             is_qresync: self.is_qresync,
+            timezone: self.timezone,
             writer: self.writer,
             span: self.span,
             in_flight: self.in_flight,
@@ -220,6 +222,7 @@ impl Session<TlsStream<TcpStream>> {
             is_tls: true,
             is_condstore: false,
             is_qresync: false,
+            timezone: manager.imap.search_timezone,
             imap: manager.imap,
             jmap: manager.jmap,
             instance: session.instance,
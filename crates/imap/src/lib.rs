@@ -52,9 +52,39 @@ impl IMAP {
                 .value("imap.folders.name.all")
                 .unwrap_or("All Mail")
                 .to_string(),
+            name_saved: config
+                .value("imap.folders.name.saved")
+                .unwrap_or("Saved Searches")
+                .to_string(),
             timeout_auth: config.property_or_static("imap.timeout.authenticated", "30m")?,
             timeout_unauth: config.property_or_static("imap.timeout.anonymous", "1m")?,
             timeout_idle: config.property_or_static("imap.timeout.idle", "30m")?,
+            search_deadline: config.property("imap.search.deadline")?,
+            search_all_exclude: config
+                .values("imap.search.all-mail.exclude")
+                .map(|(_, v)| v.to_string())
+                .collect(),
+            search_label_keywords: config
+                .property_or_static("imap.search.label-keywords", "false")?,
+            search_any_header: config.property_or_static("imap.search.any-header", "false")?,
+            search_timezone: parse_timezone(
+                config.value("imap.search.timezone").unwrap_or("+00:00"),
+            )?,
+            search_thread_max: config
+                .property_or_static("imap.search.thread-size.max-threads", "1000")?,
+            snippet_length: config.property_or_static("imap.search.snippet.length", "100")?,
+            snippet_max_results: config
+                .property_or_static("imap.search.snippet.max-results", "100")?,
+            snippet_highlight: (
+                config
+                    .value("imap.search.snippet.highlight.start")
+                    .unwrap_or("<mark>")
+                    .to_string(),
+                config
+                    .value("imap.search.snippet.highlight.end")
+                    .unwrap_or("</mark>")
+                    .to_string(),
+            ),
             greeting_plain: StatusResponse::ok(SERVER_GREETING)
                 .with_code(ResponseCode::Capability {
                     capabilities: Capability::all_capabilities(false, false),
@@ -83,6 +113,34 @@ impl IMAP {
     }
 }
 
+fn parse_timezone(value: &str) -> utils::config::Result<i32> {
+    parse_utc_offset(value).ok_or_else(|| {
+        format!("Invalid timezone offset {value:?} for property \"imap.search.timezone\".")
+    })
+}
+
+// Parses a fixed UTC offset such as "+13:00" or "-0530" into seconds
+pub(crate) fn parse_utc_offset(value: &str) -> Option<i32> {
+    let value = value.trim();
+    let (sign, offset) = if let Some(offset) = value.strip_prefix('-') {
+        (-1, offset)
+    } else {
+        (1, value.strip_prefix('+').unwrap_or(value))
+    };
+    let (hours, minutes) = offset.split_once(':').unwrap_or_else(|| {
+        if offset.len() == 4 {
+            offset.split_at(2)
+        } else {
+            (offset, "0")
+        }
+    });
+
+    match (hours.parse::<i32>(), minutes.parse::<i32>()) {
+        (Ok(hours @ 0..=14), Ok(minutes @ 0..=59)) => Some(sign * (hours * 3600 + minutes * 60)),
+        _ => None,
+    }
+}
+
 pub struct ImapError;
 
 pub type Result<T> = std::result::Result<T, ()>;
@@ -23,6 +23,7 @@
 
 use std::sync::Arc;
 
+use directory::Protocol;
 use imap_proto::{
     protocol::{authenticate::Mechanism, capability::Capability},
     receiver::{self, Request},
@@ -115,32 +116,40 @@ impl<T: AsyncRead> Session<T> {
 
         // Authenticate
         let access_token = match credentials {
-            Credentials::Plain { username, secret } | Credentials::XOauth2 { username, secret } => {
+            Credentials::Plain { username, secret } => {
                 self.jmap
                     .authenticate_plain(&username, &secret, &self.remote_addr)
                     .await
             }
             Credentials::OAuthBearer { token } => {
-                match self
-                    .jmap
-                    .validate_access_token("access_token", &token)
+                self.jmap
+                    .authenticate_bearer(&token, &self.remote_addr)
+                    .await
+            }
+            credentials @ Credentials::XOauth2 { .. } => {
+                self.jmap
+                    .authenticate_credentials(&credentials, &self.remote_addr)
                     .await
-                {
-                    Ok((account_id, _, _)) => self.jmap.get_access_token(account_id).await,
-                    Err(err) => {
-                        tracing::debug!(
-                            parent: &self.span,
-                            context = "authenticate",
-                            err = err,
-                            "Failed to validate access token."
-                        );
-                        None
-                    }
-                }
             }
         };
 
         if let Some(access_token) = access_token {
+            if !access_token.is_protocol_enabled(Protocol::Imap) {
+                tracing::debug!(parent: &self.span,
+                    event = "protocol-disabled",
+                    account = access_token.name,
+                    "IMAP is not enabled for this account.",
+                );
+                return self
+                    .write_bytes(
+                        StatusResponse::no("Protocol not enabled for this account.")
+                            .with_tag(tag)
+                            .with_code(ResponseCode::AuthorizationFailed)
+                            .into_bytes(),
+                    )
+                    .await;
+            }
+
             // Enforce concurrency limits
             let in_flight = self
                 .imap
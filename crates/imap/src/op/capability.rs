@@ -32,7 +32,7 @@ use imap_proto::{
 
 use tokio::io::AsyncRead;
 
-use crate::core::Session;
+use crate::{core::Session, parse_utc_offset};
 
 impl<T: AsyncRead> Session<T> {
     pub async fn handle_capability(&mut self, request: Request<Command>) -> crate::OpResult {
@@ -53,9 +53,34 @@ impl<T: AsyncRead> Session<T> {
     }
 
     pub async fn handle_id(&mut self, request: Request<Command>) -> crate::OpResult {
+        let arguments = match request.parse_id() {
+            Ok(arguments) => arguments,
+            Err(response) => return self.write_bytes(response.into_bytes()).await,
+        };
+
+        // Clients may send their UTC offset to set the day boundaries of date searches
+        for (field, value) in &arguments.parameters {
+            if field.eq_ignore_ascii_case("timezone") {
+                if let Some(timezone) = value.as_deref().and_then(parse_utc_offset) {
+                    self.timezone = timezone;
+                } else {
+                    return self
+                        .write_bytes(
+                            StatusResponse::bad(format!(
+                                "Invalid timezone offset {:?}.",
+                                value.as_deref().unwrap_or_default()
+                            ))
+                            .with_tag(arguments.tag)
+                            .into_bytes(),
+                        )
+                        .await;
+                }
+            }
+        }
+
         self.write_bytes(
             StatusResponse::completed(Command::Id)
-                .with_tag(request.tag)
+                .with_tag(arguments.tag)
                 .serialize(
                     concat!(
                         "* ID (\"name\" \"Stalwart IMAP\" \"version\" \"",
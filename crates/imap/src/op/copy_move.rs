@@ -159,9 +159,22 @@ impl SessionData {
         {
             Ok(ids) => {
                 if ids.is_empty() {
-                    return Err(
-                        StatusResponse::no("No messages were found.").with_tag(arguments.tag)
-                    );
+                    // A saved search whose messages were all expunged is not an error
+                    return if arguments.sequence_set.is_saved_search() {
+                        self.write_bytes(
+                            StatusResponse::completed(if is_move {
+                                Command::Move(is_uid)
+                            } else {
+                                Command::Copy(is_uid)
+                            })
+                            .with_tag(arguments.tag)
+                            .into_bytes(),
+                        )
+                        .await;
+                        Ok(())
+                    } else {
+                        Err(StatusResponse::no("No messages were found.").with_tag(arguments.tag))
+                    };
                 }
                 ids
             }
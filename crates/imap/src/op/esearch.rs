@@ -0,0 +1,181 @@
+/*
+ * Copyright (c) 2020-2022, Stalwart Labs Ltd.
+ *
+ * This file is part of Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use std::sync::Arc;
+
+use imap_proto::{
+    protocol::search::{MultiArguments, ResultOption, Source},
+    receiver::Request,
+    Command, ResponseType, StatusResponse,
+};
+
+use jmap::mailbox::INBOX_ID;
+use tokio::io::AsyncRead;
+
+use crate::core::{MailboxId, SavedSearch, SelectedMailbox, Session, SessionData};
+
+impl<T: AsyncRead> Session<T> {
+    pub async fn handle_esearch(&mut self, request: Request<Command>) -> crate::OpResult {
+        match request.parse_esearch(self.version) {
+            Ok(mut arguments) => {
+                let (data, selected) = self.state.session_mailbox_state();
+                arguments.arguments.timezone = self.timezone;
+
+                tokio::spawn(async move {
+                    let tag = std::mem::take(&mut arguments.arguments.tag);
+                    let bytes = match data.esearch(arguments, selected, &tag).await {
+                        Ok(_) => StatusResponse::completed(Command::ESearch)
+                            .with_tag(tag)
+                            .into_bytes(),
+                        Err(response) => response.with_tag(tag).into_bytes(),
+                    };
+                    data.write_bytes(bytes).await;
+                });
+                Ok(())
+            }
+            Err(response) => self.write_bytes(response.into_bytes()).await,
+        }
+    }
+}
+
+impl SessionData {
+    pub async fn esearch(
+        &self,
+        arguments: MultiArguments,
+        selected: Option<Arc<SelectedMailbox>>,
+        tag: &str,
+    ) -> Result<(), StatusResponse> {
+        if arguments
+            .arguments
+            .result_options
+            .contains(&ResultOption::Save)
+        {
+            return Err(StatusResponse::bad(
+                "SAVE is not supported in multi-mailbox searches.",
+            ));
+        }
+
+        // Refresh mailboxes
+        self.synchronize_mailboxes(false).await?;
+
+        for (mailbox_name, mailbox_id) in self.esearch_mailboxes(&arguments.sources, &selected) {
+            // Skip mailboxes that were deleted while searching
+            let state = match self.fetch_messages(&mailbox_id).await {
+                Ok(state) => state,
+                Err(_) => {
+                    tracing::debug!(parent: &self.span,
+                        event = "skip",
+                        context = "esearch",
+                        mailbox = %mailbox_name,
+                        "Mailbox not available for search.");
+                    continue;
+                }
+            };
+            let uid_validity = state.uid_validity;
+            let mailbox = Arc::new(SelectedMailbox {
+                id: mailbox_id,
+                state: parking_lot::Mutex::new(state),
+                saved_search: parking_lot::Mutex::new(SavedSearch::None),
+                is_select: false,
+                is_condstore: false,
+            });
+
+            match self
+                .search(arguments.arguments.clone(), mailbox, None, None, true)
+                .await
+            {
+                Ok((response, _)) => {
+                    if response.has_results() {
+                        self.write_bytes(response.serialize_mailbox(
+                            tag,
+                            &mailbox_name,
+                            uid_validity,
+                        ))
+                        .await;
+                    }
+                }
+                Err(response) if response.rtype == ResponseType::Bad => {
+                    return Err(response);
+                }
+                Err(_) => {
+                    tracing::debug!(parent: &self.span,
+                        event = "skip",
+                        context = "esearch",
+                        mailbox = %mailbox_name,
+                        "Mailbox search failed.");
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn esearch_mailboxes(
+        &self,
+        sources: &[Source],
+        selected: &Option<Arc<SelectedMailbox>>,
+    ) -> Vec<(String, MailboxId)> {
+        let mut mailboxes: Vec<(String, MailboxId)> = Vec::new();
+        for account in self.mailboxes.lock().iter() {
+            for (mailbox_name, mailbox_id) in account.mailbox_names.iter() {
+                let is_match = sources.iter().any(|source| match source {
+                    Source::Selected => selected.as_ref().map_or(false, |selected| {
+                        selected.id.account_id == account.account_id
+                            && selected.id.mailbox_id == Some(*mailbox_id)
+                    }),
+                    Source::Mailboxes(names) => names.iter().any(|name| {
+                        name == mailbox_name
+                            || (*mailbox_id == INBOX_ID
+                                && account.prefix.is_none()
+                                && name.eq_ignore_ascii_case("inbox"))
+                    }),
+                    Source::Subtree(names) => names.iter().any(|name| {
+                        name == mailbox_name
+                            || mailbox_name
+                                .strip_prefix(name.as_str())
+                                .map_or(false, |child| child.starts_with('/'))
+                    }),
+                    Source::SubtreeOne(names) => names.iter().any(|name| {
+                        name == mailbox_name
+                            || mailbox_name
+                                .strip_prefix(name.as_str())
+                                .and_then(|child| child.strip_prefix('/'))
+                                .map_or(false, |child| !child.is_empty() && !child.contains('/'))
+                    }),
+                });
+
+                if is_match {
+                    mailboxes.push((
+                        mailbox_name.clone(),
+                        MailboxId {
+                            account_id: account.account_id,
+                            mailbox_id: Some(*mailbox_id),
+                            query_id: None,
+                        },
+                    ));
+                }
+            }
+        }
+        mailboxes
+    }
+}
@@ -551,6 +551,7 @@ impl SessionData {
                 match self.jmap.write_batch(batch).await {
                     Ok(_) => {
                         changelog.log_update(Collection::Email, id);
+                        changelog.log_tag_set(Collection::Email, id, Keyword::Seen);
                     }
                     Err(MethodError::ServerUnavailable) => {}
                     Err(_) => {
@@ -32,10 +32,34 @@ use imap_proto::{
     Command, StatusResponse,
 };
 
+use std::ops::Bound;
+
 use tokio::io::AsyncRead;
 
 use crate::core::{Session, SessionData};
 
+const LIST_WINDOW_SIZE: usize = 100;
+
+struct ListOptions {
+    patterns: Vec<String>,
+    filter_subscribed: bool,
+    filter_special_use: bool,
+    recursive_match: bool,
+    include_special_use: bool,
+    include_subscribed: bool,
+    include_children: bool,
+}
+
+#[derive(Default)]
+struct ListCursor {
+    added_all_mail: bool,
+    added_saved_queries: bool,
+    added_shared_folder: bool,
+    added_prefix: bool,
+    account_pos: usize,
+    resume_from: Option<String>,
+}
+
 impl<T: AsyncRead> Session<T> {
     pub async fn handle_list(&mut self, request: Request<Command>) -> crate::OpResult {
         let command = request.command;
@@ -172,29 +196,127 @@ impl SessionData {
             })
         }
 
+        // Write the listing in windows so that large accounts start receiving
+        // results right away, fetching the status of each window before writing it
+        let options = ListOptions {
+            patterns,
+            filter_subscribed,
+            filter_special_use,
+            recursive_match,
+            include_special_use,
+            include_subscribed,
+            include_children,
+        };
+        let mut cursor = ListCursor::default();
+        loop {
+            let list_items = self.list_window(&options, &mut cursor, LIST_WINDOW_SIZE);
+            if list_items.is_empty() {
+                break;
+            }
+
+            let mut buf = Vec::with_capacity(list_items.len() * 64);
+            for list_item in &list_items {
+                list_item.serialize(&mut buf, version.is_rev2(), is_lsub);
+
+                // Add status response
+                if let Some(include_status) = include_status {
+                    match self
+                        .status(list_item.mailbox_name.to_string(), include_status)
+                        .await
+                    {
+                        Ok(status) => {
+                            status.serialize(&mut buf, version.is_rev2());
+                        }
+                        Err(_) => {
+                            tracing::debug!(parent: &self.span, "Failed to get mailbox status.");
+                        }
+                    }
+                }
+            }
+
+            if !self.write_bytes(buf).await {
+                return;
+            }
+        }
+
+        // Write response
+        self.write_bytes(
+            StatusResponse::completed(if !is_lsub {
+                Command::List
+            } else {
+                Command::Lsub
+            })
+            .with_tag(tag)
+            .into_bytes(),
+        )
+        .await;
+    }
+
+    fn list_window(
+        &self,
+        options: &ListOptions,
+        cursor: &mut ListCursor,
+        max_items: usize,
+    ) -> Vec<ListItem> {
         let mut list_items = Vec::with_capacity(10);
 
         // Add "All Mail" folder
-        if self.imap.name_all_enable
-            && !filter_subscribed
-            && matches_pattern(&patterns, &self.imap.name_all)
-        {
-            list_items.push(ListItem {
-                mailbox_name: self.imap.name_all.clone(),
-                attributes: vec![Attribute::All, Attribute::NoInferiors],
-                tags: vec![],
-            });
+        if !cursor.added_all_mail {
+            cursor.added_all_mail = true;
+            if self.imap.name_all_enable
+                && !options.filter_subscribed
+                && matches_pattern(&options.patterns, &self.imap.name_all)
+            {
+                list_items.push(ListItem {
+                    mailbox_name: self.imap.name_all.clone(),
+                    attributes: vec![Attribute::All, Attribute::NoInferiors],
+                    tags: vec![],
+                });
+            }
+        }
+
+        // Add saved queries of the primary account
+        let mailboxes = self.mailboxes.lock();
+        if !cursor.added_saved_queries {
+            cursor.added_saved_queries = true;
+            if let Some(account) = mailboxes.first().filter(|account| {
+                !options.filter_subscribed
+                    && !options.filter_special_use
+                    && !account.saved_queries.is_empty()
+            }) {
+                if matches_pattern(&options.patterns, &self.imap.name_saved) {
+                    list_items.push(ListItem {
+                        mailbox_name: self.imap.name_saved.clone(),
+                        attributes: if options.include_children {
+                            vec![Attribute::HasChildren, Attribute::NoSelect]
+                        } else {
+                            vec![Attribute::NoSelect]
+                        },
+                        tags: vec![],
+                    });
+                }
+                for mailbox_name in account.saved_queries.keys() {
+                    if matches_pattern(&options.patterns, mailbox_name) {
+                        list_items.push(ListItem {
+                            mailbox_name: mailbox_name.clone(),
+                            attributes: vec![Attribute::NoInferiors],
+                            tags: vec![],
+                        });
+                    }
+                }
+            }
         }
 
         // Add mailboxes
-        let mut added_shared_folder = false;
-        for account in self.mailboxes.lock().iter() {
-            if let Some(prefix) = &account.prefix {
-                if !added_shared_folder {
-                    if !filter_subscribed && matches_pattern(&patterns, &self.imap.name_shared) {
+        while let Some(account) = mailboxes.get(cursor.account_pos) {
+            if let (Some(prefix), false) = (&account.prefix, cursor.added_prefix) {
+                if !cursor.added_shared_folder {
+                    if !options.filter_subscribed
+                        && matches_pattern(&options.patterns, &self.imap.name_shared)
+                    {
                         list_items.push(ListItem {
                             mailbox_name: self.imap.name_shared.clone(),
-                            attributes: if include_children {
+                            attributes: if options.include_children {
                                 vec![Attribute::HasChildren, Attribute::NoSelect]
                             } else {
                                 vec![Attribute::NoSelect]
@@ -202,12 +324,12 @@ impl SessionData {
                             tags: vec![],
                         });
                     }
-                    added_shared_folder = true;
+                    cursor.added_shared_folder = true;
                 }
-                if !filter_subscribed && matches_pattern(&patterns, prefix) {
+                if !options.filter_subscribed && matches_pattern(&options.patterns, prefix) {
                     list_items.push(ListItem {
                         mailbox_name: prefix.clone(),
-                        attributes: if include_children {
+                        attributes: if options.include_children {
                             vec![Attribute::HasChildren, Attribute::NoSelect]
                         } else {
                             vec![Attribute::NoSelect]
@@ -216,12 +338,26 @@ impl SessionData {
                     });
                 }
             }
+            cursor.added_prefix = true;
 
-            for (mailbox_name, mailbox_id) in &account.mailbox_names {
-                if matches_pattern(&patterns, mailbox_name) {
+            let mailbox_names = if let Some(resume_from) = &cursor.resume_from {
+                account
+                    .mailbox_names
+                    .range::<str, _>((Bound::Included(resume_from.as_str()), Bound::Unbounded))
+            } else {
+                account.mailbox_names.range::<str, _>(..)
+            };
+
+            for (mailbox_name, mailbox_id) in mailbox_names {
+                if list_items.len() >= max_items {
+                    cursor.resume_from = Some(mailbox_name.clone());
+                    return list_items;
+                }
+
+                if matches_pattern(&options.patterns, mailbox_name) {
                     let mailbox = account.mailbox_state.get(mailbox_id).unwrap();
                     let mut has_recursive_match = false;
-                    if recursive_match {
+                    if options.recursive_match {
                         let prefix = format!("{}/", mailbox_name);
                         for (mailbox_name, mailbox_id) in &account.mailbox_names {
                             if mailbox_name.starts_with(&prefix)
@@ -232,22 +368,22 @@ impl SessionData {
                             }
                         }
                     }
-                    if !filter_subscribed || mailbox.is_subscribed || has_recursive_match {
+                    if !options.filter_subscribed || mailbox.is_subscribed || has_recursive_match {
                         let mut attributes = Vec::with_capacity(2);
-                        if include_children {
+                        if options.include_children {
                             attributes.push(if mailbox.has_children {
                                 Attribute::HasChildren
                             } else {
                                 Attribute::HasNoChildren
                             });
                         }
-                        if include_subscribed && mailbox.is_subscribed {
+                        if options.include_subscribed && mailbox.is_subscribed {
                             attributes.push(Attribute::Subscribed);
                         }
-                        if include_special_use {
+                        if options.include_special_use {
                             if let Some(special_use) = &mailbox.special_use {
                                 attributes.push(*special_use);
-                            } else if filter_special_use {
+                            } else if options.filter_special_use {
                                 continue;
                             }
                         }
@@ -263,45 +399,13 @@ impl SessionData {
                     }
                 }
             }
-        }
 
-        // Add status response
-        let mut status_items = Vec::new();
-        if let Some(include_status) = include_status {
-            for list_item in &list_items {
-                match self
-                    .status(list_item.mailbox_name.to_string(), include_status)
-                    .await
-                {
-                    Ok(status) => {
-                        status_items.push(status);
-                    }
-                    Err(_) => {
-                        tracing::debug!(parent: &self.span, "Failed to get mailbox status.");
-                    }
-                }
-            }
+            cursor.account_pos += 1;
+            cursor.added_prefix = false;
+            cursor.resume_from = None;
         }
 
-        // Write response
-        self.write_bytes(
-            StatusResponse::completed(if !is_lsub {
-                Command::List
-            } else {
-                Command::Lsub
-            })
-            .with_tag(tag)
-            .serialize(
-                list::Response {
-                    is_rev2: version.is_rev2(),
-                    is_lsub,
-                    list_items,
-                    status_items,
-                }
-                .serialize(),
-            ),
-        )
-        .await;
+        list_items
     }
 }
 
@@ -33,6 +33,7 @@ pub mod copy_move;
 pub mod create;
 pub mod delete;
 pub mod enable;
+pub mod esearch;
 pub mod expunge;
 pub mod fetch;
 pub mod idle;
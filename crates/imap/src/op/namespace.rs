@@ -38,11 +38,16 @@ impl<T: AsyncRead> Session<T> {
                 .with_tag(request.tag)
                 .serialize(
                     Response {
-                        shared_prefix: if self.state.session_data().mailboxes.lock().len() > 1 {
-                            self.imap.name_shared.clone().into()
+                        personal: vec!["".to_string()],
+                        // Mailboxes shared by other users are listed under the shared folder,
+                        // the prefix includes the hierarchy delimiter as recommended by RFC 2342
+                        other_users: if self.state.session_data().mailboxes.lock().len() > 1 {
+                            vec![format!("{}/", self.imap.name_shared)]
                         } else {
-                            None
+                            vec![]
                         },
+                        // Public folders are not supported
+                        shared: vec![],
                     }
                     .serialize(),
                 ),
@@ -21,32 +21,55 @@
  * for more details.
 */
 
-use std::sync::Arc;
+use std::{sync::Arc, time::Instant};
 
+use ahash::{AHashMap, AHashSet};
 use imap_proto::{
     protocol::{
-        search::{self, Arguments, Filter, Response, ResultOption},
-        Sequence,
+        search::{
+            self, Arguments, ChangeBuckets, Filter, MailboxGroup, MatchProperty, PartialResult,
+            Response, ResultOption,
+        },
+        Flag, Sequence,
     },
     receiver::Request,
-    Command, StatusResponse,
+    Command, ResponseCode, StatusResponse,
 };
 
-use jmap_proto::types::{collection::Collection, id::Id, keyword::Keyword, property::Property};
-use mail_parser::HeaderName;
+use jmap::email::index::{list_token, spam_score_index};
+use jmap_proto::{
+    object::Object,
+    types::{
+        blob::BlobId, collection::Collection, id::Id, keyword::Keyword, property::Property,
+        value::Value,
+    },
+};
+use mail_parser::{HeaderName, MessageParser};
 use nlp::language::Language;
 use store::{
     fts::builder::MAX_TOKEN_LENGTH,
     query::{self, log::Query, sort::Pagination, ResultSet},
     roaring::RoaringBitmap,
     write::now,
+    Serialize,
 };
 use tokio::{io::AsyncRead, sync::watch};
 
-use crate::core::{ImapId, MailboxState, SavedSearch, SelectedMailbox, Session, SessionData};
+use crate::core::{
+    ImapId, MailboxId, MailboxState, SavedSearch, SelectedMailbox, Session, SessionData,
+};
 
 use super::{FromModSeq, ToModSeq};
 
+const SEARCH_WINDOW_SIZE: u64 = 4096;
+const EXACT_MATCH_BATCH_SIZE: usize = 512;
+
+pub struct QueryResult {
+    pub result_set: ResultSet,
+    pub include_highest_modseq: bool,
+    pub is_truncated: bool,
+}
+
 impl<T: AsyncRead> Session<T> {
     pub async fn handle_search(
         &mut self,
@@ -61,6 +84,7 @@ impl<T: AsyncRead> Session<T> {
         } {
             Ok(mut arguments) => {
                 let (data, mailbox) = self.state.mailbox_state();
+                arguments.timezone = self.timezone;
 
                 // Create channel for results
                 let (results_tx, prev_saved_search) =
@@ -85,13 +109,22 @@ impl<T: AsyncRead> Session<T> {
                         )
                         .await
                     {
-                        Ok(response) => {
+                        Ok((response, is_truncated)) => {
                             let response = response.serialize(&tag);
-                            StatusResponse::completed(if !is_sort {
+                            let command = if !is_sort {
                                 Command::Search(is_uid)
                             } else {
                                 Command::Sort(is_uid)
-                            })
+                            };
+                            if is_truncated {
+                                StatusResponse::ok(format!(
+                                    "{} deadline reached, partial results returned",
+                                    command
+                                ))
+                                .with_code(ResponseCode::Limit)
+                            } else {
+                                StatusResponse::completed(command)
+                            }
                             .with_tag(tag)
                             .serialize(response)
                         }
@@ -122,12 +155,94 @@ impl SessionData {
         results_tx: Option<watch::Sender<Arc<Vec<ImapId>>>>,
         prev_saved_search: Option<Option<Arc<Vec<ImapId>>>>,
         is_uid: bool,
-    ) -> Result<search::Response, StatusResponse> {
+    ) -> Result<(search::Response, bool), StatusResponse> {
+        // Results must be mapped to UIDs of the epoch the search started in
+        let uid_validity = mailbox.state.lock().uid_validity;
+
+        // Collect the terms to report matches for
+        let match_terms = if arguments.result_options.contains(&ResultOption::Matches) {
+            match_terms(&arguments.filter)
+        } else {
+            vec![]
+        };
+        let snippet_terms = if arguments.result_options.contains(&ResultOption::Snippets) {
+            snippet_terms(&arguments.filter).into()
+        } else {
+            None
+        };
+
+        // Change buckets count messages by the time a searched keyword was set
+        let bucket_keywords = if arguments
+            .result_options
+            .contains(&ResultOption::ChangeBuckets)
+        {
+            let keywords = bucket_keywords(&arguments.filter);
+            if keywords.is_empty() {
+                return Err(StatusResponse::bad(
+                    "X-CHANGEBUCKETS requires a keyword search key such as SEEN or KEYWORD.",
+                ));
+            }
+            keywords.into()
+        } else {
+            None
+        };
+
+        // Clients may request a deadline shorter than the configured one
+        let deadline = arguments
+            .result_options
+            .iter()
+            .find_map(|option| option.deadline())
+            .into_iter()
+            .chain(self.imap.search_deadline)
+            .min()
+            .map(|deadline| Instant::now() + deadline);
+
         // Run query
-        let (result_set, include_highest_modseq) = self
-            .query(arguments.filter, &mailbox, &prev_saved_search, is_uid)
+        let QueryResult {
+            result_set,
+            include_highest_modseq,
+            is_truncated,
+        } = self
+            .query(
+                arguments.filter,
+                &mailbox,
+                &prev_saved_search,
+                is_uid,
+                arguments.timezone,
+                deadline,
+            )
             .await?;
 
+        // Obtain matched terms
+        let matches = if !match_terms.is_empty() {
+            self.search_matches(&mailbox, match_terms, &result_set.results, is_uid)
+                .await?
+        } else {
+            vec![]
+        };
+
+        // Count results by the age of their keywords
+        let change_buckets = if let Some(bucket_keywords) = bucket_keywords {
+            self.search_change_buckets(&mailbox, bucket_keywords, &result_set.results)
+                .await?
+                .into()
+        } else {
+            None
+        };
+
+        // Snippets are built once the returned window of results is known
+        let snippet_results = snippet_terms.as_ref().map(|_| result_set.results.clone());
+
+        // Group results by the mailboxes they belong to
+        let groups = if arguments
+            .result_options
+            .contains(&ResultOption::GroupByMailbox)
+        {
+            self.search_groups(&mailbox, &result_set.results).await?
+        } else {
+            vec![]
+        };
+
         // Obtain modseq
         let highest_modseq = if include_highest_modseq {
             self.synchronize_messages(&mailbox)
@@ -155,31 +270,38 @@ impl SessionData {
                     .store
                     .sort(
                         result_set,
-                        sort.into_iter()
-                            .map(|item| match item.sort {
-                                search::Sort::Arrival => {
-                                    query::Comparator::field(Property::ReceivedAt, item.ascending)
-                                }
-                                search::Sort::Cc => {
-                                    query::Comparator::field(Property::Cc, item.ascending)
-                                }
-                                search::Sort::Date => {
-                                    query::Comparator::field(Property::SentAt, item.ascending)
-                                }
-                                search::Sort::From | search::Sort::DisplayFrom => {
-                                    query::Comparator::field(Property::From, item.ascending)
-                                }
-                                search::Sort::Size => {
-                                    query::Comparator::field(Property::Size, item.ascending)
-                                }
-                                search::Sort::Subject => {
-                                    query::Comparator::field(Property::Subject, item.ascending)
-                                }
-                                search::Sort::To | search::Sort::DisplayTo => {
-                                    query::Comparator::field(Property::To, item.ascending)
-                                }
-                            })
-                            .collect::<Vec<_>>(),
+                        query::Comparator::with_relevance_tiebreaker(
+                            sort.into_iter()
+                                .map(|item| match item.sort {
+                                    search::Sort::Arrival => query::Comparator::field(
+                                        Property::ReceivedAt,
+                                        item.ascending,
+                                    ),
+                                    search::Sort::Cc => {
+                                        query::Comparator::field(Property::Cc, item.ascending)
+                                    }
+                                    search::Sort::Date => {
+                                        query::Comparator::field(Property::SentAt, item.ascending)
+                                    }
+                                    search::Sort::From | search::Sort::DisplayFrom => {
+                                        query::Comparator::field(Property::From, item.ascending)
+                                    }
+                                    search::Sort::Size => {
+                                        query::Comparator::field(Property::Size, item.ascending)
+                                    }
+                                    search::Sort::Subject => {
+                                        query::Comparator::field(Property::Subject, item.ascending)
+                                    }
+                                    search::Sort::To | search::Sort::DisplayTo => {
+                                        query::Comparator::field(Property::To, item.ascending)
+                                    }
+                                    search::Sort::Relevance => {
+                                        query::Comparator::relevance(item.ascending)
+                                    }
+                                })
+                                .collect::<Vec<_>>(),
+                            Property::ReceivedAt,
+                        ),
                         Pagination::new(results_len, 0, None, 0),
                     )
                     .await
@@ -213,36 +335,91 @@ impl SessionData {
             false
         };
 
-        // Save results
-        if let (Some(results_tx), Some(saved_results)) = (results_tx, saved_results) {
-            let saved_results = Arc::new(saved_results);
-            *mailbox.saved_search.lock() = SavedSearch::Results {
-                items: saved_results.clone(),
-            };
-            results_tx.send(saved_results).ok();
+        // Do not mix ids from two epochs if UIDVALIDITY changed while searching
+        if !mailbox.state.lock().is_same_epoch(uid_validity) {
+            return Err(StatusResponse::no(
+                "UIDVALIDITY changed during the search, please reselect the mailbox.",
+            ));
         }
 
-        // Build response
-        Ok(Response {
-            is_uid,
-            min: min.map(|(id, _)| id),
-            max: max.map(|(id, _)| id),
-            count: if arguments.result_options.contains(&ResultOption::Count) {
-                Some(total)
-            } else {
-                None
-            },
-            ids: if arguments.result_options.is_empty()
-                || arguments.result_options.contains(&ResultOption::All)
-            {
-                imap_ids
+        // Save results, partial results do not replace the previously saved search
+        if let (Some(results_tx), Some(saved_results)) = (results_tx, saved_results) {
+            if !is_truncated {
+                let saved_results = Arc::new(saved_results);
+                *mailbox.saved_search.lock() = SavedSearch::Results {
+                    items: saved_results.clone(),
+                };
+                results_tx.send(saved_results).ok();
             } else {
-                vec![]
+                let prev_saved_search = prev_saved_search.flatten();
+                *mailbox.saved_search.lock() = prev_saved_search
+                    .clone()
+                    .map_or(SavedSearch::None, |items| SavedSearch::Results { items });
+                results_tx.send(prev_saved_search.unwrap_or_default()).ok();
+            }
+        }
+
+        // Return only the requested window of the results
+        let partial = arguments
+            .result_options
+            .iter()
+            .find_map(|option| option.partial())
+            .map(|(start, end)| PartialResult::new(start, end, &imap_ids));
+
+        // Obtain body excerpts around the matched terms, only for the returned window
+        // as each one requires fetching and parsing the message
+        let snippets = if let (Some(snippet_terms), Some(snippet_results)) =
+            (snippet_terms, snippet_results)
+        {
+            let window = partial
+                .as_ref()
+                .map_or(&imap_ids[..], |partial| &partial.ids[..])
+                .iter()
+                .take(self.imap.snippet_max_results)
+                .copied()
+                .collect::<AHashSet<_>>();
+            self.search_snippets(&mailbox, snippet_terms, &snippet_results, &window, is_uid)
+                .await?
+        } else {
+            vec![]
+        };
+
+        // Build response, MIN, MAX and COUNT are omitted from partial results
+        // as they would only describe the messages matched before the deadline
+        Ok((
+            Response {
+                is_uid,
+                min: min.filter(|_| !is_truncated).map(|(id, _)| id),
+                max: max.filter(|_| !is_truncated).map(|(id, _)| id),
+                count: if !is_truncated
+                    && (arguments.result_options.contains(&ResultOption::Count)
+                        || partial.is_some())
+                {
+                    Some(total)
+                } else {
+                    None
+                },
+                ids: if arguments
+                    .result_options
+                    .iter()
+                    .all(|option| option.deadline().is_some())
+                    || arguments.result_options.contains(&ResultOption::All)
+                {
+                    imap_ids
+                } else {
+                    vec![]
+                },
+                is_sort,
+                is_esearch: arguments.is_esearch,
+                highest_modseq,
+                matches,
+                change_buckets,
+                groups,
+                partial,
+                snippets,
             },
-            is_sort,
-            is_esearch: arguments.is_esearch,
-            highest_modseq,
-        })
+            is_truncated,
+        ))
     }
 
     pub async fn query(
@@ -251,7 +428,9 @@ impl SessionData {
         mailbox: &SelectedMailbox,
         prev_saved_search: &Option<Option<Arc<Vec<ImapId>>>>,
         is_uid: bool,
-    ) -> Result<(ResultSet, bool), StatusResponse> {
+        timezone: i32,
+        deadline: Option<Instant>,
+    ) -> Result<QueryResult, StatusResponse> {
         // Obtain message ids
         let mut filters = Vec::with_capacity(imap_filter.len() + 1);
         let message_ids = if let Some(mailbox_id) = mailbox.id.mailbox_id {
@@ -267,6 +446,14 @@ impl SessionData {
                 .unwrap_or_default();
             filters.push(query::Filter::is_in_set(ids.clone()));
             ids
+        } else if let Some(query_id) = mailbox.id.query_id {
+            let ids = self
+                .jmap
+                .saved_query_ids(mailbox.id.account_id, query_id)
+                .await?
+                .ok_or_else(|| StatusResponse::no("Saved search no longer exists."))?;
+            filters.push(query::Filter::is_in_set(ids.clone()));
+            ids
         } else {
             self.jmap
                 .get_document_ids(mailbox.id.account_id, Collection::Email)
@@ -276,6 +463,8 @@ impl SessionData {
 
         // Convert query
         let mut include_highest_modseq = false;
+        let mut thread_sizes = None;
+        let mut include_excluded = false;
         for filter in imap_filter {
             match filter {
                 search::Filter::Sequence(sequence, uid_filter) => {
@@ -307,6 +496,91 @@ impl SessionData {
                 search::Filter::All => {
                     filters.push(query::Filter::is_in_set(message_ids.clone()));
                 }
+                search::Filter::AllMailboxes => {
+                    include_excluded = true;
+                    filters.push(query::Filter::is_in_set(message_ids.clone()));
+                }
+                search::Filter::SpamScoreMin(score) => {
+                    filters.push(query::Filter::ge(
+                        Property::SpamScore,
+                        spam_score_index(score),
+                    ));
+                }
+                search::Filter::SpamScoreMax(score) => {
+                    filters.push(query::Filter::le(
+                        Property::SpamScore,
+                        spam_score_index(score),
+                    ));
+                }
+                search::Filter::Within(mailbox_names) => {
+                    // An explicit scope overrides the All Mail exclusions
+                    include_excluded = true;
+                    let mut within_ids = RoaringBitmap::new();
+                    for mailbox_name in &mailbox_names {
+                        // Mailboxes are looked up by name first, then by JMAP id
+                        let mailbox_id = self
+                            .get_mailbox_by_name(mailbox_name)
+                            .filter(|id| id.account_id == mailbox.id.account_id)
+                            .and_then(|id| id.mailbox_id)
+                            .or_else(|| {
+                                Id::from_bytes(mailbox_name.as_bytes())
+                                    .filter(|id| id.id() <= u32::MAX as u64)
+                                    .map(|id| id.document_id())
+                            })
+                            .ok_or_else(|| {
+                                StatusResponse::no(format!("Mailbox {mailbox_name:?} not found."))
+                                    .with_code(ResponseCode::NonExistent)
+                            })?;
+                        if let Some(ids) = self
+                            .jmap
+                            .get_tag(
+                                mailbox.id.account_id,
+                                Collection::Email,
+                                Property::MailboxIds,
+                                mailbox_id,
+                            )
+                            .await?
+                        {
+                            within_ids |= ids;
+                        }
+                    }
+                    filters.push(query::Filter::is_in_set(within_ids));
+                }
+                search::Filter::Exact(header, value) => {
+                    let ids = self
+                        .exact_header_ids(mailbox.id.account_id, &message_ids, &header, &value)
+                        .await?;
+                    filters.push(query::Filter::is_in_set(ids));
+                }
+                search::Filter::Exclude(sequence) => {
+                    // Exclusions are always expressed as UIDs
+                    let mut set = RoaringBitmap::new();
+                    for id in mailbox.sequence_to_ids(&sequence, true).await?.keys() {
+                        set.insert(*id);
+                    }
+                    filters.push(query::Filter::Not);
+                    filters.push(query::Filter::is_in_set(set));
+                    filters.push(query::Filter::End);
+                }
+                search::Filter::AttachmentsMin(count) => {
+                    filters.push(query::Filter::ge(Property::AttachmentCount, count));
+                }
+                search::Filter::AttachmentsMax(count) => {
+                    filters.push(query::Filter::le(Property::AttachmentCount, count));
+                }
+                search::Filter::ThreadRoot => {
+                    // Header presence is indexed under the header id
+                    filters.push(query::Filter::Not);
+                    filters.push(query::Filter::Or);
+                    for header_name in [HeaderName::InReplyTo, HeaderName::References] {
+                        filters.push(query::Filter::has_raw_text(
+                            Property::Headers,
+                            header_name.id().to_string(),
+                        ));
+                    }
+                    filters.push(query::Filter::End);
+                    filters.push(query::Filter::End);
+                }
                 search::Filter::Answered => {
                     filters.push(query::Filter::is_in_bitmap(
                         Property::Keywords,
@@ -317,7 +591,10 @@ impl SessionData {
                     filters.push(query::Filter::has_text(Property::Bcc, text, Language::None));
                 }
                 search::Filter::Before(date) => {
-                    filters.push(query::Filter::lt(Property::ReceivedAt, date as u64));
+                    filters.push(query::Filter::lt(
+                        Property::ReceivedAt,
+                        local_day_start(date, timezone),
+                    ));
                 }
                 search::Filter::Body(text) => {
                     filters.push(query::Filter::has_text_detect(
@@ -355,6 +632,38 @@ impl SessionData {
                     ));
                 }
                 search::Filter::Header(header, value) => match HeaderName::parse(&header) {
+                    Some(HeaderName::Other(name)) if self.imap.search_any_header => {
+                        let header_name = format!("{}:", name.to_lowercase());
+                        let tokens = value
+                            .split_ascii_whitespace()
+                            .filter(|token| header_name.len() + token.len() < MAX_TOKEN_LENGTH)
+                            .map(|token| format!("{header_name}{}", token.to_lowercase()))
+                            .collect::<Vec<_>>();
+                        match tokens.len() {
+                            0 => {
+                                filters.push(query::Filter::has_raw_text(
+                                    Property::Headers,
+                                    header_name,
+                                ));
+                            }
+                            1 => {
+                                filters.push(query::Filter::has_raw_text(
+                                    Property::Headers,
+                                    tokens.into_iter().next().unwrap(),
+                                ));
+                            }
+                            _ => {
+                                filters.push(query::Filter::And);
+                                for token in tokens {
+                                    filters.push(query::Filter::has_raw_text(
+                                        Property::Headers,
+                                        token,
+                                    ));
+                                }
+                                filters.push(query::Filter::End);
+                            }
+                        }
+                    }
                     Some(HeaderName::Other(_)) | None => {
                         return Err(StatusResponse::no(format!(
                             "Querying non-RFC header '{header}' is not allowed.",
@@ -368,6 +677,10 @@ impl SessionData {
                                 | HeaderName::References
                                 | HeaderName::ResentMessageId
                         );
+                        let is_list = matches!(
+                            header_name,
+                            HeaderName::ListId | HeaderName::ListPost | HeaderName::ListUnsubscribe
+                        );
                         let tokens = if !value.is_empty() {
                             let header_num = header_name.id().to_string();
                             value
@@ -376,6 +689,8 @@ impl SessionData {
                                     if token.len() < MAX_TOKEN_LENGTH {
                                         if is_id {
                                             format!("{header_num}{token}")
+                                        } else if is_list {
+                                            format!("{header_num}{}", list_token(token))
                                         } else {
                                             format!("{header_num}{}", token.to_lowercase())
                                         }
@@ -415,20 +730,20 @@ impl SessionData {
                     }
                 },
                 search::Filter::Keyword(keyword) => {
-                    filters.push(query::Filter::is_in_bitmap(
-                        Property::Keywords,
-                        Keyword::from(keyword),
-                    ));
+                    self.push_keyword_filter(&mut filters, keyword);
                 }
                 search::Filter::Larger(size) => {
                     filters.push(query::Filter::gt(Property::Size, size));
                 }
                 search::Filter::On(date) => {
                     filters.push(query::Filter::And);
-                    filters.push(query::Filter::ge(Property::ReceivedAt, date as u64));
+                    filters.push(query::Filter::ge(
+                        Property::ReceivedAt,
+                        local_day_start(date, timezone),
+                    ));
                     filters.push(query::Filter::lt(
                         Property::ReceivedAt,
-                        (date + 86400) as u64,
+                        local_day_start(date + 86400, timezone),
                     ));
                     filters.push(query::Filter::End);
                 }
@@ -439,19 +754,34 @@ impl SessionData {
                     ));
                 }
                 search::Filter::SentBefore(date) => {
-                    filters.push(query::Filter::lt(Property::SentAt, date as u64));
+                    filters.push(query::Filter::lt(
+                        Property::SentAt,
+                        local_day_start(date, timezone),
+                    ));
                 }
                 search::Filter::SentOn(date) => {
                     filters.push(query::Filter::And);
-                    filters.push(query::Filter::ge(Property::SentAt, date as u64));
-                    filters.push(query::Filter::lt(Property::SentAt, (date + 86400) as u64));
+                    filters.push(query::Filter::ge(
+                        Property::SentAt,
+                        local_day_start(date, timezone),
+                    ));
+                    filters.push(query::Filter::lt(
+                        Property::SentAt,
+                        local_day_start(date + 86400, timezone),
+                    ));
                     filters.push(query::Filter::End);
                 }
                 search::Filter::SentSince(date) => {
-                    filters.push(query::Filter::ge(Property::SentAt, date as u64));
+                    filters.push(query::Filter::ge(
+                        Property::SentAt,
+                        local_day_start(date, timezone),
+                    ));
                 }
                 search::Filter::Since(date) => {
-                    filters.push(query::Filter::ge(Property::ReceivedAt, date as u64));
+                    filters.push(query::Filter::ge(
+                        Property::ReceivedAt,
+                        local_day_start(date, timezone),
+                    ));
                 }
                 search::Filter::Smaller(size) => {
                     filters.push(query::Filter::lt(Property::Size, size));
@@ -531,10 +861,7 @@ impl SessionData {
                 }
                 search::Filter::Unkeyword(keyword) => {
                     filters.push(query::Filter::Not);
-                    filters.push(query::Filter::is_in_bitmap(
-                        Property::Keywords,
-                        Keyword::from(keyword),
-                    ));
+                    self.push_keyword_filter(&mut filters, keyword);
                     filters.push(query::Filter::End);
                 }
                 search::Filter::Unseen => {
@@ -640,16 +967,728 @@ impl SessionData {
                         )));
                     }
                 }
+                search::Filter::ThreadSizeMin(size) | search::Filter::ThreadSizeMax(size) => {
+                    let is_min = matches!(filter, search::Filter::ThreadSizeMin(_));
+                    if thread_sizes.is_none() {
+                        thread_sizes = self
+                            .thread_sizes(mailbox.id.account_id, &message_ids)
+                            .await?
+                            .into();
+                    }
+                    filters.push(query::Filter::is_in_set(
+                        thread_sizes
+                            .as_ref()
+                            .unwrap()
+                            .iter()
+                            .filter(|(_, thread_size)| {
+                                if is_min {
+                                    **thread_size >= size
+                                } else {
+                                    **thread_size <= size
+                                }
+                            })
+                            .map(|(document_id, _)| *document_id)
+                            .collect::<RoaringBitmap>(),
+                    ));
+                }
+            }
+        }
+
+        // Exclude mailboxes such as Trash and Junk from All Mail searches
+        if mailbox.id.mailbox_id.is_none()
+            && mailbox.id.query_id.is_none()
+            && !include_excluded
+            && !self.imap.search_all_exclude.is_empty()
+        {
+            let mut excluded_ids = RoaringBitmap::new();
+            for role in &self.imap.search_all_exclude {
+                if let Some(mailbox_id) = self
+                    .jmap
+                    .mailbox_get_by_role(mailbox.id.account_id, role)
+                    .await?
+                {
+                    if let Some(ids) = self
+                        .jmap
+                        .get_tag(
+                            mailbox.id.account_id,
+                            Collection::Email,
+                            Property::MailboxIds,
+                            mailbox_id,
+                        )
+                        .await?
+                    {
+                        excluded_ids |= ids;
+                    }
+                }
+            }
+            if !excluded_ids.is_empty() {
+                filters.push(query::Filter::Not);
+                filters.push(query::Filter::is_in_set(excluded_ids));
+                filters.push(query::Filter::End);
             }
         }
 
         // Run query
-        self.jmap
-            .filter(mailbox.id.account_id, Collection::Email, filters)
-            .await
-            .map(|res| (res, include_highest_modseq))
-            .map_err(|err| err.into())
+        if let Some(deadline) = deadline {
+            self.filter_until(mailbox.id.account_id, filters, message_ids, deadline)
+                .await
+                .map(|(result_set, is_truncated)| QueryResult {
+                    result_set,
+                    include_highest_modseq,
+                    is_truncated,
+                })
+        } else {
+            self.jmap
+                .filter(mailbox.id.account_id, Collection::Email, filters)
+                .await
+                .map(|result_set| QueryResult {
+                    result_set,
+                    include_highest_modseq,
+                    is_truncated: false,
+                })
+                .map_err(|err| err.into())
+        }
+    }
+
+    async fn thread_sizes(
+        &self,
+        account_id: u32,
+        message_ids: &RoaringBitmap,
+    ) -> Result<AHashMap<u32, u32>, StatusResponse> {
+        // Each thread bitmap sizes all of its messages in the mailbox at once,
+        // so only one message per thread is looked up. The number of threads
+        // is capped to bound the cost on large mailboxes.
+        let mut pending = message_ids.clone();
+        let mut sizes = AHashMap::with_capacity(message_ids.len() as usize);
+        let mut thread_count = 0;
+        while let Some(document_id) = pending.min() {
+            pending.remove(document_id);
+            let thread_id = if let Some(thread_id) = self
+                .jmap
+                .get_property::<u32>(
+                    account_id,
+                    Collection::Email,
+                    document_id,
+                    Property::ThreadId,
+                )
+                .await?
+            {
+                thread_id
+            } else {
+                continue;
+            };
+
+            thread_count += 1;
+            if thread_count > self.imap.search_thread_max {
+                return Err(StatusResponse::no(
+                    "Too many threads to compute thread sizes, narrow down the mailbox.",
+                )
+                .with_code(ResponseCode::Limit));
+            }
+
+            let thread_ids = self
+                .jmap
+                .get_tag(account_id, Collection::Email, Property::ThreadId, thread_id)
+                .await?
+                .unwrap_or_default();
+            let size = thread_ids.len() as u32;
+            sizes.insert(document_id, size);
+            for document_id in &(thread_ids & &pending) {
+                pending.remove(document_id);
+                sizes.insert(document_id, size);
+            }
+        }
+
+        Ok(sizes)
+    }
+
+    async fn search_change_buckets(
+        &self,
+        mailbox: &SelectedMailbox,
+        keywords: Vec<Keyword>,
+        results: &RoaringBitmap,
+    ) -> Result<ChangeBuckets, StatusResponse> {
+        const DAY: u64 = 86400;
+
+        // Messages are bucketed by the time they were inserted or last had one
+        // of the keywords set. Only the last month of the change log is read,
+        // anything older (or logged before change times were recorded) is
+        // counted as older.
+        let now = now();
+        let keywords = keywords
+            .into_iter()
+            .map(|keyword| keyword.serialize())
+            .collect::<Vec<_>>();
+        let change_times = self
+            .jmap
+            .tag_set_times_(
+                mailbox.id.account_id,
+                Collection::Email,
+                &keywords,
+                now - 30 * DAY,
+            )
+            .await?
+            .into_iter()
+            .map(|(id, timestamp)| (id as u32, timestamp))
+            .collect::<AHashMap<_, _>>();
+
+        let mut buckets = ChangeBuckets::default();
+        for document_id in results {
+            match change_times
+                .get(&document_id)
+                .map(|timestamp| now.saturating_sub(*timestamp))
+            {
+                Some(age) if age < DAY => buckets.day += 1,
+                Some(age) if age < 7 * DAY => buckets.week += 1,
+                Some(_) => buckets.month += 1,
+                None => buckets.older += 1,
+            }
+        }
+
+        Ok(buckets)
+    }
+
+    // In migration compatibility mode keywords also match the other spellings
+    // a migrated Gmail label may have been stored with.
+    fn push_keyword_filter(&self, filters: &mut Vec<query::Filter>, keyword: Flag) {
+        if self.imap.search_label_keywords {
+            let variants = keyword.label_variants();
+            if variants.len() > 1 {
+                filters.push(query::Filter::Or);
+                for variant in variants {
+                    filters.push(query::Filter::is_in_bitmap(
+                        Property::Keywords,
+                        Keyword::from(variant),
+                    ));
+                }
+                filters.push(query::Filter::End);
+                return;
+            }
+        }
+
+        filters.push(query::Filter::is_in_bitmap(
+            Property::Keywords,
+            Keyword::from(keyword),
+        ));
+    }
+
+    async fn search_groups(
+        &self,
+        mailbox: &SelectedMailbox,
+        results: &RoaringBitmap,
+    ) -> Result<Vec<MailboxGroup>, StatusResponse> {
+        if mailbox.id.mailbox_id.is_some() || mailbox.id.query_id.is_some() {
+            return Err(StatusResponse::no(
+                "Grouping by mailbox is only supported when searching All Mail.",
+            ));
+        } else if results.is_empty() {
+            return Ok(vec![]);
+        }
+
+        let account_id = mailbox.id.account_id;
+        let mailboxes = self
+            .mailboxes
+            .lock()
+            .iter()
+            .find(|account| account.account_id == account_id)
+            .map(|account| {
+                account
+                    .mailbox_names
+                    .iter()
+                    .map(|(name, id)| (name.clone(), *id))
+                    .collect::<Vec<_>>()
+            })
+            .unwrap_or_default();
+
+        // Matching messages are mapped to the UIDs of each mailbox they are in
+        let mut groups = Vec::new();
+        for (mailbox_name, mailbox_id) in mailboxes {
+            let document_ids = match self
+                .jmap
+                .get_tag(
+                    account_id,
+                    Collection::Email,
+                    Property::MailboxIds,
+                    mailbox_id,
+                )
+                .await?
+            {
+                Some(document_ids) => document_ids & results,
+                None => continue,
+            };
+            if document_ids.is_empty() {
+                continue;
+            }
+
+            let state = self
+                .fetch_messages(&MailboxId {
+                    account_id,
+                    mailbox_id: Some(mailbox_id),
+                    query_id: None,
+                })
+                .await?;
+            let mut uids = document_ids
+                .iter()
+                .filter_map(|document_id| state.id_to_imap.get(&document_id))
+                .map(|imap_id| imap_id.uid)
+                .collect::<Vec<_>>();
+            uids.sort_unstable();
+            groups.push(MailboxGroup {
+                mailbox_name,
+                uid_validity: state.uid_validity,
+                uids,
+            });
+        }
+
+        Ok(groups)
+    }
+
+    async fn search_matches(
+        &self,
+        mailbox: &SelectedMailbox,
+        terms: Vec<(MatchProperty, String)>,
+        results: &RoaringBitmap,
+        is_uid: bool,
+    ) -> Result<Vec<search::Match>, StatusResponse> {
+        if results.is_empty() {
+            return Ok(vec![]);
+        }
+
+        // Run each term against the matched messages only
+        let mut matches: AHashMap<u32, Vec<(MatchProperty, String)>> = AHashMap::new();
+        for (property, term) in terms {
+            let filter = match property {
+                MatchProperty::From => {
+                    query::Filter::has_text(Property::From, &term, Language::None)
+                }
+                MatchProperty::To => query::Filter::has_text(Property::To, &term, Language::None),
+                MatchProperty::Cc => query::Filter::has_text(Property::Cc, &term, Language::None),
+                MatchProperty::Bcc => query::Filter::has_text(Property::Bcc, &term, Language::None),
+                MatchProperty::Subject => query::Filter::has_text_detect(
+                    Property::Subject,
+                    &term,
+                    self.jmap.config.default_language,
+                ),
+                MatchProperty::Body => query::Filter::has_text_detect(
+                    Property::TextBody,
+                    &term,
+                    self.jmap.config.default_language,
+                ),
+            };
+            for document_id in self
+                .jmap
+                .filter(
+                    mailbox.id.account_id,
+                    Collection::Email,
+                    vec![query::Filter::is_in_set(results.clone()), filter],
+                )
+                .await?
+                .results
+            {
+                matches
+                    .entry(document_id)
+                    .or_default()
+                    .push((property, term.clone()));
+            }
+        }
+
+        // Map document ids to IMAP ids
+        let state = mailbox.state.lock();
+        let mut matches = matches
+            .into_iter()
+            .filter_map(|(document_id, terms)| {
+                state
+                    .map_result_id(document_id, is_uid)
+                    .map(|(id, _)| search::Match { id, terms })
+            })
+            .collect::<Vec<_>>();
+        matches.sort_unstable_by_key(|item| item.id);
+
+        Ok(matches)
     }
+
+    async fn search_snippets(
+        &self,
+        mailbox: &SelectedMailbox,
+        terms: Vec<String>,
+        results: &RoaringBitmap,
+        window: &AHashSet<u32>,
+        is_uid: bool,
+    ) -> Result<Vec<search::Snippet>, StatusResponse> {
+        let account_id = mailbox.id.account_id;
+        let mut snippets = Vec::new();
+        for document_id in results {
+            let id = match mailbox.state.lock().map_result_id(document_id, is_uid) {
+                Some((id, _)) if window.contains(&id) => id,
+                _ => continue,
+            };
+            let blob_id = BlobId::maildir(account_id, document_id);
+            let raw_message = match self.jmap.get_blob(&blob_id.kind, 0..u32::MAX).await {
+                Ok(Some(raw_message)) => raw_message,
+                Ok(None) => {
+                    tracing::warn!(event = "not-found",
+                        account_id = account_id,
+                        collection = ?Collection::Email,
+                        document_id = document_id,
+                        blob_id = ?blob_id,
+                        "Blob not found");
+                    continue;
+                }
+                Err(_) => return Err(StatusResponse::database_failure()),
+            };
+
+            // Messages without body text have no snippet
+            if let Some(text) = MessageParser::new()
+                .parse(&raw_message)
+                .and_then(|message| message.body_text(0).map(|text| text.into_owned()))
+            {
+                if let Some(text) = body_snippet(
+                    &text,
+                    &terms,
+                    self.imap.snippet_length,
+                    &self.imap.snippet_highlight,
+                ) {
+                    snippets.push(search::Snippet { id, text });
+                }
+            }
+        }
+        snippets.sort_unstable_by_key(|snippet| snippet.id);
+
+        Ok(snippets)
+    }
+
+    // Exact matches bypass the full-text index: the header values stored in the
+    // message metadata are read for every message in scope containing the header
+    // and compared verbatim, so the cost grows with the size of the mailbox. Only
+    // headers kept in the metadata are supported (Message-ID, In-Reply-To,
+    // References, Subject, From, To, Cc, Bcc, Reply-To and Sender). Values are
+    // matched in their decoded form: message ids without angle brackets and
+    // addresses by name or email. Values longer than the stored field length are
+    // matched against their truncated form.
+    async fn exact_header_ids(
+        &self,
+        account_id: u32,
+        message_ids: &RoaringBitmap,
+        header: &str,
+        value: &str,
+    ) -> Result<RoaringBitmap, StatusResponse> {
+        let header_name = match HeaderName::parse(header) {
+            Some(
+                header_name @ (HeaderName::MessageId
+                | HeaderName::InReplyTo
+                | HeaderName::References
+                | HeaderName::Subject
+                | HeaderName::From
+                | HeaderName::To
+                | HeaderName::Cc
+                | HeaderName::Bcc
+                | HeaderName::ReplyTo
+                | HeaderName::Sender),
+            ) => header_name,
+            _ => {
+                return Err(StatusResponse::no(format!(
+                    "Exact matching is not supported for header '{header}'.",
+                )));
+            }
+        };
+
+        // Narrow down the candidates to the messages containing the header
+        let candidate_ids = self
+            .jmap
+            .filter(
+                account_id,
+                Collection::Email,
+                vec![
+                    query::Filter::is_in_set(message_ids.clone()),
+                    query::Filter::has_raw_text(Property::Headers, header_name.id().to_string()),
+                ],
+            )
+            .await?
+            .results
+            .into_iter()
+            .collect::<Vec<_>>();
+
+        let property = Property::from_header(&header_name);
+        let mut results = RoaringBitmap::new();
+        for document_ids in candidate_ids.chunks(EXACT_MATCH_BATCH_SIZE) {
+            for (document_id, metadata) in document_ids.iter().zip(
+                self.jmap
+                    .get_properties::<Object<Value>>(
+                        account_id,
+                        Collection::Email,
+                        document_ids.iter().copied(),
+                        Property::BodyStructure,
+                    )
+                    .await?,
+            ) {
+                if metadata
+                    .as_ref()
+                    .and_then(|metadata| metadata.properties.get(&property))
+                    .map_or(false, |header_value| contains_text(header_value, value))
+                {
+                    results.insert(*document_id);
+                }
+            }
+        }
+
+        Ok(results)
+    }
+
+    async fn filter_until(
+        &self,
+        account_id: u32,
+        filters: Vec<query::Filter>,
+        mut message_ids: RoaringBitmap,
+        deadline: Instant,
+    ) -> Result<(ResultSet, bool), StatusResponse> {
+        // Evaluate the query over increasingly larger windows, newest messages first,
+        // so that whatever matched so far can be returned once the deadline is reached,
+        // or once a window exceeds the read budget of a store transaction.
+        let mut results = RoaringBitmap::new();
+        let mut fts_terms = Vec::new();
+        let mut window_size = SEARCH_WINDOW_SIZE;
+        let mut is_truncated = false;
+
+        while !message_ids.is_empty() {
+            // Stop searching if the client disconnected
+            if self.writer.is_closed() {
+                return Err(StatusResponse::no("Search cancelled."));
+            }
+
+            let now = Instant::now();
+            if now >= deadline {
+                is_truncated = true;
+                break;
+            }
+
+            let mut window = message_ids.clone();
+            if message_ids.len() > window_size {
+                let min_id = message_ids
+                    .select((message_ids.len() - window_size) as u32)
+                    .unwrap();
+                window.remove_range(..min_id);
+                message_ids.remove_range(min_id..);
+            } else {
+                message_ids.clear();
+            }
+
+            let mut window_filters = Vec::with_capacity(filters.len() + 3);
+            window_filters.push(query::Filter::And);
+            window_filters.push(query::Filter::is_in_set(window));
+            window_filters.extend(filters.iter().cloned());
+            window_filters.push(query::Filter::End);
+
+            let result = tokio::select! {
+                result = tokio::time::timeout(
+                    deadline - now,
+                    self.jmap
+                        .store
+                        .filter(account_id, Collection::Email, window_filters),
+                ) => result,
+                _ = self.writer.closed() => {
+                    return Err(StatusResponse::no("Search cancelled."));
+                }
+            };
+
+            match result {
+                Ok(Ok(result_set)) => {
+                    results |= result_set.results;
+                    for term in result_set.fts_terms {
+                        if !fts_terms.contains(&term) {
+                            fts_terms.push(term);
+                        }
+                    }
+                }
+                Ok(Err(store::Error::Retryable(err))) => {
+                    tracing::debug!(parent: &self.span,
+                        event = "truncated",
+                        context = "search",
+                        reason = %err,
+                        "Search window exceeded the store read budget.");
+                    is_truncated = true;
+                    break;
+                }
+                Ok(Err(err)) => {
+                    tracing::error!(parent: &self.span,
+                        event = "error",
+                        context = "search",
+                        error = ?err,
+                        "Failed to execute filter.");
+                    return Err(StatusResponse::database_failure());
+                }
+                Err(_) => {
+                    is_truncated = true;
+                    break;
+                }
+            }
+
+            window_size = window_size.saturating_mul(2);
+        }
+
+        Ok((
+            ResultSet {
+                account_id,
+                collection: Collection::Email.into(),
+                results,
+                fts_terms,
+            },
+            is_truncated,
+        ))
+    }
+}
+
+fn contains_text(value: &Value, text: &str) -> bool {
+    match value {
+        Value::Text(value) => value.contains(text),
+        Value::List(values) => values.iter().any(|value| contains_text(value, text)),
+        Value::Object(object) => object
+            .properties
+            .values()
+            .any(|value| contains_text(value, text)),
+        _ => false,
+    }
+}
+
+// Body terms to center excerpts on
+fn snippet_terms(filters: &[Filter]) -> Vec<String> {
+    match_terms(filters)
+        .into_iter()
+        .filter(|(property, _)| *property == MatchProperty::Body)
+        .map(|(_, term)| term)
+        .collect()
+}
+
+// Builds an excerpt of up to `length` characters centered on the first term
+// found in the text, or the start of the text if no term is found
+fn body_snippet(
+    text: &str,
+    terms: &[String],
+    length: usize,
+    (highlight_start, highlight_end): &(String, String),
+) -> Option<String> {
+    let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+    if text.is_empty() {
+        return None;
+    }
+
+    let term_match = terms
+        .iter()
+        .filter(|term| !term.is_empty())
+        .filter_map(|term| {
+            text.char_indices()
+                .find(|(pos, _)| {
+                    text.get(*pos..*pos + term.len())
+                        .map_or(false, |value| value.eq_ignore_ascii_case(term))
+                })
+                .map(|(pos, _)| (pos, pos + term.len()))
+        })
+        .min();
+
+    let mut snippet = String::with_capacity(length + highlight_start.len() + highlight_end.len());
+    if let Some((start, end)) = term_match {
+        let term_len = text[start..end].chars().count();
+        let context = length.saturating_sub(term_len);
+        let before = text[..start].chars().rev().take(context / 2).count();
+        let after = context - before;
+        snippet.extend(
+            text[..start]
+                .chars()
+                .skip(text[..start].chars().count() - before),
+        );
+        snippet.push_str(highlight_start);
+        snippet.push_str(&text[start..end]);
+        snippet.push_str(highlight_end);
+        snippet.extend(text[end..].chars().take(after));
+    } else {
+        snippet.extend(text.chars().take(length));
+    }
+
+    Some(snippet)
+}
+
+// Text terms outside of a NOT, along with the properties they are matched against
+// Returns the keywords a message must have to match the search
+fn bucket_keywords(filters: &[Filter]) -> Vec<Keyword> {
+    let mut keywords = Vec::new();
+    let mut operators = Vec::new();
+
+    for filter in filters {
+        let keyword = match filter {
+            Filter::And | Filter::Or | Filter::Not => {
+                operators.push(filter);
+                continue;
+            }
+            Filter::End => {
+                operators.pop();
+                continue;
+            }
+            _ if operators.contains(&&Filter::Not) => continue,
+            Filter::Seen => Keyword::Seen,
+            Filter::Flagged => Keyword::Flagged,
+            Filter::Answered => Keyword::Answered,
+            Filter::Deleted => Keyword::Deleted,
+            Filter::Draft => Keyword::Draft,
+            Filter::Keyword(flag) => Keyword::from(flag.clone()),
+            _ => continue,
+        };
+        if !keywords.contains(&keyword) {
+            keywords.push(keyword);
+        }
+    }
+
+    keywords
+}
+
+fn match_terms(filters: &[Filter]) -> Vec<(MatchProperty, String)> {
+    let mut terms = Vec::new();
+    let mut operators = Vec::new();
+
+    for filter in filters {
+        let (properties, text): (&[MatchProperty], &String) = match filter {
+            Filter::And | Filter::Or | Filter::Not => {
+                operators.push(filter);
+                continue;
+            }
+            Filter::End => {
+                operators.pop();
+                continue;
+            }
+            _ if operators.contains(&&Filter::Not) => continue,
+            Filter::From(text) => (&[MatchProperty::From], text),
+            Filter::To(text) => (&[MatchProperty::To], text),
+            Filter::Cc(text) => (&[MatchProperty::Cc], text),
+            Filter::Bcc(text) => (&[MatchProperty::Bcc], text),
+            Filter::Subject(text) => (&[MatchProperty::Subject], text),
+            Filter::Body(text) => (&[MatchProperty::Body], text),
+            Filter::Text(text) => (
+                &[
+                    MatchProperty::From,
+                    MatchProperty::To,
+                    MatchProperty::Cc,
+                    MatchProperty::Bcc,
+                    MatchProperty::Subject,
+                    MatchProperty::Body,
+                ],
+                text,
+            ),
+            _ => continue,
+        };
+
+        for property in properties {
+            let term = (*property, text.clone());
+            if !terms.contains(&term) {
+                terms.push(term);
+            }
+        }
+    }
+
+    terms
+}
+
+// Dates are parsed as UTC midnight, shift them to midnight at the client's fixed
+// offset. Days are always 86400 seconds long as fixed offsets have no DST.
+fn local_day_start(date: i64, timezone: i32) -> u64 {
+    (date - timezone as i64).max(0) as u64
 }
 
 impl SelectedMailbox {
@@ -725,16 +1764,28 @@ impl SelectedMailbox {
 }
 
 impl MailboxState {
+    pub fn is_same_epoch(&self, uid_validity: u32) -> bool {
+        self.uid_validity == uid_validity
+            && self
+                .next_state
+                .as_ref()
+                .map_or(true, |s| s.next_state.uid_validity == uid_validity)
+    }
+
     pub fn map_result_id(&self, document_id: u32, is_uid: bool) -> Option<(u32, ImapId)> {
         if let Some(imap_id) = self.id_to_imap.get(&document_id) {
             Some((if is_uid { imap_id.uid } else { imap_id.seqnum }, *imap_id))
         } else if is_uid {
-            self.next_state.as_ref().and_then(|s| {
-                s.next_state
-                    .id_to_imap
-                    .get(&document_id)
-                    .map(|imap_id| (imap_id.uid, *imap_id))
-            })
+            // UIDs from a pending state are only valid within the same UIDVALIDITY epoch
+            self.next_state
+                .as_ref()
+                .filter(|s| s.next_state.uid_validity == self.uid_validity)
+                .and_then(|s| {
+                    s.next_state
+                        .id_to_imap
+                        .get(&document_id)
+                        .map(|imap_id| (imap_id.uid, *imap_id))
+                })
         } else {
             None
         }
@@ -38,12 +38,11 @@ use super::ToModSeq;
 
 impl<T: AsyncRead> Session<T> {
     pub async fn handle_select(&mut self, request: Request<Command>) -> crate::OpResult {
-        let is_select = request.command == Command::Select;
+        let data = self.state.session_data();
+        let is_select = request.command == Command::Select && !data.is_read_only;
         let command = request.command;
         match request.parse_select(self.version) {
             Ok(arguments) => {
-                let data = self.state.session_data();
-
                 // Refresh mailboxes
                 if let Err(err) = data.synchronize_mailboxes(false).await {
                     return self
@@ -57,6 +56,8 @@ impl<T: AsyncRead> Session<T> {
                         Ok(state) => {
                             let closed_previous = self.state.is_mailbox_selected();
                             let is_condstore = self.is_condstore || arguments.condstore;
+                            // Saved queries are always opened read-only
+                            let is_select = is_select && mailbox.query_id.is_none();
 
                             // Build new state
                             let uid_validity = state.uid_validity;
@@ -124,7 +125,7 @@ impl<T: AsyncRead> Session<T> {
                                 highest_modseq,
                                 mailbox_id: Id::from_parts(
                                     mailbox.id.account_id,
-                                    mailbox.id.mailbox_id.unwrap_or(u32::MAX),
+                                    mailbox.id.state_id(),
                                 )
                                 .to_string(),
                             };
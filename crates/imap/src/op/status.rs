@@ -89,6 +89,7 @@ impl SessionData {
         } else {
             // Some IMAP clients will try to get the status of a mailbox with the NoSelect flag
             return if mailbox_name == self.imap.name_shared
+                || mailbox_name == self.imap.name_saved
                 || mailbox_name
                     .split_once('/')
                     .map_or(false, |(base_name, path)| {
@@ -133,7 +134,7 @@ impl SessionData {
             if account.account_id == mailbox.account_id {
                 let mailbox_state = account
                     .mailbox_state
-                    .entry(mailbox.mailbox_id.as_ref().cloned().unwrap_or(u32::MAX))
+                    .entry(mailbox.state_id())
                     .or_insert_with(Mailbox::default);
                 for item in items {
                     match item {
@@ -191,11 +192,8 @@ impl SessionData {
                             items_response.push((
                                 *item,
                                 StatusItemType::String(
-                                    Id::from_parts(
-                                        mailbox.account_id,
-                                        mailbox.mailbox_id.unwrap_or(u32::MAX),
-                                    )
-                                    .to_string(),
+                                    Id::from_parts(mailbox.account_id, mailbox.state_id())
+                                        .to_string(),
                                 ),
                             ));
                         }
@@ -217,17 +215,27 @@ impl SessionData {
                 None
             };
 
-            if let Some(mailbox_id) = mailbox.mailbox_id {
-                let mailbox_message_ids = self
-                    .jmap
-                    .get_tag(
-                        mailbox.account_id,
-                        Collection::Email,
-                        Property::MailboxIds,
-                        mailbox_id,
-                    )
-                    .await?
-                    .map(Arc::new);
+            let mailbox_message_ids = match (mailbox.mailbox_id, mailbox.query_id) {
+                (Some(mailbox_id), _) => Some(
+                    self.jmap
+                        .get_tag(
+                            mailbox.account_id,
+                            Collection::Email,
+                            Property::MailboxIds,
+                            mailbox_id,
+                        )
+                        .await?,
+                ),
+                (None, Some(query_id)) => Some(
+                    self.jmap
+                        .saved_query_ids(mailbox.account_id, query_id)
+                        .await?,
+                ),
+                (None, None) => None,
+            };
+
+            if let Some(mailbox_message_ids) = mailbox_message_ids {
+                let mailbox_message_ids = mailbox_message_ids.map(Arc::new);
                 let message_ids = self
                     .jmap
                     .get_document_ids(mailbox.account_id, Collection::Email)
@@ -358,7 +366,7 @@ impl SessionData {
                 if account.account_id == mailbox.account_id {
                     let mailbox_state = account
                         .mailbox_state
-                        .entry(mailbox.mailbox_id.as_ref().cloned().unwrap_or(u32::MAX))
+                        .entry(mailbox.state_id())
                         .or_insert_with(Mailbox::default);
 
                     for (item, value) in values_update {
@@ -262,6 +262,7 @@ impl SessionData {
                     .with_account_id(account_id)
                     .with_collection(Collection::Email)
                     .update_document(id);
+                let added_keywords = keywords.added().to_vec();
                 keywords.update_batch(&mut batch, Property::Keywords);
                 if changelog.change_id == u64::MAX {
                     changelog.change_id =
@@ -294,7 +295,11 @@ impl SessionData {
                                 }
                             }
                         }
-                        changelog.log_update(Collection::Email, Id::from_parts(thread_id, id));
+                        let id = Id::from_parts(thread_id, id);
+                        changelog.log_update(Collection::Email, id);
+                        for keyword in added_keywords {
+                            changelog.log_tag_set(Collection::Email, id, keyword);
+                        }
 
                         // Add item to response
                         let modseq = changelog.change_id + 1;
@@ -26,14 +26,18 @@ use std::sync::Arc;
 use ahash::AHashMap;
 use imap_proto::{
     protocol::{
-        thread::{Arguments, Response},
+        thread::{Algorithm, Arguments, Response, Thread},
         ImapResponse,
     },
     receiver::Request,
     Command, StatusResponse,
 };
 
-use jmap_proto::types::{collection::Collection, property::Property};
+use jmap_proto::{
+    object::Object,
+    types::{collection::Collection, property::Property, value::Value},
+};
+use mail_parser::parsers::fields::thread::thread_name;
 use store::ValueKey;
 use tokio::io::AsyncRead;
 
@@ -49,10 +53,11 @@ impl<T: AsyncRead> Session<T> {
         match request.parse_thread() {
             Ok(mut arguments) => {
                 let (data, mailbox) = self.state.mailbox_state();
+                let timezone = self.timezone;
 
                 tokio::spawn(async move {
                     let tag = std::mem::take(&mut arguments.tag);
-                    let bytes = match data.thread(arguments, mailbox, is_uid).await {
+                    let bytes = match data.thread(arguments, mailbox, is_uid, timezone).await {
                         Ok(response) => StatusResponse::completed(command)
                             .with_tag(tag)
                             .serialize(response.serialize()),
@@ -73,11 +78,13 @@ impl SessionData {
         arguments: Arguments,
         mailbox: Arc<SelectedMailbox>,
         is_uid: bool,
+        timezone: i32,
     ) -> Result<Response, StatusResponse> {
         // Run query
-        let (result_set, _) = self
-            .query(arguments.filter, &mailbox, &None, is_uid)
-            .await?;
+        let result_set = self
+            .query(arguments.filter, &mailbox, &None, is_uid, timezone, None)
+            .await?
+            .result_set;
 
         // Synchronize mailbox
         if !result_set.results.is_empty() {
@@ -85,18 +92,18 @@ impl SessionData {
         }
 
         // Obtain threadIds for matching messages
+        let document_ids = result_set.results.iter().collect::<Vec<_>>();
         let thread_ids = self
             .jmap
             .store
             .get_values::<u32>(
-                result_set
-                    .results
+                document_ids
                     .iter()
                     .map(|document_id| {
                         ValueKey::new(
                             mailbox.id.account_id,
                             Collection::Email,
-                            document_id,
+                            *document_id,
                             Property::ThreadId,
                         )
                     })
@@ -112,27 +119,161 @@ impl SessionData {
                 StatusResponse::database_failure()
             })?;
 
-        // Group messages by thread
-        let mut threads: AHashMap<u32, Vec<u32>> = AHashMap::new();
-        let state = mailbox.state.lock();
-        for (document_id, thread_id) in result_set.results.into_iter().zip(thread_ids) {
-            if let (Some(thread_id), Some((imap_id, _))) =
-                (thread_id, state.map_result_id(document_id, is_uid))
+        // Obtain the headers needed to build the thread trees
+        let mut messages = Vec::with_capacity(document_ids.len());
+        for (document_id, thread_id) in document_ids.into_iter().zip(thread_ids) {
+            let (thread_id, imap_id) = match (
+                thread_id,
+                mailbox.state.lock().map_result_id(document_id, is_uid),
+            ) {
+                (Some(thread_id), Some((imap_id, _))) => (thread_id, imap_id),
+                _ => continue,
+            };
+            let metadata = if let Some(metadata) = self
+                .jmap
+                .get_property::<Object<Value>>(
+                    mailbox.id.account_id,
+                    Collection::Email,
+                    document_id,
+                    &Property::BodyStructure,
+                )
+                .await
+                .map_err(|_| StatusResponse::database_failure())?
             {
-                threads
-                    .entry(thread_id)
-                    .or_insert_with(Vec::new)
-                    .push(imap_id);
+                metadata
+            } else {
+                continue;
+            };
+
+            let references = message_ids(metadata.get(&Property::References));
+            messages.push(ThreadMessage {
+                id: imap_id,
+                thread_id,
+                sent_at: metadata
+                    .get(&Property::SentAt)
+                    .as_date()
+                    .or_else(|| metadata.get(&Property::ReceivedAt).as_date())
+                    .map_or(0, |date| date.timestamp()),
+                message_id: message_ids(metadata.get(&Property::MessageId))
+                    .into_iter()
+                    .next(),
+                references: if !references.is_empty() {
+                    references
+                } else {
+                    message_ids(metadata.get(&Property::InReplyTo))
+                },
+                subject: thread_name(metadata.get(&Property::Subject).as_string().unwrap_or(""))
+                    .to_lowercase(),
+            });
+        }
+        messages.sort_unstable_by_key(|message| (message.sent_at, message.id));
+
+        // Build response
+        Ok(Response {
+            is_uid,
+            threads: match arguments.algorithm {
+                Algorithm::References => thread_by_references(messages),
+                Algorithm::OrderedSubject => thread_by_subject(messages),
+            },
+        })
+    }
+}
+
+struct ThreadMessage {
+    id: u32,
+    thread_id: u32,
+    sent_at: i64,
+    message_id: Option<String>,
+    references: Vec<String>,
+    subject: String,
+}
+
+fn message_ids(value: &Value) -> Vec<String> {
+    match value {
+        Value::Text(id) => vec![id.to_string()],
+        Value::List(ids) => ids
+            .iter()
+            .filter_map(|id| id.as_string().map(|id| id.to_string()))
+            .collect(),
+        _ => vec![],
+    }
+}
+
+// Messages must be sorted by date, the order is preserved among siblings and threads
+fn thread_by_references(messages: Vec<ThreadMessage>) -> Vec<Thread> {
+    let mut groups: AHashMap<u32, Vec<ThreadMessage>> = AHashMap::new();
+    let mut group_order = Vec::new();
+    for message in messages {
+        groups
+            .entry(message.thread_id)
+            .or_insert_with(|| {
+                group_order.push(message.thread_id);
+                Vec::new()
+            })
+            .push(message);
+    }
+
+    let mut threads = Vec::with_capacity(group_order.len());
+    for thread_id in group_order {
+        let group = groups.remove(&thread_id).unwrap_or_default();
+        let positions = group
+            .iter()
+            .enumerate()
+            .filter_map(|(pos, message)| message.message_id.as_deref().map(|id| (id, pos)))
+            .collect::<AHashMap<_, _>>();
+
+        // Link each message to its closest referenced ancestor, skipping cycles
+        let mut parents = vec![None; group.len()];
+        for (pos, message) in group.iter().enumerate() {
+            for reference in message.references.iter().rev() {
+                if let Some(&parent) = positions.get(reference.as_str()) {
+                    let mut ancestor = Some(parent);
+                    while let Some(current) = ancestor.filter(|current| *current != pos) {
+                        ancestor = parents[current];
+                    }
+                    if ancestor.is_none() {
+                        parents[pos] = Some(parent);
+                        break;
+                    }
+                }
             }
         }
 
-        let mut threads = threads
-            .into_iter()
-            .map(|(_, messages)| messages)
+        let mut roots = (0..group.len())
+            .filter(|pos| parents[*pos].is_none())
+            .map(|pos| build_tree(pos, &group, &parents))
             .collect::<Vec<_>>();
-        threads.sort_unstable();
+        threads.push(if roots.len() == 1 {
+            roots.pop().unwrap()
+        } else {
+            Thread::placeholder(roots)
+        });
+    }
 
-        // Build response
-        Ok(Response { is_uid, threads })
+    threads
+}
+
+fn build_tree(pos: usize, messages: &[ThreadMessage], parents: &[Option<usize>]) -> Thread {
+    Thread::new(
+        messages[pos].id,
+        (0..messages.len())
+            .filter(|child| parents[*child] == Some(pos))
+            .map(|child| build_tree(child, messages, parents))
+            .collect(),
+    )
+}
+
+// Messages must be sorted by date, the first message of each subject is the parent
+fn thread_by_subject(messages: Vec<ThreadMessage>) -> Vec<Thread> {
+    let mut threads: Vec<Thread> = Vec::new();
+    let mut subjects: AHashMap<String, usize> = AHashMap::new();
+    for message in messages {
+        if let Some(&pos) = subjects.get(&message.subject) {
+            threads[pos].children.push(Thread::new(message.id, vec![]));
+        } else {
+            subjects.insert(message.subject, threads.len());
+            threads.push(Thread::new(message.id, vec![]));
+        }
     }
+    threads
 }
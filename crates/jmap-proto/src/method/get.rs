@@ -53,6 +53,7 @@ pub enum RequestArguments {
     VacationResponse,
     Principal,
     Quota,
+    SavedQuery,
     Blob(blob::GetArguments),
 }
 
@@ -89,6 +90,7 @@ impl JsonObjectParser for GetRequest<RequestArguments> {
                 MethodObject::Principal => RequestArguments::Principal,
                 MethodObject::Blob => RequestArguments::Blob(Default::default()),
                 MethodObject::Quota => RequestArguments::Quota,
+                MethodObject::SavedQuery => RequestArguments::SavedQuery,
                 _ => {
                     return Err(Error::Method(MethodError::UnknownMethod(format!(
                         "{}/get",
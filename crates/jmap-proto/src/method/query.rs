@@ -91,6 +91,8 @@ pub enum Filter {
     InMailboxOtherThan(Vec<Id>),
     MinSize(u32),
     MaxSize(u32),
+    MinAttachments(u32),
+    MaxAttachments(u32),
     AllInThreadHaveKeyword(Keyword),
     SomeInThreadHaveKeyword(Keyword),
     NoneInThreadHaveKeyword(Keyword),
@@ -152,6 +154,7 @@ pub enum SortProperty {
     AllInThreadHaveKeyword,
     SomeInThreadHaveKeyword,
     Used,
+    Relevance,
     _T(String),
 }
 
@@ -347,6 +350,18 @@ pub fn parse_filter(parser: &mut Parser) -> crate::parser::Result<Vec<Filter>> {
                                 .unwrap_uint_or_null("maxSize")?
                                 .unwrap_or_default() as u32,
                         ),
+                        (0x7374_6e65_6d68_6361_7474_416e_696d, _) => Filter::MinAttachments(
+                            parser
+                                .next_token::<String>()?
+                                .unwrap_uint_or_null("minAttachments")?
+                                .unwrap_or_default() as u32,
+                        ),
+                        (0x7374_6e65_6d68_6361_7474_4178_616d, _) => Filter::MaxAttachments(
+                            parser
+                                .next_token::<String>()?
+                                .unwrap_uint_or_null("maxAttachments")?
+                                .unwrap_or_default() as u32,
+                        ),
                         (0x4b65_7661_4864_6165_7268_546e_496c_6c61, 0x6472_6f77_7965) => {
                             Filter::AllInThreadHaveKeyword(
                                 parser
@@ -583,6 +598,7 @@ impl JsonObjectParser for SortProperty {
             0x4b65_7661_4864_6165_7268_546e_496c_6c61 => Ok(SortProperty::AllInThreadHaveKeyword),
             0x6576_6148_6461_6572_6854_6e49_656d_6f73 => Ok(SortProperty::SomeInThreadHaveKeyword),
             0x6465_7375 => Ok(SortProperty::Used),
+            0x0065_636e_6176_656c_6572 => Ok(SortProperty::Relevance),
             _ => {
                 if parser.is_eof || parser.skip_string() {
                     Ok(SortProperty::_T(
@@ -621,6 +637,8 @@ impl Display for Filter {
             Filter::InMailboxOtherThan(_) => "inMailboxOtherThan",
             Filter::MinSize(_) => "minSize",
             Filter::MaxSize(_) => "maxSize",
+            Filter::MinAttachments(_) => "minAttachments",
+            Filter::MaxAttachments(_) => "maxAttachments",
             Filter::AllInThreadHaveKeyword(_) => "allInThreadHaveKeyword",
             Filter::SomeInThreadHaveKeyword(_) => "someInThreadHaveKeyword",
             Filter::NoneInThreadHaveKeyword(_) => "noneInThreadHaveKeyword",
@@ -676,6 +694,7 @@ impl Display for SortProperty {
             SortProperty::AllInThreadHaveKeyword => "allInThreadHaveKeyword",
             SortProperty::SomeInThreadHaveKeyword => "someInThreadHaveKeyword",
             SortProperty::Used => "used",
+            SortProperty::Relevance => "relevance",
             SortProperty::_T(s) => s,
         })
     }
@@ -703,6 +722,8 @@ impl Filter {
                 | Filter::After(_)
                 | Filter::MinSize(_)
                 | Filter::MaxSize(_)
+                | Filter::MinAttachments(_)
+                | Filter::MaxAttachments(_)
                 | Filter::Text(_)
                 | Filter::HasAttachment(_)
                 | Filter::From(_)
@@ -71,6 +71,7 @@ pub enum RequestArguments {
     PushSubscription,
     SieveScript(sieve::SetArguments),
     VacationResponse,
+    SavedQuery,
 }
 
 #[derive(Debug, Clone, Default, serde::Serialize)]
@@ -131,6 +132,7 @@ impl JsonObjectParser for SetRequest<RequestArguments> {
                 MethodObject::PushSubscription => RequestArguments::PushSubscription,
                 MethodObject::VacationResponse => RequestArguments::VacationResponse,
                 MethodObject::SieveScript => RequestArguments::SieveScript(Default::default()),
+                MethodObject::SavedQuery => RequestArguments::SavedQuery,
                 _ => {
                     return Err(Error::Method(MethodError::UnknownMethod(format!(
                         "{}/set",
@@ -346,10 +348,12 @@ impl JsonObjectParser for Object<SetValue> {
                         parser.next_token()?,
                         parser,
                     )?),
-                    Property::Parameters => SetValue::Value(Value::parse::<String, String>(
-                        parser.next_token()?,
-                        parser,
-                    )?),
+                    Property::Parameters | Property::Filter => {
+                        SetValue::Value(Value::parse::<String, String>(
+                            parser.next_token()?,
+                            parser,
+                        )?)
+                    }
                     Property::Members => SetValue::Value(Value::parse::<ObjectProperty, Id>(
                         parser.next_token()?,
                         parser,
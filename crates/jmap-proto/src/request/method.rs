@@ -46,6 +46,7 @@ pub enum MethodObject {
     SieveScript,
     Principal,
     Quota,
+    SavedQuery,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -113,6 +114,7 @@ impl JsonObjectParser for MethodName {
                 0x0074_7069_7263_5365_7665_6953 => MethodObject::SieveScript,
                 0x006c_6170_6963_6e69_7250 => MethodObject::Principal,
                 0x0061_746f_7551 => MethodObject::Quota,
+                0x7972_6575_5164_6576_6153 => MethodObject::SavedQuery,
                 0x6572_6f43 => MethodObject::Core,
                 _ => return Err(parser.error_value()),
             },
@@ -207,6 +209,10 @@ impl MethodName {
             (MethodFunction::Query, MethodObject::Quota) => "Quota/query",
             (MethodFunction::QueryChanges, MethodObject::Quota) => "Quota/queryChanges",
 
+            (MethodFunction::Get, MethodObject::SavedQuery) => "SavedQuery/get",
+            (MethodFunction::Changes, MethodObject::SavedQuery) => "SavedQuery/changes",
+            (MethodFunction::Set, MethodObject::SavedQuery) => "SavedQuery/set",
+
             (MethodFunction::Get, MethodObject::Blob) => "Blob/get",
             (MethodFunction::Copy, MethodObject::Blob) => "Blob/copy",
             (MethodFunction::Lookup, MethodObject::Blob) => "Blob/lookup",
@@ -234,6 +240,7 @@ impl Display for MethodObject {
             MethodObject::Thread => "Thread",
             MethodObject::Email => "Email",
             MethodObject::Quota => "Quota",
+            MethodObject::SavedQuery => "SavedQuery",
         })
     }
 }
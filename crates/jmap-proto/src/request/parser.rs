@@ -143,6 +143,7 @@ impl Request {
                                 | MethodObject::SieveScript
                                 | MethodObject::Principal
                                 | MethodObject::Quota
+                                | MethodObject::SavedQuery
                                 | MethodObject::Blob,
                             ) => GetRequest::parse(parser).map(RequestMethod::Get),
                             (MethodFunction::Get, MethodObject::SearchSnippet) => {
@@ -28,8 +28,11 @@ use crate::{
     parser::{json::Parser, Error, JsonObjectParser, Token},
     request::Call,
     response::{serialize::serialize_hex, Response, ResponseMethod},
-    types::{any_id::AnyId, id::Id, state::State, type_state::DataType},
+    types::{
+        any_id::AnyId, blob::BlobId, date::UTCDate, id::Id, state::State, type_state::DataType,
+    },
 };
+use serde_json::value::RawValue;
 use utils::map::vec_map::VecMap;
 
 use super::{Request, RequestProperty};
@@ -37,6 +40,7 @@ use super::{Request, RequestProperty};
 #[derive(Debug)]
 pub struct WebSocketRequest {
     pub id: Option<String>,
+    pub idempotency_key: Option<String>,
     pub request: Request,
 }
 
@@ -70,6 +74,17 @@ pub enum WebSocketResponseType {
 pub struct WebSocketPushEnable {
     pub data_types: Vec<DataType>,
     pub push_state: Option<String>,
+    pub server_time: bool,
+    pub ack_required: bool,
+    pub client_id: Option<String>,
+    pub throttle: Option<u64>,
+    pub initial_state: bool,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct WebSocketUploadTicketRequest {
+    pub id: Option<String>,
+    pub account_id: Id,
 }
 
 #[derive(Debug)]
@@ -77,6 +92,9 @@ pub enum WebSocketMessage {
     Request(WebSocketRequest),
     PushEnable(WebSocketPushEnable),
     PushDisable,
+    PushAck(u64),
+    Batch(Vec<Result<WebSocketRequest, WebSocketRequestError>>),
+    UploadTicket(WebSocketUploadTicketRequest),
 }
 
 #[derive(serde::Serialize, Debug)]
@@ -92,6 +110,65 @@ pub struct WebSocketStateChange {
     #[serde(rename = "pushState")]
     #[serde(skip_serializing_if = "Option::is_none")]
     push_state: Option<String>,
+    #[serde(rename = "seqId")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub seq_id: Option<u64>,
+}
+
+#[derive(serde::Serialize, Debug)]
+pub enum WebSocketUploadTicketType {
+    UploadTicket,
+}
+
+#[derive(serde::Serialize, Debug)]
+pub struct WebSocketUploadTicket {
+    #[serde(rename = "@type")]
+    pub type_: WebSocketUploadTicketType,
+    #[serde(rename = "accountId")]
+    pub account_id: Id,
+    pub ticket: String,
+    #[serde(rename = "uploadUrl")]
+    pub upload_url: String,
+    pub expires: UTCDate,
+    #[serde(rename = "requestId")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub request_id: Option<String>,
+}
+
+#[derive(serde::Serialize, Debug)]
+pub enum WebSocketBlobAvailableType {
+    BlobAvailable,
+}
+
+#[derive(serde::Serialize, Debug)]
+pub struct WebSocketBlobAvailable {
+    #[serde(rename = "@type")]
+    pub type_: WebSocketBlobAvailableType,
+    #[serde(rename = "accountId")]
+    pub account_id: Id,
+    #[serde(rename = "blobId")]
+    pub blob_id: BlobId,
+    #[serde(rename = "type")]
+    pub c_type: String,
+    pub size: usize,
+    #[serde(rename = "downloadUrl")]
+    pub download_url: String,
+    #[serde(rename = "requestId")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub request_id: Option<String>,
+}
+
+#[derive(serde::Serialize, Debug)]
+pub enum WebSocketServerTimeType {
+    ServerTime,
+}
+
+#[derive(serde::Serialize, Debug)]
+pub struct WebSocketServerTime {
+    #[serde(rename = "@type")]
+    pub type_: WebSocketServerTimeType,
+    #[serde(rename = "serverTime")]
+    pub server_time: UTCDate,
 }
 
 #[derive(Debug, serde::Serialize)]
@@ -121,9 +198,18 @@ enum MessageType {
     Request,
     PushEnable,
     PushDisable,
+    PushAck,
+    UploadTicket,
     None,
 }
 
+// Transcodes a serialized JSON message to MessagePack
+pub fn json_to_msgpack(json: &str) -> Option<Vec<u8>> {
+    serde_json::from_str::<serde_json::Value>(json)
+        .ok()
+        .and_then(|value| rmp_serde::to_vec(&value).ok())
+}
+
 impl WebSocketMessage {
     pub fn parse(
         json: &[u8],
@@ -131,70 +217,191 @@ impl WebSocketMessage {
         max_size: usize,
     ) -> Result<Self, WebSocketRequestError> {
         if json.len() <= max_size {
-            let mut message_type = MessageType::None;
-            let mut request = WebSocketRequest {
-                id: None,
-                request: Request::default(),
-            };
-            let mut push_enable = WebSocketPushEnable::default();
-
-            let mut found_request_keys = false;
-            let mut found_push_keys = false;
-
-            let mut parser = Parser::new(json);
-            parser.next_token::<String>()?.assert(Token::DictStart)?;
-            while let Some(key) = parser.next_dict_key::<u128>()? {
-                match key {
-                    0x0065_7079_7440 => {
-                        let rt = parser
-                            .next_token::<RequestProperty>()?
-                            .unwrap_string("@type")?;
-                        message_type = match (rt.hash[0], rt.hash[1]) {
-                            (0x0074_7365_7571_6552, 0) => MessageType::Request,
-                            (0x616e_4568_7375_5074_656b_636f_5362_6557, 0x656c62) => {
-                                MessageType::PushEnable
-                            }
-                            (0x7369_4468_7375_5074_656b_636f_5362_6557, 0x656c6261) => {
-                                MessageType::PushDisable
-                            }
-                            _ => MessageType::None,
-                        };
-                    }
-                    0x0073_6570_7954_6174_6164 => {
-                        push_enable.data_types =
-                            <Option<Vec<DataType>>>::parse(&mut parser)?.unwrap_or_default();
-                        found_push_keys = true;
-                    }
-                    0x0065_7461_7453_6873_7570 => {
-                        push_enable.push_state = parser
-                            .next_token::<String>()?
-                            .unwrap_string_or_null("pushState")?;
-                        found_push_keys = true;
-                    }
-                    0x6469 => {
-                        request.id = parser.next_token::<String>()?.unwrap_string_or_null("id")?;
-                    }
-                    _ => {
-                        found_request_keys |=
-                            request.request.parse_key(&mut parser, max_calls, key)?;
-                    }
-                }
+            if json.iter().find(|ch| !ch.is_ascii_whitespace()) == Some(&b'[') {
+                Self::parse_batch(json, max_calls)
+            } else {
+                Self::parse_message(json, max_calls)
             }
+        } else {
+            Err(RequestError::limit(RequestLimitError::SizeRequest).into())
+        }
+    }
 
-            match message_type {
-                MessageType::Request if found_request_keys => {
-                    Ok(WebSocketMessage::Request(request))
+    // MessagePack messages are transcoded to JSON and parsed as text messages
+    pub fn parse_msgpack(
+        bytes: &[u8],
+        max_calls: usize,
+        max_size: usize,
+    ) -> Result<Self, WebSocketRequestError> {
+        if bytes.len() <= max_size {
+            let json = rmp_serde::from_slice::<serde_json::Value>(bytes)
+                .ok()
+                .and_then(|value| serde_json::to_vec(&value).ok())
+                .ok_or_else(|| {
+                    RequestError::not_request("Invalid MessagePack WebSocket message")
+                })?;
+            Self::parse(&json, max_calls, usize::MAX)
+        } else {
+            Err(RequestError::limit(RequestLimitError::SizeRequest).into())
+        }
+    }
+
+    fn parse_batch(json: &[u8], max_calls: usize) -> Result<Self, WebSocketRequestError> {
+        let messages = serde_json::from_slice::<Vec<&RawValue>>(json)
+            .map_err(|_| RequestError::not_request("Invalid WebSocket JMAP request batch"))?;
+        if messages.is_empty() {
+            return Err(RequestError::not_request("Empty WebSocket JMAP request batch").into());
+        } else if messages.len() > max_calls {
+            return Err(RequestError::limit(RequestLimitError::CallsIn).into());
+        }
+
+        // Each request is parsed on its own so that one invalid entry does not fail the batch
+        Ok(WebSocketMessage::Batch(
+            messages
+                .into_iter()
+                .map(
+                    |message| match Self::parse_message(message.get().as_bytes(), max_calls)? {
+                        WebSocketMessage::Request(request) => Ok(request),
+                        _ => Err(RequestError::not_request(
+                            "Only JMAP requests can be sent in a batch",
+                        )
+                        .into()),
+                    },
+                )
+                .collect(),
+        ))
+    }
+
+    fn parse_message(json: &[u8], max_calls: usize) -> Result<Self, WebSocketRequestError> {
+        let mut message_type = MessageType::None;
+        let mut request = WebSocketRequest {
+            id: None,
+            idempotency_key: None,
+            request: Request::default(),
+        };
+        let mut push_enable = WebSocketPushEnable::default();
+        let mut seq_id = None;
+        let mut account_id = None;
+
+        let mut found_request_keys = false;
+        let mut found_push_keys = false;
+
+        let mut parser = Parser::new(json);
+        parser.next_token::<String>()?.assert(Token::DictStart)?;
+        while let Some(key) = parser.next_dict_key::<u128>()? {
+            match key {
+                0x0065_7079_7440 => {
+                    let rt = parser
+                        .next_token::<RequestProperty>()?
+                        .unwrap_string("@type")?;
+                    message_type = match (rt.hash[0], rt.hash[1]) {
+                        (0x0074_7365_7571_6552, 0) => MessageType::Request,
+                        (0x616e_4568_7375_5074_656b_636f_5362_6557, 0x656c62) => {
+                            MessageType::PushEnable
+                        }
+                        (0x7369_4468_7375_5074_656b_636f_5362_6557, 0x656c6261) => {
+                            MessageType::PushDisable
+                        }
+                        (0x6b63_4168_7375_5074_656b_636f_5362_6557, 0) => MessageType::PushAck,
+                        (0x5464_616f_6c70_5574_656b_636f_5362_6557, 0x74_656b_6369) => {
+                            MessageType::UploadTicket
+                        }
+                        _ => MessageType::None,
+                    };
+                }
+                0x0073_6570_7954_6174_6164 => {
+                    push_enable.data_types =
+                        <Option<Vec<DataType>>>::parse(&mut parser)?.unwrap_or_default();
+                    found_push_keys = true;
+                }
+                0x0065_7461_7453_6873_7570 => {
+                    push_enable.push_state = parser
+                        .next_token::<String>()?
+                        .unwrap_string_or_null("pushState")?;
+                    found_push_keys = true;
+                }
+                0x656d_6954_7265_7672_6573 => {
+                    push_enable.server_time = parser
+                        .next_token::<String>()?
+                        .unwrap_bool_or_null("serverTime")?
+                        .unwrap_or_default();
+                    found_push_keys = true;
+                }
+                0x0064_6572_6975_7165_526b_6361 => {
+                    push_enable.ack_required = parser
+                        .next_token::<String>()?
+                        .unwrap_bool_or_null("ackRequired")?
+                        .unwrap_or_default();
+                    found_push_keys = true;
                 }
-                MessageType::PushEnable if found_push_keys => {
-                    Ok(WebSocketMessage::PushEnable(push_enable))
+                0x6449_746e_6569_6c63 => {
+                    push_enable.client_id = parser
+                        .next_token::<String>()?
+                        .unwrap_string_or_null("clientId")?;
+                    found_push_keys = true;
                 }
-                MessageType::PushDisable if !found_request_keys && !found_push_keys => {
-                    Ok(WebSocketMessage::PushDisable)
+                0x656c_7474_6f72_6874 => {
+                    push_enable.throttle = parser
+                        .next_token::<String>()?
+                        .unwrap_uint_or_null("throttle")?;
+                    found_push_keys = true;
+                }
+                0x6574_6174_536c_6169_7469_6e69 => {
+                    push_enable.initial_state = parser
+                        .next_token::<String>()?
+                        .unwrap_bool_or_null("initialState")?
+                        .unwrap_or_default();
+                    found_push_keys = true;
+                }
+                0x0064_4971_6573 => {
+                    seq_id = parser
+                        .next_token::<String>()?
+                        .unwrap_uint_or_null("seqId")?;
+                }
+                0x6469 => {
+                    request.id = parser.next_token::<String>()?.unwrap_string_or_null("id")?;
+                }
+                0x7965_4b79_636e_6574_6f70_6d65_6469 => {
+                    request.idempotency_key = parser
+                        .next_token::<String>()?
+                        .unwrap_string_or_null("idempotencyKey")?;
+                }
+                0x0064_4974_6e75_6f63_6361 => {
+                    account_id = parser
+                        .next_token::<Id>()?
+                        .unwrap_string("accountId")?
+                        .into();
+                }
+                _ => {
+                    found_request_keys |= request.request.parse_key(&mut parser, max_calls, key)?;
                 }
-                _ => Err(RequestError::not_request("Invalid WebSocket JMAP request").into()),
             }
-        } else {
-            Err(RequestError::limit(RequestLimitError::SizeRequest).into())
+        }
+
+        match message_type {
+            MessageType::Request if found_request_keys => Ok(WebSocketMessage::Request(request)),
+            MessageType::PushEnable if found_push_keys => {
+                Ok(WebSocketMessage::PushEnable(push_enable))
+            }
+            MessageType::PushDisable if !found_request_keys && !found_push_keys => {
+                Ok(WebSocketMessage::PushDisable)
+            }
+            MessageType::PushAck if !found_request_keys && !found_push_keys => match seq_id {
+                Some(seq_id) => Ok(WebSocketMessage::PushAck(seq_id)),
+                None => Err(RequestError::not_request("Missing seqId").into()),
+            },
+            MessageType::UploadTicket if !found_request_keys && !found_push_keys => {
+                match account_id {
+                    Some(account_id) => Ok(WebSocketMessage::UploadTicket(
+                        WebSocketUploadTicketRequest {
+                            id: request.id,
+                            account_id,
+                        },
+                    )),
+                    None => Err(RequestError::not_request("Missing accountId").into()),
+                }
+            }
+            _ => Err(RequestError::not_request("Invalid WebSocket JMAP request").into()),
         }
     }
 }
@@ -250,6 +457,32 @@ impl WebSocketStateChange {
             type_: WebSocketStateChangeType::StateChange,
             changed: VecMap::new(),
             push_state,
+            seq_id: None,
+        }
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).unwrap()
+    }
+}
+
+impl WebSocketUploadTicket {
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).unwrap()
+    }
+}
+
+impl WebSocketBlobAvailable {
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).unwrap()
+    }
+}
+
+impl WebSocketServerTime {
+    pub fn new(timestamp: i64) -> Self {
+        WebSocketServerTime {
+            type_: WebSocketServerTimeType::ServerTime,
+            server_time: UTCDate::from_timestamp(timestamp),
         }
     }
 
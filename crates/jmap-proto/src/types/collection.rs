@@ -38,7 +38,8 @@ pub enum Collection {
     SieveScript = 5,
     PushSubscription = 6,
     Principal = 7,
-    None = 8,
+    SavedQuery = 8,
+    None = 9,
 }
 
 impl From<u8> for Collection {
@@ -52,6 +53,7 @@ impl From<u8> for Collection {
             5 => Collection::SieveScript,
             6 => Collection::PushSubscription,
             7 => Collection::Principal,
+            8 => Collection::SavedQuery,
             _ => Collection::None,
         }
     }
@@ -68,6 +70,7 @@ impl From<u64> for Collection {
             5 => Collection::SieveScript,
             6 => Collection::PushSubscription,
             7 => Collection::Principal,
+            8 => Collection::SavedQuery,
             _ => Collection::None,
         }
     }
@@ -113,6 +116,7 @@ impl Display for Collection {
             Collection::EmailSubmission => write!(f, "emailSubmission"),
             Collection::SieveScript => write!(f, "sieveScript"),
             Collection::Principal => write!(f, "principal"),
+            Collection::SavedQuery => write!(f, "savedQuery"),
             Collection::None => write!(f, ""),
         }
     }
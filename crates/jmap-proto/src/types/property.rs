@@ -136,6 +136,9 @@ pub enum Property {
     WarnLimit,
     SoftLimit,
     Scope,
+    SpamScore,
+    AttachmentCount,
+    Filter,
     Digest(DigestProperty),
     Data(DataProperty),
     _T(String),
@@ -388,6 +391,7 @@ fn parse_property(first_char: u8, hash: u128) -> Option<Property> {
         b'f' => match hash {
             0x006d_6f72 => Property::From,
             0x0065_7461_446d_6f72 => Property::FromDate,
+            0x0072_6574_6c69 => Property::Filter,
             _ => return None,
         },
         b'h' => match hash {
@@ -900,6 +904,9 @@ impl Display for Property {
             Property::Scope => write!(f, "scope"),
             Property::WarnLimit => write!(f, "warnLimit"),
             Property::SoftLimit => write!(f, "softLimit"),
+            Property::SpamScore => write!(f, "spamScore"),
+            Property::AttachmentCount => write!(f, "attachmentCount"),
+            Property::Filter => write!(f, "filter"),
             Property::_T(s) => write!(f, "{s}"),
         }
     }
@@ -1080,6 +1087,9 @@ impl From<&Property> for u8 {
             Property::WarnLimit => 101,
             Property::SoftLimit => 102,
             Property::Scope => 103,
+            Property::SpamScore => 104,
+            Property::AttachmentCount => 105,
+            Property::Filter => 106,
             Property::Digest(_) | Property::Data(_) => unreachable!("invalid property"),
         }
     }
@@ -1222,6 +1232,9 @@ impl SerializeInto for Property {
             Property::WarnLimit => 101,
             Property::SoftLimit => 102,
             Property::Scope => 103,
+            Property::SpamScore => 104,
+            Property::AttachmentCount => 105,
+            Property::Filter => 106,
             Property::Digest(_) | Property::Data(_) => {
                 unreachable!("Property::Digest and Property::Data are not serializable")
             }
@@ -1340,6 +1353,9 @@ impl DeserializeFrom for Property {
             101 => Some(Property::WarnLimit),
             102 => Some(Property::SoftLimit),
             103 => Some(Property::Scope),
+            104 => Some(Property::SpamScore),
+            105 => Some(Property::AttachmentCount),
+            106 => Some(Property::Filter),
             _ => None,
         }
     }
@@ -23,8 +23,10 @@
 
 use std::{str::FromStr, time::Duration};
 
+use jmap_proto::types::type_state::DataType;
 use nlp::language::Language;
 use store::rand::{distributions::Alphanumeric, thread_rng, Rng};
+use utils::map::vec_map::VecMap;
 
 use super::session::BaseCapabilities;
 
@@ -84,6 +86,10 @@ impl crate::Config {
             mail_parse_max_items: settings
                 .property("jmap.email.parse.max-items")?
                 .unwrap_or(10),
+            spam_score_header: settings
+                .value("jmap.email.index.spam-score-header")
+                .map(|header| header.to_string()),
+            index_any_header: settings.property_or_static("imap.search.any-header", "false")?,
             sieve_max_script_name: settings
                 .property("sieve.untrusted.limits.name-length")?
                 .unwrap_or(512),
@@ -130,8 +136,58 @@ impl crate::Config {
             event_source_throttle: settings
                 .property_or_static("jmap.event-source.throttle", "1s")?,
             web_socket_throttle: settings.property_or_static("jmap.web-socket.throttle", "1s")?,
+            web_socket_throttle_min: settings
+                .property_or_static("jmap.web-socket.throttle-min", "100ms")?,
+            web_socket_throttle_max: settings
+                .property_or_static("jmap.web-socket.throttle-max", "1m")?,
             web_socket_timeout: settings.property_or_static("jmap.web-socket.timeout", "10m")?,
             web_socket_heartbeat: settings.property_or_static("jmap.web-socket.heartbeat", "1m")?,
+            web_socket_max_missed_heartbeats: settings
+                .property_or_static("jmap.web-socket.max-missed-heartbeats", "3")?,
+            web_socket_max_queued: settings
+                .property_or_static("jmap.web-socket.max-queued-messages", "256")?,
+            web_socket_backpressure_timeout: settings
+                .property_or_static("jmap.web-socket.backpressure-timeout", "30s")?,
+            web_socket_token_check_interval: settings
+                .property_or_static("jmap.web-socket.token-check-interval", "5m")?,
+            web_socket_rate_limit: settings
+                .property_or_static("jmap.web-socket.rate-limit.requests-per-second", "10")?,
+            web_socket_rate_burst: settings
+                .property_or_static("jmap.web-socket.rate-limit.burst", "50")?,
+            web_socket_rate_max_violations: settings
+                .property_or_static("jmap.web-socket.rate-limit.max-violations", "10")?,
+            web_socket_ack_ttl: settings.property_or_static("jmap.web-socket.ack-ttl", "1h")?,
+            web_socket_upload_ticket_ttl: settings
+                .property_or_static("jmap.web-socket.upload-ticket-ttl", "5m")?,
+            web_socket_idempotency_ttl: settings
+                .property_or_static("jmap.web-socket.idempotency-ttl", "5m")?,
+            web_socket_idempotency_max: settings
+                .property_or_static("jmap.web-socket.idempotency-cache.size", "1024")?,
+            web_socket_compression: settings
+                .property_or_static("jmap.web-socket.compression.enable", "true")?,
+            web_socket_compression_level: settings
+                .property_or_static("jmap.web-socket.compression.level", "6")
+                .and_then(|level: u32| {
+                    if level <= 9 {
+                        Ok(level)
+                    } else {
+                        Err(format!(
+                            "Invalid compression level {level} in property \"jmap.web-socket.compression.level\", expected 0-9."
+                        ))
+                    }
+                })?,
+            web_socket_compression_threshold: settings
+                .property_or_static("jmap.web-socket.compression.threshold", "128")?,
+            web_socket_type_throttle: settings
+                .properties::<Duration>("jmap.web-socket.type-throttle")
+                .map(|result| {
+                    let (key, throttle) = result?;
+                    let name = key.rsplit_once('.').map_or(key, |(_, name)| name);
+                    DataType::try_from(name)
+                        .map(|data_type| (data_type, throttle))
+                        .map_err(|_| format!("Invalid data type {name:?} in property {key:?}"))
+                })
+                .collect::<Result<VecMap<_, _>, String>>()?,
             push_max_total: settings.property_or_static("jmap.push.max-total", "100")?,
             principal_allow_lookups: settings
                 .property("jmap.principal.allow-lookups")?
@@ -73,11 +73,14 @@ pub async fn parse_jmap_request(
             {
                 Ok(Some(session)) => session,
                 Ok(None) => {
+                    if let Some(response) = jmap.handle_ticket_upload(&mut req).await {
+                        return response;
+                    }
                     return if req.method() != Method::OPTIONS {
                         RequestError::unauthorized().into_http_response()
                     } else {
                         ().into_http_response()
-                    }
+                    };
                 }
                 Err(err) => return err.into_http_response(),
             };
@@ -377,6 +380,10 @@ pub async fn parse_jmap_request(
                         .into_http_response(),
                     };
                 }
+                ("websocket", "metrics", &Method::GET) => {
+                    return JsonResponse::new(jmap.web_socket_metrics.snapshot())
+                        .into_http_response();
+                }
                 (path_1 @ ("queue" | "report"), path_2, &Method::GET) => {
                     return jmap
                         .smtp
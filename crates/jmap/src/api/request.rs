@@ -130,6 +130,18 @@ impl JMAP {
         next_call: &mut Option<Call<RequestMethod>>,
         instance: &Arc<ServerInstance>,
     ) -> Result<ResponseMethod, MethodError> {
+        // Accounts with an expired password are limited to read-only access
+        if matches!(
+            method,
+            RequestMethod::Set(_)
+                | RequestMethod::Copy(_)
+                | RequestMethod::ImportEmail(_)
+                | RequestMethod::CopyBlob(_)
+                | RequestMethod::UploadBlob(_)
+        ) {
+            access_token.assert_is_writable()?;
+        }
+
         Ok(match method {
             RequestMethod::Get(mut req) => match req.take_arguments() {
                 get::RequestArguments::Email(arguments) => {
@@ -186,6 +198,11 @@ impl JMAP {
 
                     self.quota_get(req, access_token).await?.into()
                 }
+                get::RequestArguments::SavedQuery => {
+                    access_token.assert_is_member(req.account_id)?;
+
+                    self.saved_query_get(req).await?.into()
+                }
                 get::RequestArguments::Blob(arguments) => {
                     access_token.assert_is_member(req.account_id)?;
 
@@ -274,6 +291,11 @@ impl JMAP {
 
                     self.vacation_response_set(req).await?.into()
                 }
+                set::RequestArguments::SavedQuery => {
+                    access_token.assert_is_member(req.account_id)?;
+
+                    self.saved_query_set(req).await?.into()
+                }
             },
             RequestMethod::Changes(req) => self.changes(req, access_token).await?.into(),
             RequestMethod::Copy(req) => {
@@ -27,7 +27,7 @@ use jmap_proto::{
     error::request::RequestError,
     request::capability::Capability,
     response::serialize::serialize_hex,
-    types::{acl::Acl, collection::Collection, id::Id, type_state::DataType},
+    types::{acl::Acl, collection::Collection, date::UTCDate, id::Id, type_state::DataType},
 };
 use store::ahash::AHashSet;
 use utils::{listener::ServerInstance, map::vec_map::VecMap, UnwrapFailure};
@@ -69,6 +69,9 @@ struct Account {
     is_read_only: bool,
     #[serde(rename(serialize = "accountCapabilities"))]
     account_capabilities: VecMap<Capability, Capabilities>,
+    #[serde(rename(serialize = "createdAt"))]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    created_at: Option<UTCDate>,
 }
 
 #[derive(Debug, Clone, serde::Serialize)]
@@ -200,6 +203,7 @@ impl JMAP {
             None,
             &self.config.capabilities.account,
         );
+        session.set_created_at(access_token.primary_id().into(), access_token.created_at);
 
         // Add secondary accounts
         for id in access_token.secondary_ids() {
@@ -377,6 +381,12 @@ impl Session {
         );
     }
 
+    pub fn set_created_at(&mut self, account_id: Id, created_at: Option<u64>) {
+        if let Some(account) = self.accounts.get_mut(&account_id) {
+            account.created_at = created_at.map(|date| UTCDate::from_timestamp(date as i64));
+        }
+    }
+
     pub fn set_state(&mut self, state: u32) {
         self.state = state;
     }
@@ -397,6 +407,7 @@ impl Account {
             is_personal,
             is_read_only,
             account_capabilities: VecMap::new(),
+            created_at: None,
         }
     }
 
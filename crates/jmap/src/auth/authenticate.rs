@@ -27,6 +27,7 @@ use std::{
     time::Instant,
 };
 
+use directory::Protocol;
 use hyper::header;
 use jmap_proto::{
     error::{method::MethodError, request::RequestError},
@@ -42,7 +43,25 @@ use utils::{listener::limiter::InFlight, map::ttl_dashmap::TtlMap};
 
 use crate::JMAP;
 
-use super::{rate_limit::RemoteAddress, AccessToken};
+use super::{oauth::TokenError, rate_limit::RemoteAddress, AccessToken};
+
+// Outcome of verifying a set of credentials. Accounts enrolled in two-factor
+// authentication are not granted access until their second factor is verified,
+// so no access token is issued for them.
+pub enum AuthResult {
+    Success(AccessToken),
+    SecondFactorRequired { name: String },
+    Failure,
+}
+
+// Bearer token a long-lived connection was authenticated with
+#[derive(Debug, Clone)]
+pub enum BearerToken {
+    // Issued by this server's OAuth endpoints
+    Issued(String),
+    // Verified by the directory, such as tokens issued by an OIDC provider
+    Directory(String),
+}
 
 impl JMAP {
     pub async fn authenticate_headers(
@@ -86,17 +105,7 @@ impl JMAP {
                     // Enforce anonymous rate limit for bearer auth requests
                     self.is_anonymous_allowed(&addr)?;
 
-                    match self.validate_access_token("access_token", &token).await {
-                        Ok((account_id, _, _)) => self.get_access_token(account_id).await,
-                        Err(err) => {
-                            tracing::debug!(
-                                context = "authenticate_headers",
-                                err = err,
-                                "Failed to validate access token."
-                            );
-                            None
-                        }
-                    }
+                    self.authenticate_bearer(&token, &addr).await
                 } else {
                     // Enforce anonymous rate limit
                     self.is_anonymous_allowed(&addr)?;
@@ -111,6 +120,14 @@ impl JMAP {
             };
 
             if let Some(session) = session {
+                if !session.is_protocol_enabled(Protocol::Jmap) {
+                    return Err(RequestError::blank(
+                        403,
+                        "Forbidden",
+                        "Protocol not enabled for this account.",
+                    ));
+                }
+
                 // Enforce authenticated rate limit
                 Ok(Some((self.is_account_allowed(&session)?, session)))
             } else {
@@ -272,27 +289,105 @@ impl JMAP {
         secret: &str,
         remote_addr: &RemoteAddress,
     ) -> Option<AccessToken> {
-        let mut principal = match self
-            .directory
-            .authenticate(&Credentials::Plain {
+        self.authenticate_credentials(
+            &Credentials::Plain {
                 username: username.to_string(),
                 secret: secret.to_string(),
-            })
-            .await
-        {
+            },
+            remote_addr,
+        )
+        .await
+    }
+
+    pub async fn authenticate_bearer(
+        &self,
+        token: &str,
+        remote_addr: &RemoteAddress,
+    ) -> Option<AccessToken> {
+        match self.validate_access_token("access_token", token).await {
+            Ok((account_id, _, _)) => self.get_access_token(account_id).await,
+            Err(err) if self.directory.verifies_bearer_tokens() => {
+                tracing::debug!(
+                    context = "authenticate",
+                    err = %err,
+                    "Failed to validate access token, trying directory."
+                );
+
+                // Tokens issued by an external identity provider are verified by the directory
+                self.authenticate_credentials(
+                    &Credentials::OAuthBearer {
+                        token: token.to_string(),
+                    },
+                    remote_addr,
+                )
+                .await
+            }
+            Err(err) => {
+                tracing::debug!(
+                    context = "authenticate",
+                    err = %err,
+                    "Failed to validate access token."
+                );
+                None
+            }
+        }
+    }
+
+    pub async fn authenticate_credentials(
+        &self,
+        credentials: &Credentials<String>,
+        remote_addr: &RemoteAddress,
+    ) -> Option<AccessToken> {
+        match self.verify_credentials(credentials, remote_addr).await {
+            AuthResult::Success(access_token) => Some(access_token),
+            AuthResult::SecondFactorRequired { name } => {
+                // None of the protocols using this method can prompt for a second factor
+                tracing::debug!(
+                    context = "authenticate",
+                    event = "second-factor-required",
+                    account = name,
+                    "Authentication refused, a second factor is required for this account."
+                );
+                None
+            }
+            AuthResult::Failure => None,
+        }
+    }
+
+    pub async fn verify_credentials(
+        &self,
+        credentials: &Credentials<String>,
+        remote_addr: &RemoteAddress,
+    ) -> AuthResult {
+        let mut principal = match self.directory.authenticate(credentials).await {
             Ok(Some(principal)) => principal,
             Ok(None) => {
                 let _ = self.is_auth_allowed_hard(remote_addr);
-                return None;
+                return AuthResult::Failure;
             }
             Err(_) => {
-                return None;
+                return AuthResult::Failure;
             }
         };
 
         if !principal.has_name() {
-            principal.name = username.to_string();
+            match credentials {
+                Credentials::Plain { username, .. } | Credentials::XOauth2 { username, .. } => {
+                    principal.name = username.to_string();
+                }
+                Credentials::OAuthBearer { .. } => {
+                    return AuthResult::Failure;
+                }
+            }
         }
+
+        // Access tokens are only issued once the second factor is verified
+        if principal.requires_second_factor() {
+            return AuthResult::SecondFactorRequired {
+                name: principal.name,
+            };
+        }
+
         // Obtain groups
         if let (Ok(account_id), Ok(member_of)) = (
             self.get_account_id(&principal.name).await,
@@ -304,8 +399,9 @@ impl JMAP {
                 AccessToken::new(principal, account_id).with_member_of(member_of),
             )
             .await
+            .map_or(AuthResult::Failure, AuthResult::Success)
         } else {
-            None
+            AuthResult::Failure
         }
     }
 
@@ -328,6 +424,76 @@ impl JMAP {
             None
         }
     }
+
+    // Resolves again the access token of a long-lived connection, returns None once the
+    // account is removed or loses access to JMAP, or its bearer token expires or is revoked.
+    pub async fn revalidate_access_token(
+        &self,
+        primary_id: u32,
+        bearer_token: Option<&BearerToken>,
+    ) -> Option<AccessToken> {
+        match bearer_token {
+            Some(BearerToken::Issued(token)) => {
+                match self.validate_access_token("access_token", token).await {
+                    Ok(_) | Err(TokenError::TemporaryFailure) => (),
+                    Err(err) => {
+                        tracing::debug!(
+                            context = "authenticate",
+                            account_id = primary_id,
+                            err = %err,
+                            "Access token is no longer valid."
+                        );
+                        return None;
+                    }
+                }
+            }
+            Some(BearerToken::Directory(token)) => {
+                match self
+                    .directory
+                    .authenticate(&Credentials::OAuthBearer {
+                        token: token.to_string(),
+                    })
+                    .await
+                {
+                    Ok(Some(principal)) => {
+                        if !matches!(
+                            self.try_get_account_id(&principal.name).await,
+                            Ok(Some(account_id)) if account_id == primary_id
+                        ) {
+                            tracing::debug!(
+                                context = "authenticate",
+                                account_id = primary_id,
+                                "Bearer token no longer maps to this account."
+                            );
+                            return None;
+                        }
+                    }
+                    Ok(None) => {
+                        tracing::debug!(
+                            context = "authenticate",
+                            account_id = primary_id,
+                            "Bearer token rejected by directory."
+                        );
+                        return None;
+                    }
+                    Err(err) => {
+                        // Keep the connection open on transient directory failures
+                        tracing::debug!(
+                            context = "authenticate",
+                            account_id = primary_id,
+                            err = ?err,
+                            "Failed to verify bearer token with directory."
+                        );
+                    }
+                }
+            }
+            None => (),
+        }
+
+        self.get_access_token(primary_id)
+            .await
+            .filter(|access_token| access_token.is_protocol_enabled(Protocol::Jmap))
+    }
 }
 
 pub struct AccountKey();
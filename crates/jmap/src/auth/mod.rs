@@ -31,7 +31,7 @@ use aes_gcm_siv::{
     AeadInPlace, Aes256GcmSiv, KeyInit, Nonce,
 };
 
-use directory::{Principal, Type};
+use directory::{Principal, Protocol, Type};
 use jmap_proto::{
     error::method::MethodError,
     types::{collection::Collection, id::Id},
@@ -53,6 +53,11 @@ pub struct AccessToken {
     pub description: Option<String>,
     pub quota: u32,
     pub is_superuser: bool,
+    pub password_expired: bool,
+    pub created_at: Option<u64>,
+    pub protocols: Option<u32>,
+    pub shard: Option<String>,
+    pub recovery_email: Option<String>,
 }
 
 impl AccessToken {
@@ -65,6 +70,11 @@ impl AccessToken {
             description: principal.description,
             quota: principal.quota,
             is_superuser: principal.typ == Type::Superuser,
+            password_expired: principal.password_expired,
+            created_at: principal.created_at,
+            protocols: principal.protocols,
+            shard: principal.shard,
+            recovery_email: principal.recovery_email,
         }
     }
 
@@ -98,6 +108,11 @@ impl AccessToken {
         self.primary_id == account_id || self.member_of.contains(&account_id) || self.is_superuser
     }
 
+    pub fn is_protocol_enabled(&self, protocol: Protocol) -> bool {
+        self.protocols
+            .map_or(true, |protocols| protocols & protocol.mask() != 0)
+    }
+
     pub fn is_primary_id(&self, account_id: u32) -> bool {
         self.primary_id == account_id
     }
@@ -146,6 +161,16 @@ impl AccessToken {
         }
     }
 
+    pub fn assert_is_writable(&self) -> Result<&Self, MethodError> {
+        if !self.password_expired {
+            Ok(self)
+        } else {
+            Err(MethodError::Forbidden(
+                "Password expired, the account is read-only until it is changed.".to_string(),
+            ))
+        }
+    }
+
     pub fn assert_is_member(&self, account_id: Id) -> Result<&Self, MethodError> {
         if self.is_member(account_id.document_id()) {
             Ok(self)
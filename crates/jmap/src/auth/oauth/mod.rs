@@ -73,6 +73,17 @@ pub struct OAuth {
     pub metadata: String,
 }
 
+// Reasons an OAuth token issued by this server fails validation
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TokenError {
+    Malformed,
+    Expired,
+    TemporaryFailure,
+    AccountNotFound,
+    MissingSecret,
+    InvalidSignature,
+}
+
 pub struct OAuthCode {
     pub status: AtomicU32,
     pub account_id: AtomicU32,
@@ -285,3 +296,16 @@ pub async fn fetch_body(req: &mut HttpRequest, max_len: usize) -> Option<Vec<u8>
     }
     bytes.into()
 }
+
+impl std::fmt::Display for TokenError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            TokenError::Malformed => "Failed to decode token.",
+            TokenError::Expired => "Token expired.",
+            TokenError::TemporaryFailure => "Temporary lookup error.",
+            TokenError::AccountNotFound => "Account no longer exists.",
+            TokenError::MissingSecret => "Failed to obtain password hash.",
+            TokenError::InvalidSignature => "Failed to decrypt token.",
+        })
+    }
+}
@@ -42,8 +42,8 @@ use crate::{
 };
 
 use super::{
-    ErrorType, FormData, TokenResponse, CLIENT_ID_MAX_LEN, MAX_POST_LEN, RANDOM_CODE_LEN,
-    STATUS_AUTHORIZED, STATUS_PENDING, STATUS_TOKEN_ISSUED,
+    ErrorType, FormData, TokenError, TokenResponse, CLIENT_ID_MAX_LEN, MAX_POST_LEN,
+    RANDOM_CODE_LEN, STATUS_AUTHORIZED, STATUS_PENDING, STATUS_TOKEN_ISSUED,
 };
 
 impl JMAP {
@@ -275,9 +275,9 @@ impl JMAP {
         &self,
         grant_type: &str,
         token: &str,
-    ) -> Result<(u32, String, u64), &'static str> {
+    ) -> Result<(u32, String, u64), TokenError> {
         // Base64 decode token
-        let token = base64_decode(token.as_bytes()).ok_or("Failed to decode.")?;
+        let token = base64_decode(token.as_bytes()).ok_or(TokenError::Malformed)?;
         let (account_id, expiry, client_id) = token
             .get((RANDOM_CODE_LEN + SymmetricEncrypt::ENCRYPT_TAG_LEN)..)
             .and_then(|bytes| {
@@ -289,7 +289,7 @@ impl JMAP {
                 )
                     .into()
             })
-            .ok_or("Failed to decode token.")?;
+            .ok_or(TokenError::Malformed)?;
 
         // Validate expiration
         let now = SystemTime::now()
@@ -298,25 +298,25 @@ impl JMAP {
             .unwrap_or(0)
             .saturating_sub(946684800); // Jan 1, 2000
         if expiry <= now {
-            return Err("Token expired.");
+            return Err(TokenError::Expired);
         }
 
         // Optain password hash
         let account_name = self
             .get_account_name(account_id)
             .await
-            .map_err(|_| "Temporary lookup error")?
-            .ok_or("Account no longer exists")?;
+            .map_err(|_| TokenError::TemporaryFailure)?
+            .ok_or(TokenError::AccountNotFound)?;
         let password_hash = self
             .directory
             .principal(&account_name)
             .await
-            .map_err(|_| "Temporary lookup error")?
-            .ok_or("Account no longer exists")?
+            .map_err(|_| TokenError::TemporaryFailure)?
+            .ok_or(TokenError::AccountNotFound)?
             .secrets
             .into_iter()
             .next()
-            .ok_or("Failed to obtain password hash")?;
+            .ok_or(TokenError::MissingSecret)?;
 
         // Build context
         let key = self.config.oauth_key.clone();
@@ -344,7 +344,7 @@ impl JMAP {
                 &token[..RANDOM_CODE_LEN + SymmetricEncrypt::ENCRYPT_TAG_LEN],
                 &nonce,
             )
-            .map_err(|_| "Failed to decrypt token.")?;
+            .map_err(|_| TokenError::InvalidSignature)?;
 
         // Success
         Ok((account_id, client_id, expiry - now))
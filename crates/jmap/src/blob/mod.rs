@@ -31,12 +31,12 @@ pub mod upload;
 #[derive(Debug, serde::Serialize)]
 pub struct UploadResponse {
     #[serde(rename(serialize = "accountId"))]
-    account_id: Id,
+    pub account_id: Id,
     #[serde(rename(serialize = "blobId"))]
-    blob_id: BlobId,
+    pub blob_id: BlobId,
     #[serde(rename(serialize = "type"))]
-    c_type: String,
-    size: usize,
+    pub c_type: String,
+    pub size: usize,
 }
 
 pub struct DownloadResponse {
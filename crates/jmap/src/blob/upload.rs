@@ -217,6 +217,10 @@ impl JMAP {
         data: &[u8],
         access_token: Arc<AccessToken>,
     ) -> Result<UploadResponse, RequestError> {
+        if access_token.password_expired {
+            return Err(RequestError::forbidden());
+        }
+
         // Limit concurrent uploads
         let _in_flight = self.is_upload_allowed(&access_token)?;
 
@@ -26,7 +26,10 @@ use jmap_proto::{
     method::changes::{ChangesRequest, ChangesResponse, RequestArguments},
     types::{collection::Collection, property::Property, state::State},
 };
-use store::query::log::{Change, Changes, Query};
+use store::{
+    ahash::AHashMap,
+    query::log::{Change, Changes, Query},
+};
 
 use crate::{auth::AccessToken, JMAP};
 
@@ -62,6 +65,11 @@ impl JMAP {
 
                 Collection::EmailSubmission
             }
+            RequestArguments::SavedQuery => {
+                access_token.assert_is_member(request.account_id)?;
+
+                Collection::SavedQuery
+            }
             RequestArguments::Quota => {
                 access_token.assert_is_member(request.account_id)?;
 
@@ -196,4 +204,26 @@ impl JMAP {
                 MethodError::ServerPartialFail
             })
     }
+
+    pub async fn tag_set_times_(
+        &self,
+        account_id: u32,
+        collection: Collection,
+        tags: &[Vec<u8>],
+        since: u64,
+    ) -> Result<AHashMap<u64, u64>, MethodError> {
+        self.store
+            .tag_set_times(account_id, collection, tags, since)
+            .await
+            .map_err(|err| {
+                tracing::error!(
+                event = "error",
+                context = "changes",
+                account_id = account_id,
+                collection = ?collection,
+                error = ?err,
+                "Failed to query tag set times.");
+                MethodError::ServerPartialFail
+            })
+    }
 }
@@ -65,6 +65,8 @@ pub(super) trait IndexMessage {
         mailbox_ids: Vec<u32>,
         received_at: u64,
         default_language: Language,
+        spam_score_header: Option<&str>,
+        index_any_header: bool,
     ) -> store::Result<&mut Self>;
 }
 
@@ -76,6 +78,8 @@ impl IndexMessage for BatchBuilder {
         mailbox_ids: Vec<u32>,
         received_at: u64,
         default_language: Language,
+        spam_score_header: Option<&str>,
+        index_any_header: bool,
     ) -> store::Result<&mut Self> {
         let mut metadata = Object::with_capacity(15);
 
@@ -101,6 +105,8 @@ impl IndexMessage for BatchBuilder {
         let mut seen_headers = [false; 40];
         let mut language = Language::Unknown;
         let mut has_attachments = false;
+        let mut spam_score = None;
+        let attachment_count = message.attachments.len() as u32;
         let preview_part_id = message
             .text_body
             .first()
@@ -119,7 +125,32 @@ impl IndexMessage for BatchBuilder {
                 language = part_language;
                 let mut extra_ids = Vec::new();
                 for header in part.headers.into_iter().rev() {
-                    if matches!(header.name, HeaderName::Other(_)) {
+                    if let HeaderName::Other(name) = &header.name {
+                        // Headers are visited in reverse, so the topmost score header wins
+                        if spam_score_header.map_or(false, |h| name.eq_ignore_ascii_case(h)) {
+                            header.value.visit_text(|text| {
+                                if let Some(score) = parse_spam_score(text) {
+                                    spam_score = Some(score);
+                                }
+                            });
+                        }
+
+                        // Index non-RFC headers under their lowercased name, only
+                        // when searching them is enabled as it grows the index
+                        let header_name = format!("{}:", name.to_lowercase());
+                        if index_any_header && header_name.len() < MAX_TOKEN_LENGTH {
+                            header.value.visit_text(|text| {
+                                for token in text.split_ascii_whitespace() {
+                                    if header_name.len() + token.len() < MAX_TOKEN_LENGTH {
+                                        fts.index_raw_token(
+                                            Property::Headers,
+                                            format!("{header_name}{}", token.to_lowercase()),
+                                        );
+                                    }
+                                }
+                            });
+                            fts.index_raw_token(Property::Headers, header_name);
+                        }
                         continue;
                     }
                     // Index hasHeader property
@@ -201,6 +232,16 @@ impl IndexMessage for BatchBuilder {
 
                                     // Index an address name or email without stemming
                                     fts.index_raw(u8::from(&property), value);
+
+                                    // Index the decoded tokens for header searches
+                                    for token in value.split_ascii_whitespace() {
+                                        if token.len() < MAX_TOKEN_LENGTH {
+                                            fts.index_raw_token(
+                                                Property::Headers,
+                                                format!("{header_num}{}", token.to_lowercase()),
+                                            );
+                                        }
+                                    }
                                 });
 
                                 if !seen_header {
@@ -272,11 +313,39 @@ impl IndexMessage for BatchBuilder {
                                 seen_headers[header.name.id() as usize] = true;
                             }
 
+                            // Index the decoded subject tokens for header searches
+                            for token in subject.split_ascii_whitespace() {
+                                if token.len() < MAX_TOKEN_LENGTH {
+                                    fts.index_raw_token(
+                                        Property::Headers,
+                                        format!("{header_num}{}", token.to_lowercase()),
+                                    );
+                                }
+                            }
+
                             // Index subject for FTS
                             fts.index(Property::Subject, subject, language);
                         }
 
-                        HeaderName::Comments | HeaderName::Keywords | HeaderName::ListId => {
+                        HeaderName::ListId | HeaderName::ListPost | HeaderName::ListUnsubscribe => {
+                            // Index mailing list headers
+                            let mut tokens = Vec::new();
+                            header.value.visit_addresses(|_, value| {
+                                tokens.extend(value.split_ascii_whitespace().map(list_token));
+                            });
+                            header.value.visit_text(|text| {
+                                tokens.extend(text.split_ascii_whitespace().map(list_token));
+                            });
+                            for token in tokens {
+                                if !token.is_empty() && token.len() < MAX_TOKEN_LENGTH {
+                                    fts.index_raw_token(
+                                        Property::Headers,
+                                        format!("{header_num}{token}"),
+                                    );
+                                }
+                            }
+                        }
+                        HeaderName::Comments | HeaderName::Keywords => {
                             // Index headers
                             header.value.visit_text(|text| {
                                 for token in text.split_ascii_whitespace() {
@@ -297,6 +366,13 @@ impl IndexMessage for BatchBuilder {
                 if !extra_ids.is_empty() {
                     metadata.append(Property::EmailIds, Value::List(extra_ids));
                 }
+
+                // Index spam score
+                if let Some(spam_score) = spam_score {
+                    let spam_score = spam_score_index(spam_score);
+                    metadata.append(Property::SpamScore, spam_score as u64);
+                    self.value(Property::SpamScore, spam_score, F_INDEX);
+                }
             }
 
             // Add subject to index if missing
@@ -383,6 +459,10 @@ impl IndexMessage for BatchBuilder {
             self.bitmap(Property::HasAttachment, (), 0);
         }
 
+        // Store and index the number of attachments
+        metadata.append(Property::AttachmentCount, attachment_count as u64);
+        self.value(Property::AttachmentCount, attachment_count, F_INDEX);
+
         // Store properties
         self.value(Property::BodyStructure, metadata, F_VALUE);
 
@@ -487,6 +567,12 @@ impl IntoOperations for EmailIndexBuilder {
                 (Property::HasAttachment, Value::Bool(true)) => {
                     batch.bitmap(Property::HasAttachment, (), options);
                 }
+                (Property::SpamScore, Value::UnsignedInt(spam_score)) => {
+                    batch.value(Property::SpamScore, spam_score as u32, F_INDEX | options);
+                }
+                (Property::AttachmentCount, Value::UnsignedInt(count)) => {
+                    batch.value(Property::AttachmentCount, count as u32, F_INDEX | options);
+                }
                 _ => {}
             }
         }
@@ -571,6 +657,33 @@ trait VisitValues {
     fn into_visit_text(self, visitor: impl FnMut(String));
 }
 
+/// Normalizes a token from a List-Id, List-Post or List-Unsubscribe header
+pub fn list_token(token: &str) -> String {
+    token
+        .trim_matches(|c| matches!(c, '<' | '>' | ',' | '"'))
+        .to_lowercase()
+}
+
+/// Parses a spam score header value into tenths of a point
+pub fn parse_spam_score(text: &str) -> Option<i32> {
+    text.split_ascii_whitespace()
+        .next()?
+        .trim_matches(|c| matches!(c, '(' | ')' | ',' | ';'))
+        .parse::<f64>()
+        .ok()
+        .filter(|score| score.is_finite())
+        .map(|score| {
+            (score * 10.0)
+                .round()
+                .clamp(i32::MIN as f64, i32::MAX as f64) as i32
+        })
+}
+
+/// Maps a spam score in tenths of a point to an index value that sorts numerically
+pub fn spam_score_index(score: i32) -> u32 {
+    (score as u32) ^ 0x8000_0000
+}
+
 #[derive(Debug, PartialEq, Eq)]
 enum AddressElement {
     Name,
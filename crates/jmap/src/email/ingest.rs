@@ -304,6 +304,8 @@ impl JMAP {
                 params.mailbox_ids,
                 params.received_at.unwrap_or_else(now),
                 self.config.default_language,
+                self.config.spam_score_header.as_deref(),
+                self.config.index_any_header,
             )
             .map_err(|err| {
                 tracing::error!(
@@ -36,7 +36,7 @@ use store::{
     ValueKey,
 };
 
-use crate::{auth::AccessToken, JMAP};
+use crate::{auth::AccessToken, email::index::list_token, JMAP};
 
 impl JMAP {
     pub async fn email_query(
@@ -45,9 +45,115 @@ impl JMAP {
         access_token: &AccessToken,
     ) -> Result<QueryResponse, MethodError> {
         let account_id = request.account_id.document_id();
-        let mut filters = Vec::with_capacity(request.filter.len());
+        let filters = self
+            .email_query_filters(account_id, std::mem::take(&mut request.filter))
+            .await?;
 
-        for cond in std::mem::take(&mut request.filter) {
+        let mut result_set = self.filter(account_id, Collection::Email, filters).await?;
+        if access_token.is_shared(account_id) {
+            result_set.apply_mask(
+                self.shared_messages(access_token, account_id, Acl::ReadItems)
+                    .await?,
+            );
+        }
+        let (response, paginate) = self.build_query_response(&result_set, &request).await?;
+
+        if let Some(paginate) = paginate {
+            // Parse sort criteria
+            let mut comparators = Vec::with_capacity(request.sort.as_ref().map_or(1, |s| s.len()));
+            for comparator in request
+                .sort
+                .and_then(|s| if !s.is_empty() { s.into() } else { None })
+                .unwrap_or_else(|| vec![Comparator::descending(SortProperty::ReceivedAt)])
+            {
+                comparators.push(match comparator.property {
+                    SortProperty::ReceivedAt => {
+                        query::Comparator::field(Property::ReceivedAt, comparator.is_ascending)
+                    }
+                    SortProperty::Size => {
+                        query::Comparator::field(Property::Size, comparator.is_ascending)
+                    }
+                    SortProperty::From => {
+                        query::Comparator::field(Property::From, comparator.is_ascending)
+                    }
+                    SortProperty::To => {
+                        query::Comparator::field(Property::To, comparator.is_ascending)
+                    }
+                    SortProperty::Subject => {
+                        query::Comparator::field(Property::Subject, comparator.is_ascending)
+                    }
+                    SortProperty::SentAt => {
+                        query::Comparator::field(Property::SentAt, comparator.is_ascending)
+                    }
+                    SortProperty::HasKeyword => query::Comparator::set(
+                        self.get_tag(
+                            account_id,
+                            Collection::Email,
+                            Property::Keywords,
+                            comparator.keyword.unwrap_or(Keyword::Seen),
+                        )
+                        .await?
+                        .unwrap_or_default(),
+                        comparator.is_ascending,
+                    ),
+                    SortProperty::AllInThreadHaveKeyword => query::Comparator::set(
+                        self.thread_keywords(
+                            account_id,
+                            comparator.keyword.unwrap_or(Keyword::Seen),
+                            true,
+                        )
+                        .await?,
+                        comparator.is_ascending,
+                    ),
+                    SortProperty::SomeInThreadHaveKeyword => query::Comparator::set(
+                        self.thread_keywords(
+                            account_id,
+                            comparator.keyword.unwrap_or(Keyword::Seen),
+                            false,
+                        )
+                        .await?,
+                        comparator.is_ascending,
+                    ),
+                    // Non-standard
+                    SortProperty::Cc => {
+                        query::Comparator::field(Property::Cc, comparator.is_ascending)
+                    }
+                    SortProperty::Relevance => {
+                        query::Comparator::relevance(comparator.is_ascending)
+                    }
+
+                    other => return Err(MethodError::UnsupportedSort(other.to_string())),
+                });
+            }
+
+            // Sort results
+            self.sort(
+                result_set,
+                query::Comparator::with_relevance_tiebreaker(comparators, Property::ReceivedAt),
+                paginate
+                    .with_prefix_key(ValueKey::new(
+                        account_id,
+                        Collection::Email,
+                        0,
+                        Property::ThreadId,
+                    ))
+                    .with_prefix_unique(request.arguments.collapse_threads.unwrap_or(false)),
+                response,
+            )
+            .await
+        } else {
+            Ok(response)
+        }
+    }
+
+    pub async fn email_query_filters(
+        &self,
+        account_id: u32,
+        filter: Vec<Filter>,
+    ) -> Result<Vec<query::Filter>, MethodError> {
+        let mut filters = Vec::with_capacity(filter.len());
+
+        for cond in filter {
             match cond {
                 Filter::InMailbox(mailbox) => filters.push(query::Filter::is_in_bitmap(
                     Property::MailboxIds,
@@ -69,6 +175,12 @@ impl JMAP {
                 Filter::After(date) => filters.push(query::Filter::gt(Property::ReceivedAt, date)),
                 Filter::MinSize(size) => filters.push(query::Filter::ge(Property::Size, size)),
                 Filter::MaxSize(size) => filters.push(query::Filter::lt(Property::Size, size)),
+                Filter::MinAttachments(count) => {
+                    filters.push(query::Filter::ge(Property::AttachmentCount, count))
+                }
+                Filter::MaxAttachments(count) => {
+                    filters.push(query::Filter::lt(Property::AttachmentCount, count))
+                }
                 Filter::AllInThreadHaveKeyword(keyword) => filters.push(query::Filter::is_in_set(
                     self.thread_keywords(account_id, keyword, true).await?,
                 )),
@@ -174,6 +286,12 @@ impl JMAP {
                                     | HeaderName::References
                                     | HeaderName::ResentMessageId
                             );
+                            let is_list = matches!(
+                                header_name,
+                                HeaderName::ListId
+                                    | HeaderName::ListPost
+                                    | HeaderName::ListUnsubscribe
+                            );
                             let tokens = if let Some(header_value) = header.next() {
                                 let header_num = header_name.id().to_string();
                                 header_value
@@ -182,6 +300,8 @@ impl JMAP {
                                         if token.len() < MAX_TOKEN_LENGTH {
                                             if is_id {
                                                 format!("{header_num}{token}")
+                                            } else if is_list {
+                                                format!("{header_num}{}", list_token(token))
                                             } else {
                                                 format!("{header_num}{}", token.to_lowercase())
                                             }
@@ -244,98 +364,7 @@ impl JMAP {
             }
         }
 
-        let mut result_set = self.filter(account_id, Collection::Email, filters).await?;
-        if access_token.is_shared(account_id) {
-            result_set.apply_mask(
-                self.shared_messages(access_token, account_id, Acl::ReadItems)
-                    .await?,
-            );
-        }
-        let (response, paginate) = self.build_query_response(&result_set, &request).await?;
-
-        if let Some(paginate) = paginate {
-            // Parse sort criteria
-            let mut comparators = Vec::with_capacity(request.sort.as_ref().map_or(1, |s| s.len()));
-            for comparator in request
-                .sort
-                .and_then(|s| if !s.is_empty() { s.into() } else { None })
-                .unwrap_or_else(|| vec![Comparator::descending(SortProperty::ReceivedAt)])
-            {
-                comparators.push(match comparator.property {
-                    SortProperty::ReceivedAt => {
-                        query::Comparator::field(Property::ReceivedAt, comparator.is_ascending)
-                    }
-                    SortProperty::Size => {
-                        query::Comparator::field(Property::Size, comparator.is_ascending)
-                    }
-                    SortProperty::From => {
-                        query::Comparator::field(Property::From, comparator.is_ascending)
-                    }
-                    SortProperty::To => {
-                        query::Comparator::field(Property::To, comparator.is_ascending)
-                    }
-                    SortProperty::Subject => {
-                        query::Comparator::field(Property::Subject, comparator.is_ascending)
-                    }
-                    SortProperty::SentAt => {
-                        query::Comparator::field(Property::SentAt, comparator.is_ascending)
-                    }
-                    SortProperty::HasKeyword => query::Comparator::set(
-                        self.get_tag(
-                            account_id,
-                            Collection::Email,
-                            Property::Keywords,
-                            comparator.keyword.unwrap_or(Keyword::Seen),
-                        )
-                        .await?
-                        .unwrap_or_default(),
-                        comparator.is_ascending,
-                    ),
-                    SortProperty::AllInThreadHaveKeyword => query::Comparator::set(
-                        self.thread_keywords(
-                            account_id,
-                            comparator.keyword.unwrap_or(Keyword::Seen),
-                            true,
-                        )
-                        .await?,
-                        comparator.is_ascending,
-                    ),
-                    SortProperty::SomeInThreadHaveKeyword => query::Comparator::set(
-                        self.thread_keywords(
-                            account_id,
-                            comparator.keyword.unwrap_or(Keyword::Seen),
-                            false,
-                        )
-                        .await?,
-                        comparator.is_ascending,
-                    ),
-                    // Non-standard
-                    SortProperty::Cc => {
-                        query::Comparator::field(Property::Cc, comparator.is_ascending)
-                    }
-
-                    other => return Err(MethodError::UnsupportedSort(other.to_string())),
-                });
-            }
-
-            // Sort results
-            self.sort(
-                result_set,
-                comparators,
-                paginate
-                    .with_prefix_key(ValueKey::new(
-                        account_id,
-                        Collection::Email,
-                        0,
-                        Property::ThreadId,
-                    ))
-                    .with_prefix_unique(request.arguments.collapse_threads.unwrap_or(false)),
-                response,
-            )
-            .await
-        } else {
-            Ok(response)
-        }
+        Ok(filters)
     }
 
     async fn thread_keywords(
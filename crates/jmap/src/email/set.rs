@@ -883,6 +883,9 @@ impl JMAP {
                 }
 
                 // Update keywords property
+                for keyword in keywords.added() {
+                    changes.log_tag_set(Collection::Email, id, keyword.clone());
+                }
                 keywords.update_batch(&mut batch, Property::Keywords);
 
                 // Update last change id
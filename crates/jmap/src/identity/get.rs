@@ -27,6 +27,10 @@ use jmap_proto::{
     object::Object,
     types::{collection::Collection, property::Property, value::Value},
 };
+use store::{
+    roaring::RoaringBitmap,
+    write::{log::ChangeLogBuilder, BatchBuilder, F_VALUE},
+};
 
 use crate::JMAP;
 
@@ -47,10 +51,7 @@ impl JMAP {
             Property::MayDelete,
         ]);
         let account_id = request.account_id.document_id();
-        let identity_ids = self
-            .get_document_ids(account_id, Collection::Identity)
-            .await?
-            .unwrap_or_default();
+        let identity_ids = self.identity_get_or_create(account_id).await?;
         let ids = if let Some(ids) = ids {
             ids
         } else {
@@ -98,7 +99,10 @@ impl JMAP {
                         result.append(Property::Id, Value::Id(id));
                     }
                     Property::MayDelete => {
-                        result.append(Property::MayDelete, Value::Bool(true));
+                        // Primary identities provisioned by the directory cannot be deleted
+                        let may_delete =
+                            !matches!(push.remove(&Property::MayDelete), Value::Bool(false));
+                        result.append(Property::MayDelete, Value::Bool(may_delete));
                     }
                     property => {
                         result.append(property.clone(), push.remove(property));
@@ -110,4 +114,90 @@ impl JMAP {
 
         Ok(response)
     }
+
+    pub async fn identity_get_or_create(
+        &self,
+        account_id: u32,
+    ) -> Result<RoaringBitmap, MethodError> {
+        let mut identity_ids = self
+            .get_document_ids(account_id, Collection::Identity)
+            .await?
+            .unwrap_or_default();
+        if !identity_ids.is_empty() {
+            return Ok(identity_ids);
+        }
+
+        // Provision the identities configured in the directory, if any
+        let account_name = if let Some(account_name) = self.get_account_name(account_id).await? {
+            account_name
+        } else {
+            return Ok(identity_ids);
+        };
+        let mut identities = match self.directory.principal(&account_name).await {
+            Ok(Some(principal)) => principal.identities,
+            Ok(None) => return Ok(identity_ids),
+            Err(err) => {
+                tracing::warn!(
+                    context = "identity",
+                    event = "error",
+                    account_id = account_id,
+                    reason = ?err,
+                    "Failed to obtain directory identities"
+                );
+                return Ok(identity_ids);
+            }
+        };
+        if identities.is_empty() {
+            return Ok(identity_ids);
+        }
+
+        // The primary identity is created first, defaulting to the first one listed
+        let primary_idx = identities
+            .iter()
+            .position(|identity| identity.is_primary)
+            .unwrap_or(0);
+        identities.swap(0, primary_idx);
+
+        let mut changes = ChangeLogBuilder::new();
+        for (pos, identity) in identities.into_iter().enumerate() {
+            let mut object = Object::with_capacity(6)
+                .with_property(Property::Name, identity.name.unwrap_or_default())
+                .with_property(Property::Email, identity.email);
+            if let Some(reply_to) = identity.reply_to {
+                object.set(
+                    Property::ReplyTo,
+                    Value::List(vec![Value::Object(
+                        Object::with_capacity(2)
+                            .with_property(Property::Name, Value::Null)
+                            .with_property(Property::Email, reply_to),
+                    )]),
+                );
+            }
+            if let Some(text_signature) = identity.text_signature {
+                object.set(Property::TextSignature, text_signature);
+            }
+            if let Some(html_signature) = identity.html_signature {
+                object.set(Property::HtmlSignature, html_signature);
+            }
+            if pos == 0 {
+                object.set(Property::MayDelete, false);
+            }
+
+            let mut batch = BatchBuilder::new();
+            let document_id = self
+                .assign_document_id(account_id, Collection::Identity)
+                .await?;
+            batch
+                .with_account_id(account_id)
+                .with_collection(Collection::Identity)
+                .create_document(document_id)
+                .value(Property::Value, object, F_VALUE);
+            self.write_batch(batch).await?;
+            identity_ids.insert(document_id);
+            changes.log_insert(Collection::Identity, document_id);
+        }
+        self.commit_changes(account_id, changes).await?;
+
+        Ok(identity_ids)
+    }
 }
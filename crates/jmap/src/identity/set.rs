@@ -43,10 +43,7 @@ impl JMAP {
         access_token: &AccessToken,
     ) -> Result<SetResponse, MethodError> {
         let account_id = request.account_id.document_id();
-        let mut identity_ids = self
-            .get_document_ids(account_id, Collection::Identity)
-            .await?
-            .unwrap_or_default();
+        let mut identity_ids = self.identity_get_or_create(account_id).await?;
         let mut response = SetResponse::from_request(&request, self.config.set_max_objects)?;
         let will_destroy = request.unwrap_destroy();
 
@@ -181,6 +178,25 @@ impl JMAP {
         for id in will_destroy {
             let document_id = id.document_id();
             if identity_ids.contains(document_id) {
+                // Primary identities provisioned by the directory cannot be deleted
+                if let Some(Value::Bool(false)) = self
+                    .get_property::<Object<Value>>(
+                        account_id,
+                        Collection::Identity,
+                        document_id,
+                        Property::Value,
+                    )
+                    .await?
+                    .map(|mut identity| identity.remove(&Property::MayDelete))
+                {
+                    response.not_destroyed.append(
+                        id,
+                        SetError::forbidden()
+                            .with_description("Primary identity cannot be deleted."),
+                    );
+                    continue;
+                }
+
                 // Update record
                 let mut batch = BatchBuilder::new();
                 batch
@@ -38,7 +38,7 @@ use jmap_proto::{
         query::{QueryRequest, QueryResponse},
         set::{SetRequest, SetResponse},
     },
-    types::{collection::Collection, property::Property},
+    types::{collection::Collection, property::Property, type_state::DataType},
 };
 use nlp::language::Language;
 use services::{
@@ -58,9 +58,13 @@ use tokio::sync::mpsc;
 use utils::{
     config::Rate,
     ipc::DeliveryEvent,
-    map::ttl_dashmap::{TtlDashMap, TtlMap},
+    map::{
+        ttl_dashmap::{TtlDashMap, TtlMap},
+        vec_map::VecMap,
+    },
     UnwrapFailure,
 };
+use websocket::{idempotency::IdempotentResponse, metrics::WebSocketMetrics, ticket::UploadTicket};
 
 pub mod api;
 pub mod auth;
@@ -72,6 +76,7 @@ pub mod mailbox;
 pub mod principal;
 pub mod push;
 pub mod quota;
+pub mod saved_query;
 pub mod services;
 pub mod sieve;
 pub mod submission;
@@ -93,6 +98,10 @@ pub struct JMAP {
     pub rate_limit_unauth: DashMap<RemoteAddress, Arc<Mutex<AnonymousLimiter>>>,
 
     pub oauth_codes: TtlDashMap<String, Arc<OAuthCode>>,
+    pub web_socket_pending: TtlDashMap<(u32, String), Arc<Vec<(u64, String)>>>,
+    pub web_socket_idempotency: TtlDashMap<(u32, String), IdempotentResponse>,
+    pub upload_tickets: TtlDashMap<String, Arc<UploadTicket>>,
+    pub web_socket_metrics: WebSocketMetrics,
 
     pub state_tx: mpsc::Sender<state::Event>,
     pub housekeeper_tx: mpsc::Sender<housekeeper::Event>,
@@ -125,6 +134,8 @@ pub struct Config {
     pub mailbox_name_max_len: usize,
     pub mail_attachments_max_size: usize,
     pub mail_parse_max_items: usize,
+    pub spam_score_header: Option<String>,
+    pub index_any_header: bool,
     pub mail_max_size: usize,
 
     pub sieve_max_script_name: usize,
@@ -140,8 +151,25 @@ pub struct Config {
     pub push_max_total: usize,
 
     pub web_socket_throttle: Duration,
+    pub web_socket_throttle_min: Duration,
+    pub web_socket_throttle_max: Duration,
     pub web_socket_timeout: Duration,
     pub web_socket_heartbeat: Duration,
+    pub web_socket_max_missed_heartbeats: u32,
+    pub web_socket_max_queued: usize,
+    pub web_socket_backpressure_timeout: Duration,
+    pub web_socket_token_check_interval: Duration,
+    pub web_socket_rate_limit: u64,
+    pub web_socket_rate_burst: u64,
+    pub web_socket_rate_max_violations: u32,
+    pub web_socket_ack_ttl: Duration,
+    pub web_socket_upload_ticket_ttl: Duration,
+    pub web_socket_idempotency_ttl: Duration,
+    pub web_socket_idempotency_max: usize,
+    pub web_socket_compression: bool,
+    pub web_socket_compression_level: u32,
+    pub web_socket_compression_threshold: usize,
+    pub web_socket_type_throttle: VecMap<DataType, Duration>,
 
     pub oauth_key: String,
     pub oauth_expiry_user_code: u64,
@@ -223,6 +251,25 @@ impl JMAP {
                 config.property("oauth.cache.size")?.unwrap_or(128),
                 shard_amount,
             ),
+            web_socket_pending: TtlDashMap::with_capacity(
+                config
+                    .property("jmap.web-socket.ack-cache.size")?
+                    .unwrap_or(128),
+                shard_amount,
+            ),
+            web_socket_idempotency: TtlDashMap::with_capacity(
+                config
+                    .property("jmap.web-socket.idempotency-cache.size")?
+                    .unwrap_or(1024),
+                shard_amount,
+            ),
+            upload_tickets: TtlDashMap::with_capacity(
+                config
+                    .property("jmap.web-socket.upload-ticket-cache.size")?
+                    .unwrap_or(128),
+                shard_amount,
+            ),
+            web_socket_metrics: WebSocketMetrics::default(),
             state_tx,
             housekeeper_tx,
             smtp,
@@ -375,6 +422,10 @@ impl JMAP {
                 .with_env_variable("phase", "during"),
         });
 
+        // Verify index integrity before serving requests
+        #[cfg(feature = "foundationdb")]
+        jmap_server.verify_store(config).await?;
+
         // Spawn delivery manager
         spawn_delivery_manager(jmap_server.clone(), delivery_rx);
 
@@ -706,7 +757,7 @@ impl JMAP {
     pub async fn write_batch(&self, batch: BatchBuilder) -> Result<(), MethodError> {
         self.store.write(batch.build()).await.map_err(|err| {
             match err {
-                store::Error::InternalError(err) => {
+                store::Error::InternalError(err) | store::Error::Retryable(err) => {
                     tracing::error!(
                         event = "error",
                         context = "write_batch",
@@ -723,6 +774,14 @@ impl JMAP {
                     );
                     MethodError::ServerUnavailable
                 }
+                store::Error::ChecksumMismatch(err) => {
+                    tracing::error!(
+                        event = "error",
+                        context = "write_batch",
+                        error = ?err,
+                        "Data corruption detected.");
+                    MethodError::ServerPartialFail
+                }
             }
         })
     }
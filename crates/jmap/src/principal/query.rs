@@ -36,11 +36,8 @@ impl JMAP {
         mut request: QueryRequest<RequestArguments>,
     ) -> Result<QueryResponse, MethodError> {
         let account_id = request.account_id.document_id();
-        let mut result_set = ResultSet {
-            account_id,
-            collection: Collection::Principal.into(),
-            results: RoaringBitmap::new(),
-        };
+        let mut result_set =
+            ResultSet::new(account_id, Collection::Principal, RoaringBitmap::new());
         let mut is_set = true;
 
         for cond in std::mem::take(&mut request.filter) {
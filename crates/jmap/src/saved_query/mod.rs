@@ -0,0 +1,118 @@
+/*
+ * Copyright (c) 2023 Stalwart Labs Ltd.
+ *
+ * This file is part of Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+pub mod get;
+pub mod set;
+
+use jmap_proto::{
+    error::method::MethodError,
+    method::query::{parse_filter, Filter},
+    object::Object,
+    parser::{self, json::Parser, Ignore, Token},
+    types::{collection::Collection, property::Property, value::Value},
+};
+use store::roaring::RoaringBitmap;
+
+use crate::JMAP;
+
+impl JMAP {
+    // Returns the document id and name of each saved query in the account
+    pub async fn saved_queries(&self, account_id: u32) -> Result<Vec<(u32, String)>, MethodError> {
+        let mut saved_queries = Vec::new();
+        for document_id in self
+            .get_document_ids(account_id, Collection::SavedQuery)
+            .await?
+            .unwrap_or_default()
+        {
+            if let Some(mut saved_query) = self
+                .get_property::<Object<Value>>(
+                    account_id,
+                    Collection::SavedQuery,
+                    document_id,
+                    Property::Value,
+                )
+                .await?
+            {
+                if let Value::Text(name) = saved_query.remove(&Property::Name) {
+                    saved_queries.push((document_id, name));
+                }
+            }
+        }
+
+        Ok(saved_queries)
+    }
+
+    // Evaluates a saved query, returning the ids of the matching messages
+    pub async fn saved_query_ids(
+        &self,
+        account_id: u32,
+        document_id: u32,
+    ) -> Result<Option<RoaringBitmap>, MethodError> {
+        let filter = if let Some(filter) = self
+            .get_property::<Object<Value>>(
+                account_id,
+                Collection::SavedQuery,
+                document_id,
+                Property::Value,
+            )
+            .await?
+            .map(|mut saved_query| saved_query.remove(&Property::Filter))
+        {
+            filter
+        } else {
+            return Ok(None);
+        };
+
+        let filters = self
+            .email_query_filters(account_id, parse_saved_query_filter(&filter)?)
+            .await?;
+        self.filter(account_id, Collection::Email, filters)
+            .await
+            .map(|result_set| Some(result_set.results))
+    }
+}
+
+// Filters are stored as the JSON value received from the client, they are
+// converted back to JSON and parsed as an Email/query filter.
+pub(crate) fn parse_saved_query_filter(filter: &Value) -> Result<Vec<Filter>, MethodError> {
+    let json = match filter {
+        Value::Object(_) => serde_json::to_vec(filter).map_err(|err| {
+            MethodError::InvalidArguments(format!("Failed to serialize filter: {err}"))
+        })?,
+        Value::Null => return Ok(vec![]),
+        _ => {
+            return Err(MethodError::InvalidArguments(
+                "Filter must be an object.".to_string(),
+            ))
+        }
+    };
+    let mut parser = Parser::new(&json);
+    parser
+        .next_token::<Ignore>()
+        .and_then(|token| token.assert(Token::DictStart))
+        .and_then(|_| parse_filter(&mut parser))
+        .map_err(|err| match err {
+            parser::Error::Method(err) => err,
+            parser::Error::Request(err) => MethodError::InvalidArguments(err.detail.to_string()),
+        })
+}
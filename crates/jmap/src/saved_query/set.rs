@@ -0,0 +1,284 @@
+/*
+ * Copyright (c) 2023 Stalwart Labs Ltd.
+ *
+ * This file is part of Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use jmap_proto::{
+    error::{method::MethodError, set::SetError},
+    method::set::{RequestArguments, SetRequest, SetResponse},
+    object::Object,
+    response::references::EvalObjectReferences,
+    types::{
+        collection::Collection,
+        property::Property,
+        value::{MaybePatchValue, Value},
+    },
+};
+use store::write::{log::ChangeLogBuilder, BatchBuilder, F_CLEAR, F_VALUE};
+
+use crate::JMAP;
+
+use super::parse_saved_query_filter;
+
+impl JMAP {
+    pub async fn saved_query_set(
+        &self,
+        mut request: SetRequest<RequestArguments>,
+    ) -> Result<SetResponse, MethodError> {
+        let account_id = request.account_id.document_id();
+        let mut saved_query_ids = self
+            .get_document_ids(account_id, Collection::SavedQuery)
+            .await?
+            .unwrap_or_default();
+        let mut saved_queries = self.saved_queries(account_id).await?;
+        let mut response = SetResponse::from_request(&request, self.config.set_max_objects)?;
+        let will_destroy = request.unwrap_destroy();
+
+        // Process creates
+        let mut changes = ChangeLogBuilder::new();
+        'create: for (id, object) in request.unwrap_create() {
+            let mut saved_query = Object::with_capacity(object.properties.len());
+
+            for (property, value) in object.properties {
+                match response
+                    .eval_object_references(value)
+                    .and_then(|value| validate_saved_query_value(&property, value))
+                {
+                    Ok(value) => {
+                        saved_query.set(property, value);
+                    }
+                    Err(err) => {
+                        response.not_created.append(id, err);
+                        continue 'create;
+                    }
+                }
+            }
+
+            // Validate name and filter
+            if let Err(err) = self
+                .validate_saved_query(account_id, &saved_query, &saved_queries, None)
+                .await?
+            {
+                response.not_created.append(id, err);
+                continue 'create;
+            }
+
+            // Insert record
+            let mut batch = BatchBuilder::new();
+            let document_id = self
+                .assign_document_id(account_id, Collection::SavedQuery)
+                .await?;
+            saved_queries.push((
+                document_id,
+                saved_query
+                    .get(&Property::Name)
+                    .as_string()
+                    .unwrap_or_default()
+                    .to_string(),
+            ));
+            batch
+                .with_account_id(account_id)
+                .with_collection(Collection::SavedQuery)
+                .create_document(document_id)
+                .value(Property::Value, saved_query, F_VALUE);
+            saved_query_ids.insert(document_id);
+            self.write_batch(batch).await?;
+            changes.log_insert(Collection::SavedQuery, document_id);
+            response.created(id, document_id);
+        }
+
+        // Process updates
+        'update: for (id, object) in request.unwrap_update() {
+            // Make sure id won't be destroyed
+            if will_destroy.contains(&id) {
+                response.not_updated.append(id, SetError::will_destroy());
+                continue 'update;
+            }
+
+            // Obtain saved query
+            let document_id = id.document_id();
+            let mut saved_query = if let Some(saved_query) = self
+                .get_property::<Object<Value>>(
+                    account_id,
+                    Collection::SavedQuery,
+                    document_id,
+                    Property::Value,
+                )
+                .await?
+            {
+                saved_query
+            } else {
+                response.not_updated.append(id, SetError::not_found());
+                continue 'update;
+            };
+
+            for (property, value) in object.properties {
+                match response
+                    .eval_object_references(value)
+                    .and_then(|value| validate_saved_query_value(&property, value))
+                {
+                    Ok(value) => {
+                        saved_query.set(property, value);
+                    }
+                    Err(err) => {
+                        response.not_updated.append(id, err);
+                        continue 'update;
+                    }
+                };
+            }
+
+            // Validate name and filter
+            if let Err(err) = self
+                .validate_saved_query(account_id, &saved_query, &saved_queries, document_id.into())
+                .await?
+            {
+                response.not_updated.append(id, err);
+                continue 'update;
+            }
+            if let Some((_, name)) = saved_queries
+                .iter_mut()
+                .find(|(saved_query_id, _)| *saved_query_id == document_id)
+            {
+                *name = saved_query
+                    .get(&Property::Name)
+                    .as_string()
+                    .unwrap_or_default()
+                    .to_string();
+            }
+
+            // Update record
+            let mut batch = BatchBuilder::new();
+            batch
+                .with_account_id(account_id)
+                .with_collection(Collection::SavedQuery)
+                .update_document(document_id)
+                .value(Property::Value, saved_query, F_VALUE);
+            self.write_batch(batch).await?;
+            changes.log_update(Collection::SavedQuery, document_id);
+            response.updated.append(id, None);
+        }
+
+        // Process deletions
+        for id in will_destroy {
+            let document_id = id.document_id();
+            if saved_query_ids.contains(document_id) {
+                // Delete record, including the IMAP UID map of the query
+                let mut batch = BatchBuilder::new();
+                batch
+                    .with_account_id(account_id)
+                    .with_collection(Collection::SavedQuery)
+                    .delete_document(document_id)
+                    .value(Property::Value, (), F_VALUE | F_CLEAR)
+                    .value(Property::EmailIds, (), F_VALUE | F_CLEAR);
+                self.write_batch(batch).await?;
+                changes.log_delete(Collection::SavedQuery, document_id);
+                response.destroyed.push(id);
+            } else {
+                response.not_destroyed.append(id, SetError::not_found());
+            }
+        }
+
+        // Write changes
+        if !changes.is_empty() {
+            response.new_state = Some(self.commit_changes(account_id, changes).await?.into());
+        }
+
+        Ok(response)
+    }
+
+    async fn validate_saved_query(
+        &self,
+        account_id: u32,
+        saved_query: &Object<Value>,
+        saved_queries: &[(u32, String)],
+        document_id: Option<u32>,
+    ) -> Result<Result<(), SetError>, MethodError> {
+        match saved_query.get(&Property::Name) {
+            Value::Text(name) => {
+                if let Some((existing_id, _)) =
+                    saved_queries.iter().find(|(saved_query_id, saved_name)| {
+                        saved_name == name && Some(*saved_query_id) != document_id
+                    })
+                {
+                    return Ok(Err(SetError::already_exists()
+                        .with_existing_id((*existing_id).into())
+                        .with_description(format!(
+                            "A saved query with name '{name}' already exists."
+                        ))));
+                }
+            }
+            _ => {
+                return Ok(Err(SetError::invalid_properties()
+                    .with_property(Property::Name)
+                    .with_description("Missing saved query name.")));
+            }
+        }
+
+        // Make sure the filter can be evaluated as an Email/query filter
+        let filter = match saved_query.get(&Property::Filter) {
+            Value::Null => {
+                return Ok(Err(SetError::invalid_properties()
+                    .with_property(Property::Filter)
+                    .with_description("Missing saved query filter.")));
+            }
+            filter => parse_saved_query_filter(filter),
+        };
+        let filter = match filter {
+            Ok(filter) => filter,
+            Err(err) => {
+                return Ok(Err(SetError::invalid_properties()
+                    .with_property(Property::Filter)
+                    .with_description(err.to_string())));
+            }
+        };
+        match self.email_query_filters(account_id, filter).await {
+            Ok(_) => Ok(Ok(())),
+            Err(
+                err @ (MethodError::InvalidArguments(_)
+                | MethodError::UnsupportedFilter(_)
+                | MethodError::UnsupportedSort(_)),
+            ) => Ok(Err(SetError::invalid_properties()
+                .with_property(Property::Filter)
+                .with_description(err.to_string()))),
+            Err(err) => Err(err),
+        }
+    }
+}
+
+fn validate_saved_query_value(
+    property: &Property,
+    value: MaybePatchValue,
+) -> Result<Value, SetError> {
+    Ok(match (property, value) {
+        (Property::Name, MaybePatchValue::Value(Value::Text(value)))
+            if !value.is_empty() && value.len() < 255 && !value.contains('/') =>
+        {
+            Value::Text(value)
+        }
+        (Property::Filter, MaybePatchValue::Value(value @ Value::Object(_))) => value,
+
+        (property, _) => {
+            return Err(SetError::invalid_properties()
+                .with_property(property.clone())
+                .with_description("Field could not be set."));
+        }
+    })
+}
@@ -122,6 +122,9 @@ pub fn spawn_housekeeper(core: Arc<JMAP>, settings: &Config, mut rx: mpsc::Recei
                             core.sessions.cleanup();
                             core.access_tokens.cleanup();
                             core.oauth_codes.cleanup();
+                            core.web_socket_pending.cleanup();
+                            core.web_socket_idempotency.cleanup();
+                            core.upload_tickets.cleanup();
                             core.rate_limit_auth
                                 .retain(|_, limiter| limiter.lock().is_active());
                             core.rate_limit_unauth
@@ -25,5 +25,7 @@ pub mod delivery;
 pub mod housekeeper;
 pub mod ingest;
 pub mod state;
+#[cfg(feature = "foundationdb")]
+pub mod verify;
 
 pub const IPC_CHANNEL_BUFFER: usize = 1024;
@@ -0,0 +1,113 @@
+/*
+ * Copyright (c) 2023 Stalwart Labs Ltd.
+ *
+ * This file is part of Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use jmap_proto::types::{collection::Collection, property::Property};
+use store::{
+    rand::{seq::IteratorRandom, thread_rng},
+    BitmapKey,
+};
+
+use crate::JMAP;
+
+impl JMAP {
+    pub async fn verify_store(&self, config: &utils::config::Config) -> Result<(), String> {
+        if !config.property_or_static::<bool>("store.db.verify.enable", "false")? {
+            return Ok(());
+        }
+        let sample_size =
+            config.property_or_static::<usize>("store.db.verify.sample-size", "100")?;
+        let max_failures =
+            config.property_or_static::<usize>("store.db.verify.max-failures", "0")?;
+        let refuse = config.property_or_static::<bool>("store.db.verify.refuse", "true")?;
+
+        // Spot check a random sample of accounts
+        let account_ids = self
+            .store
+            .get_bitmap(BitmapKey::document_ids(u32::MAX, Collection::Principal))
+            .await
+            .map_err(|err| format!("Failed to obtain account ids: {err:?}"))?
+            .unwrap_or_default();
+        let sample = account_ids
+            .iter()
+            .choose_multiple(&mut thread_rng(), sample_size);
+
+        tracing::info!(
+            context = "store",
+            event = "verify",
+            accounts = sample.len(),
+            total = account_ids.len(),
+            "Verifying index integrity."
+        );
+
+        let mut failures = 0;
+        for account_id in &sample {
+            match self
+                .store
+                .verify_document_ids(*account_id, Collection::Email, Property::ReceivedAt)
+                .await
+            {
+                Ok(0) => (),
+                Ok(mismatches) => {
+                    tracing::error!(
+                        context = "store",
+                        event = "corrupted",
+                        account_id = account_id,
+                        mismatches = mismatches,
+                        "Document ids do not match the index entries."
+                    );
+                    failures += 1;
+                }
+                Err(err) => {
+                    tracing::error!(
+                        context = "store",
+                        event = "error",
+                        account_id = account_id,
+                        error = ?err,
+                        "Failed to verify account."
+                    );
+                    failures += 1;
+                }
+            }
+        }
+
+        if failures > max_failures {
+            let message = format!(
+                "Index integrity check failed for {failures} out of {} sampled accounts.",
+                sample.len()
+            );
+            if refuse {
+                return Err(message);
+            }
+            tracing::warn!(context = "store", event = "verify", "{}", message);
+        } else {
+            tracing::info!(
+                context = "store",
+                event = "verify",
+                failures = failures,
+                "Index integrity check completed."
+            );
+        }
+
+        Ok(())
+    }
+}
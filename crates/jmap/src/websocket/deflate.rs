@@ -0,0 +1,711 @@
+/*
+ * Copyright (c) 2023 Stalwart Labs Ltd.
+ *
+ * This file is part of Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use std::{
+    io,
+    pin::Pin,
+    task::{ready, Context, Poll},
+};
+
+use flate2::{Compress, Compression, Decompress, FlushCompress, FlushDecompress, Status};
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+
+// Same limits tungstenite applies to uncompressed frames and messages
+const MAX_FRAME_SIZE: usize = 16 << 20;
+const MAX_MESSAGE_SIZE: usize = 64 << 20;
+
+// Compressed output queued before writes start applying backpressure
+const MAX_PENDING_WRITE: usize = 64 * 1024;
+const READ_CHUNK_SIZE: usize = 8192;
+
+const DEFLATE_TRAILER: [u8; 4] = [0x00, 0x00, 0xff, 0xff];
+const RSV1: u8 = 0x40;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DeflateParams {
+    pub server_no_context_takeover: bool,
+    pub server_max_window_bits: bool,
+}
+
+// Implements the permessage-deflate extension (RFC 7692) below the WebSocket codec,
+// which does not support extensions: frames read from the peer are inflated and
+// frames written to it deflated, with the RSV1 bit adjusted accordingly.
+// Without negotiated parameters all I/O passes through untouched.
+pub struct DeflateStream<T> {
+    inner: T,
+    state: Option<Box<DeflateState>>,
+}
+
+struct DeflateState {
+    params: DeflateParams,
+    read_buf: Vec<u8>,
+    read_out: Vec<u8>,
+    read_pos: usize,
+    inflate: Decompress,
+    inflating: bool,
+    inflated_size: usize,
+    write_buf: Vec<u8>,
+    write_out: Vec<u8>,
+    write_pos: usize,
+    deflate: Compress,
+    deflating: bool,
+    threshold: usize,
+}
+
+struct FrameHeader {
+    fin: bool,
+    rsv1: bool,
+    opcode: u8,
+    mask: Option<[u8; 4]>,
+    header_len: usize,
+    payload_len: usize,
+}
+
+impl DeflateParams {
+    // Accepts the first permessage-deflate offer whose parameters can be honoured.
+    // Frames are always compressed using the maximum window size.
+    pub fn negotiate<'x>(offers: impl IntoIterator<Item = &'x str>) -> Option<Self> {
+        'offer: for offer in offers.into_iter().flat_map(|offers| offers.split(',')) {
+            let mut params = offer.split(';').map(|param| param.trim());
+            if !params.next().map_or(false, |name| {
+                name.eq_ignore_ascii_case("permessage-deflate")
+            }) {
+                continue;
+            }
+
+            let mut result = DeflateParams::default();
+            for param in params {
+                let (name, value) = match param.split_once('=') {
+                    Some((name, value)) => (name.trim(), Some(value.trim().trim_matches('"'))),
+                    None => (param, None),
+                };
+                match (name, value) {
+                    ("server_no_context_takeover", None) => {
+                        result.server_no_context_takeover = true;
+                    }
+                    ("client_no_context_takeover", None) | ("client_max_window_bits", None) => (),
+                    ("client_max_window_bits", Some(bits))
+                        if matches!(bits.parse::<u8>(), Ok(8..=15)) => {}
+                    ("server_max_window_bits", Some("15")) => {
+                        result.server_max_window_bits = true;
+                    }
+                    _ => continue 'offer,
+                }
+            }
+            return Some(result);
+        }
+
+        None
+    }
+
+    pub fn to_header(&self) -> String {
+        let mut header = "permessage-deflate".to_string();
+        if self.server_no_context_takeover {
+            header.push_str("; server_no_context_takeover");
+        }
+        if self.server_max_window_bits {
+            header.push_str("; server_max_window_bits=15");
+        }
+        header
+    }
+}
+
+impl<T> DeflateStream<T> {
+    pub fn new(inner: T, params: Option<DeflateParams>) -> Self {
+        DeflateStream {
+            inner,
+            state: params.map(|params| {
+                Box::new(DeflateState {
+                    params,
+                    read_buf: Vec::new(),
+                    read_out: Vec::new(),
+                    read_pos: 0,
+                    inflate: Decompress::new(false),
+                    inflating: false,
+                    inflated_size: 0,
+                    write_buf: Vec::new(),
+                    write_out: Vec::new(),
+                    write_pos: 0,
+                    deflate: Compress::new(Compression::default(), false),
+                    deflating: false,
+                    threshold: 0,
+                })
+            }),
+        }
+    }
+
+    // Sets the compression level (0-9) of outgoing messages, messages smaller than
+    // the threshold are sent uncompressed.
+    pub fn with_compression(mut self, level: u32, threshold: usize) -> Self {
+        if let Some(state) = self.state.as_mut() {
+            state.deflate = Compress::new(Compression::new(level), false);
+            state.threshold = threshold;
+        }
+        self
+    }
+
+    pub fn is_compressed(&self) -> bool {
+        self.state.is_some()
+    }
+}
+
+impl DeflateState {
+    fn inflate_frames(&mut self) -> io::Result<()> {
+        while let Some(frame) = parse_frame(&self.read_buf)? {
+            let frame_len = frame.header_len + frame.payload_len;
+            let is_control = frame.opcode & 0x08 != 0;
+            if !is_control && frame.opcode != 0 {
+                self.inflating = frame.rsv1;
+                self.inflated_size = 0;
+            }
+
+            if is_control || !self.inflating {
+                self.read_out.extend_from_slice(&self.read_buf[..frame_len]);
+            } else {
+                let mut payload = self.read_buf[frame.header_len..frame_len].to_vec();
+                if let Some(mask) = frame.mask {
+                    apply_mask(&mut payload, mask);
+                }
+                let mut inflated = Vec::with_capacity(payload.len() * 4);
+                inflate(&mut self.inflate, &payload, &mut inflated)?;
+                if frame.fin {
+                    inflate(&mut self.inflate, &DEFLATE_TRAILER, &mut inflated)?;
+                }
+                self.inflated_size += inflated.len();
+                if self.inflated_size > MAX_MESSAGE_SIZE {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        "Inflated WebSocket message too large",
+                    ));
+                }
+
+                // Only the first frame of a message carries the RSV1 bit
+                let mut first_byte = self.read_buf[0];
+                if frame.opcode != 0 {
+                    first_byte &= !RSV1;
+                }
+                write_frame(&mut self.read_out, first_byte, frame.mask, &mut inflated);
+            }
+
+            self.read_buf.drain(..frame_len);
+        }
+
+        Ok(())
+    }
+
+    fn deflate_frames(&mut self) -> io::Result<()> {
+        while let Some(frame) = parse_frame(&self.write_buf)? {
+            let frame_len = frame.header_len + frame.payload_len;
+            let is_control = frame.opcode & 0x08 != 0;
+            if !is_control && frame.opcode != 0 {
+                // Fragmented messages are always compressed as their size is not known
+                self.deflating = !frame.fin || frame.payload_len >= self.threshold;
+            }
+
+            if is_control || !self.deflating {
+                // Control frames and small messages are never compressed
+                self.write_out
+                    .extend_from_slice(&self.write_buf[..frame_len]);
+            } else {
+                let mut payload = self.write_buf[frame.header_len..frame_len].to_vec();
+                if let Some(mask) = frame.mask {
+                    apply_mask(&mut payload, mask);
+                }
+                let mut deflated = Vec::with_capacity(payload.len() / 2 + 64);
+                if frame.fin {
+                    deflate(
+                        &mut self.deflate,
+                        &payload,
+                        &mut deflated,
+                        FlushCompress::Sync,
+                    )?;
+                    if deflated.ends_with(&DEFLATE_TRAILER) {
+                        deflated.truncate(deflated.len() - DEFLATE_TRAILER.len());
+                    }
+                    if self.params.server_no_context_takeover {
+                        self.deflate.reset();
+                    }
+                } else {
+                    deflate(
+                        &mut self.deflate,
+                        &payload,
+                        &mut deflated,
+                        FlushCompress::None,
+                    )?;
+                }
+
+                let mut first_byte = self.write_buf[0];
+                if frame.opcode != 0 {
+                    first_byte |= RSV1;
+                }
+                write_frame(&mut self.write_out, first_byte, frame.mask, &mut deflated);
+            }
+
+            self.write_buf.drain(..frame_len);
+        }
+
+        Ok(())
+    }
+
+    fn poll_drain<T: AsyncWrite + Unpin>(
+        &mut self,
+        inner: &mut T,
+        cx: &mut Context<'_>,
+    ) -> Poll<io::Result<()>> {
+        while self.write_pos < self.write_out.len() {
+            let bytes =
+                ready!(Pin::new(&mut *inner).poll_write(cx, &self.write_out[self.write_pos..]))?;
+            if bytes == 0 {
+                return Poll::Ready(Err(io::ErrorKind::WriteZero.into()));
+            }
+            self.write_pos += bytes;
+        }
+        self.write_out.clear();
+        self.write_pos = 0;
+
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl<T: AsyncRead + Unpin> AsyncRead for DeflateStream<T> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        let state = match this.state.as_mut() {
+            Some(state) => state,
+            None => return Pin::new(&mut this.inner).poll_read(cx, buf),
+        };
+
+        loop {
+            if state.read_pos < state.read_out.len() {
+                let bytes = buf.remaining().min(state.read_out.len() - state.read_pos);
+                buf.put_slice(&state.read_out[state.read_pos..state.read_pos + bytes]);
+                state.read_pos += bytes;
+                if state.read_pos == state.read_out.len() {
+                    state.read_out.clear();
+                    state.read_pos = 0;
+                }
+                return Poll::Ready(Ok(()));
+            }
+
+            let mut chunk = [0u8; READ_CHUNK_SIZE];
+            let mut chunk_buf = ReadBuf::new(&mut chunk);
+            ready!(Pin::new(&mut this.inner).poll_read(cx, &mut chunk_buf))?;
+            if chunk_buf.filled().is_empty() {
+                return Poll::Ready(Ok(()));
+            }
+            state.read_buf.extend_from_slice(chunk_buf.filled());
+            state.inflate_frames()?;
+        }
+    }
+}
+
+impl<T: AsyncWrite + Unpin> AsyncWrite for DeflateStream<T> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        match this.state.as_mut() {
+            Some(state) => {
+                if state.write_out.len() - state.write_pos >= MAX_PENDING_WRITE {
+                    ready!(state.poll_drain(&mut this.inner, cx))?;
+                }
+                state.write_buf.extend_from_slice(buf);
+                state.deflate_frames()?;
+                Poll::Ready(Ok(buf.len()))
+            }
+            None => Pin::new(&mut this.inner).poll_write(cx, buf),
+        }
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        if let Some(state) = this.state.as_mut() {
+            ready!(state.poll_drain(&mut this.inner, cx))?;
+        }
+        Pin::new(&mut this.inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        if let Some(state) = this.state.as_mut() {
+            ready!(state.poll_drain(&mut this.inner, cx))?;
+        }
+        Pin::new(&mut this.inner).poll_shutdown(cx)
+    }
+}
+
+fn parse_frame(buf: &[u8]) -> io::Result<Option<FrameHeader>> {
+    if buf.len() < 2 {
+        return Ok(None);
+    }
+    let (payload_len, mut header_len) = match buf[1] & 0x7f {
+        126 if buf.len() >= 4 => (u16::from_be_bytes([buf[2], buf[3]]) as u64, 4),
+        127 if buf.len() >= 10 => (
+            u64::from_be_bytes([
+                buf[2], buf[3], buf[4], buf[5], buf[6], buf[7], buf[8], buf[9],
+            ]),
+            10,
+        ),
+        126 | 127 => return Ok(None),
+        len => (len as u64, 2),
+    };
+    if payload_len > MAX_FRAME_SIZE as u64 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "WebSocket frame too large",
+        ));
+    }
+    let mask = if buf[1] & 0x80 != 0 {
+        if buf.len() < header_len + 4 {
+            return Ok(None);
+        }
+        let mask = [
+            buf[header_len],
+            buf[header_len + 1],
+            buf[header_len + 2],
+            buf[header_len + 3],
+        ];
+        header_len += 4;
+        Some(mask)
+    } else {
+        None
+    };
+    let payload_len = payload_len as usize;
+    if buf.len() < header_len + payload_len {
+        return Ok(None);
+    }
+
+    Ok(Some(FrameHeader {
+        fin: buf[0] & 0x80 != 0,
+        rsv1: buf[0] & RSV1 != 0,
+        opcode: buf[0] & 0x0f,
+        mask,
+        header_len,
+        payload_len,
+    }))
+}
+
+fn write_frame(out: &mut Vec<u8>, first_byte: u8, mask: Option<[u8; 4]>, payload: &mut [u8]) {
+    let mask_bit = if mask.is_some() { 0x80 } else { 0 };
+    out.push(first_byte);
+    if payload.len() < 126 {
+        out.push(mask_bit | payload.len() as u8);
+    } else if payload.len() <= u16::MAX as usize {
+        out.push(mask_bit | 126);
+        out.extend_from_slice(&(payload.len() as u16).to_be_bytes());
+    } else {
+        out.push(mask_bit | 127);
+        out.extend_from_slice(&(payload.len() as u64).to_be_bytes());
+    }
+    if let Some(mask) = mask {
+        out.extend_from_slice(&mask);
+        apply_mask(payload, mask);
+    }
+    out.extend_from_slice(payload);
+}
+
+fn apply_mask(payload: &mut [u8], mask: [u8; 4]) {
+    for (pos, byte) in payload.iter_mut().enumerate() {
+        *byte ^= mask[pos & 3];
+    }
+}
+
+fn inflate(inflate: &mut Decompress, mut input: &[u8], output: &mut Vec<u8>) -> io::Result<()> {
+    loop {
+        if output.capacity() - output.len() < 1024 {
+            output.reserve(output.capacity().max(4096));
+        }
+        let total_in = inflate.total_in();
+        let status = inflate
+            .decompress_vec(input, output, FlushDecompress::Sync)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+        input = &input[(inflate.total_in() - total_in) as usize..];
+        if output.len() > MAX_MESSAGE_SIZE {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "Inflated WebSocket message too large",
+            ));
+        }
+        if status == Status::StreamEnd || (input.is_empty() && output.len() < output.capacity()) {
+            return Ok(());
+        }
+    }
+}
+
+fn deflate(
+    deflate: &mut Compress,
+    mut input: &[u8],
+    output: &mut Vec<u8>,
+    flush: FlushCompress,
+) -> io::Result<()> {
+    loop {
+        if output.capacity() - output.len() < 64 {
+            output.reserve(output.capacity().max(1024));
+        }
+        let total_in = deflate.total_in();
+        deflate
+            .compress_vec(input, output, flush)
+            .map_err(|err| io::Error::new(io::ErrorKind::Other, err))?;
+        input = &input[(deflate.total_in() - total_in) as usize..];
+        if input.is_empty() && output.len() < output.capacity() {
+            return Ok(());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use flate2::{Compress, Compression, FlushCompress};
+
+    use super::{
+        apply_mask, deflate, parse_frame, write_frame, DeflateParams, DeflateState, DeflateStream,
+        DEFLATE_TRAILER, MAX_FRAME_SIZE, MAX_MESSAGE_SIZE, RSV1,
+    };
+
+    const MASK: [u8; 4] = [0x37, 0xfa, 0x21, 0x3d];
+
+    fn frame(first_byte: u8, mask: Option<[u8; 4]>, payload: &[u8]) -> Vec<u8> {
+        let mut out = Vec::new();
+        write_frame(&mut out, first_byte, mask, &mut payload.to_vec());
+        out
+    }
+
+    fn state(params: DeflateParams, level: u32, threshold: usize) -> Box<DeflateState> {
+        DeflateStream::new((), params.into())
+            .with_compression(level, threshold)
+            .state
+            .unwrap()
+    }
+
+    // Returns the first byte and the unmasked payload of each frame
+    fn split_frames(mut frames: &[u8]) -> Vec<(u8, Vec<u8>)> {
+        let mut result = Vec::new();
+        while let Some(header) = parse_frame(frames).unwrap() {
+            let frame_len = header.header_len + header.payload_len;
+            let mut payload = frames[header.header_len..frame_len].to_vec();
+            if let Some(mask) = header.mask {
+                apply_mask(&mut payload, mask);
+            }
+            result.push((frames[0], payload));
+            frames = &frames[frame_len..];
+        }
+        assert!(frames.is_empty());
+        result
+    }
+
+    fn deflate_then_inflate(frames: &[u8], threshold: usize) -> (Vec<u8>, Vec<u8>) {
+        let mut writer = state(DeflateParams::default(), 6, threshold);
+        writer.write_buf.extend_from_slice(frames);
+        writer.deflate_frames().unwrap();
+        assert!(writer.write_buf.is_empty());
+
+        let mut reader = state(DeflateParams::default(), 6, 0);
+        reader.read_buf.extend_from_slice(&writer.write_out);
+        reader.inflate_frames().unwrap();
+        assert!(reader.read_buf.is_empty());
+
+        (writer.write_out, reader.read_out)
+    }
+
+    #[test]
+    fn parse_frame_header() {
+        // Incomplete headers and payloads need more data
+        for buf in [
+            &[0x81][..],
+            &[0x81, 0x7e, 0x01][..],
+            &[0x81, 0xff, 0x00, 0x00][..],
+            &[0x81, 0x85, 0x37, 0xfa][..],
+            &[0x81, 0x05, b'H', b'e'][..],
+        ] {
+            assert!(parse_frame(buf).unwrap().is_none(), "{buf:?}");
+        }
+
+        // Short, extended and masked lengths
+        let header = parse_frame(&frame(0x81, None, b"Hello")).unwrap().unwrap();
+        assert!(header.fin && !header.rsv1 && header.mask.is_none());
+        assert_eq!(
+            (header.opcode, header.header_len, header.payload_len),
+            (1, 2, 5)
+        );
+        let header = parse_frame(&frame(0x02 | RSV1, Some(MASK), &[0u8; 300]))
+            .unwrap()
+            .unwrap();
+        assert!(!header.fin && header.rsv1);
+        assert_eq!(header.mask, Some(MASK));
+        assert_eq!(
+            (header.opcode, header.header_len, header.payload_len),
+            (2, 8, 300)
+        );
+        let mut buf = vec![0x82, 0x7f];
+        buf.extend_from_slice(&70_000u64.to_be_bytes());
+        buf.resize(10 + 70_000, 0);
+        assert_eq!(parse_frame(&buf).unwrap().unwrap().payload_len, 70_000);
+
+        // Frames above the size limit are rejected before their payload arrives
+        let mut buf = vec![0x82, 0x7f];
+        buf.extend_from_slice(&(MAX_FRAME_SIZE as u64 + 1).to_be_bytes());
+        assert!(parse_frame(&buf).is_err());
+    }
+
+    #[test]
+    fn negotiate_params() {
+        for (offers, expected) in [
+            (vec!["permessage-deflate"], Some(DeflateParams::default())),
+            (
+                vec!["permessage-deflate; server_no_context_takeover; client_max_window_bits"],
+                Some(DeflateParams {
+                    server_no_context_takeover: true,
+                    server_max_window_bits: false,
+                }),
+            ),
+            (
+                vec![
+                    "permessage-deflate; server_max_window_bits=\"15\"; client_max_window_bits=10",
+                ],
+                Some(DeflateParams {
+                    server_no_context_takeover: false,
+                    server_max_window_bits: true,
+                }),
+            ),
+            // Smaller server windows are not supported, the next offer is used
+            (
+                vec!["permessage-deflate; server_max_window_bits=10, permessage-deflate"],
+                Some(DeflateParams::default()),
+            ),
+            (
+                vec![
+                    "permessage-deflate; server_max_window_bits=10",
+                    "permessage-deflate; server_no_context_takeover",
+                ],
+                Some(DeflateParams {
+                    server_no_context_takeover: true,
+                    server_max_window_bits: false,
+                }),
+            ),
+            (vec!["permessage-deflate; server_max_window_bits=10"], None),
+            (vec!["permessage-deflate; client_max_window_bits=7"], None),
+            (vec!["permessage-deflate; unknown_param"], None),
+            (vec!["x-webkit-deflate-frame"], None),
+            (vec![], None),
+        ] {
+            assert_eq!(
+                DeflateParams::negotiate(offers.iter().copied()),
+                expected,
+                "{offers:?}"
+            );
+        }
+
+        assert_eq!(
+            DeflateParams {
+                server_no_context_takeover: true,
+                server_max_window_bits: true,
+            }
+            .to_header(),
+            "permessage-deflate; server_no_context_takeover; server_max_window_bits=15"
+        );
+    }
+
+    #[test]
+    fn deflate_round_trip() {
+        let text = "The quick brown fox jumps over the lazy dog. ".repeat(20);
+
+        for mask in [None, Some(MASK)] {
+            // Single frame messages are compressed with RSV1 set
+            let message = frame(0x81, mask, text.as_bytes());
+            let (deflated, inflated) = deflate_then_inflate(&message, 0);
+            assert_ne!(deflated[0] & RSV1, 0);
+            assert!(deflated.len() < message.len());
+            assert_eq!(inflated, message);
+
+            // Fragmented messages only set RSV1 on the first frame, control frames
+            // in between are neither compressed nor interrupt the message
+            let mut message = frame(0x01, mask, &text.as_bytes()[..400]);
+            message.extend(frame(0x89, mask, b"ping"));
+            message.extend(frame(0x80, mask, &text.as_bytes()[400..]));
+            let (deflated, inflated) = deflate_then_inflate(&message, usize::MAX);
+            let deflated = split_frames(&deflated);
+            assert_eq!(
+                deflated.iter().map(|(byte, _)| *byte).collect::<Vec<_>>(),
+                [0x01 | RSV1, 0x89, 0x80]
+            );
+            assert_eq!(deflated[1].1, b"ping");
+            let inflated = split_frames(&inflated);
+            assert_eq!(
+                inflated.iter().map(|(byte, _)| *byte).collect::<Vec<_>>(),
+                [0x01, 0x89, 0x80]
+            );
+            assert_eq!(inflated[1].1, b"ping");
+            assert_eq!(
+                [&inflated[0].1[..], &inflated[2].1[..]].concat(),
+                text.as_bytes()
+            );
+
+            // Messages below the threshold are passed through
+            let message = frame(0x81, mask, b"Hello");
+            let (deflated, inflated) = deflate_then_inflate(&message, 100);
+            assert_eq!(deflated, message);
+            assert_eq!(inflated, message);
+        }
+    }
+
+    #[test]
+    fn inflated_message_size_limit() {
+        // Each fragment inflates below the limit but the whole message does not
+        let mut compress = Compress::new(Compression::best(), false);
+        let zeros = vec![0u8; MAX_MESSAGE_SIZE / 2 + 1];
+        let mut message = Vec::new();
+        for (first_byte, flush) in [
+            (0x02 | RSV1, FlushCompress::Sync),
+            (0x00, FlushCompress::Sync),
+            (0x80, FlushCompress::Sync),
+        ] {
+            let mut deflated = Vec::new();
+            deflate(&mut compress, &zeros, &mut deflated, flush).unwrap();
+            if first_byte & 0x80 != 0 && deflated.ends_with(&DEFLATE_TRAILER) {
+                deflated.truncate(deflated.len() - DEFLATE_TRAILER.len());
+            }
+            write_frame(&mut message, first_byte, None, &mut deflated);
+        }
+
+        let mut reader = state(DeflateParams::default(), 6, 0);
+        reader.read_buf.extend_from_slice(&message);
+        assert_eq!(
+            reader.inflate_frames().unwrap_err().kind(),
+            std::io::ErrorKind::InvalidData
+        );
+
+        // Uncompressed messages are left to the WebSocket codec limits
+        let mut reader = state(DeflateParams::default(), 6, 0);
+        reader
+            .read_buf
+            .extend_from_slice(&frame(0x82, None, &[0u8; 1024]));
+        reader.inflate_frames().unwrap();
+        assert_eq!(reader.read_out, frame(0x82, None, &[0u8; 1024]));
+    }
+}
@@ -0,0 +1,88 @@
+/*
+ * Copyright (c) 2023 Stalwart Labs Ltd.
+ *
+ * This file is part of Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use jmap_proto::request::websocket::{json_to_msgpack, WebSocketMessage, WebSocketRequestError};
+use tungstenite::Message;
+
+// Format of the messages exchanged with the client, JSON is sent in text frames
+// and MessagePack in binary frames.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum WebSocketEncoding {
+    #[default]
+    Json,
+    MsgPack,
+}
+
+impl WebSocketEncoding {
+    // Selects the first supported subprotocol offered by the client, defaulting to JSON
+    pub fn negotiate<'x>(protocols: impl IntoIterator<Item = &'x str>) -> Self {
+        protocols
+            .into_iter()
+            .flat_map(|protocols| protocols.split(','))
+            .find_map(|protocol| match protocol.trim() {
+                "jmap" => Some(WebSocketEncoding::Json),
+                "jmap+msgpack" => Some(WebSocketEncoding::MsgPack),
+                _ => None,
+            })
+            .unwrap_or_default()
+    }
+
+    pub fn protocol(&self) -> &'static str {
+        match self {
+            WebSocketEncoding::Json => "jmap",
+            WebSocketEncoding::MsgPack => "jmap+msgpack",
+        }
+    }
+
+    // Replies are encoded in the same format as the frame they answer
+    pub fn of(message: &Message) -> Self {
+        if message.is_binary() {
+            WebSocketEncoding::MsgPack
+        } else {
+            WebSocketEncoding::Json
+        }
+    }
+
+    pub fn parse(
+        &self,
+        bytes: &[u8],
+        max_calls: usize,
+        max_size: usize,
+    ) -> Result<WebSocketMessage, WebSocketRequestError> {
+        match self {
+            WebSocketEncoding::Json => WebSocketMessage::parse(bytes, max_calls, max_size),
+            WebSocketEncoding::MsgPack => {
+                WebSocketMessage::parse_msgpack(bytes, max_calls, max_size)
+            }
+        }
+    }
+
+    pub fn encode(&self, json: String) -> Message {
+        match self {
+            WebSocketEncoding::Json => Message::Text(json),
+            WebSocketEncoding::MsgPack => json_to_msgpack(&json)
+                .map(Message::Binary)
+                .unwrap_or(Message::Text(json)),
+        }
+    }
+}
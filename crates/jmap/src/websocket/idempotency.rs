@@ -0,0 +1,122 @@
+/*
+ * Copyright (c) 2023 Stalwart Labs Ltd.
+ *
+ * This file is part of Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use std::{sync::Arc, time::Instant};
+
+use jmap_proto::{
+    error::request::RequestError,
+    request::websocket::{WebSocketRequest, WebSocketRequestError, WebSocketResponse},
+};
+use tokio::sync::watch;
+use utils::{
+    listener::{limiter::InFlight, ServerInstance},
+    map::ttl_dashmap::TtlMap,
+};
+
+use crate::{auth::AccessToken, JMAP};
+
+// Response of a request sent with an idempotency key, empty while it is still
+// being processed. Keyed requests run detached from the connection so that a
+// client reconnecting after a dropped socket can pick up the original result.
+pub type IdempotentResponse = watch::Receiver<Option<Arc<String>>>;
+
+impl JMAP {
+    pub async fn handle_idempotent_request(
+        self: &Arc<Self>,
+        key: String,
+        request: WebSocketRequest,
+        in_flight: InFlight,
+        access_token: Arc<AccessToken>,
+        instance: &Arc<ServerInstance>,
+    ) -> String {
+        let cache_key = (access_token.primary_id(), key);
+
+        // Join the call if it is still running or completed recently
+        if let Some(mut response) = self.web_socket_idempotency.get_with_ttl(&cache_key) {
+            if let Ok(response) = response.wait_for(Option::is_some).await {
+                if let Some(response) = response.as_ref() {
+                    return response.as_ref().clone();
+                }
+            }
+        }
+
+        // Bound the number of cached responses, keyed requests are processed
+        // without caching when the cache is full of unexpired entries
+        if self.web_socket_idempotency.len() >= self.config.web_socket_idempotency_max {
+            self.web_socket_idempotency.cleanup();
+            if self.web_socket_idempotency.len() >= self.config.web_socket_idempotency_max {
+                return self
+                    .handle_websocket_request(request, access_token, instance)
+                    .await;
+            }
+        }
+
+        let (tx, mut response) = watch::channel(None);
+        self.web_socket_idempotency.insert_with_ttl(
+            cache_key.clone(),
+            response.clone(),
+            Instant::now() + self.config.web_socket_idempotency_ttl,
+        );
+
+        let jmap = self.clone();
+        let instance = instance.clone();
+        let cached_response = response.clone();
+        tokio::spawn(async move {
+            let _in_flight = in_flight;
+            let response = jmap
+                .handle_websocket_request(request, access_token, &instance)
+                .await;
+            let _ = tx.send(Some(Arc::new(response)));
+
+            // Keep the response around for the full TTL after completion
+            jmap.web_socket_idempotency.insert_with_ttl(
+                cache_key,
+                cached_response,
+                Instant::now() + jmap.config.web_socket_idempotency_ttl,
+            );
+        });
+
+        match response.wait_for(Option::is_some).await {
+            Ok(response) => response
+                .as_ref()
+                .map(|r| r.as_ref().clone())
+                .unwrap_or_default(),
+            Err(_) => WebSocketRequestError::from(RequestError::internal_server_error()).to_json(),
+        }
+    }
+
+    pub async fn handle_websocket_request(
+        &self,
+        request: WebSocketRequest,
+        access_token: Arc<AccessToken>,
+        instance: &Arc<ServerInstance>,
+    ) -> String {
+        match self
+            .handle_request(request.request, access_token, instance)
+            .await
+        {
+            Ok(response) => WebSocketResponse::from_response(response, request.id).to_json(),
+            Err(err) => WebSocketRequestError::from_error(err, request.id).to_json(),
+        }
+    }
+}
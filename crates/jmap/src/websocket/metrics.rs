@@ -0,0 +1,113 @@
+/*
+ * Copyright (c) 2023 Stalwart Labs Ltd.
+ *
+ * This file is part of Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use std::{
+    sync::atomic::{AtomicU64, Ordering},
+    time::Duration,
+};
+
+use serde::{Deserialize, Serialize};
+
+// Server wide WebSocket push statistics, exported through the admin API
+#[derive(Debug, Default)]
+pub struct WebSocketMetrics {
+    connections: AtomicU64,
+    active: AtomicU64,
+    pushes: AtomicU64,
+    coalesced: AtomicU64,
+    dropped: AtomicU64,
+    heartbeats: AtomicU64,
+    push_latency_ms: AtomicU64,
+    max_push_latency_ms: AtomicU64,
+    connection_ms: AtomicU64,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct WebSocketMetricsSnapshot {
+    pub connections: u64,
+    pub active: u64,
+    pub pushes: u64,
+    pub coalesced: u64,
+    pub dropped: u64,
+    pub heartbeats: u64,
+    pub avg_push_latency_ms: u64,
+    pub max_push_latency_ms: u64,
+    pub avg_connection_ms: u64,
+}
+
+impl WebSocketMetrics {
+    pub fn connection_opened(&self) {
+        self.connections.fetch_add(1, Ordering::Relaxed);
+        self.active.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn connection_closed(&self, duration: Duration) {
+        self.active.fetch_sub(1, Ordering::Relaxed);
+        self.connection_ms
+            .fetch_add(duration.as_millis() as u64, Ordering::Relaxed);
+    }
+
+    pub fn record_push(&self, latency: Duration, coalesced: u64) {
+        let latency = latency.as_millis() as u64;
+        self.pushes.fetch_add(1, Ordering::Relaxed);
+        self.coalesced.fetch_add(coalesced, Ordering::Relaxed);
+        self.push_latency_ms.fetch_add(latency, Ordering::Relaxed);
+        self.max_push_latency_ms
+            .fetch_max(latency, Ordering::Relaxed);
+    }
+
+    pub fn record_dropped(&self) {
+        self.dropped.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_heartbeat(&self) {
+        self.heartbeats.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn snapshot(&self) -> WebSocketMetricsSnapshot {
+        let connections = self.connections.load(Ordering::Relaxed);
+        let active = self.active.load(Ordering::Relaxed);
+        let pushes = self.pushes.load(Ordering::Relaxed);
+        let closed = connections.saturating_sub(active);
+
+        WebSocketMetricsSnapshot {
+            connections,
+            active,
+            pushes,
+            coalesced: self.coalesced.load(Ordering::Relaxed),
+            dropped: self.dropped.load(Ordering::Relaxed),
+            heartbeats: self.heartbeats.load(Ordering::Relaxed),
+            avg_push_latency_ms: self
+                .push_latency_ms
+                .load(Ordering::Relaxed)
+                .checked_div(pushes)
+                .unwrap_or(0),
+            max_push_latency_ms: self.max_push_latency_ms.load(Ordering::Relaxed),
+            avg_connection_ms: self
+                .connection_ms
+                .load(Ordering::Relaxed)
+                .checked_div(closed)
+                .unwrap_or(0),
+        }
+    }
+}
@@ -21,5 +21,12 @@
  * for more details.
 */
 
+pub mod deflate;
+pub mod encoding;
+pub mod idempotency;
+pub mod metrics;
+pub mod outbound;
 pub mod stream;
+pub mod throttle;
+pub mod ticket;
 pub mod upgrade;
@@ -0,0 +1,151 @@
+/*
+ * Copyright (c) 2023 Stalwart Labs Ltd.
+ *
+ * This file is part of Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use std::time::Duration;
+
+use futures_util::{stream::SplitSink, SinkExt};
+use tokio::{
+    io::{AsyncRead, AsyncWrite},
+    sync::{
+        mpsc::{self, error::TrySendError},
+        oneshot,
+    },
+    task::JoinHandle,
+};
+use tokio_tungstenite::WebSocketStream;
+use tungstenite::{
+    protocol::{frame::coding::CloseCode, CloseFrame},
+    Message,
+};
+
+use super::deflate::DeflateStream;
+
+// Time allowed to flush queued messages and the close frame on disconnect
+const CLOSE_TIMEOUT: Duration = Duration::from_secs(1);
+
+// Bounded queue of messages written to the client by a separate task, so a
+// slow client never blocks the connection loop.
+pub struct OutboundQueue {
+    tx: mpsc::Sender<Message>,
+    close_tx: Option<oneshot::Sender<CloseFrame<'static>>>,
+    writer: JoinHandle<()>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QueueError {
+    Full,
+    Closed,
+}
+
+impl OutboundQueue {
+    pub fn new<S>(
+        sink: SplitSink<WebSocketStream<DeflateStream<S>>, Message>,
+        high_water_mark: usize,
+    ) -> Self
+    where
+        S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+    {
+        let (tx, rx) = mpsc::channel(high_water_mark.max(1));
+        let (close_tx, close_rx) = oneshot::channel();
+        OutboundQueue {
+            tx,
+            close_tx: Some(close_tx),
+            writer: tokio::spawn(write_messages(sink, rx, close_rx)),
+        }
+    }
+
+    pub fn is_backed_up(&self) -> bool {
+        self.tx.capacity() == 0
+    }
+
+    // Queues a message, the connection is closed with a policy violation
+    // when the client is not reading fast enough to make room for it.
+    pub fn send(&mut self, message: Message) -> Result<(), QueueError> {
+        match self.tx.try_send(message) {
+            Ok(_) => Ok(()),
+            Err(TrySendError::Full(_)) => {
+                self.reject("Client is not reading messages fast enough");
+                Err(QueueError::Full)
+            }
+            Err(TrySendError::Closed(_)) => Err(QueueError::Closed),
+        }
+    }
+
+    // Discards the queued messages and closes the connection with a policy violation
+    pub fn reject(&mut self, reason: &'static str) {
+        if let Some(close_tx) = self.close_tx.take() {
+            let _ = close_tx.send(CloseFrame {
+                code: CloseCode::Policy,
+                reason: reason.into(),
+            });
+        }
+    }
+
+    // Writes the remaining queued messages before closing the connection
+    pub async fn shutdown(self) {
+        let OutboundQueue {
+            tx,
+            close_tx,
+            mut writer,
+        } = self;
+        drop(tx);
+        let _ = tokio::time::timeout(CLOSE_TIMEOUT, &mut writer).await;
+        drop(close_tx);
+    }
+}
+
+async fn write_messages<S>(
+    mut sink: SplitSink<WebSocketStream<DeflateStream<S>>, Message>,
+    mut rx: mpsc::Receiver<Message>,
+    mut close_rx: oneshot::Receiver<CloseFrame<'static>>,
+) where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let close = loop {
+        tokio::select! {
+            biased;
+            close = &mut close_rx => break close,
+            message = rx.recv() => {
+                if let Some(message) = message {
+                    tokio::select! {
+                        biased;
+                        close = &mut close_rx => break close,
+                        result = sink.send(message) => {
+                            if result.is_err() {
+                                return;
+                            }
+                        }
+                    }
+                } else {
+                    let _ = tokio::time::timeout(CLOSE_TIMEOUT, sink.close()).await;
+                    return;
+                }
+            }
+        }
+    };
+
+    // The connection is abandoned without a close frame if the queue was dropped
+    if let Ok(frame) = close {
+        let _ = tokio::time::timeout(CLOSE_TIMEOUT, sink.send(Message::Close(Some(frame)))).await;
+    }
+}
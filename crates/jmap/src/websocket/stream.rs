@@ -21,45 +21,80 @@
  * for more details.
 */
 
-use std::{sync::Arc, time::Instant};
+use std::{
+    sync::Arc,
+    time::{Duration, Instant, SystemTime},
+};
 
-use futures_util::{SinkExt, StreamExt};
-use hyper::upgrade::Upgraded;
-use hyper_util::rt::TokioIo;
+use futures_util::{future::join_all, stream::FuturesUnordered, SinkExt, StreamExt};
 use jmap_proto::{
     error::request::RequestError,
     request::websocket::{
-        WebSocketMessage, WebSocketRequestError, WebSocketResponse, WebSocketStateChange,
+        WebSocketMessage, WebSocketRequestError, WebSocketServerTime, WebSocketStateChange,
     },
-    types::type_state::DataType,
+    types::{collection::Collection, state::StateChange, type_state::DataType},
+};
+use tokio::{
+    io::{AsyncRead, AsyncWrite},
+    sync::mpsc,
 };
 use tokio_tungstenite::WebSocketStream;
-use tungstenite::Message;
-use utils::{listener::ServerInstance, map::bitmap::Bitmap};
+use tungstenite::{
+    protocol::{frame::coding::CloseCode, CloseFrame},
+    Message,
+};
+use utils::{
+    listener::{limiter::TokenBucket, ServerInstance},
+    map::{bitmap::Bitmap, ttl_dashmap::TtlMap},
+};
+
+use crate::{
+    auth::{authenticate::BearerToken, AccessToken},
+    websocket::{
+        deflate::DeflateStream, encoding::WebSocketEncoding, outbound::OutboundQueue,
+        throttle::ThrottledChanges,
+    },
+    JMAP,
+};
+
+// Maximum number of unacknowledged state changes kept per client
+const MAX_PENDING_ACKS: usize = 256;
+
+// Maximum number of queued blob availability notices per client
+const MAX_PENDING_NOTICES: usize = 32;
 
-use crate::{auth::AccessToken, JMAP};
+// Attempts made to restore a dropped state manager subscription
+const MAX_RESUBSCRIBE_ATTEMPTS: u32 = 5;
+const RESUBSCRIBE_BACKOFF: Duration = Duration::from_millis(100);
+
+// Interval at which a backed up client is checked for room to push state changes
+const BACKPRESSURE_INTERVAL: Duration = Duration::from_millis(100);
 
 impl JMAP {
-    pub async fn handle_websocket_stream(
-        &self,
-        mut stream: WebSocketStream<TokioIo<Upgraded>>,
-        access_token: Arc<AccessToken>,
+    pub async fn handle_websocket_stream<S: AsyncRead + AsyncWrite + Unpin + Send + 'static>(
+        self: &Arc<Self>,
+        mut stream: WebSocketStream<DeflateStream<S>>,
+        mut access_token: Arc<AccessToken>,
         instance: Arc<ServerInstance>,
+        encoding: WebSocketEncoding,
+        bearer_token: Option<BearerToken>,
     ) {
         let span = tracing::info_span!(
             "WebSocket connection established",
             "account_id" = access_token.primary_id(),
             "url" = instance.data,
+            "compression" = stream.get_ref().is_compressed(),
+            "protocol" = encoding.protocol(),
         );
 
         // Set timeouts
-        let throttle = self.config.web_socket_throttle;
         let timeout = self.config.web_socket_timeout;
         let heartbeat = self.config.web_socket_heartbeat;
         let mut last_request = Instant::now();
-        let mut last_changes_sent = Instant::now() - throttle;
         let mut last_heartbeat = Instant::now() - heartbeat;
         let mut next_event = heartbeat;
+        let max_missed_heartbeats = self.config.web_socket_max_missed_heartbeats;
+        let mut missed_heartbeats = 0;
 
         // Register with state manager
         let mut change_rx = if let Some(change_rx) = self
@@ -73,45 +108,108 @@ impl JMAP {
             change_rx
         } else {
             let _ = stream
-                .send(Message::Text(
+                .send(encoding.encode(
                     WebSocketRequestError::from(RequestError::internal_server_error()).to_json(),
                 ))
                 .await;
             return;
         };
-        let mut changes = WebSocketStateChange::new(None);
+
+        // Messages are written by a separate task, a full queue means the client can't keep up
+        let (sink, mut stream) = stream.split();
+        let mut outbound = OutboundQueue::new(sink, self.config.web_socket_max_queued);
+        let backpressure_timeout = self.config.web_socket_backpressure_timeout;
+        let mut backed_up_since: Option<Instant> = None;
+
+        let mut changes = ThrottledChanges::new(
+            self.config.web_socket_throttle,
+            self.config.web_socket_type_throttle.clone(),
+        );
+        let mut metrics = PushMetrics::default();
+        let connected_at = Instant::now();
+        self.web_socket_metrics.connection_opened();
         let mut change_types: Bitmap<DataType> = Bitmap::new();
+        let mut send_server_time = false;
+        let mut requests = FuturesUnordered::new();
+        let mut shutdown_rx = instance.shutdown_rx.clone();
+        let mut shutting_down = false;
+
+        // Per connection request rate limit
+        let mut rate_limiter = (self.config.web_socket_rate_limit > 0).then(|| {
+            TokenBucket::new(
+                self.config.web_socket_rate_limit,
+                self.config.web_socket_rate_burst,
+            )
+        });
+        let max_rate_violations = self.config.web_socket_rate_max_violations;
+        let mut rate_violations = 0;
+
+        // Access tokens are verified again periodically for as long as the connection is open
+        let token_check_interval = self.config.web_socket_token_check_interval;
+        let mut last_token_check = Instant::now();
+
+        // Acknowledged delivery
+        let mut ack_key: Option<(u32, String)> = None;
+        let mut pending_acks: Vec<(u64, String)> = Vec::new();
+        let mut next_seq_id = 0;
 
-        loop {
+        // Blob availability notices for uploads made with tickets issued on this connection
+        let (notify_tx, mut notify_rx) = mpsc::channel::<String>(MAX_PENDING_NOTICES);
+
+        'outer: loop {
             tokio::select! {
-                event = tokio::time::timeout(next_event, stream.next()) => {
+                event = tokio::time::timeout(next_event, stream.next()), if !shutting_down => {
                     match event {
                         Ok(Some(Ok(event))) => {
                             match event {
-                                Message::Text(text) => {
-                                    let response = match WebSocketMessage::parse(
-                                        text.as_bytes(),
+                                Message::Text(_) | Message::Binary(_) => {
+                                    let reply_encoding = WebSocketEncoding::of(&event);
+                                    let message = reply_encoding.parse(
+                                        &event.into_data(),
                                         self.config.request_max_calls,
                                         self.config.request_max_size,
-                                    ) {
+                                    );
+
+                                    // Reject requests over the rate limit, closing the connection on repeated violations.
+                                    // Each request in a batch takes a token and batches are rejected as a whole.
+                                    let tokens = match &message {
+                                        Ok(WebSocketMessage::Batch(batch_requests)) => batch_requests.len().max(1) as u64,
+                                        _ => 1,
+                                    };
+                                    if !rate_limiter.as_mut().map_or(true, |limiter| limiter.is_allowed_n(tokens)) {
+                                        rate_violations += 1;
+                                        if rate_violations > max_rate_violations {
+                                            tracing::debug!(
+                                                parent: &span,
+                                                event = "disconnect",
+                                                violations = rate_violations,
+                                                "Disconnecting client, too many requests"
+                                            );
+                                            let _ = outbound.send(Message::Close(Some(CloseFrame {
+                                                code: CloseCode::Policy,
+                                                reason: "Too many requests".into(),
+                                            })));
+                                            break;
+                                        }
+                                        if let Err(err) = outbound.send(reply_encoding.encode(
+                                            WebSocketRequestError::from(RequestError::too_many_requests()).to_json(),
+                                        )) {
+                                            tracing::debug!(parent: &span, error = ?err, "Failed to queue rate limit message");
+                                            break;
+                                        }
+                                        continue;
+                                    }
+                                    rate_violations = 0;
+
+                                    let mut batch = None;
+                                    let response = match message {
                                         Ok(WebSocketMessage::Request(request)) => {
-                                            match self
-                                                .handle_request(
-                                                    request.request,
-                                                    access_token.clone(),
-                                                    &instance,
-                                                )
-                                                .await
-                                            {
-                                                Ok(response) => {
-                                                    WebSocketResponse::from_response(response, request.id)
-                                                        .to_json()
-                                                }
-                                                Err(err) => {
-                                                    WebSocketRequestError::from_error(err, request.id)
-                                                        .to_json()
-                                                }
-                                            }
+                                            batch = Some((vec![Ok(request)], false));
+                                            None
+                                        }
+                                        Ok(WebSocketMessage::Batch(batch_requests)) => {
+                                            batch = Some((batch_requests, true));
+                                            None
                                         }
                                         Ok(WebSocketMessage::PushEnable(push_enable)) => {
                                             change_types = if !push_enable.data_types.is_empty() {
@@ -119,25 +217,158 @@ impl JMAP {
                                             } else {
                                                 Bitmap::all()
                                             };
+                                            send_server_time = push_enable.server_time;
+                                            changes.set_throttle(
+                                                push_enable
+                                                    .throttle
+                                                    .map(|ms| {
+                                                        Duration::from_millis(ms).clamp(
+                                                            self.config.web_socket_throttle_min,
+                                                            self.config.web_socket_throttle_max,
+                                                        )
+                                                    })
+                                                    .unwrap_or(self.config.web_socket_throttle),
+                                            );
+                                            if push_enable.ack_required {
+                                                // Re-send the changes a previous connection did not acknowledge
+                                                let key = (
+                                                    access_token.primary_id(),
+                                                    push_enable.client_id.unwrap_or_default(),
+                                                );
+                                                if ack_key.as_ref() != Some(&key) {
+                                                    pending_acks = self
+                                                        .web_socket_pending
+                                                        .remove_with_ttl(&key)
+                                                        .map(|pending| pending.as_ref().clone())
+                                                        .unwrap_or_default();
+                                                    for (seq_id, change) in &pending_acks {
+                                                        next_seq_id = next_seq_id.max(*seq_id + 1);
+                                                        if let Err(err) = outbound.send(encoding.encode(change.clone())) {
+                                                            tracing::debug!(parent: &span, error = ?err, "Failed to queue state change message");
+                                                            break 'outer;
+                                                        }
+                                                    }
+                                                    ack_key = Some(key);
+                                                }
+                                            } else {
+                                                ack_key = None;
+                                                pending_acks.clear();
+                                            }
+                                            if push_enable.initial_state {
+                                                // Send the current state of the requested types before any live change
+                                                let mut snapshot = WebSocketStateChange::new(None);
+                                                for data_type in change_types {
+                                                    let collection = match data_type {
+                                                        DataType::Email | DataType::EmailDelivery => Collection::Email,
+                                                        DataType::Mailbox => Collection::Mailbox,
+                                                        DataType::Thread => Collection::Thread,
+                                                        DataType::Identity => Collection::Identity,
+                                                        DataType::EmailSubmission => Collection::EmailSubmission,
+                                                        DataType::SieveScript => Collection::SieveScript,
+                                                        DataType::PushSubscription => Collection::PushSubscription,
+                                                        _ => continue,
+                                                    };
+                                                    if let Ok(state) = self.get_state(access_token.primary_id(), collection).await {
+                                                        snapshot
+                                                            .changed
+                                                            .get_mut_or_insert(access_token.primary_id().into())
+                                                            .set(data_type, state);
+                                                    }
+                                                }
+                                                if !snapshot.changed.is_empty() {
+                                                    if let Err(err) = outbound.send(encoding.encode(snapshot.to_json())) {
+                                                        tracing::debug!(parent: &span, error = ?err, "Failed to queue state snapshot message");
+                                                        break;
+                                                    }
+                                                }
+                                            }
                                             continue;
                                         }
                                         Ok(WebSocketMessage::PushDisable) => {
                                             change_types = Bitmap::new();
+                                            send_server_time = false;
+                                            ack_key = None;
+                                            pending_acks.clear();
+                                            continue;
+                                        }
+                                        Ok(WebSocketMessage::PushAck(seq_id)) => {
+                                            pending_acks.retain(|(id, _)| *id != seq_id);
                                             continue;
                                         }
-                                        Err(err) => err.to_json(),
+                                        Ok(WebSocketMessage::UploadTicket(request)) => {
+                                            let request_id = request.id.clone();
+                                            match self.issue_upload_ticket(
+                                                request,
+                                                &access_token,
+                                                &instance.data,
+                                                notify_tx.clone(),
+                                            ) {
+                                                Ok(ticket) => ticket.to_json().into(),
+                                                Err(err) => WebSocketRequestError::from_error(err, request_id)
+                                                    .to_json()
+                                                    .into(),
+                                            }
+                                        }
+                                        Err(err) => err.to_json().into(),
                                     };
-                                    if let Err(err) = stream.send(Message::Text(response)).await {
-                                        tracing::debug!(parent: &span, error = ?err, "Failed to send text message");
+                                    if let Some((batch, is_batch)) = batch {
+                                        // Enforce the advertised maxConcurrentRequests limit on each request
+                                        let batch = batch
+                                            .into_iter()
+                                            .map(|request| match request {
+                                                Ok(request) => match self.is_account_allowed(&access_token) {
+                                                    Ok(in_flight) => Ok((request, in_flight)),
+                                                    Err(err) => Err(WebSocketRequestError::from_error(err, request.id).to_json()),
+                                                },
+                                                Err(err) => Err(err.to_json()),
+                                            })
+                                            .collect::<Vec<_>>();
+                                        let access_token = access_token.clone();
+                                        let instance = &instance;
+                                        requests.push(async move {
+                                            // Requests in a batch are processed independently, a failure does not abort the others
+                                            let responses = join_all(batch.into_iter().map(|request| {
+                                                let access_token = access_token.clone();
+                                                async move {
+                                                    let (mut request, in_flight) = match request {
+                                                        Ok(request) => request,
+                                                        Err(response) => return response,
+                                                    };
+                                                    if let Some(key) = request.idempotency_key.take() {
+                                                        return self
+                                                            .handle_idempotent_request(key, request, in_flight, access_token, instance)
+                                                            .await;
+                                                    }
+                                                    self.handle_websocket_request(request, access_token, instance)
+                                                        .await
+                                                }
+                                            }))
+                                            .await;
+                                            reply_encoding.encode(if is_batch {
+                                                format!("[{}]", responses.join(","))
+                                            } else {
+                                                responses.into_iter().next().unwrap_or_default()
+                                            })
+                                        });
+                                    }
+                                    if let Some(response) = response {
+                                        if let Err(err) = outbound.send(reply_encoding.encode(response)) {
+                                            tracing::debug!(parent: &span, error = ?err, "Failed to queue text message");
+                                            break;
+                                        }
                                     }
                                 }
                                 Message::Ping(bytes) => {
-                                    if let Err(err) = stream.send(Message::Pong(bytes)).await {
-                                        tracing::debug!(parent: &span, error = ?err, "Failed to send pong message");
+                                    if let Err(err) = outbound.send(Message::Pong(bytes)) {
+                                        tracing::debug!(parent: &span, error = ?err, "Failed to queue pong message");
+                                        break;
                                     }
                                 }
+                                Message::Pong(_) => {
+                                    missed_heartbeats = 0;
+                                }
                                 Message::Close(frame) => {
-                                    let _ = stream.close(frame).await;
+                                    let _ = outbound.send(Message::Close(frame));
                                     break;
                                 }
                                 _ => (),
@@ -164,6 +395,29 @@ impl JMAP {
                         }
                     }
                 }
+                Some(notice) = notify_rx.recv() => {
+                    if let Err(err) = outbound.send(encoding.encode(notice)) {
+                        tracing::debug!(parent: &span, error = ?err, "Failed to queue blob notice message");
+                        break;
+                    }
+                }
+                _ = shutdown_rx.changed(), if !shutting_down => {
+                    // Stop reading new requests and close once in-flight requests complete
+                    tracing::debug!(
+                        parent: &span,
+                        event = "shutdown",
+                        in_flight = requests.len(),
+                        "WebSocket server shutting down"
+                    );
+                    shutting_down = true;
+                }
+                Some(response) = requests.next(), if !requests.is_empty() => {
+                    if let Err(err) = outbound.send(response) {
+                        tracing::debug!(parent: &span, error = ?err, "Failed to queue text message");
+                        break;
+                    }
+                    last_request = Instant::now();
+                }
                 state_change = change_rx.recv() => {
                     if let Some(state_change) = state_change {
                         if !change_types.is_empty() && state_change
@@ -171,13 +425,18 @@ impl JMAP {
                             .iter()
                             .any(|(t, _)| change_types.contains(*t))
                             {
+                                let now = Instant::now();
                                 for (type_state, change_id) in state_change.types {
-                                    changes
-                                        .changed
-                                        .get_mut_or_insert(state_change.account_id.into())
-                                        .set(type_state, change_id.into());
+                                    changes.insert(
+                                        state_change.account_id.into(),
+                                        type_state,
+                                        change_id.into(),
+                                        now,
+                                    );
                                 }
                             }
+                    } else if let Some(new_change_rx) = self.resubscribe_state_manager(&access_token, &span).await {
+                        change_rx = new_change_rx;
                     } else {
                         tracing::debug!(
                             parent: &span,
@@ -189,28 +448,210 @@ impl JMAP {
                 }
             }
 
-            if !changes.changed.is_empty() {
-                // Send any queued changes
-                let elapsed = last_changes_sent.elapsed();
-                if elapsed >= throttle {
-                    if let Err(err) = stream.send(Message::Text(changes.to_json())).await {
-                        tracing::debug!(parent: &span, error = ?err, "Failed to send state change message");
+            if last_token_check.elapsed() >= token_check_interval {
+                last_token_check = Instant::now();
+                if let Some(new_access_token) = self
+                    .revalidate_access_token(access_token.primary_id(), bearer_token.as_ref())
+                    .await
+                {
+                    let new_access_token = Arc::new(new_access_token);
+                    self.cache_access_token(new_access_token.clone());
+                    access_token = new_access_token;
+                } else {
+                    tracing::debug!(
+                        parent: &span,
+                        event = "disconnect",
+                        "Disconnecting client, access token expired or revoked"
+                    );
+                    let _ = outbound.send(encoding.encode(
+                        WebSocketRequestError::from(RequestError::unauthorized()).to_json(),
+                    ));
+                    let _ = outbound.send(Message::Close(Some(CloseFrame {
+                        code: CloseCode::Policy,
+                        reason: "Access token expired or revoked".into(),
+                    })));
+                    break;
+                }
+            }
+
+            if shutting_down && requests.is_empty() {
+                let _ = outbound.send(Message::Close(Some(CloseFrame {
+                    code: CloseCode::Away,
+                    reason: "Server shutting down".into(),
+                })));
+                break;
+            } else if !changes.is_empty() && outbound.is_backed_up() {
+                // Keep coalescing changes until the client catches up
+                let now = Instant::now();
+                if now.duration_since(*backed_up_since.get_or_insert(now)) > backpressure_timeout {
+                    tracing::debug!(
+                        parent: &span,
+                        event = "disconnect",
+                        "Disconnecting client, outbound queue is full"
+                    );
+                    outbound.reject("Client is not reading state changes fast enough");
+                    break;
+                }
+                next_event = BACKPRESSURE_INTERVAL;
+            } else if !changes.is_empty() {
+                // Send the queued changes of the types whose throttle interval has elapsed
+                let now = Instant::now();
+                backed_up_since = None;
+                if let Some(mut push) = changes.take_due(now) {
+                    let change = if ack_key.is_some() {
+                        push.changes.seq_id = Some(next_seq_id);
+                        next_seq_id += 1;
+                        let change = push.changes.to_json();
+                        if pending_acks.len() == MAX_PENDING_ACKS {
+                            tracing::debug!(parent: &span, "Too many unacknowledged state changes, discarding oldest");
+                            pending_acks.remove(0);
+                            metrics.dropped += 1;
+                            self.web_socket_metrics.record_dropped();
+                        }
+                        pending_acks.push((push.changes.seq_id.unwrap(), change.clone()));
+                        change
+                    } else {
+                        push.changes.to_json()
+                    };
+                    if let Err(err) = outbound.send(encoding.encode(change)) {
+                        tracing::debug!(parent: &span, error = ?err, "Failed to queue state change message");
+                        break;
                     }
-                    changes.changed.clear();
-                    last_changes_sent = Instant::now();
+                    tracing::trace!(
+                        parent: &span,
+                        event = "push",
+                        latency_ms = push.latency.as_millis() as u64,
+                        coalesced = push.coalesced,
+                        "State change pushed"
+                    );
+                    metrics.record_push(push.latency, push.coalesced);
+                    self.web_socket_metrics
+                        .record_push(push.latency, push.coalesced);
                     last_heartbeat = Instant::now();
-                    next_event = heartbeat;
-                } else {
-                    next_event = throttle - elapsed;
                 }
+                next_event = changes.next_deadline(now).unwrap_or(heartbeat);
             } else if last_heartbeat.elapsed() > heartbeat {
-                if let Err(err) = stream.send(Message::Ping(vec![])).await {
-                    tracing::debug!(parent: &span, error = ?err, "Failed to send ping message");
+                // Disconnect clients that stopped answering pings
+                if max_missed_heartbeats > 0 && missed_heartbeats >= max_missed_heartbeats {
+                    tracing::debug!(
+                        parent: &span,
+                        event = "disconnect",
+                        missed = missed_heartbeats,
+                        "Disconnecting unresponsive client"
+                    );
+                    outbound.reject("Heartbeats not acknowledged");
+                    break;
+                }
+
+                // Send the server time along with the heartbeat to clients that opted in
+                if send_server_time {
+                    let now = SystemTime::now()
+                        .duration_since(SystemTime::UNIX_EPOCH)
+                        .map_or(0, |d| d.as_secs()) as i64;
+                    if let Err(err) =
+                        outbound.send(encoding.encode(WebSocketServerTime::new(now).to_json()))
+                    {
+                        tracing::debug!(parent: &span, error = ?err, "Failed to queue server time message");
+                        break;
+                    }
+                }
+                if let Err(err) = outbound.send(Message::Ping(vec![])) {
+                    tracing::debug!(parent: &span, error = ?err, "Failed to queue ping message");
                     break;
                 }
+                metrics.heartbeats += 1;
+                self.web_socket_metrics.record_heartbeat();
+                missed_heartbeats += 1;
                 last_heartbeat = Instant::now();
                 next_event = heartbeat;
             }
         }
+
+        outbound.shutdown().await;
+        self.web_socket_metrics
+            .connection_closed(connected_at.elapsed());
+
+        tracing::debug!(
+            parent: &span,
+            event = "metrics",
+            duration_ms = connected_at.elapsed().as_millis() as u64,
+            pushes = metrics.pushes,
+            coalesced = metrics.coalesced,
+            dropped = metrics.dropped,
+            heartbeats = metrics.heartbeats,
+            avg_latency_ms = metrics.avg_latency().as_millis() as u64,
+            max_latency_ms = metrics.max_latency.as_millis() as u64,
+            "WebSocket connection closed"
+        );
+
+        // Keep unacknowledged changes so they can be re-sent when the client reconnects
+        if let Some(key) = ack_key {
+            if !pending_acks.is_empty() {
+                self.web_socket_pending.insert_with_ttl(
+                    key,
+                    Arc::new(pending_acks),
+                    Instant::now() + self.config.web_socket_ack_ttl,
+                );
+            }
+        }
+    }
+
+    async fn resubscribe_state_manager(
+        &self,
+        access_token: &AccessToken,
+        span: &tracing::Span,
+    ) -> Option<mpsc::Receiver<StateChange>> {
+        // The state manager dropped the subscription, try to register again before giving up
+        let mut backoff = RESUBSCRIBE_BACKOFF;
+        for attempt in 1..=MAX_RESUBSCRIBE_ATTEMPTS {
+            tokio::time::sleep(backoff).await;
+            if let Some(change_rx) = self
+                .subscribe_state_manager(
+                    access_token.primary_id(),
+                    access_token.primary_id(),
+                    Bitmap::all(),
+                )
+                .await
+            {
+                tracing::debug!(
+                    parent: span,
+                    event = "resubscribed",
+                    attempt = attempt,
+                    "Subscription to state manager restored"
+                );
+                return Some(change_rx);
+            }
+            backoff *= 2;
+        }
+
+        None
+    }
+}
+
+// Push delivery statistics for a single connection
+#[derive(Default)]
+struct PushMetrics {
+    pushes: u64,
+    coalesced: u64,
+    dropped: u64,
+    heartbeats: u64,
+    total_latency: Duration,
+    max_latency: Duration,
+}
+
+impl PushMetrics {
+    fn record_push(&mut self, latency: Duration, coalesced: u64) {
+        self.pushes += 1;
+        self.coalesced += coalesced;
+        self.total_latency += latency;
+        self.max_latency = self.max_latency.max(latency);
+    }
+
+    fn avg_latency(&self) -> Duration {
+        if self.pushes > 0 {
+            self.total_latency / self.pushes as u32
+        } else {
+            Duration::ZERO
+        }
     }
 }
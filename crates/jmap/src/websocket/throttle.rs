@@ -0,0 +1,146 @@
+/*
+ * Copyright (c) 2023 Stalwart Labs Ltd.
+ *
+ * This file is part of Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use std::time::{Duration, Instant};
+
+use jmap_proto::{
+    request::websocket::WebSocketStateChange,
+    types::{id::Id, state::State, type_state::DataType},
+};
+use utils::map::vec_map::VecMap;
+
+// Queues state changes per data type, each type is pushed once its own
+// throttle interval has elapsed since it was last sent.
+pub struct ThrottledChanges {
+    throttle: Duration,
+    type_throttle: VecMap<DataType, Duration>,
+    pending: VecMap<DataType, PendingChanges>,
+    last_sent: VecMap<DataType, Instant>,
+}
+
+struct PendingChanges {
+    changed: VecMap<Id, State>,
+    since: Instant,
+    coalesced: u64,
+}
+
+pub struct ThrottledPush {
+    pub changes: WebSocketStateChange,
+    pub latency: Duration,
+    pub coalesced: u64,
+}
+
+impl ThrottledChanges {
+    pub fn new(throttle: Duration, type_throttle: VecMap<DataType, Duration>) -> Self {
+        ThrottledChanges {
+            throttle,
+            type_throttle,
+            pending: VecMap::new(),
+            last_sent: VecMap::new(),
+        }
+    }
+
+    // Changes the interval of the types without a configured throttle
+    pub fn set_throttle(&mut self, throttle: Duration) {
+        self.throttle = throttle;
+    }
+
+    pub fn throttle(&self, data_type: DataType) -> Duration {
+        self.type_throttle
+            .get(&data_type)
+            .copied()
+            .unwrap_or(self.throttle)
+    }
+
+    pub fn insert(&mut self, account_id: Id, data_type: DataType, state: State, now: Instant) {
+        if let Some(pending) = self.pending.get_mut(&data_type) {
+            pending.changed.set(account_id, state);
+            pending.coalesced += 1;
+        } else {
+            let mut changed = VecMap::new();
+            changed.append(account_id, state);
+            self.pending.append(
+                data_type,
+                PendingChanges {
+                    changed,
+                    since: now,
+                    coalesced: 0,
+                },
+            );
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pending.is_empty()
+    }
+
+    // Time left until the first queued type is due, zero if one already is
+    pub fn next_deadline(&self, now: Instant) -> Option<Duration> {
+        self.pending
+            .keys()
+            .map(|data_type| self.deadline(*data_type, now))
+            .min()
+    }
+
+    // Removes the types that are due and merges them into a single state change
+    pub fn take_due(&mut self, now: Instant) -> Option<ThrottledPush> {
+        let mut push: Option<ThrottledPush> = None;
+        let mut idx = 0;
+        while idx < self.pending.len() {
+            let data_type = self.pending.k[idx];
+            if self.deadline(data_type, now) > Duration::ZERO {
+                idx += 1;
+                continue;
+            }
+            self.pending.k.remove(idx);
+            let pending = self.pending.v.remove(idx);
+            self.last_sent.set(data_type, now);
+
+            let push = push.get_or_insert_with(|| ThrottledPush {
+                changes: WebSocketStateChange::new(None),
+                latency: Duration::ZERO,
+                coalesced: 0,
+            });
+            for (account_id, state) in pending.changed.k.into_iter().zip(pending.changed.v) {
+                push.changes
+                    .changed
+                    .get_mut_or_insert(account_id)
+                    .set(data_type, state);
+            }
+            push.latency = push
+                .latency
+                .max(now.saturating_duration_since(pending.since));
+            push.coalesced += pending.coalesced;
+        }
+
+        push
+    }
+
+    fn deadline(&self, data_type: DataType, now: Instant) -> Duration {
+        self.last_sent
+            .get(&data_type)
+            .map_or(Duration::ZERO, |last_sent| {
+                (*last_sent + self.throttle(data_type)).saturating_duration_since(now)
+            })
+    }
+}
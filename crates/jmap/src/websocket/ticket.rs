@@ -0,0 +1,182 @@
+/*
+ * Copyright (c) 2023 Stalwart Labs Ltd.
+ *
+ * This file is part of Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use std::{
+    sync::Arc,
+    time::{Instant, SystemTime},
+};
+
+use hyper::{header::CONTENT_TYPE, Method};
+use jmap_proto::{
+    error::request::{RequestError, RequestLimitError},
+    request::websocket::{
+        WebSocketBlobAvailable, WebSocketBlobAvailableType, WebSocketUploadTicket,
+        WebSocketUploadTicketRequest, WebSocketUploadTicketType,
+    },
+    types::{date::UTCDate, id::Id},
+};
+use store::rand::{distributions::Alphanumeric, thread_rng, Rng};
+use tokio::sync::mpsc;
+use utils::map::ttl_dashmap::TtlMap;
+
+use crate::{
+    api::{
+        http::{fetch_body, ToHttpResponse},
+        HttpRequest, HttpResponse,
+    },
+    auth::AccessToken,
+    JMAP,
+};
+
+const TICKET_LEN: usize = 40;
+
+pub struct UploadTicket {
+    pub account_id: Id,
+    pub primary_id: u32,
+    pub base_url: String,
+    pub request_id: Option<String>,
+    pub notify: mpsc::Sender<String>,
+}
+
+impl JMAP {
+    pub fn issue_upload_ticket(
+        &self,
+        request: WebSocketUploadTicketRequest,
+        access_token: &AccessToken,
+        base_url: &str,
+        notify: mpsc::Sender<String>,
+    ) -> Result<WebSocketUploadTicket, RequestError> {
+        if !access_token.is_member(request.account_id.document_id()) {
+            return Err(RequestError::forbidden());
+        }
+
+        let ticket = thread_rng()
+            .sample_iter(Alphanumeric)
+            .take(TICKET_LEN)
+            .map(char::from)
+            .collect::<String>();
+        let ttl = self.config.web_socket_upload_ticket_ttl;
+        self.upload_tickets.insert_with_ttl(
+            ticket.clone(),
+            Arc::new(UploadTicket {
+                account_id: request.account_id,
+                primary_id: access_token.primary_id(),
+                base_url: base_url.to_string(),
+                request_id: request.id.clone(),
+                notify,
+            }),
+            Instant::now() + ttl,
+        );
+
+        Ok(WebSocketUploadTicket {
+            type_: WebSocketUploadTicketType::UploadTicket,
+            account_id: request.account_id,
+            upload_url: format!(
+                "{}/jmap/upload/{}/?ticket={}",
+                base_url, request.account_id, ticket
+            ),
+            ticket,
+            expires: UTCDate::from_timestamp(
+                (SystemTime::now()
+                    .duration_since(SystemTime::UNIX_EPOCH)
+                    .map_or(0, |d| d.as_secs())
+                    + ttl.as_secs()) as i64,
+            ),
+            request_id: request.id,
+        })
+    }
+
+    // Handles unauthenticated uploads that carry a one-time ticket issued over
+    // a WebSocket, returns None when the request is not a ticket upload.
+    pub async fn handle_ticket_upload(&self, req: &mut HttpRequest) -> Option<HttpResponse> {
+        if req.method() != Method::POST {
+            return None;
+        }
+        let account_id = req
+            .uri()
+            .path()
+            .strip_prefix("/jmap/upload/")?
+            .split('/')
+            .next()
+            .and_then(|p| Id::from_bytes(p.as_bytes()))?;
+        let ticket = req.uri().query().and_then(|q| {
+            form_urlencoded::parse(q.as_bytes())
+                .find(|(k, _)| k == "ticket")
+                .map(|(_, v)| v.into_owned())
+        })?;
+
+        // Tickets can only be redeemed once
+        let ticket = match self.upload_tickets.remove_with_ttl(&ticket) {
+            Some(ticket) if ticket.account_id == account_id => ticket,
+            _ => return Some(RequestError::unauthorized().into_http_response()),
+        };
+        let access_token = match self.get_cached_access_token(ticket.primary_id).await {
+            Some(access_token) => access_token,
+            None => return Some(RequestError::unauthorized().into_http_response()),
+        };
+        let bytes = match fetch_body(req, self.config.upload_max_size, &access_token).await {
+            Some(bytes) => bytes,
+            None => {
+                return Some(
+                    RequestError::limit(RequestLimitError::SizeUpload).into_http_response(),
+                )
+            }
+        };
+
+        Some(
+            match self
+                .blob_upload(
+                    account_id,
+                    req.headers()
+                        .get(CONTENT_TYPE)
+                        .and_then(|h| h.to_str().ok())
+                        .unwrap_or("application/octet-stream"),
+                    &bytes,
+                    access_token,
+                )
+                .await
+            {
+                Ok(response) => {
+                    // Let the WebSocket connection know that the blob can be downloaded
+                    let _ = ticket.notify.try_send(
+                        WebSocketBlobAvailable {
+                            type_: WebSocketBlobAvailableType::BlobAvailable,
+                            account_id: response.account_id,
+                            download_url: format!(
+                                "{}/jmap/download/{}/{}/blob",
+                                ticket.base_url, response.account_id, response.blob_id
+                            ),
+                            blob_id: response.blob_id.clone(),
+                            c_type: response.c_type.clone(),
+                            size: response.size,
+                            request_id: ticket.request_id.clone(),
+                        }
+                        .to_json(),
+                    );
+                    response.into_http_response()
+                }
+                Err(err) => err.into_http_response(),
+            },
+        )
+    }
+}
@@ -33,7 +33,11 @@ use utils::listener::ServerInstance;
 
 use crate::{
     api::{http::ToHttpResponse, HttpRequest, HttpResponse},
-    auth::AccessToken,
+    auth::{authenticate::BearerToken, AccessToken},
+    websocket::{
+        deflate::{DeflateParams, DeflateStream},
+        encoding::WebSocketEncoding,
+    },
     JMAP,
 };
 
@@ -79,16 +83,69 @@ pub async fn upgrade_websocket_connection(
         }
     };
 
+    // Negotiate permessage-deflate if the client offered it
+    let deflate = if jmap.config.web_socket_compression {
+        DeflateParams::negotiate(
+            headers
+                .get_all("Sec-WebSocket-Extensions")
+                .iter()
+                .filter_map(|h| h.to_str().ok()),
+        )
+    } else {
+        None
+    };
+    let extensions = deflate.map(|params| params.to_header());
+
+    // Negotiate the message encoding, JSON unless MessagePack is preferred by the client
+    let encoding = WebSocketEncoding::negotiate(
+        headers
+            .get_all("Sec-WebSocket-Protocol")
+            .iter()
+            .filter_map(|h| h.to_str().ok()),
+    );
+
+    // Bearer tokens are validated again while the connection is open
+    let bearer_token = match headers
+        .get(hyper::header::AUTHORIZATION)
+        .and_then(|h| h.to_str().ok())
+        .and_then(|h| h.split_once(' '))
+    {
+        Some((mechanism, token)) if mechanism.eq_ignore_ascii_case("bearer") => {
+            let token = token.trim();
+            if jmap
+                .validate_access_token("access_token", token)
+                .await
+                .is_ok()
+            {
+                Some(BearerToken::Issued(token.to_string()))
+            } else if jmap.directory.verifies_bearer_tokens() {
+                Some(BearerToken::Directory(token.to_string()))
+            } else {
+                None
+            }
+        }
+        _ => None,
+    };
+
     // Spawn WebSocket connection
     tokio::spawn(async move {
         // Upgrade connection
         match hyper::upgrade::on(req).await {
             Ok(upgraded) => {
                 jmap.handle_websocket_stream(
-                    WebSocketStream::from_raw_socket(TokioIo::new(upgraded), Role::Server, None)
-                        .await,
+                    WebSocketStream::from_raw_socket(
+                        DeflateStream::new(TokioIo::new(upgraded), deflate).with_compression(
+                            jmap.config.web_socket_compression_level,
+                            jmap.config.web_socket_compression_threshold,
+                        ),
+                        Role::Server,
+                        None,
+                    )
+                    .await,
                     access_token,
                     instance,
+                    encoding,
+                    bearer_token,
                 )
                 .await;
             }
@@ -98,12 +155,16 @@ pub async fn upgrade_websocket_connection(
         }
     });
 
-    Response::builder()
+    let mut response = Response::builder()
         .status(hyper::StatusCode::SWITCHING_PROTOCOLS)
         .header(hyper::header::CONNECTION, "upgrade")
         .header(hyper::header::UPGRADE, "websocket")
         .header("Sec-WebSocket-Accept", &derived_key)
-        .header("Sec-WebSocket-Protocol", "jmap")
+        .header("Sec-WebSocket-Protocol", encoding.protocol());
+    if let Some(extensions) = extensions {
+        response = response.header("Sec-WebSocket-Extensions", extensions);
+    }
+    response
         .body(
             Full::new(Bytes::from("Switching to WebSocket protocol"))
                 .map_err(|never| match never {})
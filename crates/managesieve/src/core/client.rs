@@ -224,7 +224,20 @@ impl ValidateRequest for Request<Command> {
             | Command::CheckScript
             | Command::Unauthenticate => {
                 if let State::Authenticated { access_token, .. } = state {
-                    if imap
+                    // Accounts with an expired password are limited to read-only access
+                    if access_token.password_expired
+                        && matches!(
+                            self.command,
+                            Command::PutScript
+                                | Command::SetActive
+                                | Command::DeleteScript
+                                | Command::RenameScript
+                        )
+                    {
+                        Err(StatusResponse::no(
+                            "Password expired, the account is read-only until it is changed.",
+                        ))
+                    } else if imap
                         .get_authenticated_limiter(access_token.primary_id())
                         .lock()
                         .request_limiter
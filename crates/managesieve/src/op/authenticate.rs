@@ -23,6 +23,7 @@
 
 use std::sync::Arc;
 
+use directory::Protocol;
 use imap::op::authenticate::{decode_challenge_oauth, decode_challenge_plain};
 use imap_proto::{
     protocol::authenticate::Mechanism,
@@ -88,32 +89,35 @@ impl<T: AsyncRead + AsyncWrite + IsTls> Session<T> {
 
         // Authenticate
         let access_token = match credentials {
-            Credentials::Plain { username, secret } | Credentials::XOauth2 { username, secret } => {
+            Credentials::Plain { username, secret } => {
                 self.jmap
                     .authenticate_plain(&username, &secret, &self.remote_addr)
                     .await
             }
             Credentials::OAuthBearer { token } => {
-                match self
-                    .jmap
-                    .validate_access_token("access_token", &token)
+                self.jmap
+                    .authenticate_bearer(&token, &self.remote_addr)
+                    .await
+            }
+            credentials @ Credentials::XOauth2 { .. } => {
+                self.jmap
+                    .authenticate_credentials(&credentials, &self.remote_addr)
                     .await
-                {
-                    Ok((account_id, _, _)) => self.jmap.get_access_token(account_id).await,
-                    Err(err) => {
-                        tracing::debug!(
-                            parent: &self.span,
-                            context = "authenticate",
-                            err = err,
-                            "Failed to validate access token."
-                        );
-                        None
-                    }
-                }
             }
         };
 
         if let Some(access_token) = access_token {
+            if !access_token.is_protocol_enabled(Protocol::ManageSieve) {
+                tracing::debug!(parent: &self.span,
+                    event = "protocol-disabled",
+                    account = access_token.name,
+                    "ManageSieve is not enabled for this account.",
+                );
+                return Ok(
+                    StatusResponse::no("Protocol not enabled for this account.").into_bytes()
+                );
+            }
+
             // Enforce concurrency limits
             let in_flight = self
                 .imap
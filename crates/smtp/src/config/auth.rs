@@ -35,8 +35,8 @@ use utils::config::{
 
 use super::{
     if_block::ConfigIf, ArcAuthConfig, ArcSealer, ConfigContext, DkimAuthConfig,
-    DkimCanonicalization, DkimSigner, DmarcAuthConfig, EnvelopeKey, IfBlock, IpRevAuthConfig,
-    MailAuthConfig, SpfAuthConfig, VerifyStrategy,
+    DkimCanonicalization, DkimSigner, DmarcAuthConfig, DomainSigner, EnvelopeKey, IfBlock,
+    IpRevAuthConfig, MailAuthConfig, SpfAuthConfig, VerifyStrategy,
 };
 
 pub trait ConfigAuth {
@@ -74,6 +74,20 @@ impl ConfigAuth for Config {
                     )?
                     .unwrap_or_default()
                     .map_if_block(&ctx.signers, "auth.dkim.sign", "signature")?,
+                signers: ctx
+                    .signers
+                    .iter()
+                    .filter_map(|(id, signer)| {
+                        Some((
+                            id.to_string(),
+                            DomainSigner {
+                                domain: self.value(("signature", id, "domain"))?.to_lowercase(),
+                                selector: self.value(("signature", id, "selector"))?.to_string(),
+                                signer: signer.clone(),
+                            },
+                        ))
+                    })
+                    .collect(),
             },
             arc: ArcAuthConfig {
                 verify: self
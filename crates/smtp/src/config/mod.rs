@@ -266,6 +266,10 @@ pub struct Data {
     pub add_auth_results: IfBlock<bool>,
     pub add_message_id: IfBlock<bool>,
     pub add_date: IfBlock<bool>,
+
+    // Sender rewriting
+    pub srs_secret: Option<String>,
+    pub srs_max_age: Duration,
 }
 
 pub struct Pipe {
@@ -312,6 +316,7 @@ pub struct SessionThrottle {
     pub rcpt_to: Vec<Throttle>,
 }
 
+#[derive(Clone)]
 pub struct RelayHost {
     pub address: String,
     pub port: u16,
@@ -333,6 +338,7 @@ pub struct QueueConfig {
     // Outbound
     pub hostname: IfBlock<String>,
     pub next_hop: IfBlock<Option<RelayHost>>,
+    pub relay_hosts: AHashMap<String, RelayHost>,
     pub max_mx: IfBlock<usize>,
     pub max_multihomed: IfBlock<usize>,
     pub ip_strategy: IfBlock<IpLookupStrategy>,
@@ -496,6 +502,13 @@ pub enum ArcSealer {
 pub struct DkimAuthConfig {
     pub verify: IfBlock<VerifyStrategy>,
     pub sign: IfBlock<Vec<MaybeDynValue<DkimSigner>>>,
+    pub signers: AHashMap<String, DomainSigner>,
+}
+
+pub struct DomainSigner {
+    pub domain: String,
+    pub selector: String,
+    pub signer: Arc<DkimSigner>,
 }
 
 pub struct ArcAuthConfig {
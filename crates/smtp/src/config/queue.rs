@@ -138,6 +138,11 @@ impl ConfigQueue for Config {
                     .unwrap_or_else(|| IfBlock::new(Vec::new())),
             },
             next_hop: next_hop.into_relay_host(ctx)?,
+            relay_hosts: ctx
+                .hosts
+                .iter()
+                .map(|(id, host)| (id.to_string(), host.into()))
+                .collect(),
             tls: QueueOutboundTls {
                 dane: self
                     .parse_if_block("queue.outbound.tls.dane", ctx, &mx_envelope_keys)?
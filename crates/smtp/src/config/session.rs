@@ -426,6 +426,10 @@ impl ConfigSession for Config {
             add_date: self
                 .parse_if_block("session.data.add-headers.date", ctx, &available_keys)?
                 .unwrap_or_else(|| IfBlock::new(true)),
+            srs_secret: self
+                .value("session.data.srs.secret")
+                .map(|secret| secret.to_string()),
+            srs_max_age: self.property_or_static("session.data.srs.max-age", "21d")?,
             pipe_commands: self.parse_pipes(ctx, &available_keys)?,
             milters: self.parse_milters(ctx, &available_keys)?,
         })
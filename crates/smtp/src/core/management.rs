@@ -258,6 +258,13 @@ impl SMTP {
                         .authenticate(&Credentials::Plain { username, secret })
                         .await
                     {
+                        Ok(Some(principal)) if principal.requires_second_factor() => {
+                            tracing::debug!(
+                                context = "management",
+                                event = "auth-error",
+                                "Second factor required."
+                            );
+                        }
                         Ok(Some(principal)) if principal.typ == Type::Superuser => {
                             is_authenticated = true;
                         }
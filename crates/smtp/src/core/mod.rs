@@ -210,6 +210,7 @@ pub struct SessionAddress {
     pub domain: String,
     pub flags: u64,
     pub dsn_info: Option<String>,
+    pub relay: Option<String>,
 }
 
 #[derive(Debug, Default)]
@@ -227,6 +228,8 @@ pub struct SessionParameters {
     pub auth_errors_max: usize,
     pub auth_errors_wait: Duration,
     pub auth_plain_text: bool,
+    pub auth_max_message_size: Option<usize>,
+    pub auth_max_recipients: Option<usize>,
 
     // Rcpt parameters
     pub rcpt_errors_max: usize,
@@ -428,6 +431,8 @@ impl Session<NullIo> {
                 auth_errors_max: Default::default(),
                 auth_errors_wait: Default::default(),
                 auth_plain_text: false,
+                auth_max_message_size: None,
+                auth_max_recipients: None,
                 rcpt_errors_max: Default::default(),
                 rcpt_errors_wait: Default::default(),
                 rcpt_max: Default::default(),
@@ -77,5 +77,13 @@ impl<T: AsyncRead + AsyncWrite> Session<T> {
             .max_message_size
             .eval(self)
             .await;
+
+        // Apply the limits of the authenticated account, if any
+        if let Some(rcpt_max) = self.params.auth_max_recipients {
+            self.params.rcpt_max = rcpt_max;
+        }
+        if let Some(max_message_size) = self.params.auth_max_message_size {
+            self.params.max_message_size = max_message_size;
+        }
     }
 }
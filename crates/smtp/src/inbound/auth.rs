@@ -21,6 +21,7 @@
  * for more details.
 */
 
+use directory::Protocol;
 use mail_parser::decoders::base64::base64_decode;
 use mail_send::Credentials;
 use smtp_proto::{IntoString, AUTH_LOGIN, AUTH_OAUTHBEARER, AUTH_PLAIN, AUTH_XOAUTH2};
@@ -180,17 +181,57 @@ impl<T: AsyncWrite + AsyncRead + Unpin> Session<T> {
                 | Credentials::XOauth2 { username, .. }
                 | Credentials::OAuthBearer { token: username } => username.to_string(),
             };
-            if let Ok(is_authenticated) =
-                lookup.authenticate(&credentials).await.map(|r| r.is_some())
-            {
+            if let Ok(principal) = lookup.authenticate(&credentials).await {
                 tracing::debug!(
                     parent: &self.span,
                     context = "auth",
                     event = "authenticate",
-                    result = if is_authenticated {"success"} else {"failed"}
+                    result = if principal.is_some() {"success"} else {"failed"}
                 );
-                return if is_authenticated {
+                return if let Some(principal) = principal {
+                    if principal.password_expired {
+                        // Expired passwords may still read mail but are not allowed to submit
+                        tracing::debug!(
+                            parent: &self.span,
+                            context = "auth",
+                            event = "password-expired",
+                            account = authenticated_as,
+                            "Submission refused, password expired."
+                        );
+                        return self
+                            .auth_error(b"535 5.7.8 Password expired, please change it before sending mail.\r\n")
+                            .await;
+                    }
+                    if principal.requires_second_factor() {
+                        // SMTP AUTH has no way of prompting for a second factor
+                        tracing::debug!(
+                            parent: &self.span,
+                            context = "auth",
+                            event = "second-factor-required",
+                            account = authenticated_as,
+                            "Submission refused, a second factor is required for this account."
+                        );
+                        return self
+                            .auth_error(
+                                b"535 5.7.8 A second factor is required for this account.\r\n",
+                            )
+                            .await;
+                    }
+                    if !principal.is_protocol_enabled(Protocol::Smtp) {
+                        tracing::debug!(
+                            parent: &self.span,
+                            context = "auth",
+                            event = "protocol-disabled",
+                            account = authenticated_as,
+                            "Submission refused, SMTP not enabled for this account."
+                        );
+                        return self
+                            .auth_error(b"535 5.7.8 Protocol not enabled for this account.\r\n")
+                            .await;
+                    }
                     self.data.authenticated_as = authenticated_as;
+                    self.params.auth_max_message_size = principal.max_message_size;
+                    self.params.auth_max_recipients = principal.max_recipients;
                     self.eval_post_auth_params().await;
                     self.write(b"235 2.7.0 Authentication succeeded.\r\n")
                         .await?;
@@ -29,6 +29,7 @@ use std::{
     time::{Duration, Instant, SystemTime},
 };
 
+use directory::{Domain, SenderRewrite};
 use mail_auth::{
     common::{headers::HeaderWriter, verify::VerifySignature},
     dmarc, AuthenticatedMessage, AuthenticationResults, DkimResult, DmarcResult, ReceivedSpf,
@@ -44,13 +45,14 @@ use tokio::{
 };
 
 use crate::{
+    config::DkimSigner,
     core::{Session, SessionAddress, State},
     queue::{self, Message, SimpleEnvelope},
     reporting::analysis::AnalyzeReport,
     scripts::{ScriptModification, ScriptResult},
 };
 
-use super::{AuthResult, IsTls};
+use super::{srs::srs_encode, AuthResult, IsTls};
 
 impl<T: AsyncWrite + AsyncRead + IsTls + Unpin> Session<T> {
     pub async fn queue_message(&mut self) -> Cow<'static, [u8]> {
@@ -452,6 +454,10 @@ impl<T: AsyncWrite + AsyncRead + IsTls + Unpin> Session<T> {
                         .as_ref()
                         .map(|a| a.as_str())
                         .unwrap_or_default(),
+                )
+                .set_variable(
+                    "spam_policy",
+                    self.recipient_spam_policy().await.unwrap_or_default(),
                 );
 
             let modifications = match self.run_script(script.clone(), params).await {
@@ -554,6 +560,15 @@ impl<T: AsyncWrite + AsyncRead + IsTls + Unpin> Session<T> {
             headers.extend_from_slice(b"\r\n");
         }
 
+        // Apply per-domain sender rewriting
+        let sender_domain = self.sender_domain(&message.return_path_domain).await;
+        if let Some(rewrite) = sender_domain
+            .as_ref()
+            .and_then(|domain| domain.sender_rewrite.as_ref())
+        {
+            self.rewrite_sender(&mut message, rewrite);
+        }
+
         // Add Return-Path
         if *dc.add_return_path.eval(self).await {
             headers.extend_from_slice(b"Return-Path: <");
@@ -563,7 +578,15 @@ impl<T: AsyncWrite + AsyncRead + IsTls + Unpin> Session<T> {
 
         // DKIM sign
         let raw_message = edited_message.unwrap_or(raw_message);
-        for signer in ac.dkim.sign.eval_and_capture(self).await.into_value(self) {
+        let signers = if let Some(signer) = sender_domain
+            .as_ref()
+            .and_then(|domain| self.domain_signer(domain))
+        {
+            vec![signer]
+        } else {
+            ac.dkim.sign.eval_and_capture(self).await.into_value(self)
+        };
+        for signer in signers {
             match signer.sign_chained(&[headers.as_ref(), &raw_message]) {
                 Ok(signature) => {
                     signature.write_header(&mut headers);
@@ -634,12 +657,16 @@ impl<T: AsyncWrite + AsyncRead + IsTls + Unpin> Session<T> {
 
         // Add recipients
         let future_release = Duration::from_secs(self.data.future_release);
-        rcpt_to.sort_unstable();
+        // Recipients of the same domain can be routed to different hosts by the directory,
+        // so each (domain, relay) pair is queued as a separate domain
+        rcpt_to.sort_unstable_by(|a, b| {
+            (&a.domain, &a.relay, &a.address_lcase).cmp(&(&b.domain, &b.relay, &b.address_lcase))
+        });
         for rcpt in rcpt_to {
             if message
                 .domains
                 .last()
-                .map_or(true, |d| d.domain != rcpt.domain)
+                .map_or(true, |d| d.domain != rcpt.domain || d.relay != rcpt.relay)
             {
                 let envelope = SimpleEnvelope::new(message.as_ref(), &rcpt.domain);
 
@@ -694,6 +721,7 @@ impl<T: AsyncWrite + AsyncRead + IsTls + Unpin> Session<T> {
                     status: queue::Status::Scheduled,
                     domain: rcpt.domain,
                     disable_tls: false,
+                    relay: rcpt.relay,
                     changed: false,
                 });
             }
@@ -743,6 +771,130 @@ impl<T: AsyncWrite + AsyncRead + IsTls + Unpin> Session<T> {
         }
     }
 
+    async fn sender_domain(&self, domain: &str) -> Option<Domain> {
+        if self.data.authenticated_as.is_empty() || domain.is_empty() {
+            return None;
+        }
+
+        match self.params.auth_directory.as_ref()?.domain(domain).await {
+            Ok(domain) => domain,
+            Err(_) => {
+                tracing::debug!(parent: &self.span,
+                    context = "directory",
+                    event = "error",
+                    domain = domain,
+                    "Failed to obtain sender domain from directory.");
+                None
+            }
+        }
+    }
+
+    // Returns the spam policy shared by all local recipients, falling back to the
+    // recipient domain's policy. Recipients with different policies, or without
+    // one, leave the decision to the script defaults.
+    async fn recipient_spam_policy(&self) -> Option<String> {
+        let directory = self
+            .core
+            .session
+            .config
+            .rcpt
+            .directory
+            .eval_and_capture(self)
+            .await
+            .into_value(self)?;
+        let mut result = None;
+
+        for rcpt in &self.data.rcpt_to {
+            if rcpt.relay.is_some() || !directory.is_local_domain(&rcpt.domain).await.ok()? {
+                continue;
+            }
+            let mut policy = None;
+            if let Some(name) = directory
+                .names_by_email(&rcpt.address_lcase)
+                .await
+                .ok()?
+                .into_iter()
+                .next()
+            {
+                policy = directory
+                    .principal(&name)
+                    .await
+                    .ok()?
+                    .and_then(|principal| principal.spam_policy);
+            }
+            if policy.is_none() {
+                policy = directory
+                    .domain(&rcpt.domain)
+                    .await
+                    .ok()?
+                    .and_then(|domain| domain.spam_policy);
+            }
+
+            match (&result, policy) {
+                (_, None) => return None,
+                (None, policy) => result = policy,
+                (Some(result), Some(policy)) if result != &policy => return None,
+                _ => (),
+            }
+        }
+
+        result
+    }
+
+    fn domain_signer(&self, domain: &Domain) -> Option<Arc<DkimSigner>> {
+        if domain.dkim_key.is_none() && domain.dkim_selector.is_none() {
+            return None;
+        }
+        let signer = self.core.mail_auth.dkim.domain_signer(domain);
+        if signer.is_none() {
+            tracing::debug!(parent: &self.span,
+                context = "dkim",
+                event = "sign-failed",
+                domain = domain.name,
+                key = domain.dkim_key.as_deref().unwrap_or_default(),
+                selector = domain.dkim_selector.as_deref().unwrap_or_default(),
+                "Signature not found for domain.");
+        }
+        signer
+    }
+
+    fn rewrite_sender(&self, message: &mut Message, rewrite: &SenderRewrite) {
+        if message.return_path.is_empty() {
+            return;
+        }
+
+        let return_path = match rewrite {
+            SenderRewrite::Address(address) => address.clone(),
+            SenderRewrite::Srs => {
+                if let Some(secret) = &self.core.session.config.data.srs_secret {
+                    srs_encode(secret, &message.return_path, &self.instance.hostname)
+                } else {
+                    tracing::debug!(parent: &self.span,
+                        context = "srs",
+                        event = "error",
+                        domain = message.return_path_domain,
+                        "SRS rewriting requested but no SRS secret is configured.");
+                    return;
+                }
+            }
+        };
+
+        tracing::debug!(parent: &self.span,
+            context = "data",
+            event = "rewrite-sender",
+            from = message.return_path,
+            to = return_path,
+            "Rewrote envelope sender.");
+
+        message.return_path_lcase = return_path.to_lowercase();
+        message.return_path_domain = message
+            .return_path_lcase
+            .rsplit_once('@')
+            .map(|(_, domain)| domain.to_string())
+            .unwrap_or_default();
+        message.return_path = return_path;
+    }
+
     fn write_received(&self, headers: &mut Vec<u8>, id: u64) {
         headers.extend_from_slice(b"Received: from ");
         headers.extend_from_slice(self.data.helo_domain.as_bytes());
@@ -111,6 +111,7 @@ impl<T: AsyncWrite + AsyncRead + Unpin + IsTls> Session<T> {
             domain,
             flags: from.flags,
             dsn_info: from.env_id,
+            relay: None,
         }
         .into();
 
@@ -220,7 +221,13 @@ impl<T: AsyncWrite + AsyncRead + Unpin + IsTls> Session<T> {
                     .await;
             }
         }
-        if from.size > 0 && from.size > *config_data.max_message_size.eval(self).await {
+        if from.size > 0
+            && from.size
+                > match self.params.auth_max_message_size {
+                    Some(max_message_size) => max_message_size,
+                    None => *config_data.max_message_size.eval(self).await,
+                }
+        {
             self.data.mail_from = None;
             return self
                 .write(b"552 5.3.4 Message too big for system.\r\n")
@@ -265,6 +265,7 @@ impl SessionData {
                         address: sender,
                         flags: 0,
                         dsn_info: None,
+                        relay: None,
                     };
                     if !args.is_empty() {
                         args.push('\n');
@@ -300,6 +301,7 @@ impl SessionData {
                             address: recipient,
                             flags: 0,
                             dsn_info: None,
+                            relay: None,
                         };
                         if !args.is_empty() {
                             args.push('\n');
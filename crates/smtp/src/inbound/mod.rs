@@ -21,6 +21,9 @@
  * for more details.
 */
 
+use std::sync::Arc;
+
+use directory::Domain;
 use mail_auth::{
     arc::ArcSet, dkim::Signature, dmarc::Policy, ArcOutput, AuthenticatedMessage,
     AuthenticationResults, DkimResult, DmarcResult, IprevResult, SpfResult,
@@ -28,7 +31,7 @@ use mail_auth::{
 use tokio::net::TcpStream;
 use tokio_rustls::server::TlsStream;
 
-use crate::config::{ArcSealer, DkimSigner};
+use crate::config::{ArcSealer, DkimAuthConfig, DkimSigner};
 
 pub mod auth;
 pub mod data;
@@ -38,6 +41,7 @@ pub mod milter;
 pub mod rcpt;
 pub mod session;
 pub mod spawn;
+pub mod srs;
 pub mod vrfy;
 
 pub trait IsTls {
@@ -133,6 +137,26 @@ impl DkimSigner {
     }
 }
 
+impl DkimAuthConfig {
+    // Resolves the signer assigned to a domain by the directory. The key id names
+    // one of the configured signatures, while the selector only has to match the
+    // selector of the signature used, or of one configured for the same domain.
+    pub fn domain_signer(&self, domain: &Domain) -> Option<Arc<DkimSigner>> {
+        let selector = domain.dkim_selector.as_deref();
+        if let Some(key_id) = domain.dkim_key.as_deref() {
+            self.signers
+                .get(key_id)
+                .filter(|signer| selector.map_or(true, |selector| selector == signer.selector))
+        } else {
+            let selector = selector?;
+            self.signers.values().find(|signer| {
+                signer.selector == selector && signer.domain.eq_ignore_ascii_case(&domain.name)
+            })
+        }
+        .map(|signer| signer.signer.clone())
+    }
+}
+
 pub trait AuthResult {
     fn as_str(&self) -> &'static str;
 }
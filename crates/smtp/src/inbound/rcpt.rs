@@ -21,6 +21,7 @@
  * for more details.
 */
 
+use directory::{DeliveryPolicy, Directory, RecipientRoute};
 use smtp_proto::{
     RcptTo, RCPT_NOTIFY_DELAY, RCPT_NOTIFY_FAILURE, RCPT_NOTIFY_NEVER, RCPT_NOTIFY_SUCCESS,
 };
@@ -32,7 +33,10 @@ use crate::{
     scripts::{ScriptModification, ScriptResult},
 };
 
-use super::IsTls;
+use super::{
+    srs::{is_srs_address, srs_decode},
+    IsTls,
+};
 
 impl<T: AsyncWrite + AsyncRead + Unpin + IsTls> Session<T> {
     pub async fn handle_rcpt_to(&mut self, to: RcptTo<String>) -> Result<(), ()> {
@@ -65,12 +69,50 @@ impl<T: AsyncWrite + AsyncRead + Unpin + IsTls> Session<T> {
 
         // Build RCPT
         let address_lcase = to.address.to_lowercase();
-        let rcpt = SessionAddress {
+        let mut rcpt = SessionAddress {
             domain: address_lcase.domain_part().to_string(),
             address_lcase,
             address: to.address,
             flags: to.flags,
             dsn_info: to.orcpt,
+            relay: None,
+        };
+
+        // Reverse SRS rewritten envelope senders
+        let srs_address = match &self.core.session.config.data.srs_secret {
+            Some(secret)
+                if rcpt.domain.eq_ignore_ascii_case(&self.instance.hostname)
+                    && is_srs_address(&rcpt.address_lcase) =>
+            {
+                let max_age = self.core.session.config.data.srs_max_age.as_secs() / 86400;
+                Some(srs_decode(secret, &rcpt.address, max_age))
+            }
+            _ => None,
+        };
+        let is_srs = match srs_address {
+            Some(Some(address)) => {
+                tracing::debug!(parent: &self.span,
+                    context = "srs",
+                    event = "reverse",
+                    from = &rcpt.address,
+                    to = &address,
+                    "Reversed SRS address.");
+
+                rcpt.address_lcase = address.to_lowercase();
+                rcpt.domain = rcpt.address_lcase.domain_part().to_string();
+                rcpt.address = address;
+                true
+            }
+            Some(None) => {
+                tracing::debug!(parent: &self.span,
+                    context = "srs",
+                    event = "error",
+                    address = &rcpt.address_lcase,
+                    "Invalid or expired SRS address.");
+
+                return self.rcpt_error(b"550 5.1.1 Invalid SRS address.\r\n").await;
+            }
+            None => false,
         };
 
         if self.data.rcpt_to.contains(&rcpt) {
@@ -166,8 +208,25 @@ impl<T: AsyncWrite + AsyncRead + Unpin + IsTls> Session<T> {
         {
             if let Ok(is_local_domain) = directory.is_local_domain(&rcpt.domain).await {
                 if is_local_domain {
-                    if let Ok(is_local_address) = directory.rcpt(&rcpt.address_lcase).await {
-                        if !is_local_address {
+                    match directory.route(&rcpt.address_lcase).await {
+                        Ok(RecipientRoute::Local) => {
+                            if let Some(response) =
+                                self.apply_delivery_policy(directory.as_ref()).await
+                            {
+                                self.data.rcpt_to.pop();
+                                return self.rcpt_error(response).await;
+                            }
+                        }
+                        Ok(RecipientRoute::Relay(host)) => {
+                            tracing::debug!(parent: &self.span,
+                                            context = "rcpt",
+                                            event = "relay",
+                                            address = &rcpt.address_lcase,
+                                            host = &host);
+
+                            self.data.rcpt_to.last_mut().unwrap().relay = host.into();
+                        }
+                        Ok(RecipientRoute::Reject) => {
                             tracing::debug!(parent: &self.span,
                                             context = "rcpt", 
                                             event = "error",
@@ -179,19 +238,20 @@ impl<T: AsyncWrite + AsyncRead + Unpin + IsTls> Session<T> {
                                 .rcpt_error(b"550 5.1.2 Mailbox does not exist.\r\n")
                                 .await;
                         }
-                    } else {
-                        tracing::debug!(parent: &self.span,
-                            context = "rcpt", 
-                            event = "error",
-                            address = &rcpt.address_lcase,
-                            "Temporary address verification failure.");
+                        Err(_) => {
+                            tracing::debug!(parent: &self.span,
+                                context = "rcpt", 
+                                event = "error",
+                                address = &rcpt.address_lcase,
+                                "Temporary address verification failure.");
 
-                        self.data.rcpt_to.pop();
-                        return self
-                            .write(b"451 4.4.3 Unable to verify address at this time.\r\n")
-                            .await;
+                            self.data.rcpt_to.pop();
+                            return self
+                                .write(b"451 4.4.3 Unable to verify address at this time.\r\n")
+                                .await;
+                        }
                     }
-                } else if !*self.core.session.config.rcpt.relay.eval(self).await {
+                } else if !is_srs && !*self.core.session.config.rcpt.relay.eval(self).await {
                     tracing::debug!(parent: &self.span,
                         context = "rcpt", 
                         event = "error",
@@ -213,7 +273,7 @@ impl<T: AsyncWrite + AsyncRead + Unpin + IsTls> Session<T> {
                     .write(b"451 4.4.3 Unable to verify address at this time.\r\n")
                     .await;
             }
-        } else if !*self.core.session.config.rcpt.relay.eval(self).await {
+        } else if !is_srs && !*self.core.session.config.rcpt.relay.eval(self).await {
             tracing::debug!(parent: &self.span,
                 context = "rcpt", 
                 event = "error",
@@ -239,6 +299,103 @@ impl<T: AsyncWrite + AsyncRead + Unpin + IsTls> Session<T> {
         self.write(b"250 2.1.5 OK\r\n").await
     }
 
+    async fn apply_delivery_policy(&mut self, directory: &dyn Directory) -> Option<&'static [u8]> {
+        let rcpt = self.data.rcpt_to.last().unwrap().address_lcase.clone();
+        let group = match directory.delivery_policy(&rcpt).await {
+            Ok(Some(group)) => group,
+            Ok(None) => return None,
+            Err(_) => {
+                tracing::debug!(parent: &self.span,
+                    context = "rcpt",
+                    event = "error",
+                    address = &rcpt,
+                    "Temporary delivery policy lookup failure.");
+
+                return Some(b"451 4.4.3 Unable to verify address at this time.\r\n");
+            }
+        };
+        let sender = self
+            .data
+            .mail_from
+            .as_ref()
+            .map(|mail_from| mail_from.address_lcase.clone())
+            .unwrap_or_default();
+
+        let is_allowed = match group.policy {
+            DeliveryPolicy::Open => true,
+            DeliveryPolicy::MembersOnly => {
+                group.is_moderator(&sender)
+                    || directory.expn(&rcpt).await.map_or(false, |members| {
+                        members
+                            .iter()
+                            .any(|member| member.eq_ignore_ascii_case(&sender))
+                    })
+            }
+            DeliveryPolicy::AnnouncementOnly => group.is_moderator(&sender),
+            DeliveryPolicy::ForwardToModerators => {
+                if group.is_moderator(&sender) {
+                    true
+                } else if !group.moderators.is_empty() {
+                    // Replace the list with its moderators, the message is delivered
+                    // to them directly rather than held for approval
+                    let new_rcpts = group
+                        .moderators
+                        .iter()
+                        .filter(|moderator| {
+                            !self
+                                .data
+                                .rcpt_to
+                                .iter()
+                                .any(|rcpt| &rcpt.address_lcase == *moderator)
+                        })
+                        .count();
+                    if self.data.rcpt_to.len() - 1 + new_rcpts > self.params.rcpt_max {
+                        return Some(b"451 4.5.3 Too many recipients.\r\n");
+                    }
+
+                    let list = self.data.rcpt_to.pop().unwrap();
+                    for moderator in group.moderators {
+                        let moderator = SessionAddress {
+                            domain: moderator.domain_part().to_string(),
+                            address: moderator.clone(),
+                            address_lcase: moderator,
+                            flags: list.flags,
+                            dsn_info: None,
+                            relay: None,
+                        };
+                        if !self.data.rcpt_to.contains(&moderator) {
+                            self.data.rcpt_to.push(moderator);
+                        }
+                    }
+
+                    tracing::debug!(parent: &self.span,
+                        context = "rcpt",
+                        event = "forward",
+                        address = &rcpt,
+                        "Message forwarded to list moderators.");
+
+                    return None;
+                } else {
+                    false
+                }
+            }
+        };
+
+        if is_allowed {
+            None
+        } else {
+            tracing::debug!(parent: &self.span,
+                context = "rcpt",
+                event = "error",
+                address = &rcpt,
+                sender = &sender,
+                policy = ?group.policy,
+                "Sender not allowed to post to list.");
+
+            Some(b"550 5.7.1 Sender is not allowed to post to this list.\r\n")
+        }
+    }
+
     async fn rcpt_error(&mut self, response: &[u8]) -> Result<(), ()> {
         tokio::time::sleep(self.params.rcpt_errors_wait).await;
         self.data.rcpt_errors += 1;
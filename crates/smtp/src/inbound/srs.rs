@@ -0,0 +1,189 @@
+/*
+ * Copyright (c) 2023 Stalwart Labs Ltd.
+ *
+ * This file is part of Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+// Sender Rewriting Scheme using the Shevek/libsrs2 address format:
+//
+//   SRS0=HHHH=TT=domain=local@srs-domain
+//   SRS1=HHHH=first-hop==HHHH=TT=domain=local@srs-domain
+//
+// HHHH is the base64 encoded HMAC-SHA1 of the lowercased address fields and
+// TT the number of days since the epoch modulo 1024 in base32.
+
+use std::time::SystemTime;
+
+use mail_builder::encoders::base64::base64_encode;
+use sha1::{Digest, Sha1};
+
+const BASE32: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ234567";
+const HASH_LEN: usize = 4;
+const TIMESTAMP_CYCLE: u64 = 1024;
+
+pub fn srs_encode(secret: &str, address: &str, srs_domain: &str) -> String {
+    let (local_part, domain) = address.rsplit_once('@').unwrap_or((address, ""));
+
+    if let Some((hop, srs_user)) = parse_srs(local_part, b"SRS0")
+        .map(|srs_user| (domain, srs_user))
+        .or_else(|| {
+            parse_srs(local_part, b"SRS1").and_then(|rest| {
+                // Keep the first hop of an address that was already forwarded
+                let (_, rest) = rest.get(1..)?.split_once('=')?;
+                rest.split_once('=')
+            })
+        })
+    {
+        // Forwarded SRS address, rewrite it as SRS1
+        format!(
+            "SRS1={}={}={}@{}",
+            srs_hash(secret, &[hop, srs_user]),
+            hop,
+            srs_user,
+            srs_domain
+        )
+    } else {
+        let timestamp = srs_timestamp(days_since_epoch());
+        format!(
+            "SRS0={}={}={}={}@{}",
+            srs_hash(secret, &[&timestamp, domain, local_part]),
+            timestamp,
+            domain,
+            local_part,
+            srs_domain
+        )
+    }
+}
+
+// Returns the original address of an SRS encoded recipient, or `None` when
+// the hash does not match or the SRS0 timestamp is older than `max_age` days.
+pub fn srs_decode(secret: &str, address: &str, max_age: u64) -> Option<String> {
+    let (local_part, _) = address.rsplit_once('@')?;
+
+    if let Some(srs_user) = parse_srs(local_part, b"SRS0") {
+        let mut parts = srs_user.get(1..)?.splitn(4, '=');
+        let hash = parts.next()?;
+        let timestamp = parts.next()?;
+        let domain = parts.next()?;
+        let local_part = parts.next()?;
+
+        if !domain.is_empty()
+            && !local_part.is_empty()
+            && srs_hash(secret, &[timestamp, domain, local_part]).eq_ignore_ascii_case(hash)
+            && srs_age(timestamp)? <= max_age
+        {
+            Some(format!("{local_part}@{domain}"))
+        } else {
+            None
+        }
+    } else if let Some(rest) = parse_srs(local_part, b"SRS1") {
+        let (hash, rest) = rest.get(1..)?.split_once('=')?;
+        let (hop, srs_user) = rest.split_once('=')?;
+
+        if !hop.is_empty()
+            && !srs_user.is_empty()
+            && srs_hash(secret, &[hop, srs_user]).eq_ignore_ascii_case(hash)
+        {
+            Some(format!("SRS0{srs_user}@{hop}"))
+        } else {
+            None
+        }
+    } else {
+        None
+    }
+}
+
+pub fn is_srs_address(address: &str) -> bool {
+    let local_part = address
+        .rsplit_once('@')
+        .map_or(address, |(local_part, _)| local_part);
+    parse_srs(local_part, b"SRS0").is_some() || parse_srs(local_part, b"SRS1").is_some()
+}
+
+// Returns the local part following the SRS tag, including its separator
+fn parse_srs<'x>(local_part: &'x str, tag: &[u8]) -> Option<&'x str> {
+    let bytes = local_part.as_bytes();
+    if bytes.len() > tag.len() + 1
+        && bytes[..tag.len()].eq_ignore_ascii_case(tag)
+        && matches!(bytes[tag.len()], b'=' | b'+' | b'-')
+    {
+        local_part.get(tag.len()..)
+    } else {
+        None
+    }
+}
+
+fn srs_hash(secret: &str, fields: &[&str]) -> String {
+    let mut data = Vec::new();
+    for field in fields {
+        data.extend_from_slice(field.to_lowercase().as_bytes());
+    }
+    let mut hash =
+        String::from_utf8(base64_encode(&hmac_sha1(secret.as_bytes(), &data)).unwrap_or_default())
+            .unwrap_or_default();
+    hash.truncate(HASH_LEN);
+    hash
+}
+
+fn hmac_sha1(key: &[u8], data: &[u8]) -> [u8; 20] {
+    const BLOCK_LEN: usize = 64;
+
+    let mut block = [0u8; BLOCK_LEN];
+    if key.len() > BLOCK_LEN {
+        block[..20].copy_from_slice(&Sha1::digest(key));
+    } else {
+        block[..key.len()].copy_from_slice(key);
+    }
+
+    let mut inner = Sha1::new();
+    inner.update(block.map(|b| b ^ 0x36));
+    inner.update(data);
+    let mut outer = Sha1::new();
+    outer.update(block.map(|b| b ^ 0x5c));
+    outer.update(inner.finalize());
+    outer.finalize().into()
+}
+
+fn days_since_epoch() -> u64 {
+    SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs() / 86400)
+}
+
+fn srs_timestamp(days: u64) -> String {
+    let days = days % TIMESTAMP_CYCLE;
+    [
+        BASE32[(days >> 5) as usize] as char,
+        BASE32[(days & 0x1f) as usize] as char,
+    ]
+    .into_iter()
+    .collect()
+}
+
+fn srs_age(timestamp: &str) -> Option<u64> {
+    if timestamp.len() != 2 {
+        return None;
+    }
+    let mut days = 0;
+    for ch in timestamp.as_bytes() {
+        days = (days << 5) | BASE32.iter().position(|b| b.eq_ignore_ascii_case(ch))? as u64;
+    }
+    Some((days_since_epoch() % TIMESTAMP_CYCLE + TIMESTAMP_CYCLE - days) % TIMESTAMP_CYCLE)
+}
@@ -36,7 +36,7 @@ use smtp_proto::MAIL_REQUIRETLS;
 use utils::config::ServerProtocol;
 
 use crate::{
-    config::{AggregateFrequency, TlsStrategy},
+    config::{AggregateFrequency, RelayHost, TlsStrategy},
     core::SMTP,
     queue::ErrorDetails,
     reporting::{tls::TlsRptOptions, PolicyType, TlsEvent},
@@ -159,31 +159,55 @@ impl DeliveryAttempt {
                     }
                 }
 
+                // Use the relay host returned by the directory, if any
+                let directory_relay = domain.relay.as_ref().map(|relay| {
+                    queue_config
+                        .relay_hosts
+                        .get(relay)
+                        .cloned()
+                        .unwrap_or_else(|| RelayHost {
+                            address: relay.to_string(),
+                            port: 25,
+                            protocol: ServerProtocol::Smtp,
+                            auth: None,
+                            tls_implicit: false,
+                            tls_allow_invalid_certs: false,
+                        })
+                });
+
                 // Obtain next hop
-                let (mut remote_hosts, is_smtp) = match queue_config.next_hop.eval(&envelope).await
-                {
-                    #[cfg(feature = "local_delivery")]
-                    Some(next_hop) if next_hop.protocol == ServerProtocol::Jmap => {
-                        // Deliver message locally
-                        let delivery_result = self
-                            .message
-                            .deliver_local(
-                                recipients.iter_mut().filter(|r| r.domain_idx == domain_idx),
-                                &core.delivery_tx,
-                                &span,
-                            )
-                            .await;
+                let (mut remote_hosts, is_smtp) = if let Some(relay) = &directory_relay {
+                    (
+                        vec![NextHop::Relay(relay)],
+                        relay.protocol == ServerProtocol::Smtp,
+                    )
+                } else {
+                    match queue_config.next_hop.eval(&envelope).await {
+                        #[cfg(feature = "local_delivery")]
+                        Some(next_hop) if next_hop.protocol == ServerProtocol::Jmap => {
+                            // Deliver message locally
+                            let delivery_result = self
+                                .message
+                                .deliver_local(
+                                    recipients.iter_mut().filter(|r| r.domain_idx == domain_idx),
+                                    &core.delivery_tx,
+                                    &span,
+                                )
+                                .await;
 
-                        // Update status for the current domain and continue with the next one
-                        domain
-                            .set_status(delivery_result, queue_config.retry.eval(&envelope).await);
-                        continue 'next_domain;
+                            // Update status for the current domain and continue with the next one
+                            domain.set_status(
+                                delivery_result,
+                                queue_config.retry.eval(&envelope).await,
+                            );
+                            continue 'next_domain;
+                        }
+                        Some(next_hop) => (
+                            vec![NextHop::Relay(next_hop)],
+                            next_hop.protocol == ServerProtocol::Smtp,
+                        ),
+                        None => (Vec::with_capacity(0), true),
                     }
-                    Some(next_hop) => (
-                        vec![NextHop::Relay(next_hop)],
-                        next_hop.protocol == ServerProtocol::Smtp,
-                    ),
-                    None => (Vec::with_capacity(0), true),
                 };
 
                 // Prepare TLS strategy
@@ -103,6 +103,7 @@ pub struct Domain {
     pub expires: Instant,
     pub status: Status<(), Error>,
     pub disable_tls: bool,
+    pub relay: Option<String>,
     pub changed: bool,
 }
 
@@ -79,6 +79,14 @@ impl Message {
             domain.serialize(idx, now, &mut buf);
         }
 
+        // Serialize relay hosts
+        for (idx, domain) in self.domains.iter().enumerate() {
+            if let Some(relay) = &domain.relay {
+                let _ = write!(buf, "H{} ", idx);
+                relay.serialize(&mut buf);
+            }
+        }
+
         // Serialize recipient status
         for (idx, rcpt) in self.recipients.iter().enumerate() {
             rcpt.serialize(idx, &mut buf);
@@ -212,6 +220,7 @@ impl Message {
                 notify: Schedule::now(),
                 status: Status::Scheduled,
                 disable_tls: false,
+                relay: None,
                 changed: false,
             });
         }
@@ -257,6 +266,16 @@ impl Message {
                         break;
                     }
                 }
+                b'H' => {
+                    if let (Some(domain), Some(relay)) = (
+                        message.domains.get_mut(idx),
+                        String::deserialize(&mut bytes),
+                    ) {
+                        domain.relay = relay.into();
+                    } else {
+                        break;
+                    }
+                }
                 b'R' => {
                     if let (Some(rcpt), Some(flags), Some(status)) = (
                         message.recipients.get_mut(idx),
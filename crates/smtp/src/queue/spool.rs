@@ -200,26 +200,30 @@ impl Message {
         config: &QueueConfig,
     ) {
         let rcpt_domain = rcpt_domain.into();
-        let domain_idx =
-            if let Some(idx) = self.domains.iter().position(|d| d.domain == rcpt_domain) {
-                idx
-            } else {
-                let idx = self.domains.len();
-                let expires = *config
-                    .expire
-                    .eval(&SimpleEnvelope::new(self, &rcpt_domain))
-                    .await;
-                self.domains.push(Domain {
-                    domain: rcpt_domain,
-                    retry: Schedule::now(),
-                    notify: Schedule::later(expires + Duration::from_secs(10)),
-                    expires: Instant::now() + expires,
-                    status: Status::Scheduled,
-                    disable_tls: false,
-                    changed: false,
-                });
-                idx
-            };
+        let domain_idx = if let Some(idx) = self
+            .domains
+            .iter()
+            .position(|d| d.domain == rcpt_domain && d.relay.is_none())
+        {
+            idx
+        } else {
+            let idx = self.domains.len();
+            let expires = *config
+                .expire
+                .eval(&SimpleEnvelope::new(self, &rcpt_domain))
+                .await;
+            self.domains.push(Domain {
+                domain: rcpt_domain,
+                retry: Schedule::now(),
+                notify: Schedule::later(expires + Duration::from_secs(10)),
+                expires: Instant::now() + expires,
+                status: Status::Scheduled,
+                disable_tls: false,
+                relay: None,
+                changed: false,
+            });
+            idx
+        };
         self.recipients.push(Recipient {
             domain_idx,
             address: rcpt.into(),
@@ -56,6 +56,7 @@ impl SessionData {
                         domain,
                         flags: 0,
                         dsn_info: None,
+                        relay: None,
                     }
                     .into();
                 }
@@ -75,6 +76,7 @@ impl SessionData {
                             domain,
                             flags: 0,
                             dsn_info: None,
+                            relay: None,
                         });
                     }
                 }
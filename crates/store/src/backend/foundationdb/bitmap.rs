@@ -21,50 +21,175 @@
  * for more details.
 */
 
+use std::ops::Range;
+
 use ahash::AHashSet;
 use roaring::RoaringBitmap;
 
 const WORD_SIZE_BITS: u32 = (WORD_SIZE * 8) as u32;
 const WORD_SIZE: usize = std::mem::size_of::<u128>();
-const WORDS_PER_BLOCK: u32 = 8;
-pub const BITS_PER_BLOCK: u32 = WORD_SIZE_BITS * WORDS_PER_BLOCK;
-const BITS_MASK: u32 = BITS_PER_BLOCK - 1;
+const MIN_BITS_PER_BLOCK: u32 = WORD_SIZE_BITS;
+const MAX_BITS_PER_BLOCK: u32 = WORD_SIZE_BITS * 64;
+pub const DEFAULT_BITS_PER_BLOCK: u32 = WORD_SIZE_BITS * 8;
+const CHECKSUM_SIZE: usize = std::mem::size_of::<u32>();
+
+// Number of document ids covered by each bitmap key. Larger blocks mean fewer
+// keys to read for fields that are set on most documents (such as mailbox or
+// keyword tags), at the cost of writing and storing mostly empty values for
+// sparse fields, where every document set in a different block costs a full
+// block. Blocks are self-describing on read, their size is derived from the
+// value length, but writes must use the same size the blocks were created
+// with, which is why the size is fixed once the database is created.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BlockSize {
+    bits: u32,
+}
+
+impl BlockSize {
+    pub fn new(bits: u32) -> Option<Self> {
+        if bits.is_power_of_two() && (MIN_BITS_PER_BLOCK..=MAX_BITS_PER_BLOCK).contains(&bits) {
+            Some(Self { bits })
+        } else {
+            None
+        }
+    }
+
+    pub fn bits(&self) -> u32 {
+        self.bits
+    }
+
+    pub fn bytes(&self) -> usize {
+        (self.bits / 8) as usize
+    }
+
+    pub fn block_num(&self, index: u32) -> u32 {
+        index / self.bits
+    }
+
+    // Range of block numbers containing the documents in a bitmap
+    pub fn block_span(&self, bitmap: &RoaringBitmap) -> Option<Range<u32>> {
+        Some(self.block_num(bitmap.min()?)..self.block_num(bitmap.max()?) + 1)
+    }
+}
 
+impl Default for BlockSize {
+    fn default() -> Self {
+        Self {
+            bits: DEFAULT_BITS_PER_BLOCK,
+        }
+    }
+}
+
+// Blocks are stored uncompressed on purpose: writes are applied with the
+// BitOr/BitXor atomic mutations and empty blocks are purged with CompareAndClear,
+// all of which operate on the raw fixed-size value. Compressing the block bytes
+// would turn every bitmap update into a conflicting read-modify-write.
 pub struct DenseBitmap {
-    pub bitmap: [u8; WORD_SIZE * WORDS_PER_BLOCK as usize],
+    pub bitmap: Vec<u8>,
 }
 
 impl DenseBitmap {
-    pub fn empty() -> Self {
+    pub fn empty(size: BlockSize) -> Self {
         Self {
-            bitmap: [0; WORD_SIZE * WORDS_PER_BLOCK as usize],
+            bitmap: vec![0; size.bytes()],
         }
     }
 
-    pub fn full() -> Self {
+    pub fn full(size: BlockSize) -> Self {
         Self {
-            bitmap: [u8::MAX; WORD_SIZE * WORDS_PER_BLOCK as usize],
+            bitmap: vec![u8::MAX; size.bytes()],
         }
     }
 
     pub fn set(&mut self, index: u32) {
-        let index = index & BITS_MASK;
+        let index = index & self.bits_mask();
         self.bitmap[(index / 8) as usize] |= 1 << (index & 7);
     }
 
     pub fn clear(&mut self, index: u32) {
-        let index = index & BITS_MASK;
+        let index = index & self.bits_mask();
         self.bitmap[(index / 8) as usize] &= !(1 << (index & 7));
     }
 
-    pub fn block_num(index: u32) -> u32 {
-        index / BITS_PER_BLOCK
+    pub fn from_block(bitmap: &RoaringBitmap, block_num: u32, size: BlockSize) -> Self {
+        let mut block = Self::empty(size);
+        let start = block_num * size.bits();
+        for index in bitmap.range(start..=start + (size.bits() - 1)) {
+            block.set(index);
+        }
+        block
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bitmap.iter().all(|byte| *byte == 0)
     }
+
+    pub fn with_checksum(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(self.bitmap.len() + CHECKSUM_SIZE);
+        bytes.extend_from_slice(&self.bitmap);
+        bytes.extend_from_slice(&block_checksum(&self.bitmap));
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8], size: BlockSize) -> Self {
+        let mut block = Self::empty(size);
+        if bytes.len() == block.bitmap.len() {
+            block.bitmap.copy_from_slice(bytes);
+        }
+        block
+    }
+
+    #[inline(always)]
+    fn bits_mask(&self) -> u32 {
+        (self.bitmap.len() * 8) as u32 - 1
+    }
+}
+
+// Checksummed blocks carry a trailing xxh3 checksum of the block bytes. Since
+// block sizes are multiples of the word size, the checksum is detected from the
+// value length, which allows checksummed and plain blocks to coexist. Blocks
+// are checksummed only when enabled, as it requires replacing the atomic
+// mutations with a read-modify-write of each block.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct BlockChecksum {
+    pub write: bool,
+    pub verify: bool,
+}
+
+pub fn block_checksum(bytes: &[u8]) -> [u8; CHECKSUM_SIZE] {
+    (xxhash_rust::xxh3::xxh3_64(bytes) as u32).to_le_bytes()
+}
+
+pub fn block_bytes(bytes: &[u8], block_num: u32, verify: bool) -> crate::Result<&[u8]> {
+    if bytes.len() % WORD_SIZE == CHECKSUM_SIZE {
+        let (block, checksum) = bytes.split_at(bytes.len() - CHECKSUM_SIZE);
+        if !verify || checksum == block_checksum(block) {
+            Ok(block)
+        } else {
+            Err(crate::Error::ChecksumMismatch(format!(
+                "bitmap block {block_num} failed checksum verification"
+            )))
+        }
+    } else {
+        Ok(bytes)
+    }
+}
+
+pub fn block_cardinality(bytes: &[u8], block_num: u32, verify: bool) -> crate::Result<u64> {
+    Ok(block_bytes(bytes, block_num, verify)?
+        .chunks_exact(WORD_SIZE)
+        .map(|word| u128::from_le_bytes(word.try_into().unwrap()).count_ones() as u64)
+        .sum())
 }
 
 pub trait DeserializeBlock {
-    fn deserialize_block(&mut self, bytes: &[u8], block_num: u32);
-    fn deserialize_word(&mut self, word: &[u8], block_num: u32, word_num: u32);
+    fn deserialize_block(
+        &mut self,
+        bytes: &[u8],
+        block_num: u32,
+        verify: bool,
+    ) -> crate::Result<()>;
+    fn deserialize_word(&mut self, word: &[u8], block_start: u32, word_num: u32);
 }
 
 pub fn next_available_index(
@@ -72,6 +197,7 @@ pub fn next_available_index(
     block_num: u32,
     reserved_ids: &AHashSet<u32>,
 ) -> Option<u32> {
+    let bits_per_block = (bytes.len() * 8) as u32;
     'outer: for (byte_pos, byte) in bytes.iter().enumerate() {
         if *byte != u8::MAX {
             let mut index = 0;
@@ -83,7 +209,7 @@ pub fn next_available_index(
                     }
                 }
 
-                let id = (block_num * BITS_PER_BLOCK) + ((byte_pos * 8) + index) as u32;
+                let id = (block_num * bits_per_block) + ((byte_pos * 8) + index) as u32;
                 if !reserved_ids.contains(&id) {
                     return Some(id);
                 } else if index < 7 {
@@ -100,35 +226,37 @@ pub fn next_available_index(
 }
 
 impl DeserializeBlock for RoaringBitmap {
-    fn deserialize_block(&mut self, bytes: &[u8], block_num: u32) {
-        debug_assert_eq!(bytes.len(), WORD_SIZE * WORDS_PER_BLOCK as usize);
+    fn deserialize_block(
+        &mut self,
+        bytes: &[u8],
+        block_num: u32,
+        verify: bool,
+    ) -> crate::Result<()> {
+        let bytes = block_bytes(bytes, block_num, verify)?;
+        debug_assert!(bytes.len() % WORD_SIZE == 0 && bytes.len() >= WORD_SIZE);
 
-        self.deserialize_word(&bytes[..WORD_SIZE], block_num, 0);
-        self.deserialize_word(&bytes[WORD_SIZE..WORD_SIZE * 2], block_num, 1);
-        self.deserialize_word(&bytes[WORD_SIZE * 2..WORD_SIZE * 3], block_num, 2);
-        self.deserialize_word(&bytes[WORD_SIZE * 3..WORD_SIZE * 4], block_num, 3);
-        self.deserialize_word(&bytes[WORD_SIZE * 4..WORD_SIZE * 5], block_num, 4);
-        self.deserialize_word(&bytes[WORD_SIZE * 5..WORD_SIZE * 6], block_num, 5);
-        self.deserialize_word(&bytes[WORD_SIZE * 6..WORD_SIZE * 7], block_num, 6);
-        self.deserialize_word(&bytes[WORD_SIZE * 7..], block_num, 7);
+        let bits_per_block = (bytes.len() * 8) as u32;
+        for (word_num, word) in bytes.chunks_exact(WORD_SIZE).enumerate() {
+            self.deserialize_word(word, block_num * bits_per_block, word_num as u32);
+        }
+
+        Ok(())
     }
 
     #[inline(always)]
-    fn deserialize_word(&mut self, word: &[u8], block_num: u32, word_num: u32) {
+    fn deserialize_word(&mut self, word: &[u8], block_start: u32, word_num: u32) {
         match u128::from_le_bytes(word.try_into().unwrap()) {
             0 => (),
             u128::MAX => {
                 self.insert_range(
-                    block_num * BITS_PER_BLOCK + word_num * WORD_SIZE_BITS
-                        ..(block_num * BITS_PER_BLOCK + word_num * WORD_SIZE_BITS) + WORD_SIZE_BITS,
+                    block_start + word_num * WORD_SIZE_BITS
+                        ..(block_start + word_num * WORD_SIZE_BITS) + WORD_SIZE_BITS,
                 );
             }
             mut word => {
                 while word != 0 {
                     let trailing_zeros = word.trailing_zeros();
-                    self.insert(
-                        block_num * BITS_PER_BLOCK + word_num * WORD_SIZE_BITS + trailing_zeros,
-                    );
+                    self.insert(block_start + word_num * WORD_SIZE_BITS + trailing_zeros);
                     word ^= 1 << trailing_zeros;
                 }
             }
@@ -144,27 +272,44 @@ mod tests {
     use roaring::RoaringBitmap;
 
     use crate::backend::foundationdb::bitmap::{
-        next_available_index, DenseBitmap, DeserializeBlock, BITS_PER_BLOCK,
+        block_cardinality, next_available_index, BlockSize, DenseBitmap, DeserializeBlock,
     };
+    use crate::Error;
 
     #[test]
     fn serialize_bitmap_block() {
-        for range in [(0..128), (128..256), (5076..5093), (1762..19342)] {
-            let mut blocks = HashMap::new();
-            let mut bitmap = RoaringBitmap::new();
-            for item in range {
-                bitmap.insert(item);
-                blocks
-                    .entry(item / BITS_PER_BLOCK)
-                    .or_insert_with(DenseBitmap::empty)
-                    .set(item);
-            }
-            let mut bitmap_blocks = RoaringBitmap::new();
-            for (block_num, dense_bitmap) in blocks {
-                bitmap_blocks.deserialize_block(&dense_bitmap.bitmap, block_num);
-            }
+        for bits in [128, 1024, 8192] {
+            let size = BlockSize::new(bits).unwrap();
+            for range in [(0..128), (128..256), (5076..5093), (1762..19342)] {
+                let mut blocks = HashMap::new();
+                let mut bitmap = RoaringBitmap::new();
+                for item in range {
+                    bitmap.insert(item);
+                    blocks
+                        .entry(size.block_num(item))
+                        .or_insert_with(|| DenseBitmap::empty(size))
+                        .set(item);
+                }
+                let mut bitmap_blocks = RoaringBitmap::new();
+                for (block_num, dense_bitmap) in &blocks {
+                    bitmap_blocks
+                        .deserialize_block(&dense_bitmap.bitmap, *block_num, true)
+                        .unwrap();
+                }
 
-            assert_eq!(bitmap, bitmap_blocks);
+                assert_eq!(bitmap, bitmap_blocks, "failed for block size {bits}");
+                assert_eq!(
+                    blocks
+                        .iter()
+                        .map(|(block_num, dense_bitmap)| {
+                            block_cardinality(&dense_bitmap.with_checksum(), *block_num, true)
+                                .unwrap()
+                        })
+                        .sum::<u64>(),
+                    bitmap.len(),
+                    "count failed for block size {bits}"
+                );
+            }
         }
     }
 
@@ -172,7 +317,7 @@ mod tests {
     fn get_next_available_index() {
         let eh = AHashSet::new();
         let mut uh = AHashSet::new();
-        let mut bm = DenseBitmap::empty();
+        let mut bm = DenseBitmap::empty(BlockSize::default());
         for id in 0..1024 {
             uh.insert(id);
             assert_eq!(
@@ -188,4 +333,38 @@ mod tests {
             bm.set(id);
         }
     }
+
+    #[test]
+    fn verify_block_checksum() {
+        let size = BlockSize::default();
+        let mut block = DenseBitmap::empty(size);
+        for id in [0, 17, 512, 1023] {
+            block.set(id);
+        }
+        let mut bytes = block.with_checksum();
+
+        let mut bitmap = RoaringBitmap::new();
+        bitmap.deserialize_block(&bytes, 3, true).unwrap();
+        assert_eq!(
+            bitmap.iter().collect::<Vec<_>>(),
+            vec![3072, 3089, 3584, 4095]
+        );
+
+        // Flip a bit, verification must fail while unverified reads still decode
+        bytes[10] ^= 0x04;
+        assert!(matches!(
+            RoaringBitmap::new().deserialize_block(&bytes, 3, true),
+            Err(Error::ChecksumMismatch(_))
+        ));
+        let mut bitmap = RoaringBitmap::new();
+        bitmap.deserialize_block(&bytes, 3, false).unwrap();
+        assert_eq!(bitmap.len(), 5);
+    }
+
+    #[test]
+    fn invalid_block_sizes() {
+        for bits in [0, 64, 1000, 16384] {
+            assert_eq!(BlockSize::new(bits), None, "accepted block size {bits}");
+        }
+    }
 }
@@ -0,0 +1,154 @@
+/*
+ * Copyright (c) 2023 Stalwart Labs Ltd.
+ *
+ * This file is part of the Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use foundationdb::{options::StreamingMode, KeySelector, RangeOption, Transaction};
+use futures::StreamExt;
+
+use crate::write::key::DeserializeBigEndian;
+
+// Values larger than the chunk size are split: the first chunk is stored under
+// the value key itself, followed by a header under `key + [0]` containing the
+// total length and the number of chunks, and the remaining chunks under
+// `key + [1..]`. Readers only look for continuation chunks when the value
+// stored under the key is exactly one chunk long, so small values cost a
+// single read just like before. The chunk size is recorded in the database
+// when it is first opened and a different configured size is refused, so
+// existing values are never read back with the wrong size.
+
+// FoundationDB rejects values larger than 100,000 bytes.
+pub const MAX_VALUE_SIZE: usize = 100_000;
+const MIN_VALUE_SIZE: usize = 1024;
+
+// Chunk indexes are a single byte appended to the value key, index 0 holds the
+// header and u8::MAX is reserved as the exclusive end of the chunk range.
+const CHUNK_HEADER: u8 = 0;
+const MAX_CHUNKS: usize = u8::MAX as usize - 1;
+
+pub(crate) fn chunk_size(size: Option<usize>) -> usize {
+    size.unwrap_or(MAX_VALUE_SIZE)
+        .clamp(MIN_VALUE_SIZE, MAX_VALUE_SIZE)
+}
+
+pub(crate) fn set_chunked_value(
+    trx: &Transaction,
+    key: &[u8],
+    value: &[u8],
+    chunk_size: usize,
+) -> crate::Result<()> {
+    clear_chunks(trx, key);
+
+    if value.len() <= chunk_size {
+        trx.set(key, value);
+        return Ok(());
+    }
+
+    let mut chunks = value.chunks(chunk_size);
+    let num_chunks = chunks.len();
+    if num_chunks > MAX_CHUNKS + 1 {
+        return Err(crate::Error::InternalError(format!(
+            "Value of {} bytes exceeds the maximum of {} chunks.",
+            value.len(),
+            MAX_CHUNKS + 1
+        )));
+    }
+
+    trx.set(key, chunks.next().unwrap());
+
+    let mut header = Vec::with_capacity(std::mem::size_of::<u32>() * 2);
+    header.extend_from_slice(&(value.len() as u32).to_be_bytes());
+    header.extend_from_slice(&(num_chunks as u32).to_be_bytes());
+    trx.set(&chunk_key(key, CHUNK_HEADER), &header);
+
+    for (chunk_num, chunk) in chunks.enumerate() {
+        trx.set(&chunk_key(key, chunk_num as u8 + 1), chunk);
+    }
+
+    Ok(())
+}
+
+pub(crate) fn clear_chunks(trx: &Transaction, key: &[u8]) {
+    trx.clear_range(&chunk_key(key, CHUNK_HEADER), &chunk_key(key, u8::MAX));
+}
+
+pub(crate) async fn read_chunks(
+    trx: &Transaction,
+    key: &[u8],
+    first_chunk: &[u8],
+    snapshot: bool,
+) -> crate::Result<Option<Vec<u8>>> {
+    let mut values = trx.get_ranges(
+        RangeOption {
+            begin: KeySelector::first_greater_or_equal(chunk_key(key, CHUNK_HEADER)),
+            end: KeySelector::first_greater_or_equal(chunk_key(key, u8::MAX)),
+            mode: StreamingMode::WantAll,
+            reverse: false,
+            ..RangeOption::default()
+        },
+        snapshot,
+    );
+
+    let mut value: Option<Vec<u8>> = None;
+    let mut expected_len = 0;
+    let mut expected_chunks = 0;
+    let mut num_chunks = 1;
+
+    while let Some(values) = values.next().await {
+        for chunk in values? {
+            let chunk_num = *chunk.key().last().unwrap();
+            if let Some(value) = &mut value {
+                if chunk_num as usize != num_chunks {
+                    return Err(corrupted_value(key));
+                }
+                value.extend_from_slice(chunk.value());
+                num_chunks += 1;
+            } else if chunk_num == CHUNK_HEADER {
+                let header = chunk.value();
+                expected_len = header.deserialize_be_u32(0)? as usize;
+                expected_chunks = header.deserialize_be_u32(std::mem::size_of::<u32>())? as usize;
+                let mut buf = Vec::with_capacity(expected_len);
+                buf.extend_from_slice(first_chunk);
+                value = Some(buf);
+            } else {
+                return Err(corrupted_value(key));
+            }
+        }
+    }
+
+    match value {
+        Some(value) if value.len() != expected_len || num_chunks != expected_chunks => {
+            Err(corrupted_value(key))
+        }
+        value => Ok(value),
+    }
+}
+
+fn chunk_key(key: &[u8], chunk_num: u8) -> Vec<u8> {
+    let mut chunk_key = Vec::with_capacity(key.len() + 1);
+    chunk_key.extend_from_slice(key);
+    chunk_key.push(chunk_num);
+    chunk_key
+}
+
+fn corrupted_value(key: &[u8]) -> crate::Error {
+    crate::Error::InternalError(format!("Chunked value for key {key:?} is corrupted."))
+}
@@ -0,0 +1,168 @@
+/*
+ * Copyright (c) 2023 Stalwart Labs Ltd.
+ *
+ * This file is part of the Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use std::{
+    sync::atomic::{AtomicUsize, Ordering},
+    time::{Duration, Instant},
+};
+
+use tokio::sync::Notify;
+use utils::config::Config;
+
+// Adaptive limit on the number of range scans running concurrently. The limit
+// grows by one after a window of scans completes under the target latency and
+// is halved whenever a scan exceeds it (AIMD), so that the backend backs off
+// when the cluster is saturated instead of piling up more scans.
+pub struct ScanLimiter {
+    min: usize,
+    max: usize,
+    target_latency: Duration,
+    limit: AtomicUsize,
+    in_flight: AtomicUsize,
+    successes: AtomicUsize,
+    notify: Notify,
+}
+
+pub struct ScanPermit<'x> {
+    limiter: &'x ScanLimiter,
+    start: Instant,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ScanMetrics {
+    pub min: usize,
+    pub max: usize,
+    pub limit: usize,
+    pub in_flight: usize,
+}
+
+impl ScanLimiter {
+    pub fn from_config(config: &Config) -> crate::Result<Option<Self>> {
+        if !config.property_or_static::<bool>("store.db.scan-concurrency.enable", "false")? {
+            return Ok(None);
+        }
+
+        let min = config.property_or_static::<usize>("store.db.scan-concurrency.min", "4")?;
+        let max = config.property_or_static::<usize>("store.db.scan-concurrency.max", "256")?;
+        if min == 0 || min > max {
+            return Err(crate::Error::InternalError(format!(
+                "Invalid scan concurrency limits: min {min}, max {max}."
+            )));
+        }
+
+        Ok(Some(ScanLimiter {
+            min,
+            max,
+            target_latency: config.property_or_static::<Duration>(
+                "store.db.scan-concurrency.target-latency",
+                "200ms",
+            )?,
+            limit: AtomicUsize::new(max),
+            in_flight: AtomicUsize::new(0),
+            successes: AtomicUsize::new(0),
+            notify: Notify::new(),
+        }))
+    }
+
+    pub async fn acquire(&self) -> ScanPermit<'_> {
+        loop {
+            let in_flight = self.in_flight.load(Ordering::Acquire);
+            if in_flight < self.limit.load(Ordering::Relaxed) {
+                if self
+                    .in_flight
+                    .compare_exchange(
+                        in_flight,
+                        in_flight + 1,
+                        Ordering::AcqRel,
+                        Ordering::Relaxed,
+                    )
+                    .is_ok()
+                {
+                    return ScanPermit {
+                        limiter: self,
+                        start: Instant::now(),
+                    };
+                }
+            } else {
+                self.notify.notified().await;
+            }
+        }
+    }
+
+    pub fn metrics(&self) -> ScanMetrics {
+        ScanMetrics {
+            min: self.min,
+            max: self.max,
+            limit: self.limit.load(Ordering::Relaxed),
+            in_flight: self.in_flight.load(Ordering::Relaxed),
+        }
+    }
+
+    fn release(&self, latency: Duration) {
+        self.in_flight.fetch_sub(1, Ordering::AcqRel);
+
+        let limit = self.limit.load(Ordering::Relaxed);
+        if latency > self.target_latency {
+            let new_limit = (limit / 2).max(self.min);
+            if new_limit < limit
+                && self
+                    .limit
+                    .compare_exchange(limit, new_limit, Ordering::AcqRel, Ordering::Relaxed)
+                    .is_ok()
+            {
+                self.successes.store(0, Ordering::Relaxed);
+                tracing::debug!(
+                    context = "store",
+                    event = "scan-limit",
+                    limit = new_limit,
+                    in_flight = self.in_flight.load(Ordering::Relaxed),
+                    latency_ms = latency.as_millis() as u64,
+                    "Decreased range scan concurrency limit."
+                );
+            }
+        } else if limit < self.max
+            && self.successes.fetch_add(1, Ordering::Relaxed) + 1 >= limit
+            && self
+                .limit
+                .compare_exchange(limit, limit + 1, Ordering::AcqRel, Ordering::Relaxed)
+                .is_ok()
+        {
+            self.successes.store(0, Ordering::Relaxed);
+            tracing::trace!(
+                context = "store",
+                event = "scan-limit",
+                limit = limit + 1,
+                in_flight = self.in_flight.load(Ordering::Relaxed),
+                "Increased range scan concurrency limit."
+            );
+        }
+
+        self.notify.notify_one();
+    }
+}
+
+impl Drop for ScanPermit<'_> {
+    fn drop(&mut self) {
+        self.limiter.release(self.start.elapsed());
+    }
+}
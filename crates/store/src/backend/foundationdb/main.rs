@@ -21,17 +21,121 @@
  * for more details.
 */
 
-use foundationdb::Database;
+use std::time::Duration;
+
+use foundationdb::{Database, FdbError};
 use utils::config::Config;
 
-use crate::{blob::BlobStore, Store};
+use crate::{
+    blob::BlobStore, write::key::KeySerializer, Deserialize, Serialize, Store, SUBSPACE_VALUES,
+};
+
+use super::{
+    bitmap::{BlockChecksum, BlockSize},
+    chunk::chunk_size,
+    limiter::ScanLimiter,
+};
+
+const BITMAP_BLOCK_SIZE_KEY: &[u8] = b"bitmap-block-size";
+const VALUE_CHUNK_SIZE_KEY: &[u8] = b"value-chunk-size";
 
 impl Store {
     pub async fn open(config: &Config) -> crate::Result<Self> {
-        Ok(Self {
+        let bitmap_block = if let Some(bits) =
+            config.property::<u32>("store.db.bitmap.block-size")?
+        {
+            BlockSize::new(bits).ok_or_else(|| {
+                crate::Error::InternalError(format!(
+                    "Invalid bitmap block size {bits}, expected a power of two between 128 and 8192."
+                ))
+            })?
+        } else {
+            BlockSize::default()
+        };
+
+        // Checksums are verified by default whenever they are written
+        let write_checksums =
+            config.property_or_static::<bool>("store.db.bitmap.checksum", "false")?;
+        let bitmap_checksum = BlockChecksum {
+            write: write_checksums,
+            verify: config
+                .property::<bool>("store.db.bitmap.verify-checksum")?
+                .unwrap_or(write_checksums),
+        };
+
+        let store = Self {
             guard: unsafe { foundationdb::boot() },
             db: Database::default()?,
             blob: BlobStore::new(config).await?,
-        })
+            fast_fail: config.property::<Duration>("store.db.fast-fail-timeout")?,
+            refresh_interval: config
+                .property_or_static::<Duration>("store.db.refresh-interval", "2s")?,
+            key_prefix: config
+                .value("store.db.key-prefix")
+                .filter(|prefix| !prefix.is_empty())
+                .map(|prefix| {
+                    // Terminate the prefix so that no instance prefix is a
+                    // prefix of another instance's keys.
+                    let mut key_prefix = prefix.as_bytes().to_vec();
+                    key_prefix.push(0);
+                    key_prefix
+                })
+                .unwrap_or_default(),
+            value_chunk_size: chunk_size(config.property::<usize>("store.db.value-chunk-size")?),
+            bitmap_block,
+            bitmap_checksum,
+            scan_limiter: ScanLimiter::from_config(config)?,
+        };
+        store
+            .verify_setting(
+                BITMAP_BLOCK_SIZE_KEY,
+                store.bitmap_block.bits(),
+                "Bitmap block size",
+            )
+            .await?;
+        store
+            .verify_setting(
+                VALUE_CHUNK_SIZE_KEY,
+                store.value_chunk_size as u32,
+                "Value chunk size",
+            )
+            .await?;
+
+        Ok(store)
+    }
+
+    // Settings that change how existing keys are read (the bitmap block size
+    // and the value chunk size) are recorded the first time the database is
+    // opened, since existing data can't be read back correctly once they change.
+    async fn verify_setting(
+        &self,
+        name: &[u8],
+        value: u32,
+        description: &str,
+    ) -> crate::Result<()> {
+        let key = self.key(
+            KeySerializer::new(std::mem::size_of::<u32>() + name.len() + 1)
+                .write(SUBSPACE_VALUES)
+                .write(u32::MAX)
+                .write(name)
+                .finalize(),
+        );
+        let trx = self.db.create_trx()?;
+        match trx.get(&key, false).await? {
+            Some(bytes) => {
+                let stored_value = u32::deserialize(&bytes)?;
+                if stored_value != value {
+                    return Err(crate::Error::InternalError(format!(
+                        "{description} is set to {value} but the database was created with {stored_value}.",
+                    )));
+                }
+            }
+            None => {
+                trx.set(&key, &value.serialize());
+                trx.commit().await.map_err(FdbError::from)?;
+            }
+        }
+
+        Ok(())
     }
 }
@@ -23,16 +23,67 @@
 
 use foundationdb::FdbError;
 
-use crate::Error;
+use crate::{Error, ReadTransaction, Store};
 
 pub mod bitmap;
+pub mod chunk;
+pub mod limiter;
 pub mod main;
 pub mod purge;
 pub mod read;
+pub mod repair;
 pub mod write;
 
+// transaction_timed_out
+const FDB_TIMED_OUT: i32 = 1031;
+// transaction_too_old
+const FDB_TOO_OLD: i32 = 1007;
+// future_version
+const FDB_FUTURE_VERSION: i32 = 1009;
+// not_committed
+const FDB_CONFLICT: i32 = 1020;
+
 impl From<FdbError> for Error {
     fn from(error: FdbError) -> Self {
-        Self::InternalError(format!("FoundationDB error: {}", error.message()))
+        match error.code() {
+            FDB_TIMED_OUT => Self::store_unavailable(),
+            FDB_TOO_OLD | FDB_FUTURE_VERSION | FDB_CONFLICT => {
+                Self::Retryable(format!("FoundationDB error: {}", error.message()))
+            }
+            _ => Self::InternalError(format!("FoundationDB error: {}", error.message())),
+        }
+    }
+}
+
+impl Error {
+    pub(crate) fn store_unavailable() -> Self {
+        Self::InternalError("Store unavailable: FoundationDB cluster did not respond".to_string())
+    }
+}
+
+// Keys are stored under the configured instance prefix, if any, which allows
+// multiple instances to share the same cluster.
+fn with_prefix(prefix: &[u8], key: Vec<u8>) -> Vec<u8> {
+    if !prefix.is_empty() {
+        let mut prefixed = Vec::with_capacity(prefix.len() + key.len());
+        prefixed.extend_from_slice(prefix);
+        prefixed.extend_from_slice(&key);
+        prefixed
+    } else {
+        key
+    }
+}
+
+impl Store {
+    #[inline(always)]
+    pub(crate) fn key(&self, key: impl Into<Vec<u8>>) -> Vec<u8> {
+        with_prefix(&self.key_prefix, key.into())
+    }
+}
+
+impl ReadTransaction<'_> {
+    #[inline(always)]
+    pub(crate) fn key(&self, key: impl Into<Vec<u8>>) -> Vec<u8> {
+        with_prefix(self.key_prefix, key.into())
     }
 }
@@ -28,11 +28,11 @@ use foundationdb::{
 use futures::StreamExt;
 
 use crate::{
-    write::key::KeySerializer, Store, SUBSPACE_BITMAPS, SUBSPACE_INDEXES, SUBSPACE_LOGS,
-    SUBSPACE_QUOTAS, SUBSPACE_VALUES,
+    write::key::{DeserializeBigEndian, KeySerializer},
+    Store, SUBSPACE_BITMAPS, SUBSPACE_INDEXES, SUBSPACE_LOGS, SUBSPACE_QUOTAS, SUBSPACE_VALUES,
 };
 
-use super::bitmap::DenseBitmap;
+use super::bitmap::block_bytes;
 
 const MAX_COMMIT_ATTEMPTS: u8 = 25;
 
@@ -42,8 +42,8 @@ impl Store {
         let trx = self.db.create_trx()?;
         let mut iter = trx.get_ranges(
             RangeOption {
-                begin: KeySelector::first_greater_or_equal(&[SUBSPACE_BITMAPS, 0u8][..]),
-                end: KeySelector::first_greater_or_equal(&[SUBSPACE_BITMAPS, u8::MAX][..]),
+                begin: KeySelector::first_greater_or_equal(self.key([SUBSPACE_BITMAPS, 0u8])),
+                end: KeySelector::first_greater_or_equal(self.key([SUBSPACE_BITMAPS, u8::MAX])),
                 mode: options::StreamingMode::WantAll,
                 reverse: false,
                 ..Default::default()
@@ -54,8 +54,13 @@ impl Store {
 
         while let Some(values) = iter.next().await {
             for value in values? {
-                if value.value().iter().all(|byte| *byte == 0) {
-                    delete_keys.push(value.key().to_vec());
+                // Blocks failing checksum verification are left for the repair task
+                let key = value.key();
+                let block_num = key.deserialize_be_u32(key.len() - std::mem::size_of::<u32>())?;
+                if block_bytes(value.value(), block_num, self.bitmap_checksum.verify)
+                    .map_or(false, |block| block.iter().all(|byte| *byte == 0))
+                {
+                    delete_keys.push((key.to_vec(), value.value().to_vec()));
                 }
             }
         }
@@ -63,14 +68,13 @@ impl Store {
             return Ok(());
         }
 
-        // Delete keys
-        let bitmap = DenseBitmap::empty();
+        // Delete keys, only if they were not modified since they were read
         for chunk in delete_keys.chunks(1024) {
             let mut retry_count = 0;
             loop {
                 let trx = self.db.create_trx()?;
-                for key in chunk {
-                    trx.atomic_op(key, &bitmap.bitmap, MutationType::CompareAndClear);
+                for (key, value) in chunk {
+                    trx.atomic_op(key, value, MutationType::CompareAndClear);
                 }
                 match trx.commit().await {
                     Ok(_) => {
@@ -98,16 +102,20 @@ impl Store {
             SUBSPACE_LOGS,
             SUBSPACE_INDEXES,
         ] {
-            let from_key = KeySerializer::new(std::mem::size_of::<u32>() + 2)
-                .write(subspace)
-                .write(account_id)
-                .write(0u8)
-                .finalize();
-            let to_key = KeySerializer::new(std::mem::size_of::<u32>() + 2)
-                .write(subspace)
-                .write(account_id)
-                .write(u8::MAX)
-                .finalize();
+            let from_key = self.key(
+                KeySerializer::new(std::mem::size_of::<u32>() + 2)
+                    .write(subspace)
+                    .write(account_id)
+                    .write(0u8)
+                    .finalize(),
+            );
+            let to_key = self.key(
+                KeySerializer::new(std::mem::size_of::<u32>() + 2)
+                    .write(subspace)
+                    .write(account_id)
+                    .write(u8::MAX)
+                    .finalize(),
+            );
 
             let trx = self.db.create_trx()?;
             trx.clear_range(&from_key, &to_key);
@@ -119,10 +127,12 @@ impl Store {
         // Delete quota key
         let trx = self.db.create_trx()?;
         trx.clear(
-            &KeySerializer::new(5)
-                .write(SUBSPACE_QUOTAS)
-                .write(account_id)
-                .finalize(),
+            &self.key(
+                KeySerializer::new(5)
+                    .write(SUBSPACE_QUOTAS)
+                    .write(account_id)
+                    .finalize(),
+            ),
         );
         if let Err(err) = trx.commit().await {
             return Err(FdbError::from(err).into());
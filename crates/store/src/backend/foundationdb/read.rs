@@ -22,7 +22,7 @@
 */
 
 use std::{
-    ops::BitAndAssign,
+    ops::{BitAndAssign, Range},
     time::{Duration, Instant},
 };
 
@@ -30,7 +30,7 @@ use foundationdb::{
     options::{self, StreamingMode},
     KeySelector, RangeOption,
 };
-use futures::StreamExt;
+use futures::{future::BoxFuture, Future, StreamExt, TryStreamExt};
 use roaring::RoaringBitmap;
 
 use crate::{
@@ -40,7 +40,61 @@ use crate::{
     Store, SUBSPACE_INDEXES, SUBSPACE_QUOTAS,
 };
 
-use super::bitmap::DeserializeBlock;
+use super::{
+    bitmap::{block_cardinality, DeserializeBlock},
+    chunk::read_chunks,
+    limiter::{ScanMetrics, ScanPermit},
+};
+
+const MAX_CONCURRENT_BITMAPS: usize = 8;
+const MAX_CONCURRENT_VALUES: usize = 32;
+const MAX_READ_ATTEMPTS: u32 = 5;
+const READ_RETRY_BACKOFF: Duration = Duration::from_millis(10);
+const MAX_READ_RETRY_BACKOFF: Duration = Duration::from_millis(500);
+
+// When a fast-fail timeout is configured, the transaction is bounded by it and
+// the read version is obtained upfront so that an unreachable cluster is
+// reported immediately instead of on the first read.
+async fn create_read_trx(
+    db: &foundationdb::Database,
+    fast_fail: Option<Duration>,
+) -> crate::Result<foundationdb::Transaction> {
+    let trx = db.create_trx()?;
+    if let Some(fast_fail) = fast_fail {
+        trx.set_option(options::TransactionOption::Timeout(
+            fast_fail.as_millis().min(i32::MAX as u128) as i32,
+        ))?;
+        match tokio::time::timeout(fast_fail, trx.get_read_version()).await {
+            Ok(result) => {
+                result?;
+            }
+            Err(_) => return Err(crate::Error::store_unavailable()),
+        }
+    }
+    Ok(trx)
+}
+
+async fn with_retry<Trx, T, C, CF, F>(mut trx: Trx, mut create: C, mut f: F) -> crate::Result<T>
+where
+    C: FnMut() -> CF,
+    CF: Future<Output = crate::Result<Trx>>,
+    F: for<'t> FnMut(&'t Trx) -> BoxFuture<'t, crate::Result<T>>,
+{
+    let mut retry_count = 0;
+    loop {
+        match f(&trx).await {
+            Err(crate::Error::Retryable(_)) if retry_count < MAX_READ_ATTEMPTS => {
+                tokio::time::sleep(
+                    (READ_RETRY_BACKOFF * 2u32.pow(retry_count)).min(MAX_READ_RETRY_BACKOFF),
+                )
+                .await;
+                retry_count += 1;
+                trx = create().await?;
+            }
+            result => return result,
+        }
+    }
+}
 
 impl ReadTransaction<'_> {
     #[inline(always)]
@@ -48,24 +102,92 @@ impl ReadTransaction<'_> {
     where
         U: Deserialize,
     {
-        let key = key.serialize();
+        let key = self.key(key.serialize());
 
-        if let Some(bytes) = self.trx.get(&key, true).await? {
+        if let Some(bytes) = self.trx.get(&key, self.snapshot).await? {
+            if bytes.len() == self.value_chunk_size {
+                if let Some(value) = read_chunks(&self.trx, &key, &bytes, self.snapshot).await? {
+                    return U::deserialize(&value).map(Some);
+                }
+            }
             U::deserialize(&bytes).map(Some)
         } else {
             Ok(None)
         }
     }
 
+    // Presence checks only need the first chunk of a value and skip deserialization
+    pub async fn exists(&self, key: impl Key) -> crate::Result<bool> {
+        Ok(self
+            .trx
+            .get(&self.key(key.serialize()), self.snapshot)
+            .await?
+            .is_some())
+    }
+
+    // Reads are issued concurrently on the same transaction, which the FDB client
+    // allows as they are all driven from this task. Results keep the key order.
+    pub async fn get_values<U>(&self, keys: Vec<impl Key>) -> crate::Result<Vec<Option<U>>>
+    where
+        U: Deserialize,
+    {
+        futures::stream::iter(keys)
+            .map(|key| self.get_value(key))
+            .buffered(MAX_CONCURRENT_VALUES)
+            .try_collect()
+            .await
+    }
+
     async fn get_bitmap_<T: AsRef<[u8]>>(
         &self,
-        mut key: BitmapKey<T>,
+        key: BitmapKey<T>,
         bm: &mut RoaringBitmap,
     ) -> crate::Result<()> {
-        let begin = (&key).serialize();
-        key.block_num = u32::MAX;
-        let end = key.serialize();
+        let first_block = key.block_num;
+        self.get_bitmap_blocks_(key, first_block..u32::MAX, bm)
+            .await
+    }
+
+    // Reads the blocks of a bitmap within the given range of block numbers
+    async fn get_bitmap_blocks_<T: AsRef<[u8]>>(
+        &self,
+        key: BitmapKey<T>,
+        blocks: Range<u32>,
+        bm: &mut RoaringBitmap,
+    ) -> crate::Result<()> {
+        let verify = self.verify_checksums;
+        self.iterate_bitmap_blocks_(key, blocks, |bytes, block_num| {
+            bm.deserialize_block(bytes, block_num, verify)
+        })
+        .await
+    }
+
+    // Counts the documents in a bitmap from the set bits of each block, without
+    // building a RoaringBitmap. Absent bitmaps have a count of zero.
+    pub async fn get_bitmap_count<T: AsRef<[u8]>>(&self, key: BitmapKey<T>) -> crate::Result<u64> {
+        let verify = self.verify_checksums;
+        let first_block = key.block_num;
+        let mut count = 0;
+        self.iterate_bitmap_blocks_(key, first_block..u32::MAX, |bytes, block_num| {
+            count += block_cardinality(bytes, block_num, verify)?;
+            Ok(())
+        })
+        .await?;
+        Ok(count)
+    }
+
+    async fn iterate_bitmap_blocks_<T: AsRef<[u8]>>(
+        &self,
+        mut key: BitmapKey<T>,
+        blocks: Range<u32>,
+        mut cb: impl FnMut(&[u8], u32) -> crate::Result<()>,
+    ) -> crate::Result<()> {
+        key.block_num = blocks.start;
+        let begin = self.key((&key).serialize());
+        key.block_num = blocks.end;
+        let end = self.key(key.serialize());
         let key_len = begin.len();
+        let _permit = self.scan_permit().await;
         let mut values = self.trx.get_ranges(
             RangeOption {
                 begin: KeySelector::first_greater_or_equal(begin),
@@ -74,17 +196,17 @@ impl ReadTransaction<'_> {
                 reverse: false,
                 ..RangeOption::default()
             },
-            true,
+            self.snapshot,
         );
 
         while let Some(values) = values.next().await {
             for value in values? {
                 let key = value.key();
                 if key.len() == key_len {
-                    bm.deserialize_block(
+                    cb(
                         value.value(),
                         key.deserialize_be_u32(key.len() - std::mem::size_of::<u32>())?,
-                    );
+                    )?;
                 }
             }
         }
@@ -101,36 +223,90 @@ impl ReadTransaction<'_> {
         Ok(if !bm.is_empty() { Some(bm) } else { None })
     }
 
+    // Bitmaps are read in ascending order of their estimated size, and each read
+    // after the first one is limited to the blocks spanned by the intersection so
+    // far. Selective terms therefore bound how much of a broad bitmap is fetched,
+    // and no further bitmaps are read once the intersection is empty.
     pub(crate) async fn get_bitmaps_intersection<T: AsRef<[u8]>>(
         &self,
         keys: Vec<BitmapKey<T>>,
     ) -> crate::Result<Option<RoaringBitmap>> {
+        // Estimate the stored size of each bitmap concurrently
+        let mut keys = futures::stream::iter(keys)
+            .map(|key| async move {
+                let size = self.estimated_bitmap_size(&key).await?;
+                crate::Result::Ok((size, key))
+            })
+            .buffered(MAX_CONCURRENT_BITMAPS)
+            .collect::<Vec<_>>()
+            .await
+            .into_iter()
+            .collect::<crate::Result<Vec<_>>>()?;
+        keys.sort_by_key(|(size, _)| *size);
+
         let mut result: Option<RoaringBitmap> = None;
-        for key in keys {
-            if let Some(bitmap) = self.get_bitmap(key).await? {
-                if let Some(result) = &mut result {
-                    result.bitand_assign(&bitmap);
-                    if result.is_empty() {
-                        break;
-                    }
-                } else {
-                    result = Some(bitmap);
+        for (_, key) in keys {
+            let mut bm = RoaringBitmap::new();
+            if let Some(result) = &result {
+                if let Some(blocks) = self.bitmap_block.block_span(result) {
+                    self.get_bitmap_blocks_(key, blocks, &mut bm).await?;
                 }
             } else {
+                self.get_bitmap_(key, &mut bm).await?;
+            }
+            if bm.is_empty() {
                 return Ok(None);
             }
+
+            if let Some(result) = &mut result {
+                result.bitand_assign(&bm);
+                if result.is_empty() {
+                    return Ok(None);
+                }
+            } else {
+                result = Some(bm);
+            }
         }
+
         Ok(result)
     }
 
+    // Ranges below the cluster's byte sampling threshold are estimated as empty,
+    // which is accurate enough to read the most selective bitmaps first
+    async fn estimated_bitmap_size<T: AsRef<[u8]>>(
+        &self,
+        key: &BitmapKey<T>,
+    ) -> crate::Result<i64> {
+        let begin = self.key(key.serialize());
+        let end = self.key(
+            (&BitmapKey {
+                account_id: key.account_id,
+                collection: key.collection,
+                family: key.family,
+                field: key.field,
+                block_num: u32::MAX,
+                key: key.key.as_ref(),
+            })
+                .serialize(),
+        );
+        Ok(self
+            .trx
+            .get_estimated_range_size_bytes(&begin, &end)
+            .await?)
+    }
+
     pub(crate) async fn get_bitmaps_union<T: AsRef<[u8]>>(
         &self,
         keys: Vec<BitmapKey<T>>,
     ) -> crate::Result<Option<RoaringBitmap>> {
         let mut bm = RoaringBitmap::new();
-
-        for key in keys {
-            self.get_bitmap_(key, &mut bm).await?;
+        let mut results = futures::stream::iter(keys)
+            .map(|key| self.get_bitmap(key))
+            .buffer_unordered(MAX_CONCURRENT_BITMAPS);
+        while let Some(bitmap) = results.next().await {
+            if let Some(bitmap) = bitmap? {
+                bm |= bitmap;
+            }
         }
 
         Ok(if !bm.is_empty() { Some(bm) } else { None })
@@ -144,47 +320,7 @@ impl ReadTransaction<'_> {
         value: Vec<u8>,
         op: Operator,
     ) -> crate::Result<Option<RoaringBitmap>> {
-        let k1 = KeySerializer::new(
-            std::mem::size_of::<IndexKey<&[u8]>>() + value.len() + 1 + std::mem::size_of::<u32>(),
-        )
-        .write(SUBSPACE_INDEXES)
-        .write(account_id)
-        .write(collection)
-        .write(field);
-        let k2 = KeySerializer::new(
-            std::mem::size_of::<IndexKey<&[u8]>>() + value.len() + 1 + std::mem::size_of::<u32>(),
-        )
-        .write(SUBSPACE_INDEXES)
-        .write(account_id)
-        .write(collection)
-        .write(field + matches!(op, Operator::GreaterThan | Operator::GreaterEqualThan) as u8);
-
-        let (begin, end) = match op {
-            Operator::LowerThan => (
-                KeySelector::first_greater_or_equal(k1.finalize()),
-                KeySelector::first_greater_or_equal(k2.write(&value[..]).write(0u32).finalize()),
-            ),
-            Operator::LowerEqualThan => (
-                KeySelector::first_greater_or_equal(k1.finalize()),
-                KeySelector::first_greater_or_equal(
-                    k2.write(&value[..]).write(u32::MAX).finalize(),
-                ),
-            ),
-            Operator::GreaterThan => (
-                KeySelector::first_greater_than(k1.write(&value[..]).write(u32::MAX).finalize()),
-                KeySelector::first_greater_or_equal(k2.finalize()),
-            ),
-            Operator::GreaterEqualThan => (
-                KeySelector::first_greater_or_equal(k1.write(&value[..]).write(0u32).finalize()),
-                KeySelector::first_greater_or_equal(k2.finalize()),
-            ),
-            Operator::Equal => (
-                KeySelector::first_greater_or_equal(k1.write(&value[..]).write(0u32).finalize()),
-                KeySelector::first_greater_or_equal(
-                    k2.write(&value[..]).write(u32::MAX).finalize(),
-                ),
-            ),
-        };
+        let (begin, end) = self.index_range(account_id, collection, field, &value, op);
         let key_len = begin.key().len();
 
         let opt = RangeOption {
@@ -196,7 +332,8 @@ impl ReadTransaction<'_> {
         };
 
         let mut bm = RoaringBitmap::new();
-        let mut range_stream = self.trx.get_ranges(opt, true);
+        let _permit = self.scan_permit().await;
+        let mut range_stream = self.trx.get_ranges(opt, self.snapshot);
 
         if op != Operator::Equal {
             while let Some(values) = range_stream.next().await {
@@ -219,6 +356,119 @@ impl ReadTransaction<'_> {
         Ok(Some(bm))
     }
 
+    // Returns up to `limit` documents matching the range in index order, or in
+    // reverse index order, without reading the rest of the range
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) async fn range_to_ids(
+        &self,
+        account_id: u32,
+        collection: u8,
+        field: u8,
+        value: Vec<u8>,
+        op: Operator,
+        limit: usize,
+        reverse: bool,
+    ) -> crate::Result<Vec<u32>> {
+        let mut ids = Vec::with_capacity(std::cmp::min(limit, 1024));
+        if limit == 0 {
+            return Ok(ids);
+        }
+
+        let (begin, end) = self.index_range(account_id, collection, field, &value, op);
+        let key_len = begin.key().len();
+        let _permit = self.scan_permit().await;
+        let mut range_stream = self.trx.get_ranges(
+            RangeOption {
+                begin,
+                end,
+                // Equality matches are filtered by key length, so the limit can't
+                // be pushed down to the range read
+                limit: if op != Operator::Equal {
+                    Some(limit)
+                } else {
+                    None
+                },
+                mode: StreamingMode::Iterator,
+                reverse,
+                ..RangeOption::default()
+            },
+            self.snapshot,
+        );
+
+        while let Some(values) = range_stream.next().await {
+            for value in values? {
+                let key = value.key();
+                if op != Operator::Equal || key.len() == key_len {
+                    ids.push(key.deserialize_be_u32(key.len() - std::mem::size_of::<u32>())?);
+                    if ids.len() == limit {
+                        return Ok(ids);
+                    }
+                }
+            }
+        }
+
+        Ok(ids)
+    }
+
+    fn index_range(
+        &self,
+        account_id: u32,
+        collection: u8,
+        field: u8,
+        value: &[u8],
+        op: Operator,
+    ) -> (KeySelector<'static>, KeySelector<'static>) {
+        let k1 = KeySerializer::new(
+            std::mem::size_of::<IndexKey<&[u8]>>() + value.len() + 1 + std::mem::size_of::<u32>(),
+        )
+        .write(SUBSPACE_INDEXES)
+        .write(account_id)
+        .write(collection)
+        .write(field);
+        let k2 = KeySerializer::new(
+            std::mem::size_of::<IndexKey<&[u8]>>() + value.len() + 1 + std::mem::size_of::<u32>(),
+        )
+        .write(SUBSPACE_INDEXES)
+        .write(account_id)
+        .write(collection)
+        .write(field + matches!(op, Operator::GreaterThan | Operator::GreaterEqualThan) as u8);
+
+        match op {
+            Operator::LowerThan => (
+                KeySelector::first_greater_or_equal(self.key(k1.finalize())),
+                KeySelector::first_greater_or_equal(
+                    self.key(k2.write(value).write(0u32).finalize()),
+                ),
+            ),
+            Operator::LowerEqualThan => (
+                KeySelector::first_greater_or_equal(self.key(k1.finalize())),
+                KeySelector::first_greater_or_equal(
+                    self.key(k2.write(value).write(u32::MAX).finalize()),
+                ),
+            ),
+            Operator::GreaterThan => (
+                KeySelector::first_greater_than(
+                    self.key(k1.write(value).write(u32::MAX).finalize()),
+                ),
+                KeySelector::first_greater_or_equal(self.key(k2.finalize())),
+            ),
+            Operator::GreaterEqualThan => (
+                KeySelector::first_greater_or_equal(
+                    self.key(k1.write(value).write(0u32).finalize()),
+                ),
+                KeySelector::first_greater_or_equal(self.key(k2.finalize())),
+            ),
+            Operator::Equal => (
+                KeySelector::first_greater_or_equal(
+                    self.key(k1.write(value).write(0u32).finalize()),
+                ),
+                KeySelector::first_greater_or_equal(
+                    self.key(k2.write(value).write(u32::MAX).finalize()),
+                ),
+            ),
+        }
+    }
+
     pub(crate) async fn sort_index(
         &self,
         account_id: u32,
@@ -227,19 +477,24 @@ impl ReadTransaction<'_> {
         ascending: bool,
         mut cb: impl FnMut(&[u8], u32) -> bool,
     ) -> crate::Result<()> {
-        let from_key = IndexKeyPrefix {
-            account_id,
-            collection,
-            field,
-        }
-        .serialize();
-        let to_key = IndexKeyPrefix {
-            account_id,
-            collection,
-            field: field + 1,
-        }
-        .serialize();
+        let from_key = self.key(
+            IndexKeyPrefix {
+                account_id,
+                collection,
+                field,
+            }
+            .serialize(),
+        );
+        let to_key = self.key(
+            IndexKeyPrefix {
+                account_id,
+                collection,
+                field: field + 1,
+            }
+            .serialize(),
+        );
         let prefix_len = from_key.len();
+        let _permit = self.scan_permit().await;
         let mut sorted_iter = self.trx.get_ranges(
             RangeOption {
                 begin: KeySelector::first_greater_or_equal(&from_key),
@@ -248,7 +503,7 @@ impl ReadTransaction<'_> {
                 reverse: !ascending,
                 ..Default::default()
             },
-            true,
+            self.snapshot,
         );
 
         while let Some(values) = sorted_iter.next().await {
@@ -279,9 +534,11 @@ impl ReadTransaction<'_> {
         ascending: bool,
         cb: impl Fn(&mut T, &[u8], &[u8]) -> crate::Result<bool> + Sync + Send + 'static,
     ) -> crate::Result<T> {
-        let begin = begin.serialize();
-        let end = end.serialize();
+        let begin = self.key(begin.serialize());
+        let end = self.key(end.serialize());
+        let prefix_len = self.key_prefix.len() + 1;
 
+        let _permit = self.scan_permit().await;
         let mut iter = self.trx.get_ranges(
             RangeOption {
                 begin: KeySelector::first_greater_or_equal(&begin),
@@ -294,12 +551,12 @@ impl ReadTransaction<'_> {
                 reverse: !ascending,
                 ..Default::default()
             },
-            true,
+            self.snapshot,
         );
 
         while let Some(values) = iter.next().await {
             for value in values? {
-                let key = value.key().get(1..).unwrap_or_default();
+                let key = value.key().get(prefix_len..).unwrap_or_default();
                 let value = value.value();
 
                 if !cb(&mut acc, key, value)? || first {
@@ -316,18 +573,22 @@ impl ReadTransaction<'_> {
         account_id: u32,
         collection: u8,
     ) -> crate::Result<Option<u64>> {
-        let from_key = LogKey {
-            account_id,
-            collection,
-            change_id: 0,
-        }
-        .serialize();
-        let to_key = LogKey {
-            account_id,
-            collection,
-            change_id: u64::MAX,
-        }
-        .serialize();
+        let from_key = self.key(
+            LogKey {
+                account_id,
+                collection,
+                change_id: 0,
+            }
+            .serialize(),
+        );
+        let to_key = self.key(
+            LogKey {
+                account_id,
+                collection,
+                change_id: u64::MAX,
+            }
+            .serialize(),
+        );
 
         let mut iter = self.trx.get_ranges(
             RangeOption {
@@ -337,7 +598,7 @@ impl ReadTransaction<'_> {
                 reverse: true,
                 ..Default::default()
             },
-            true,
+            self.snapshot,
         );
 
         while let Some(values) = iter.next().await {
@@ -353,15 +614,25 @@ impl ReadTransaction<'_> {
         Ok(None)
     }
 
+    // Range scans wait for a permit when the adaptive scan limit is enabled
+    async fn scan_permit(&self) -> Option<ScanPermit<'_>> {
+        match self.scan_limiter {
+            Some(limiter) => Some(limiter.acquire().await),
+            None => None,
+        }
+    }
+
     pub async fn get_quota(&self, account_id: u32) -> crate::Result<i64> {
         if let Some(bytes) = self
             .trx
             .get(
-                &KeySerializer::new(5)
-                    .write(SUBSPACE_QUOTAS)
-                    .write(account_id)
-                    .finalize(),
-                true,
+                &self.key(
+                    KeySerializer::new(5)
+                        .write(SUBSPACE_QUOTAS)
+                        .write(account_id)
+                        .finalize(),
+                ),
+                self.snapshot,
             )
             .await?
         {
@@ -376,9 +647,19 @@ impl ReadTransaction<'_> {
         }
     }
 
+    // Reads are snapshot reads by default, which don't add read conflict ranges
+    // and never cause the transaction to fail on commit. Serializable reads
+    // register each key or range read as a conflict range, so committing writes
+    // made from this transaction fails if any of the data read was modified by
+    // another transaction in the meantime, at the cost of more aborted commits.
+    pub fn serializable(mut self) -> Self {
+        self.snapshot = false;
+        self
+    }
+
     pub async fn refresh_if_old(&mut self) -> crate::Result<()> {
-        if self.trx_age.elapsed() > Duration::from_millis(2000) {
-            self.trx = self.db.create_trx()?;
+        if self.trx_age.elapsed() > self.refresh_interval {
+            self.trx = create_read_trx(self.db, self.fast_fail).await?;
             self.trx_age = Instant::now();
         }
         Ok(())
@@ -389,11 +670,38 @@ impl Store {
     pub async fn read_transaction(&self) -> crate::Result<ReadTransaction<'_>> {
         Ok(ReadTransaction {
             db: &self.db,
-            trx: self.db.create_trx()?,
+            trx: create_read_trx(&self.db, self.fast_fail).await?,
             trx_age: Instant::now(),
+            fast_fail: self.fast_fail,
+            refresh_interval: self.refresh_interval,
+            key_prefix: &self.key_prefix,
+            value_chunk_size: self.value_chunk_size,
+            verify_checksums: self.bitmap_checksum.verify,
+            bitmap_block: self.bitmap_block,
+            snapshot: true,
+            scan_limiter: self.scan_limiter.as_ref(),
         })
     }
 
+    // Runs a read on a fresh transaction, recreating it and running the read again
+    // when it fails with a retryable error such as an expired read version, which
+    // is the case of reads taking longer than the FoundationDB transaction window.
+    pub async fn read_with_retry<'x, T, F>(&'x self, f: F) -> crate::Result<T>
+    where
+        F: for<'t> FnMut(&'t ReadTransaction<'x>) -> BoxFuture<'t, crate::Result<T>>,
+    {
+        with_retry(
+            self.read_transaction().await?,
+            || self.read_transaction(),
+            f,
+        )
+        .await
+    }
+
+    pub fn scan_metrics(&self) -> Option<ScanMetrics> {
+        self.scan_limiter.as_ref().map(|limiter| limiter.metrics())
+    }
+
     #[cfg(feature = "test_mode")]
     pub async fn assert_is_empty(&self) {
         use crate::{SUBSPACE_BITMAPS, SUBSPACE_LOGS, SUBSPACE_VALUES};
@@ -405,8 +713,8 @@ impl Store {
 
         let mut iter = conn.trx.get_ranges(
             RangeOption {
-                begin: KeySelector::first_greater_or_equal(&[0u8][..]),
-                end: KeySelector::first_greater_or_equal(&[u8::MAX][..]),
+                begin: KeySelector::first_greater_or_equal(self.key([0u8])),
+                end: KeySelector::first_greater_or_equal(self.key([u8::MAX])),
                 mode: options::StreamingMode::WantAll,
                 reverse: false,
                 ..Default::default()
@@ -417,7 +725,7 @@ impl Store {
         let mut delete_keys = Vec::new();
         while let Some(values) = iter.next().await {
             for value in values.unwrap() {
-                let key_ = value.key();
+                let key_ = &value.key()[self.key_prefix.len()..];
                 let value = value.value();
                 let subspace = key_[0];
                 let key = &key_[1..];
@@ -483,7 +791,7 @@ impl Store {
         // Empty database
         let trx = self.db.create_trx().unwrap();
         for key in delete_keys {
-            trx.clear(&key);
+            trx.clear(&self.key(key));
         }
         trx.commit().await.unwrap();
 
@@ -491,3 +799,57 @@ impl Store {
         crate::backend::foundationdb::write::BITMAPS.lock().clear();
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    use foundationdb::FdbError;
+
+    use super::with_retry;
+
+    #[tokio::test]
+    async fn retry_expired_transaction() {
+        let created = AtomicU32::new(0);
+        let create = || async { crate::Result::Ok(created.fetch_add(1, Ordering::Relaxed) + 1) };
+
+        // Fails once with transaction_too_old and succeeds on a new transaction
+        let mut attempts = 0;
+        let result = with_retry(create().await.unwrap(), create, |trx: &u32| {
+            attempts += 1;
+            let trx = *trx;
+            Box::pin(async move {
+                if trx == 1 {
+                    Err(FdbError::from_code(1007).into())
+                } else {
+                    Ok(trx)
+                }
+            })
+        })
+        .await
+        .unwrap();
+        assert_eq!(result, 2);
+        assert_eq!(attempts, 2);
+
+        // Non retryable errors are returned immediately
+        created.store(0, Ordering::Relaxed);
+        let mut attempts = 0;
+        let result = with_retry(create().await.unwrap(), create, |_: &u32| {
+            attempts += 1;
+            Box::pin(async { Err::<(), _>(FdbError::from_code(2000).into()) })
+        })
+        .await;
+        assert!(matches!(result, Err(crate::Error::InternalError(_))));
+        assert_eq!(attempts, 1);
+
+        // Retries are bounded
+        let mut attempts = 0;
+        let result = with_retry(create().await.unwrap(), create, |_: &u32| {
+            attempts += 1;
+            Box::pin(async { Err::<(), _>(FdbError::from_code(1020).into()) })
+        })
+        .await;
+        assert!(matches!(result, Err(crate::Error::Retryable(_))));
+        assert_eq!(attempts, super::MAX_READ_ATTEMPTS + 1);
+    }
+}
@@ -0,0 +1,245 @@
+/*
+ * Copyright (c) 2023 Stalwart Labs Ltd.
+ *
+ * This file is part of the Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use std::collections::BTreeSet;
+
+use foundationdb::{options::StreamingMode, FdbError, KeySelector, RangeOption};
+use futures::StreamExt;
+use roaring::RoaringBitmap;
+
+use crate::{
+    write::key::{DeserializeBigEndian, KeySerializer},
+    BitmapKey, Serialize, Store, SUBSPACE_INDEXES,
+};
+
+use super::bitmap::{block_bytes, DenseBitmap};
+
+const MAX_COMMIT_ATTEMPTS: u8 = 25;
+const MAX_BLOCKS_PER_TRX: usize = 256;
+const MAX_KEYS_PER_TRX: usize = 10_000;
+
+impl Store {
+    /// Returns the ids of all documents that have at least one entry for
+    /// the given indexed field.
+    pub async fn document_ids_from_index(
+        &self,
+        account_id: u32,
+        collection: impl Into<u8>,
+        field: impl Into<u8>,
+    ) -> crate::Result<RoaringBitmap> {
+        let prefix = self.key(
+            KeySerializer::new(std::mem::size_of::<u32>() + 4)
+                .write(SUBSPACE_INDEXES)
+                .write(account_id)
+                .write(collection.into())
+                .write(field.into())
+                .finalize(),
+        );
+        let end = prefix_end(&prefix);
+        let mut begin = KeySelector::first_greater_or_equal(prefix);
+
+        // Large indexes are read in batches, each one using a new transaction
+        // so that the scan does not exceed the transaction time limit.
+        let mut document_ids = RoaringBitmap::new();
+        loop {
+            let trx = self.db.create_trx()?;
+            let mut iter = trx.get_ranges(
+                RangeOption {
+                    begin: begin.clone(),
+                    end: KeySelector::first_greater_or_equal(end.clone()),
+                    limit: Some(MAX_KEYS_PER_TRX),
+                    mode: StreamingMode::WantAll,
+                    reverse: false,
+                    ..Default::default()
+                },
+                true,
+            );
+
+            let mut num_keys = 0;
+            let mut last_key = None;
+            while let Some(values) = iter.next().await {
+                let values = values?;
+                for value in values.iter() {
+                    let key = value.key();
+                    document_ids
+                        .insert(key.deserialize_be_u32(key.len() - std::mem::size_of::<u32>())?);
+                }
+                if let Some(value) = values.last() {
+                    last_key = Some(value.key().to_vec());
+                }
+                num_keys += values.len();
+            }
+
+            match last_key {
+                Some(last_key) if num_keys >= MAX_KEYS_PER_TRX => {
+                    begin = KeySelector::first_greater_than(last_key);
+                }
+                _ => break,
+            }
+        }
+
+        Ok(document_ids)
+    }
+
+    /// Compares the document ids bitmap of a collection with the documents
+    /// referenced by one of its indexes. Returns the number of documents
+    /// that are present in only one of them.
+    pub async fn verify_document_ids(
+        &self,
+        account_id: u32,
+        collection: impl Into<u8>,
+        field: impl Into<u8>,
+    ) -> crate::Result<u64> {
+        let collection = collection.into();
+        let document_ids = self
+            .get_bitmap(BitmapKey::document_ids(account_id, collection))
+            .await?
+            .unwrap_or_default();
+        let indexed_ids = self
+            .document_ids_from_index(account_id, collection, field)
+            .await?;
+
+        Ok((&document_ids ^ &indexed_ids).len())
+    }
+
+    /// Rewrites the blocks of a bitmap so that it contains exactly the
+    /// provided document ids. Blocks that already match are left untouched,
+    /// which makes the operation idempotent. Each block is read within the
+    /// transaction that rewrites it, so concurrent updates cause a conflict
+    /// and a retry rather than being overwritten. Returns the number of
+    /// blocks that were rewritten.
+    pub async fn repair_bitmap<T: AsRef<[u8]>>(
+        &self,
+        mut key: BitmapKey<T>,
+        document_ids: &RoaringBitmap,
+    ) -> crate::Result<usize> {
+        // Obtain the blocks currently stored
+        key.block_num = 0;
+        let begin = self.key((&key).serialize());
+        key.block_num = u32::MAX;
+        let end = self.key((&key).serialize());
+        let key_len = begin.len();
+
+        let mut block_nums = BTreeSet::new();
+        let trx = self.db.create_trx()?;
+        let mut iter = trx.get_ranges(
+            RangeOption {
+                begin: KeySelector::first_greater_or_equal(begin),
+                end: KeySelector::first_greater_or_equal(end),
+                mode: StreamingMode::WantAll,
+                reverse: false,
+                ..Default::default()
+            },
+            true,
+        );
+        while let Some(values) = iter.next().await {
+            for value in values? {
+                let key = value.key();
+                if key.len() == key_len {
+                    block_nums
+                        .insert(key.deserialize_be_u32(key.len() - std::mem::size_of::<u32>())?);
+                }
+            }
+        }
+
+        // Add the blocks that should exist
+        let mut last_block_num = u32::MAX;
+        for document_id in document_ids {
+            let block_num = self.bitmap_block.block_num(document_id);
+            if block_num != last_block_num {
+                block_nums.insert(block_num);
+                last_block_num = block_num;
+            }
+        }
+
+        // Rewrite any blocks that differ, in bounded batches
+        let block_nums = block_nums.into_iter().collect::<Vec<_>>();
+        let mut repaired = 0;
+        for chunk in block_nums.chunks(MAX_BLOCKS_PER_TRX) {
+            let mut retry_count = 0;
+            loop {
+                let trx = self.db.create_trx()?;
+                let mut changed = 0;
+                for block_num in chunk {
+                    key.block_num = *block_num;
+                    let block_key = self.key((&key).serialize());
+                    let expected =
+                        DenseBitmap::from_block(document_ids, *block_num, self.bitmap_block);
+                    // Blocks failing checksum verification are rewritten too
+                    let matches = match trx.get(&block_key, false).await? {
+                        Some(bytes) => match block_bytes(&bytes, *block_num, true) {
+                            Ok(block) => {
+                                block == expected.bitmap.as_slice()
+                                    && (bytes.len() != block.len()) == self.bitmap_checksum.write
+                            }
+                            Err(_) => false,
+                        },
+                        None => expected.is_empty(),
+                    };
+                    if !matches {
+                        if expected.is_empty() {
+                            trx.clear(&block_key);
+                        } else if self.bitmap_checksum.write {
+                            trx.set(&block_key, &expected.with_checksum());
+                        } else {
+                            trx.set(&block_key, &expected.bitmap);
+                        }
+                        changed += 1;
+                    }
+                }
+                if changed == 0 {
+                    break;
+                }
+
+                match trx.commit().await {
+                    Ok(_) => {
+                        repaired += changed;
+                        break;
+                    }
+                    Err(err) => {
+                        if retry_count < MAX_COMMIT_ATTEMPTS {
+                            err.on_error().await?;
+                            retry_count += 1;
+                        } else {
+                            return Err(FdbError::from(err).into());
+                        }
+                    }
+                }
+            }
+        }
+
+        Ok(repaired)
+    }
+}
+
+// Returns the first key after all the keys starting with the given prefix
+fn prefix_end(prefix: &[u8]) -> Vec<u8> {
+    let mut key = prefix.to_vec();
+    while let Some(byte) = key.pop() {
+        if byte != u8::MAX {
+            key.push(byte + 1);
+            break;
+        }
+    }
+    key
+}
@@ -26,7 +26,7 @@ use std::time::{Duration, Instant};
 use ahash::{AHashMap, AHashSet};
 use foundationdb::{
     options::{MutationType, StreamingMode},
-    FdbError, KeySelector, RangeOption,
+    FdbError, KeySelector, RangeOption, Transaction,
 };
 use futures::StreamExt;
 use rand::Rng;
@@ -40,7 +40,10 @@ use crate::{
     SUBSPACE_VALUES,
 };
 
-use super::bitmap::{next_available_index, DenseBitmap, BITS_PER_BLOCK};
+use super::{
+    bitmap::{block_bytes, next_available_index, DenseBitmap},
+    chunk::{clear_chunks, read_chunks, set_chunked_value},
+};
 
 #[cfg(not(feature = "test_mode"))]
 pub const ID_ASSIGNMENT_EXPIRY: u64 = 60 * 60; // seconds
@@ -95,6 +98,9 @@ impl Store {
                         document_id = *document_id_;
                     }
                     Operation::Value { class, set } => {
+                        // Custom keys are variable length and may be a prefix of
+                        // other keys, so they are never chunked.
+                        let is_chunked = !matches!(class, ValueClass::Custom { .. });
                         let key = match class {
                             ValueClass::Property { field, family } => ValueKey {
                                 account_id,
@@ -118,21 +124,31 @@ impl Store {
                                 key
                             }
                         };
+                        let key = self.key(key);
                         if let Some(value) = set {
-                            trx.set(&key, value);
+                            if is_chunked {
+                                set_chunked_value(&trx, &key, value, self.value_chunk_size)?;
+                            } else {
+                                trx.set(&key, value);
+                            }
                         } else {
                             trx.clear(&key);
+                            if is_chunked {
+                                clear_chunks(&trx, &key);
+                            }
                         }
                     }
                     Operation::Index { field, key, set } => {
-                        let key = IndexKey {
-                            account_id,
-                            collection,
-                            document_id,
-                            field: *field,
-                            key,
-                        }
-                        .serialize();
+                        let key = self.key(
+                            IndexKey {
+                                account_id,
+                                collection,
+                                document_id,
+                                field: *field,
+                                key,
+                            }
+                            .serialize(),
+                        );
                         if *set {
                             trx.set(&key, &[]);
                         } else {
@@ -157,12 +173,12 @@ impl Store {
                                     collection,
                                     family: *family,
                                     field: *field,
-                                    block_num: DenseBitmap::block_num(document_id),
+                                    block_num: self.bitmap_block.block_num(document_id),
                                     key,
                                 }
                                 .serialize(),
                             )
-                            .or_insert_with(DenseBitmap::empty)
+                            .or_insert_with(|| DenseBitmap::empty(self.bitmap_block))
                             .set(document_id);
                         }
                     }
@@ -171,12 +187,14 @@ impl Store {
                         change_id,
                         set,
                     } => {
-                        let key = LogKey {
-                            account_id,
-                            collection: *collection,
-                            change_id: *change_id,
-                        }
-                        .serialize();
+                        let key = self.key(
+                            LogKey {
+                                account_id,
+                                collection: *collection,
+                                change_id: *change_id,
+                            }
+                            .serialize(),
+                        );
                         trx.set(&key, set);
                     }
                     Operation::AssertValue {
@@ -207,9 +225,18 @@ impl Store {
                             }
                         };
 
+                        let key = self.key(key);
                         let matches = if let Ok(bytes) = trx.get(&key, false).await {
                             if let Some(bytes) = bytes {
-                                assert_value.matches(bytes.as_ref())
+                                if bytes.len() == self.value_chunk_size {
+                                    match read_chunks(&trx, &key, &bytes, false).await {
+                                        Ok(Some(value)) => assert_value.matches(&value),
+                                        Ok(None) => assert_value.matches(bytes.as_ref()),
+                                        Err(_) => false,
+                                    }
+                                } else {
+                                    assert_value.matches(bytes.as_ref())
+                                }
                             } else {
                                 assert_value.is_none()
                             }
@@ -224,10 +251,12 @@ impl Store {
                     }
                     Operation::UpdateQuota { bytes } => {
                         trx.atomic_op(
-                            &KeySerializer::new(5)
-                                .write(SUBSPACE_QUOTAS)
-                                .write(account_id)
-                                .finalize(),
+                            &self.key(
+                                KeySerializer::new(5)
+                                    .write(SUBSPACE_QUOTAS)
+                                    .write(account_id)
+                                    .finalize(),
+                            ),
                             &bytes.to_le_bytes()[..],
                             MutationType::Add,
                         );
@@ -235,12 +264,28 @@ impl Store {
                 }
             }
 
-            for (key, bitmap) in &set_bitmaps {
-                trx.atomic_op(key, &bitmap.bitmap, MutationType::BitOr);
-            }
+            if !self.bitmap_checksum.write {
+                for (key, bitmap) in &set_bitmaps {
+                    trx.atomic_op(
+                        &self.key(key.as_slice()),
+                        &bitmap.bitmap,
+                        MutationType::BitOr,
+                    );
+                }
 
-            for (key, bitmap) in &clear_bitmaps {
-                trx.atomic_op(key, &bitmap.bitmap, MutationType::BitXor);
+                for (key, bitmap) in &clear_bitmaps {
+                    trx.atomic_op(
+                        &self.key(key.as_slice()),
+                        &bitmap.bitmap,
+                        MutationType::BitXor,
+                    );
+                }
+            } else if let Err(err) = self
+                .write_checksummed_bitmaps(&trx, &set_bitmaps, &clear_bitmaps)
+                .await
+            {
+                trx.cancel();
+                return Err(err);
             }
 
             match trx.commit().await {
@@ -275,7 +320,7 @@ impl Store {
                                         collection,
                                         family: *family,
                                         field: *field,
-                                        block_num: DenseBitmap::block_num(document_id),
+                                        block_num: self.bitmap_block.block_num(document_id),
                                         key,
                                     }
                                     .serialize();
@@ -328,22 +373,26 @@ impl Store {
 
         loop {
             // First try to reuse an expired assigned id
-            let begin = IndexKey {
-                account_id,
-                collection,
-                document_id: 0,
-                field: u8::MAX,
-                key: &[],
-            }
-            .serialize();
-            let end = IndexKey {
-                account_id,
-                collection,
-                document_id: u32::MAX,
-                field: u8::MAX,
-                key: &[],
-            }
-            .serialize();
+            let begin = self.key(
+                IndexKey {
+                    account_id,
+                    collection,
+                    document_id: 0,
+                    field: u8::MAX,
+                    key: &[],
+                }
+                .serialize(),
+            );
+            let end = self.key(
+                IndexKey {
+                    account_id,
+                    collection,
+                    document_id: u32::MAX,
+                    field: u8::MAX,
+                    key: &[],
+                }
+                .serialize(),
+            );
             let trx = self.db.create_trx()?;
 
             let mut values = trx.get_ranges(
@@ -392,9 +441,9 @@ impl Store {
             } else {
                 // Find the next available id
                 let mut key = BitmapKey::document_ids(account_id, collection);
-                let begin = key.serialize();
+                let begin = self.key(key.serialize());
                 key.block_num = u32::MAX;
-                let end = key.serialize();
+                let end = self.key(key.serialize());
                 let mut values = trx.get_ranges(
                     RangeOption {
                         begin: KeySelector::first_greater_or_equal(begin),
@@ -409,9 +458,11 @@ impl Store {
                 'outer: while let Some(values) = values.next().await {
                     for value in values? {
                         let key = value.key();
+                        let block_num =
+                            key.deserialize_be_u32(key.len() - std::mem::size_of::<u32>())?;
                         if let Some(next_id) = next_available_index(
-                            value.value(),
-                            key.deserialize_be_u32(key.len() - std::mem::size_of::<u32>())?,
+                            block_bytes(value.value(), block_num, self.bitmap_checksum.verify)?,
+                            block_num,
                             &reserved_ids,
                         ) {
                             document_id = next_id;
@@ -425,8 +476,8 @@ impl Store {
 
             // If no ids were found, assign the first available id that is not reserved
             if document_id == u32::MAX {
-                document_id = 1024;
-                for document_id_ in 0..BITS_PER_BLOCK {
+                document_id = self.bitmap_block.bits();
+                for document_id_ in 0..self.bitmap_block.bits() {
                     if !reserved_ids.contains(&document_id_) {
                         document_id = document_id_;
                         break;
@@ -435,14 +486,16 @@ impl Store {
             }
 
             // Reserve the id
-            let key = IndexKey {
-                account_id,
-                collection,
-                document_id,
-                field: u8::MAX,
-                key: &[],
-            }
-            .serialize();
+            let key = self.key(
+                IndexKey {
+                    account_id,
+                    collection,
+                    document_id,
+                    field: u8::MAX,
+                    key: &[],
+                }
+                .serialize(),
+            );
             trx.get(&key, false).await?; // Read to create conflict range
             trx.set(&key, &now().serialize());
 
@@ -461,12 +514,58 @@ impl Store {
         }
     }
 
+    // Checksummed blocks can't be updated with atomic mutations, so each modified
+    // block is read, updated and written back along with its checksum.
+    async fn write_checksummed_bitmaps(
+        &self,
+        trx: &Transaction,
+        set_bitmaps: &AHashMap<Vec<u8>, DenseBitmap>,
+        clear_bitmaps: &AHashMap<Vec<u8>, DenseBitmap>,
+    ) -> crate::Result<()> {
+        for key in set_bitmaps.keys().chain(
+            clear_bitmaps
+                .keys()
+                .filter(|key| !set_bitmaps.contains_key(*key)),
+        ) {
+            let block_num = key.deserialize_be_u32(key.len() - std::mem::size_of::<u32>())?;
+            let block_key = self.key(key.as_slice());
+            let mut block = if let Some(bytes) = trx.get(&block_key, false).await? {
+                DenseBitmap::from_bytes(
+                    block_bytes(&bytes, block_num, self.bitmap_checksum.verify)?,
+                    self.bitmap_block,
+                )
+            } else {
+                DenseBitmap::empty(self.bitmap_block)
+            };
+            if let Some(set) = set_bitmaps.get(key) {
+                for (byte, set) in block.bitmap.iter_mut().zip(set.bitmap.iter()) {
+                    *byte |= *set;
+                }
+            }
+            if let Some(clear) = clear_bitmaps.get(key) {
+                for (byte, clear) in block.bitmap.iter_mut().zip(clear.bitmap.iter()) {
+                    *byte &= !*clear;
+                }
+            }
+
+            if !block.is_empty() {
+                trx.set(&block_key, &block.with_checksum());
+            } else {
+                trx.clear(&block_key);
+            }
+        }
+
+        Ok(())
+    }
+
     pub async fn assign_change_id(&self, account_id: u32) -> crate::Result<u64> {
         let start = Instant::now();
-        let counter = KeySerializer::new(std::mem::size_of::<u32>() + 2)
-            .write(SUBSPACE_VALUES)
-            .write(account_id)
-            .finalize();
+        let counter = self.key(
+            KeySerializer::new(std::mem::size_of::<u32>() + 2)
+                .write(SUBSPACE_VALUES)
+                .write(account_id)
+                .finalize(),
+        );
 
         loop {
             // Read id
@@ -496,7 +595,7 @@ impl Store {
     #[cfg(feature = "test_mode")]
     pub async fn destroy(&self) {
         let trx = self.db.create_trx().unwrap();
-        trx.clear_range(&[0u8], &[u8::MAX]);
+        trx.clear_range(&self.key([0u8]), &self.key([u8::MAX]));
         trx.commit().await.unwrap();
         BITMAPS.lock().clear();
     }
@@ -53,6 +53,15 @@ impl ReadTransaction<'_> {
             .map_err(Into::into)
     }
 
+    #[maybe_async::maybe_async]
+    pub async fn exists(&self, key: impl Key) -> crate::Result<bool> {
+        let key = key.serialize();
+        self.conn
+            .prepare_cached("SELECT 1 FROM v WHERE k = ?")?
+            .exists([&key])
+            .map_err(Into::into)
+    }
+
     #[maybe_async::maybe_async]
     async fn get_bitmap_<T: AsRef<[u8]>>(
         &self,
@@ -158,6 +167,75 @@ impl ReadTransaction<'_> {
         value: Vec<u8>,
         op: Operator,
     ) -> crate::Result<Option<RoaringBitmap>> {
+        let (query, begin, end) = Self::index_range(account_id, collection, field, &value, op);
+
+        let mut bm = RoaringBitmap::new();
+        let mut query = self.conn.prepare_cached(query)?;
+        let mut rows = query.query([&begin, &end])?;
+
+        if op != Operator::Equal {
+            while let Some(row) = rows.next()? {
+                let key = row.get_ref(0)?.as_bytes()?;
+                bm.insert(key.deserialize_be_u32(key.len() - std::mem::size_of::<u32>())?);
+            }
+        } else {
+            let key_len = begin.len();
+            while let Some(row) = rows.next()? {
+                let key = row.get_ref(0)?.as_bytes()?;
+                if key.len() == key_len {
+                    bm.insert(key.deserialize_be_u32(key.len() - std::mem::size_of::<u32>())?);
+                }
+            }
+        }
+
+        Ok(Some(bm))
+    }
+
+    #[maybe_async::maybe_async]
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) async fn range_to_ids(
+        &self,
+        account_id: u32,
+        collection: u8,
+        field: u8,
+        value: Vec<u8>,
+        op: Operator,
+        limit: usize,
+        reverse: bool,
+    ) -> crate::Result<Vec<u32>> {
+        let mut ids = Vec::with_capacity(std::cmp::min(limit, 1024));
+        if limit == 0 {
+            return Ok(ids);
+        }
+
+        let (query, begin, end) = Self::index_range(account_id, collection, field, &value, op);
+        let key_len = begin.len();
+        let mut query = self.conn.prepare_cached(&format!(
+            "{query} ORDER BY k {}",
+            if reverse { "DESC" } else { "ASC" }
+        ))?;
+        let mut rows = query.query([&begin, &end])?;
+
+        while let Some(row) = rows.next()? {
+            let key = row.get_ref(0)?.as_bytes()?;
+            if op != Operator::Equal || key.len() == key_len {
+                ids.push(key.deserialize_be_u32(key.len() - std::mem::size_of::<u32>())?);
+                if ids.len() == limit {
+                    break;
+                }
+            }
+        }
+
+        Ok(ids)
+    }
+
+    fn index_range(
+        account_id: u32,
+        collection: u8,
+        field: u8,
+        value: &[u8],
+        op: Operator,
+    ) -> (&'static str, Vec<u8>, Vec<u8>) {
         let k1 = KeySerializer::new(
             std::mem::size_of::<IndexKey<&[u8]>>() + value.len() + 1 + std::mem::size_of::<u32>(),
         )
@@ -171,54 +249,33 @@ impl ReadTransaction<'_> {
         .write(collection)
         .write(field + matches!(op, Operator::GreaterThan | Operator::GreaterEqualThan) as u8);
 
-        let (query, begin, end) = match op {
+        match op {
             Operator::LowerThan => (
                 ("SELECT k FROM i WHERE k >= ? AND k < ?"),
                 (k1.finalize()),
-                (k2.write(&value[..]).write(0u32).finalize()),
+                (k2.write(value).write(0u32).finalize()),
             ),
             Operator::LowerEqualThan => (
                 ("SELECT k FROM i WHERE k >= ? AND k <= ?"),
                 (k1.finalize()),
-                (k2.write(&value[..]).write(u32::MAX).finalize()),
+                (k2.write(value).write(u32::MAX).finalize()),
             ),
             Operator::GreaterThan => (
                 ("SELECT k FROM i WHERE k > ? AND k <= ?"),
-                (k1.write(&value[..]).write(u32::MAX).finalize()),
+                (k1.write(value).write(u32::MAX).finalize()),
                 (k2.finalize()),
             ),
             Operator::GreaterEqualThan => (
                 ("SELECT k FROM i WHERE k >= ? AND k <= ?"),
-                (k1.write(&value[..]).write(0u32).finalize()),
+                (k1.write(value).write(0u32).finalize()),
                 (k2.finalize()),
             ),
             Operator::Equal => (
                 ("SELECT k FROM i WHERE k >= ? AND k <= ?"),
-                (k1.write(&value[..]).write(0u32).finalize()),
-                (k2.write(&value[..]).write(u32::MAX).finalize()),
+                (k1.write(value).write(0u32).finalize()),
+                (k2.write(value).write(u32::MAX).finalize()),
             ),
-        };
-
-        let mut bm = RoaringBitmap::new();
-        let mut query = self.conn.prepare_cached(query)?;
-        let mut rows = query.query([&begin, &end])?;
-
-        if op != Operator::Equal {
-            while let Some(row) = rows.next()? {
-                let key = row.get_ref(0)?.as_bytes()?;
-                bm.insert(key.deserialize_be_u32(key.len() - std::mem::size_of::<u32>())?);
-            }
-        } else {
-            let key_len = begin.len();
-            while let Some(row) = rows.next()? {
-                let key = row.get_ref(0)?.as_bytes()?;
-                if key.len() == key_len {
-                    bm.insert(key.deserialize_be_u32(key.len() - std::mem::size_of::<u32>())?);
-                }
-            }
         }
-
-        Ok(Some(bm))
     }
 
     #[maybe_async::maybe_async]
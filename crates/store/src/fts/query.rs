@@ -21,11 +21,13 @@
  * for more details.
 */
 
+use ahash::AHashMap;
 use nlp::language::{stemmer::Stemmer, Language};
 use roaring::RoaringBitmap;
 
 use crate::{
-    fts::builder::MAX_TOKEN_LENGTH, BitmapKey, ReadTransaction, ValueKey, HASH_EXACT, HASH_STEMMED,
+    fts::builder::MAX_TOKEN_LENGTH, query::ResultSet, BitmapKey, ReadTransaction, ValueKey,
+    HASH_EXACT, HASH_STEMMED,
 };
 
 use super::term_index::TermIndex;
@@ -152,4 +154,146 @@ impl ReadTransaction<'_> {
             Ok(Some(bitmaps))
         }
     }
+
+    // Scores each document with BM25 over the full-text conditions that matched it,
+    // using the term frequencies stored in its term index. The term index of every
+    // result is read, so this only runs when the results are sorted by relevance.
+    #[maybe_async::maybe_async]
+    pub(crate) async fn fts_scores(
+        &mut self,
+        result_set: &ResultSet,
+    ) -> crate::Result<AHashMap<u32, u32>> {
+        let account_id = result_set.account_id;
+        let collection = result_set.collection;
+        let mut scores = AHashMap::with_capacity(result_set.results.len() as usize);
+        if result_set.fts_terms.is_empty() || result_set.results.is_empty() {
+            return Ok(scores);
+        }
+
+        // Obtain the inverse document frequency of each term
+        let total_docs = self
+            .get_bitmap(BitmapKey::document_ids(account_id, collection))
+            .await?
+            .map_or(0, |b| b.len()) as f64;
+        let mut terms = Vec::new();
+        for fts_term in &result_set.fts_terms {
+            let mut words = Vec::new();
+            let mut bit_keys = Vec::new();
+            if fts_term.match_phrase {
+                for token in fts_term
+                    .language
+                    .tokenize_text(&fts_term.text, MAX_TOKEN_LENGTH)
+                {
+                    bit_keys.push(BitmapKey::hash(
+                        token.word.as_ref(),
+                        account_id,
+                        collection,
+                        HASH_EXACT,
+                        fts_term.field,
+                    ));
+                    words.push((token.word.into_owned(), None));
+                }
+                if !words.is_empty() {
+                    self.refresh_if_old().await?;
+                    let doc_freq = self
+                        .get_bitmaps_intersection(bit_keys)
+                        .await?
+                        .map_or(0, |b| b.len());
+                    terms.push((fts_term.field, true, words, idf(total_docs, doc_freq)));
+                }
+            } else {
+                for token in Stemmer::new(&fts_term.text, fts_term.language, MAX_TOKEN_LENGTH) {
+                    let word = token.word.into_owned();
+                    let stemmed_word = token.stemmed_word.map(|w| w.into_owned());
+                    let token1 =
+                        BitmapKey::hash(&word, account_id, collection, HASH_EXACT, fts_term.field);
+                    let token2 = BitmapKey::hash(
+                        stemmed_word.as_deref().unwrap_or(&word),
+                        account_id,
+                        collection,
+                        HASH_STEMMED,
+                        fts_term.field,
+                    );
+                    self.refresh_if_old().await?;
+                    let doc_freq = self
+                        .get_bitmaps_union(vec![token1, token2])
+                        .await?
+                        .map_or(0, |b| b.len());
+                    terms.push((
+                        fts_term.field,
+                        false,
+                        vec![(word, stemmed_word)],
+                        idf(total_docs, doc_freq),
+                    ));
+                }
+            }
+        }
+
+        // Obtain the term frequencies and field lengths of each document
+        let mut doc_stats = Vec::with_capacity(result_set.results.len() as usize);
+        let mut total_len = 0;
+        for document_id in &result_set.results {
+            self.refresh_if_old().await?;
+            let term_index = if let Some(term_index) = self
+                .get_value::<TermIndex>(ValueKey::term_index(account_id, collection, document_id))
+                .await?
+            {
+                term_index
+            } else {
+                continue;
+            };
+
+            let mut freqs = Vec::with_capacity(terms.len());
+            let mut doc_len = 0;
+            for (field, match_phrase, words, _) in &terms {
+                let match_terms = words
+                    .iter()
+                    .map(|(word, stemmed_word)| {
+                        term_index.get_match_term(word, stemmed_word.as_deref())
+                    })
+                    .collect::<Vec<_>>();
+                let matches = term_index
+                    .match_terms(&match_terms, Some(*field), *match_phrase, true, false)
+                    .map_err(|e| {
+                        crate::Error::InternalError(format!(
+                            "TermIndex match_terms failed for {account_id}/{collection}/{document_id}: {e:?}"
+                        ))
+                    })?
+                    .map_or(0, |groups| {
+                        groups.iter().map(|group| group.terms.len()).sum::<usize>()
+                    });
+                freqs.push((matches / words.len()) as f64);
+            }
+            for item in &term_index.items {
+                if terms.iter().any(|(field, ..)| *field == item.field_id) {
+                    doc_len += item.terms_len;
+                }
+            }
+            total_len += doc_len;
+            doc_stats.push((document_id, freqs, doc_len as f64));
+        }
+
+        if !doc_stats.is_empty() {
+            let avg_len = (total_len as f64 / doc_stats.len() as f64).max(1.0);
+            for (document_id, freqs, doc_len) in doc_stats {
+                let norm = BM25_K1 * (1.0 - BM25_B + BM25_B * doc_len / avg_len);
+                let score = terms
+                    .iter()
+                    .zip(freqs)
+                    .map(|((.., idf), tf)| idf * (tf * (BM25_K1 + 1.0)) / (tf + norm))
+                    .sum::<f64>();
+                scores.insert(document_id, (score * 1000.0) as u32);
+            }
+        }
+
+        Ok(scores)
+    }
+}
+
+const BM25_K1: f64 = 1.2;
+const BM25_B: f64 = 0.75;
+
+fn idf(total_docs: f64, doc_freq: u64) -> f64 {
+    let doc_freq = doc_freq as f64;
+    (1.0 + (total_docs - doc_freq + 0.5) / (doc_freq + 0.5)).ln()
 }
@@ -48,6 +48,13 @@ pub struct Store {
     db: foundationdb::Database,
     guard: foundationdb::api::NetworkAutoStop,
     blob: BlobStore,
+    fast_fail: Option<std::time::Duration>,
+    refresh_interval: std::time::Duration,
+    key_prefix: Vec<u8>,
+    value_chunk_size: usize,
+    bitmap_block: backend::foundationdb::bitmap::BlockSize,
+    bitmap_checksum: backend::foundationdb::bitmap::BlockChecksum,
+    scan_limiter: Option<backend::foundationdb::limiter::ScanLimiter>,
 }
 
 #[cfg(feature = "foundation")]
@@ -55,6 +62,14 @@ pub struct ReadTransaction<'x> {
     db: &'x foundationdb::Database,
     pub trx: foundationdb::Transaction,
     trx_age: std::time::Instant,
+    fast_fail: Option<std::time::Duration>,
+    refresh_interval: std::time::Duration,
+    key_prefix: &'x [u8],
+    value_chunk_size: usize,
+    verify_checksums: bool,
+    bitmap_block: backend::foundationdb::bitmap::BlockSize,
+    snapshot: bool,
+    scan_limiter: Option<&'x backend::foundationdb::limiter::ScanLimiter>,
 }
 
 #[cfg(feature = "sqlite")]
@@ -195,6 +210,8 @@ pub type Result<T> = std::result::Result<T, Error>;
 pub enum Error {
     InternalError(String),
     AssertValueFailed,
+    ChecksumMismatch(String),
+    Retryable(String),
 }
 
 impl std::error::Error for Error {}
@@ -204,6 +221,8 @@ impl Display for Error {
         match self {
             Error::InternalError(msg) => write!(f, "Internal Error: {}", msg),
             Error::AssertValueFailed => write!(f, "Transaction failed: Hash mismatch"),
+            Error::ChecksumMismatch(msg) => write!(f, "Data corruption detected: {}", msg),
+            Error::Retryable(msg) => write!(f, "Transaction must be retried: {}", msg),
         }
     }
 }
@@ -288,6 +307,17 @@ impl ReadTransaction<'_> {
         unimplemented!("No backend selected")
     }
 
+    pub async fn exists(&self, _key: impl Key) -> crate::Result<bool> {
+        unimplemented!("No backend selected")
+    }
+
+    pub async fn get_values<U>(&self, _keys: Vec<impl Key>) -> crate::Result<Vec<Option<U>>>
+    where
+        U: Deserialize,
+    {
+        unimplemented!("No backend selected")
+    }
+
     pub async fn get_bitmap<T: AsRef<[u8]>>(
         &self,
         _key: BitmapKey<T>,
@@ -295,6 +325,10 @@ impl ReadTransaction<'_> {
         unimplemented!("No backend selected")
     }
 
+    pub async fn get_bitmap_count<T: AsRef<[u8]>>(&self, _key: BitmapKey<T>) -> crate::Result<u64> {
+        unimplemented!("No backend selected")
+    }
+
     pub(crate) async fn get_bitmaps_intersection<T: AsRef<[u8]>>(
         &self,
         _keys: Vec<BitmapKey<T>>,
@@ -320,6 +354,20 @@ impl ReadTransaction<'_> {
         unimplemented!("No backend selected")
     }
 
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) async fn range_to_ids(
+        &self,
+        _account_id: u32,
+        _collection: u8,
+        _field: u8,
+        _value: Vec<u8>,
+        _op: query::Operator,
+        _limit: usize,
+        _reverse: bool,
+    ) -> crate::Result<Vec<u32>> {
+        unimplemented!("No backend selected")
+    }
+
     pub(crate) async fn sort_index(
         &self,
         _account_id: u32,
@@ -21,7 +21,7 @@
  * for more details.
 */
 
-use std::ops::{BitAndAssign, BitOrAssign, BitXorAssign};
+use std::ops::{BitAndAssign, BitOrAssign};
 
 use ahash::HashSet;
 use nlp::tokenizers::space::SpaceTokenizer;
@@ -29,11 +29,12 @@ use roaring::RoaringBitmap;
 
 use crate::{fts::builder::MAX_TOKEN_LENGTH, BitmapKey, ReadTransaction, Store};
 
-use super::{Filter, ResultSet, TextMatch};
+use super::{Filter, FtsTerm, ResultSet, TextMatch};
 
 struct State {
     op: Filter,
     bm: Option<RoaringBitmap>,
+    mask: Option<RoaringBitmap>,
 }
 
 impl ReadTransaction<'_> {
@@ -54,11 +55,13 @@ impl ReadTransaction<'_> {
                     .get_bitmap(BitmapKey::document_ids(account_id, collection))
                     .await?
                     .unwrap_or_else(RoaringBitmap::new),
+                fts_terms: Vec::new(),
             });
         }
 
         let mut state: State = Filter::And.into();
-        let mut stack = Vec::new();
+        let mut stack: Vec<State> = Vec::new();
+        let mut fts_terms = Vec::new();
         let mut filters = filters.into_iter().peekable();
 
         while let Some(filter) = filters.next() {
@@ -71,12 +74,38 @@ impl ReadTransaction<'_> {
                 }
                 Filter::HasText { field, text, op } => match op {
                     TextMatch::Exact(language) => {
-                        self.fts_query(account_id, collection, field, &text, language, true)
-                            .await?
+                        let result = self
+                            .fts_query(account_id, collection, field, &text, language, true)
+                            .await?;
+                        if !state.is_negated(&stack) {
+                            let term = FtsTerm {
+                                field,
+                                text,
+                                language,
+                                match_phrase: true,
+                            };
+                            if !fts_terms.contains(&term) {
+                                fts_terms.push(term);
+                            }
+                        }
+                        result
                     }
                     TextMatch::Stemmed(language) => {
-                        self.fts_query(account_id, collection, field, &text, language, false)
-                            .await?
+                        let result = self
+                            .fts_query(account_id, collection, field, &text, language, false)
+                            .await?;
+                        if !state.is_negated(&stack) {
+                            let term = FtsTerm {
+                                field,
+                                text,
+                                language,
+                                match_phrase: false,
+                            };
+                            if !fts_terms.contains(&term) {
+                                fts_terms.push(term);
+                            }
+                        }
+                        result
                     }
                     TextMatch::Tokenized => {
                         self.get_bitmaps_intersection(
@@ -108,8 +137,16 @@ impl ReadTransaction<'_> {
                 }
                 Filter::DocumentSet(set) => Some(set),
                 op @ (Filter::And | Filter::Or | Filter::Not) => {
+                    // Negations inside an AND only need to be complemented against
+                    // the candidates matched so far rather than the full document set
+                    let mask = if matches!(op, Filter::Not) && matches!(state.op, Filter::And) {
+                        state.bm.clone()
+                    } else {
+                        None
+                    };
                     stack.push(state);
                     state = op.into();
+                    state.mask = mask;
                     continue;
                 }
                 Filter::End => {
@@ -123,7 +160,7 @@ impl ReadTransaction<'_> {
                 }
             };
 
-            if matches!(state.op, Filter::Not) && !not_fetch {
+            if matches!(state.op, Filter::Not) && state.mask.is_none() && !not_fetch {
                 not_mask = self
                     .get_bitmap(BitmapKey::document_ids(account_id, collection))
                     .await?
@@ -131,7 +168,11 @@ impl ReadTransaction<'_> {
                 not_fetch = true;
             }
 
-            state.op.apply(&mut state.bm, result, &not_mask);
+            state.op.apply(
+                &mut state.bm,
+                result,
+                state.mask.as_ref().unwrap_or(&not_mask),
+            );
 
             if matches!(state.op, Filter::And) && state.bm.as_ref().unwrap().is_empty() {
                 while let Some(filter) = filters.peek() {
@@ -148,6 +189,7 @@ impl ReadTransaction<'_> {
             account_id,
             collection,
             results: state.bm.unwrap_or_else(RoaringBitmap::new),
+            fts_terms,
         })
     }
 }
@@ -182,7 +224,7 @@ impl Filter {
     pub fn apply(
         &self,
         dest: &mut Option<RoaringBitmap>,
-        mut src: Option<RoaringBitmap>,
+        src: Option<RoaringBitmap>,
         not_mask: &RoaringBitmap,
     ) {
         if let Some(dest) = dest {
@@ -200,18 +242,18 @@ impl Filter {
                     }
                 }
                 Filter::Not => {
-                    if let Some(mut src) = src {
-                        src.bitxor_assign(not_mask);
-                        dest.bitand_assign(src);
+                    if let Some(src) = src {
+                        dest.bitand_assign(not_mask - src);
                     }
                 }
                 _ => unreachable!(),
             }
-        } else if let Some(ref mut src_) = src {
-            if let Filter::Not = self {
-                src_.bitxor_assign(not_mask);
-            }
-            *dest = src;
+        } else if let Some(src) = src {
+            *dest = Some(if let Filter::Not = self {
+                not_mask - src
+            } else {
+                src
+            });
         } else if let Filter::Not = self {
             *dest = Some(not_mask.clone());
         } else {
@@ -220,11 +262,18 @@ impl Filter {
     }
 }
 
+impl State {
+    fn is_negated(&self, stack: &[State]) -> bool {
+        matches!(self.op, Filter::Not) || stack.iter().any(|s| matches!(s.op, Filter::Not))
+    }
+}
+
 impl From<Filter> for State {
     fn from(value: Filter) -> Self {
         Self {
             op: value,
             bm: None,
+            mask: None,
         }
     }
 }
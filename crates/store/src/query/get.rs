@@ -23,7 +23,10 @@
 
 use roaring::RoaringBitmap;
 
-use crate::{BitmapKey, Deserialize, Key, Store};
+use crate::{query::Operator, BitmapKey, Deserialize, Key, Store};
+
+#[cfg(not(feature = "is_sync"))]
+const MAX_VALUES_PER_READ: usize = 256;
 
 impl Store {
     pub async fn get_value<U>(&self, key: impl Key) -> crate::Result<Option<U>>
@@ -42,6 +45,19 @@ impl Store {
         }
     }
 
+    pub async fn exists(&self, key: impl Key) -> crate::Result<bool> {
+        #[cfg(not(feature = "is_sync"))]
+        {
+            self.read_transaction().await?.exists(key).await
+        }
+
+        #[cfg(feature = "is_sync")]
+        {
+            let trx = self.read_transaction()?;
+            self.spawn_worker(move || trx.exists(key)).await
+        }
+    }
+
     pub async fn get_values<U>(&self, key: Vec<impl Key>) -> crate::Result<Vec<Option<U>>>
     where
         U: Deserialize + 'static,
@@ -50,10 +66,15 @@ impl Store {
         {
             let mut trx = self.read_transaction().await?;
             let mut results = Vec::with_capacity(key.len());
+            let mut keys = key.into_iter().peekable();
 
-            for key in key {
+            // Keys are read concurrently in batches, refreshing the transaction between them
+            while keys.peek().is_some() {
                 trx.refresh_if_old().await?;
-                results.push(trx.get_value(key).await?);
+                results.extend(
+                    trx.get_values(keys.by_ref().take(MAX_VALUES_PER_READ).collect::<Vec<_>>())
+                        .await?,
+                );
             }
 
             Ok(results)
@@ -126,6 +147,59 @@ impl Store {
         }
     }
 
+    pub async fn get_bitmap_count<T: AsRef<[u8]> + Send + Sync + 'static>(
+        &self,
+        key: BitmapKey<T>,
+    ) -> crate::Result<u64> {
+        #[cfg(not(feature = "is_sync"))]
+        {
+            self.read_transaction().await?.get_bitmap_count(key).await
+        }
+
+        #[cfg(feature = "is_sync")]
+        {
+            let trx = self.read_transaction()?;
+            self.spawn_worker(move || {
+                trx.get_bitmap(key)
+                    .map(|bitmap| bitmap.map_or(0, |bitmap| bitmap.len()))
+            })
+            .await
+        }
+    }
+
+    // Documents matching an index range in index order, stopping at the limit
+    #[allow(clippy::too_many_arguments)]
+    pub async fn range_to_ids(
+        &self,
+        account_id: u32,
+        collection: impl Into<u8>,
+        field: impl Into<u8>,
+        value: Vec<u8>,
+        op: Operator,
+        limit: usize,
+        reverse: bool,
+    ) -> crate::Result<Vec<u32>> {
+        let collection = collection.into();
+        let field = field.into();
+
+        #[cfg(not(feature = "is_sync"))]
+        {
+            self.read_transaction()
+                .await?
+                .range_to_ids(account_id, collection, field, value, op, limit, reverse)
+                .await
+        }
+
+        #[cfg(feature = "is_sync")]
+        {
+            let trx = self.read_transaction()?;
+            self.spawn_worker(move || {
+                trx.range_to_ids(account_id, collection, field, value, op, limit, reverse)
+            })
+            .await
+        }
+    }
+
     pub async fn iterate<T: Sync + Send + 'static>(
         &self,
         acc: T,
@@ -21,6 +21,7 @@
  * for more details.
 */
 
+use ahash::AHashMap;
 use utils::codec::leb128::Leb128Iterator;
 
 use crate::{write::key::DeserializeBigEndian, Error, LogKey, Store};
@@ -125,6 +126,113 @@ impl Store {
     }
 }
 
+impl Store {
+    /// Returns the time each id was inserted or last had one of `tags` set,
+    /// for changes logged at or after `since` (a UNIX timestamp). The log is
+    /// read backwards and stops at the first older entry, so entries logged
+    /// before change times were recorded are never visited.
+    pub async fn tag_set_times(
+        &self,
+        account_id: u32,
+        collection: impl Into<u8>,
+        tags: &[Vec<u8>],
+        since: u64,
+    ) -> crate::Result<AHashMap<u64, u64>> {
+        let collection = collection.into();
+        let tags = tags.to_vec();
+        let from_key = LogKey {
+            account_id,
+            collection,
+            change_id: 0,
+        };
+        let to_key = LogKey {
+            account_id,
+            collection,
+            change_id: u64::MAX,
+        };
+
+        self.iterate(
+            AHashMap::new(),
+            from_key,
+            to_key,
+            false,
+            false,
+            move |tag_set_times, _, value| {
+                let entry = deserialize_tag_sets(value).ok_or_else(|| {
+                    Error::InternalError(format!(
+                        "Failed to deserialize changelog for [{}/{}]",
+                        account_id, collection
+                    ))
+                })?;
+                match entry {
+                    Some(entry) if entry.timestamp >= since => {
+                        // Tags present when an item is inserted are set at insertion time
+                        for id in entry.inserts {
+                            tag_set_times.entry(id).or_insert(entry.timestamp);
+                        }
+                        for (id, tag) in entry.tag_sets {
+                            if tags.iter().any(|t| t.as_slice() == tag) {
+                                tag_set_times.entry(id).or_insert(entry.timestamp);
+                            }
+                        }
+                        Ok(true)
+                    }
+                    _ => Ok(false),
+                }
+            },
+        )
+        .await
+    }
+}
+
+struct TagSetEntry<'x> {
+    inserts: Vec<u64>,
+    tag_sets: Vec<(u64, &'x [u8])>,
+    timestamp: u64,
+}
+
+// Returns the inserted ids and the tags set on updated ids of a changelog
+// entry along with the time it was written, or `None` if the entry predates
+// change times.
+fn deserialize_tag_sets(bytes: &[u8]) -> Option<Option<TagSetEntry<'_>>> {
+    let mut bytes_it = bytes.iter();
+    let total_inserts: usize = bytes_it.next_leb128()?;
+    let total_updates: usize = bytes_it.next_leb128()?;
+    let total_child_updates: usize = bytes_it.next_leb128()?;
+    let total_deletes: usize = bytes_it.next_leb128()?;
+
+    let mut inserts = Vec::with_capacity(total_inserts);
+    for _ in 0..total_inserts {
+        inserts.push(bytes_it.next_leb128()?);
+    }
+    for _ in 0..(total_updates + total_child_updates + total_deletes) {
+        bytes_it.next_leb128::<u64>()?;
+    }
+
+    let timestamp = if let Some(timestamp) = bytes_it.next_leb128() {
+        timestamp
+    } else {
+        return Some(None);
+    };
+
+    let total_tag_sets: usize = bytes_it.next_leb128().unwrap_or(0);
+    let mut tag_sets = Vec::with_capacity(total_tag_sets);
+    for _ in 0..total_tag_sets {
+        let id = bytes_it.next_leb128()?;
+        let tag_len: usize = bytes_it.next_leb128()?;
+        let offset = bytes.len() - bytes_it.len();
+        let tag = bytes.get(offset..offset + tag_len)?;
+        bytes_it = bytes.get(offset + tag_len..)?.iter();
+        tag_sets.push((id, tag));
+    }
+
+    Some(Some(TagSetEntry {
+        inserts,
+        tag_sets,
+        timestamp,
+    }))
+}
+
 impl Changes {
     pub fn deserialize(&mut self, bytes: &[u8]) -> Option<()> {
         let mut bytes_it = bytes.iter();
@@ -26,6 +26,7 @@ pub mod get;
 pub mod log;
 pub mod sort;
 
+use ahash::AHashMap;
 use nlp::language::Language;
 use roaring::RoaringBitmap;
 
@@ -40,7 +41,7 @@ pub enum Operator {
     Equal,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum Filter {
     MatchValue {
         field: u8,
@@ -64,7 +65,7 @@ pub enum Filter {
     End,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum TextMatch {
     Exact(Language),
     Stemmed(Language),
@@ -76,6 +77,7 @@ pub enum TextMatch {
 pub enum Comparator {
     Field { field: u8, ascending: bool },
     DocumentSet { set: RoaringBitmap, ascending: bool },
+    Relevance { ascending: bool },
 }
 
 #[derive(Debug)]
@@ -83,12 +85,24 @@ pub struct ResultSet {
     pub account_id: u32,
     pub collection: u8,
     pub results: RoaringBitmap,
+    pub fts_terms: Vec<FtsTerm>,
+}
+
+// Full-text conditions that matched the result set, kept so that relevance
+// scores are only computed when the results are sorted by relevance.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FtsTerm {
+    pub field: u8,
+    pub text: String,
+    pub language: Language,
+    pub match_phrase: bool,
 }
 
 pub struct SortedResultSet {
     pub position: i32,
     pub ids: Vec<u64>,
     pub found_anchor: bool,
+    pub prefetched: AHashMap<ValueKey, Vec<u8>>,
 }
 
 impl ResultSet {
@@ -97,6 +111,7 @@ impl ResultSet {
             account_id,
             collection: collection.into(),
             results,
+            fts_terms: Vec::new(),
         }
     }
 
@@ -221,6 +236,21 @@ impl Comparator {
         Self::DocumentSet { set, ascending }
     }
 
+    pub fn relevance(ascending: bool) -> Self {
+        Self::Relevance { ascending }
+    }
+
+    // Results with the same relevance are returned newest first
+    pub fn with_relevance_tiebreaker(
+        mut comparators: Vec<Comparator>,
+        date_field: impl Into<u8>,
+    ) -> Vec<Comparator> {
+        if matches!(comparators.last(), Some(Comparator::Relevance { .. })) {
+            comparators.push(Comparator::descending(date_field));
+        }
+        comparators
+    }
+
     pub fn ascending(field: impl Into<u8>) -> Self {
         Self::Field {
             field: field.into(),
@@ -25,7 +25,7 @@ use std::cmp::Ordering;
 
 use ahash::{AHashMap, AHashSet};
 
-use crate::{ReadTransaction, Store, ValueKey};
+use crate::{Deserialize, ReadTransaction, Store, ValueKey};
 
 use super::{Comparator, ResultSet, SortedResultSet};
 
@@ -40,16 +40,55 @@ pub struct Pagination {
     ids: Vec<u64>,
     prefix_key: Option<ValueKey>,
     prefix_unique: bool,
+    prefetch: Vec<ValueKey>,
 }
 
+struct RawBytes(Vec<u8>);
+
 impl ReadTransaction<'_> {
     #[maybe_async::maybe_async]
     pub async fn sort(
+        &mut self,
+        result_set: ResultSet,
+        comparators: Vec<Comparator>,
+        mut paginate: Pagination,
+    ) -> crate::Result<SortedResultSet> {
+        let prefetch = std::mem::take(&mut paginate.prefetch);
+        let mut sorted_results = self.sort_(result_set, comparators, paginate).await?;
+
+        // Fetch the requested values of the returned documents within the same transaction
+        if !prefetch.is_empty() {
+            for id in &sorted_results.ids {
+                for key in &prefetch {
+                    let key = key.with_document_id(*id as u32);
+                    self.refresh_if_old().await?;
+                    if let Some(RawBytes(value)) = self.get_value::<RawBytes>(key).await? {
+                        sorted_results.prefetched.insert(key, value);
+                    }
+                }
+            }
+        }
+
+        Ok(sorted_results)
+    }
+
+    #[maybe_async::maybe_async]
+    async fn sort_(
         &mut self,
         result_set: ResultSet,
         mut comparators: Vec<Comparator>,
         mut paginate: Pagination,
     ) -> crate::Result<SortedResultSet> {
+        let scores = if comparators
+            .iter()
+            .any(|comparator| matches!(comparator, Comparator::Relevance { .. }))
+        {
+            self.fts_scores(&result_set).await?
+        } else {
+            AHashMap::new()
+        };
+        let score = |document_id: u32| scores.get(&document_id).copied().unwrap_or_default();
+
         if comparators.len() == 1 && !paginate.prefix_unique {
             match comparators.pop().unwrap() {
                 Comparator::Field { field, ascending } => {
@@ -91,6 +130,22 @@ impl ReadTransaction<'_> {
                         }
                     }
                 }
+                Comparator::Relevance { ascending } => {
+                    let mut document_ids = result_set.results.iter().collect::<Vec<_>>();
+                    document_ids.sort_by(|a, b| {
+                        let (a, b) = (score(*a), score(*b));
+                        if ascending {
+                            a.cmp(&b)
+                        } else {
+                            b.cmp(&a)
+                        }
+                    });
+                    for document_id in document_ids {
+                        if !paginate.add(0, document_id) {
+                            break;
+                        }
+                    }
+                }
             }
 
             // Obtain prefixes
@@ -174,6 +229,16 @@ impl ReadTransaction<'_> {
                             }
                         }
                     }
+                    Comparator::Relevance { ascending } => {
+                        for document_id in &result_set.results {
+                            let doc_score = score(document_id);
+                            sorted_ids.entry(document_id).or_insert([0u32; 4])[pos] = if ascending {
+                                doc_score
+                            } else {
+                                u32::MAX - doc_score
+                            };
+                        }
+                    }
                 }
             }
 
@@ -253,6 +318,7 @@ impl Store {
                     position: paginate.position,
                     ids: vec![],
                     found_anchor: true,
+                    prefetched: AHashMap::new(),
                 });
             }
             (_, 0) => result_set.results.len() as usize,
@@ -291,6 +357,7 @@ impl Pagination {
             ids: Vec::with_capacity(limit),
             prefix_key: None,
             prefix_unique: false,
+            prefetch: Vec::new(),
         }
     }
 
@@ -304,6 +371,11 @@ impl Pagination {
         self
     }
 
+    pub fn with_prefetch(mut self, prefetch: Vec<ValueKey>) -> Self {
+        self.prefetch = prefetch;
+        self
+    }
+
     pub fn add(&mut self, prefix_id: u32, document_id: u32) -> bool {
         let id = ((prefix_id as u64) << 32) | document_id as u64;
 
@@ -359,6 +431,7 @@ impl Pagination {
             ids: self.ids,
             position: 0,
             found_anchor: !self.has_anchor || self.anchor_found,
+            prefetched: AHashMap::new(),
         };
 
         if result.found_anchor {
@@ -391,3 +464,9 @@ impl Pagination {
         result
     }
 }
+
+impl Deserialize for RawBytes {
+    fn deserialize(bytes: &[u8]) -> crate::Result<Self> {
+        Ok(RawBytes(bytes.to_vec()))
+    }
+}
@@ -26,7 +26,7 @@ use utils::{codec::leb128::Leb128Vec, map::vec_map::VecMap};
 
 use crate::Serialize;
 
-use super::{IntoOperations, Operation};
+use super::{now, IntoOperations, Operation};
 
 #[derive(Default)]
 pub struct ChangeLogBuilder {
@@ -40,6 +40,7 @@ pub struct Changes {
     pub updates: AHashSet<u64>,
     pub deletes: AHashSet<u64>,
     pub child_updates: AHashSet<u64>,
+    pub tag_sets: AHashSet<(u64, Vec<u8>)>,
 }
 
 impl ChangeLogBuilder {
@@ -85,6 +86,20 @@ impl ChangeLogBuilder {
             .insert(jmap_id.into());
     }
 
+    // Records that a tag (such as a keyword) was added to an updated item, so
+    // that the time it was set can be looked up later.
+    pub fn log_tag_set(
+        &mut self,
+        collection: impl Into<u8>,
+        jmap_id: impl Into<u64>,
+        tag: impl Serialize,
+    ) {
+        self.changes
+            .get_mut_or_insert(collection.into())
+            .tag_sets
+            .insert((jmap_id.into(), tag.serialize()));
+    }
+
     pub fn log_move(
         &mut self,
         collection: impl Into<u8>,
@@ -130,10 +145,12 @@ impl ChangeLogBuilder {
                 }
                 this.updates.remove(&id);
                 this.child_updates.remove(&id);
+                this.tag_sets.retain(|(tag_id, _)| *tag_id != id);
             }
             this.inserts.extend(other.inserts);
             this.updates.extend(other.updates);
             this.child_updates.extend(other.child_updates);
+            this.tag_sets.extend(other.tag_sets);
         }
     }
 
@@ -175,6 +192,17 @@ impl Serialize for Changes {
                 buf.push_leb128(id);
             }
         }
+
+        // Time of the change, entries written before it was added end after the ids
+        buf.push_leb128(now());
+
+        // Tags added to updated items
+        buf.push_leb128(self.tag_sets.len());
+        for (id, tag) in self.tag_sets {
+            buf.push_leb128(id);
+            buf.push_leb128(tag.len());
+            buf.extend_from_slice(&tag);
+        }
         buf
     }
 }
@@ -37,6 +37,15 @@ pub struct RateLimiter {
     tokens: u64,
 }
 
+// Refills continuously at a fixed rate, allowing bursts of up to `burst` requests
+#[derive(Debug)]
+pub struct TokenBucket {
+    pub rate: u64,
+    pub burst: u64,
+    last_refill: Instant,
+    tokens: f64,
+}
+
 #[derive(Debug, Clone)]
 pub struct ConcurrencyLimiter {
     pub max_concurrent: u64,
@@ -105,6 +114,46 @@ impl RateLimiter {
     }
 }
 
+impl TokenBucket {
+    pub fn new(rate: u64, burst: u64) -> Self {
+        let burst = burst.max(1);
+        TokenBucket {
+            rate,
+            burst,
+            last_refill: Instant::now(),
+            tokens: burst as f64,
+        }
+    }
+
+    pub fn is_allowed(&mut self) -> bool {
+        self.is_allowed_at(Instant::now())
+    }
+
+    pub fn is_allowed_n(&mut self, tokens: u64) -> bool {
+        self.is_allowed_n_at(tokens, Instant::now())
+    }
+
+    pub fn is_allowed_at(&mut self, now: Instant) -> bool {
+        self.is_allowed_n_at(1, now)
+    }
+
+    // Takes either all the requested tokens or none of them
+    pub fn is_allowed_n_at(&mut self, tokens: u64, now: Instant) -> bool {
+        // Refill tokens for the elapsed time
+        let elapsed = now.saturating_duration_since(self.last_refill);
+        self.tokens =
+            (self.tokens + elapsed.as_secs_f64() * self.rate as f64).min(self.burst as f64);
+        self.last_refill = now;
+
+        if self.tokens >= tokens as f64 {
+            self.tokens -= tokens as f64;
+            true
+        } else {
+            false
+        }
+    }
+}
+
 impl ConcurrencyLimiter {
     pub fn new(max_concurrent: u64) -> Self {
         ConcurrencyLimiter {
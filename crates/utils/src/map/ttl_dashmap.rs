@@ -27,6 +27,10 @@ pub trait TtlMap<K, V>: Sized {
         K: Borrow<Q>,
         Q: Hash + Eq;
     fn insert_with_ttl(&self, name: K, value: V, valid_until: Instant) -> V;
+    fn remove_with_ttl<Q: ?Sized>(&self, name: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq;
     fn cleanup(&self);
 }
 
@@ -61,6 +65,17 @@ impl<K: Hash + Eq, V: Clone> TtlMap<K, V> for TtlDashMap<K, V> {
         item
     }
 
+    fn remove_with_ttl<Q: ?Sized>(&self, name: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq,
+    {
+        match self.remove(name) {
+            Some((_, entry)) if entry.valid_until >= Instant::now() => entry.item.into(),
+            _ => None,
+        }
+    }
+
     fn cleanup(&self) {
         self.retain(|_, entry| entry.valid_until >= Instant::now());
     }
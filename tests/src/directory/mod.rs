@@ -23,8 +23,10 @@
 
 pub mod imap;
 pub mod ldap;
+pub mod oidc;
 pub mod smtp;
 pub mod sql;
+pub mod webhook;
 
 use directory::{config::ConfigDirectory, AddressMapping, DirectoryConfig};
 use mail_send::Credentials;
@@ -42,18 +44,24 @@ address = "sqlite::memory:"
 [directory."sql".options]
 catch-all = true
 subaddressing = true
+lowercase-local-part = true
+idn-to-ascii = true
+allow-plaintext = true
 
 [directory."sql".pool]
 max-connections = 1
 
 [directory."sql".query]
 name = "SELECT name, type, secret, description, quota FROM accounts WHERE name = ? AND active = true"
-members = "SELECT member_of FROM group_members WHERE name = ?"
+members = "SELECT g.member_of, a.type FROM group_members AS g LEFT JOIN accounts AS a ON a.name = g.member_of WHERE g.name = ?"
 recipients = "SELECT name FROM emails WHERE address = ?"
 emails = "SELECT address FROM emails WHERE name = ? AND type != 'list' ORDER BY type DESC, address ASC"
 verify = "SELECT address FROM emails WHERE address LIKE '%' || ? || '%' AND type = 'primary' ORDER BY address LIMIT 5"
-expand = "SELECT p.address FROM emails AS p JOIN emails AS l ON p.name = l.name WHERE p.type = 'primary' AND l.address = ? AND l.type = 'list' ORDER BY p.address LIMIT 50"
+expand = "SELECT p.address, a.type FROM emails AS p JOIN emails AS l ON p.name = l.name JOIN accounts AS a ON a.name = p.name WHERE p.type = 'primary' AND l.address = ? AND l.type = 'list' ORDER BY p.address LIMIT 50"
 domains = "SELECT 1 FROM emails WHERE address LIKE '%@' || ? LIMIT 1"
+forwards = "SELECT address FROM forwards WHERE name = ?"
+vacation = "SELECT subject, message, from_date, to_date, rate FROM vacations WHERE name = ? AND active = true"
+by-external-id = "SELECT name FROM external_ids WHERE external_id = ?"
 
 [directory."sql".columns]
 name = "name"
@@ -62,6 +70,11 @@ secret = "secret"
 email = "address"
 quota = "quota"
 type = "type"
+vacation-subject = "subject"
+vacation-message = "message"
+vacation-from = "from_date"
+vacation-to = "to_date"
+vacation-rate = "rate"
 
 [directory."ldap"]
 type = "ldap"
@@ -120,6 +133,22 @@ port = 9199
 auth-errors = 3
 rcpt = 5
 
+[directory."sql-cached"]
+type = "sql"
+address = "sqlite::memory:"
+
+[directory."sql-cached".pool]
+max-connections = 1
+
+[directory."sql-cached".query]
+name = "SELECT name, type, secret, description, quota FROM accounts WHERE name = ? AND active = true"
+recipients = "SELECT name FROM emails WHERE address = ?"
+domains = "SELECT 1 FROM emails WHERE address LIKE '%@' || ? LIMIT 1"
+
+[directory."sql-cached".cache]
+entries = 100
+ttl = {positive = '2s', negative = '1s'}
+
 [directory."smtp".pool]
 max-connections = 5
 
@@ -491,12 +520,24 @@ fn address_mappings() {
     subaddressing = { map = "^([^.]+)\.([^.]+)@(.+)$", to = "${2}@${3}" }
     expected-sub = "doe+alias@example.org"
     expected-catch = "info@example.org"
+
+    [delimiter]
+    catch-all = { delimiter = "+" }
+    subaddressing = { delimiter = "+-" }
+    expected-sub = "john.doe@example.org"
+    expected-catch = "@example.org"
+
+    [other-domain]
+    catch-all = { domains = ["example.net"] }
+    subaddressing = { domains = ["example.net"] }
+    expected-sub = "john.doe+alias@example.org"
+    expected-catch = false
     "#;
 
     let config = utils::config::Config::new(MAPPINGS).unwrap();
     const ADDR: &str = "john.doe+alias@example.org";
 
-    for test in ["enable", "disable", "custom"] {
+    for test in ["enable", "disable", "custom", "delimiter", "other-domain"] {
         let catch_all = AddressMapping::from_config(&config, (test, "catch-all")).unwrap();
         let subaddressing = AddressMapping::from_config(&config, (test, "subaddressing")).unwrap();
 
@@ -515,4 +556,23 @@ fn address_mappings() {
             "failed catch-all for {test:?}"
         );
     }
+
+    // Tags split on any of the configured delimiters
+    let subaddressing =
+        AddressMapping::from_config(&config, ("delimiter", "subaddressing")).unwrap();
+    assert_eq!(
+        subaddressing.to_subaddress("john-project@example.org"),
+        "john@example.org"
+    );
+    let subaddressing =
+        AddressMapping::from_config(&config, ("other-domain", "subaddressing")).unwrap();
+    assert_eq!(
+        subaddressing.to_subaddress("john+project@Example.NET"),
+        "john@Example.NET"
+    );
+    let catch_all = AddressMapping::from_config(&config, ("other-domain", "catch-all")).unwrap();
+    assert_eq!(
+        catch_all.to_catch_all("john@Example.NET"),
+        Some(Cow::Borrowed("@Example.NET"))
+    );
 }
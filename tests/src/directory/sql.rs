@@ -21,8 +21,13 @@
  * for more details.
 */
 
-use directory::{Directory, Principal, Type};
+use std::time::Duration;
+
+use directory::{
+    config::ConfigDirectory, Directory, DomainStatus, Principal, RecipientRoute, Type, Vacation,
+};
 use mail_send::Credentials;
+use utils::config::Rate;
 
 use crate::directory::parse_config;
 
@@ -77,10 +82,26 @@ async fn sql_directory() {
     link_test_address(handle.as_ref(), "jane", "info@example.org", "list").await;
     link_test_address(handle.as_ref(), "bill", "info@example.org", "list").await;
 
+    // Add forwarding and vacation settings
+    for query in [
+        "INSERT INTO forwards (name, address) VALUES ('jane', 'jane@forward.example.net')",
+        "INSERT INTO vacations (name, subject, message, rate) VALUES ('jane', 'Away', 'I am out of the office.', '1/1d')",
+        "INSERT INTO external_ids (name, external_id) VALUES ('john', 'idp|5f2a9c')",
+    ] {
+        handle.query(query, &[]).await.unwrap();
+    }
+
     // Add catch-all user
     create_test_user(handle.as_ref(), "robert", "abcde", "Robert Foobar").await;
     link_test_address(handle.as_ref(), "robert", "robert@catchall.org", "primary").await;
     link_test_address(handle.as_ref(), "robert", "@catchall.org", "alias").await;
+    link_test_address(
+        handle.as_ref(),
+        "robert",
+        "robert@xn--bcher-kva.example",
+        "alias",
+    )
+    .await;
 
     // Text lookup
     assert!(lookups
@@ -138,6 +159,31 @@ async fn sql_directory() {
         .unwrap()
         .is_none());
 
+    // Login names that are addresses are normalized like any other address
+    create_test_user(handle.as_ref(), "alice@example.org", "fghij", "Alice").await;
+    link_test_address(
+        handle.as_ref(),
+        "alice@example.org",
+        "alice@example.org",
+        "primary",
+    )
+    .await;
+    assert_eq!(
+        handle
+            .authenticate(&Credentials::Plain {
+                username: "Alice@Example.ORG".to_string(),
+                secret: "fghij".to_string()
+            })
+            .await
+            .unwrap()
+            .map(|principal| principal.name),
+        Some("alice@example.org".to_string())
+    );
+    assert_eq!(
+        handle.emails_by_name("ALICE@example.org").await.unwrap(),
+        vec!["alice@example.org".to_string()]
+    );
+
     // Get user by name
     assert_eq!(
         handle.principal("jane").await.unwrap().unwrap(),
@@ -147,6 +193,19 @@ async fn sql_directory() {
             typ: Type::Individual,
             secrets: vec!["abcde".to_string()],
             member_of: vec!["sales".to_string(), "support".to_string()],
+            forward_to: vec!["jane@forward.example.net".to_string()],
+            vacation: Vacation {
+                subject: "Away".to_string().into(),
+                message: "I am out of the office.".to_string(),
+                from_date: None,
+                to_date: None,
+                rate: Rate {
+                    requests: 1,
+                    period: Duration::from_secs(86400),
+                }
+                .into(),
+            }
+            .into(),
             ..Default::default()
         }
     );
@@ -162,6 +221,22 @@ async fn sql_directory() {
         }
     );
 
+    // Get user by external id
+    assert_eq!(
+        handle
+            .principal_by_external_id("idp|5f2a9c")
+            .await
+            .unwrap()
+            .unwrap()
+            .name,
+        "john"
+    );
+    assert!(handle
+        .principal_by_external_id("idp|unknown")
+        .await
+        .unwrap()
+        .is_none());
+
     // Emails by id
     assert_eq!(
         handle.emails_by_name("john").await.unwrap(),
@@ -214,6 +289,15 @@ async fn sql_directory() {
     // Domain validation
     assert!(handle.is_local_domain("example.org").await.unwrap());
     assert!(!handle.is_local_domain("other.org").await.unwrap());
+    assert!(handle.is_local_domain("Example.ORG").await.unwrap());
+    assert!(handle.is_local_domain("b\u{fc}cher.example").await.unwrap());
+    assert_eq!(
+        handle
+            .domains_status(&["example.org", "other.org"])
+            .await
+            .unwrap(),
+        vec![DomainStatus::Local, DomainStatus::Unknown]
+    );
 
     // RCPT TO
     assert!(handle.rcpt("jane@example.org").await.unwrap());
@@ -223,6 +307,31 @@ async fn sql_directory() {
     assert!(handle.rcpt("random_user@catchall.org").await.unwrap());
     assert!(!handle.rcpt("invalid@example.org").await.unwrap());
 
+    // Addresses are lowercased, NFC normalized and IDN domains converted to A-labels
+    assert!(handle.rcpt("Jane@Example.ORG").await.unwrap());
+    assert!(handle.rcpt("Robert@B\u{dc}CHER.example").await.unwrap());
+    assert_eq!(
+        handle
+            .names_by_email("ROBERT@bu\u{308}cher.example")
+            .await
+            .unwrap(),
+        vec!["robert".to_string()]
+    );
+
+    // Recipient routing
+    assert_eq!(
+        handle.route("jane+alias@example.org").await.unwrap(),
+        RecipientRoute::Local
+    );
+    assert_eq!(
+        handle.route("random_user@catchall.org").await.unwrap(),
+        RecipientRoute::Local
+    );
+    assert_eq!(
+        handle.route("invalid@example.org").await.unwrap(),
+        RecipientRoute::Reject
+    );
+
     // VRFY
     assert_eq!(
         handle.vrfy("jane").await.unwrap(),
@@ -254,12 +363,239 @@ async fn sql_directory() {
     );
 }
 
+#[tokio::test]
+async fn sql_directory_cache() {
+    let mut config = parse_config();
+    let handle = config.directories.remove("sql-cached").unwrap();
+    create_test_directory(handle.as_ref()).await;
+    create_test_user(handle.as_ref(), "jane", "abcde", "Jane Doe").await;
+    link_test_address(handle.as_ref(), "jane", "jane@example.org", "primary").await;
+
+    // Positive lookups are answered from the cache until they expire
+    assert!(handle.rcpt("jane@example.org").await.unwrap());
+    handle
+        .query("DELETE FROM emails WHERE name = ?", &["jane".into()])
+        .await
+        .unwrap();
+    assert!(handle.rcpt("jane@example.org").await.unwrap());
+    tokio::time::sleep(Duration::from_millis(2100)).await;
+    assert!(!handle.rcpt("jane@example.org").await.unwrap());
+
+    // Negative lookups are cached separately with a shorter TTL
+    link_test_address(handle.as_ref(), "jane", "jane@example.org", "primary").await;
+    assert!(!handle.rcpt("jane@example.org").await.unwrap());
+    tokio::time::sleep(Duration::from_millis(1100)).await;
+    assert!(handle.rcpt("jane@example.org").await.unwrap());
+}
+
+const RECOVERY_CONFIG: &str = r#"
+[directory."sql-recovery"]
+type = "sql"
+address = "sqlite::memory:"
+
+[directory."sql-recovery".options]
+allow-recovery = true
+
+[directory."sql-recovery".pool]
+max-connections = 1
+
+[directory."sql-recovery".query]
+name = "SELECT name, type, secret, recovery_secret, description, quota FROM accounts WHERE name = ? AND active = true"
+
+[directory."sql-recovery".columns]
+name = "name"
+secret = "secret"
+recovery-secret = "recovery_secret"
+type = "type"
+
+[directory."sql-no-recovery"]
+type = "sql"
+address = "sqlite::memory:"
+
+[directory."sql-no-recovery".pool]
+max-connections = 1
+
+[directory."sql-no-recovery".query]
+name = "SELECT name, type, secret, recovery_secret, description, quota FROM accounts WHERE name = ? AND active = true"
+
+[directory."sql-no-recovery".columns]
+name = "name"
+secret = "secret"
+recovery-secret = "recovery_secret"
+type = "type"
+"#;
+
+#[tokio::test]
+async fn sql_directory_recovery_secret() {
+    let mut config = utils::config::Config::new(RECOVERY_CONFIG)
+        .unwrap()
+        .parse_directory()
+        .unwrap();
+
+    for (id, allow_recovery) in [("sql-recovery", true), ("sql-no-recovery", false)] {
+        let handle = config.directories.remove(id).unwrap();
+        create_test_directory(handle.as_ref()).await;
+        handle
+            .query("ALTER TABLE accounts ADD COLUMN recovery_secret TEXT", &[])
+            .await
+            .unwrap();
+        create_test_user(handle.as_ref(), "john", "{PLAIN}12345", "John Doe").await;
+        handle
+            .query(
+                "UPDATE accounts SET recovery_secret = ? WHERE name = ?",
+                &["{PLAIN}recover-me".into(), "john".into()],
+            )
+            .await
+            .unwrap();
+
+        // The primary secret is always accepted
+        assert_eq!(
+            handle
+                .authenticate(&Credentials::Plain {
+                    username: "john".to_string(),
+                    secret: "12345".to_string()
+                })
+                .await
+                .unwrap()
+                .map(|p| p.name),
+            Some("john".to_string()),
+            "{id}"
+        );
+
+        // Recovery secrets are only accepted when enabled
+        assert_eq!(
+            handle
+                .authenticate(&Credentials::Plain {
+                    username: "john".to_string(),
+                    secret: "recover-me".to_string()
+                })
+                .await
+                .unwrap()
+                .map(|p| p.name),
+            if allow_recovery {
+                Some("john".to_string())
+            } else {
+                None
+            },
+            "{id}"
+        );
+        assert_eq!(
+            handle
+                .authenticate(&Credentials::Plain {
+                    username: "john".to_string(),
+                    secret: "wrong".to_string()
+                })
+                .await
+                .unwrap(),
+            None,
+            "{id}"
+        );
+    }
+}
+
+#[tokio::test]
+async fn sql_directory_nested_groups() {
+    let mut config = parse_config();
+    let handle = config.directories.remove("sql").unwrap();
+    create_test_directory(handle.as_ref()).await;
+    for (login, name) in [
+        ("john", "John Doe"),
+        ("jane", "Jane Doe"),
+        ("bill", "Bill Foobar"),
+    ] {
+        create_test_user(handle.as_ref(), login, "abcde", name).await;
+        link_test_address(
+            handle.as_ref(),
+            login,
+            &format!("{login}@example.org"),
+            "primary",
+        )
+        .await;
+    }
+    for (login, name) in [
+        ("sales", "Sales Team"),
+        ("support", "Support Team"),
+        ("staff", "Staff"),
+    ] {
+        create_test_group(handle.as_ref(), login, name).await;
+        link_test_address(
+            handle.as_ref(),
+            login,
+            &format!("{login}@example.org"),
+            "primary",
+        )
+        .await;
+    }
+
+    // Two levels of nested groups, with staff and sales members of each other
+    add_to_group(handle.as_ref(), "john", "sales").await;
+    add_to_group(handle.as_ref(), "jane", "support").await;
+    add_to_group(handle.as_ref(), "sales", "staff").await;
+    add_to_group(handle.as_ref(), "support", "staff").await;
+    add_to_group(handle.as_ref(), "staff", "sales").await;
+
+    assert_eq!(
+        handle.principal("john").await.unwrap().unwrap().member_of,
+        vec!["sales".to_string(), "staff".to_string()]
+    );
+    assert_eq!(
+        handle.principal("jane").await.unwrap().unwrap().member_of,
+        vec![
+            "support".to_string(),
+            "staff".to_string(),
+            "sales".to_string()
+        ]
+    );
+    assert_eq!(
+        handle.principal("bill").await.unwrap().unwrap().member_of,
+        Vec::<String>::new()
+    );
+
+    // Nested mailing lists, with support and sales members of each other
+    link_test_address(handle.as_ref(), "bill", "all@example.org", "list").await;
+    link_test_address(handle.as_ref(), "sales", "all@example.org", "list").await;
+    link_test_address(handle.as_ref(), "john", "sales@example.org", "list").await;
+    link_test_address(handle.as_ref(), "support", "sales@example.org", "list").await;
+    link_test_address(handle.as_ref(), "jane", "support@example.org", "list").await;
+    link_test_address(handle.as_ref(), "sales", "support@example.org", "list").await;
+
+    assert_eq!(
+        handle.expn("all@example.org").await.unwrap(),
+        vec![
+            "bill@example.org".to_string(),
+            "john@example.org".to_string(),
+            "jane@example.org".to_string()
+        ]
+    );
+    assert_eq!(
+        handle.expn("support@example.org").await.unwrap(),
+        vec![
+            "jane@example.org".to_string(),
+            "john@example.org".to_string()
+        ]
+    );
+
+    // Individual addresses are not expanded, even when also used as a list
+    link_test_address(handle.as_ref(), "jane", "bill@example.org", "list").await;
+    assert_eq!(
+        handle.expn("all@example.org").await.unwrap(),
+        vec![
+            "bill@example.org".to_string(),
+            "john@example.org".to_string(),
+            "jane@example.org".to_string()
+        ]
+    );
+}
+
 pub async fn create_test_directory(handle: &dyn Directory) {
     // Create tables
     for query in [
-        "CREATE TABLE accounts (name TEXT PRIMARY KEY, secret TEXT, description TEXT, type TEXT NOT NULL, quota INTEGER DEFAULT 0, active BOOLEAN DEFAULT 1)",
+        "CREATE TABLE accounts (name TEXT PRIMARY KEY, secret TEXT, description TEXT, type TEXT NOT NULL, quota INTEGER DEFAULT 0, totp_secret TEXT, password_expired BOOLEAN DEFAULT 0, active BOOLEAN DEFAULT 1)",
         "CREATE TABLE group_members (name TEXT NOT NULL, member_of TEXT NOT NULL, PRIMARY KEY (name, member_of))",
         "CREATE TABLE emails (name TEXT NOT NULL, address TEXT NOT NULL, type TEXT, PRIMARY KEY (name, address))",
+        "CREATE TABLE forwards (name TEXT NOT NULL, address TEXT NOT NULL, PRIMARY KEY (name, address))",
+        "CREATE TABLE vacations (name TEXT PRIMARY KEY, subject TEXT, message TEXT NOT NULL, from_date INTEGER, to_date INTEGER, rate TEXT, active BOOLEAN DEFAULT 1)",
+        "CREATE TABLE external_ids (name TEXT NOT NULL, external_id TEXT PRIMARY KEY)",
         "INSERT INTO accounts (name, secret, type) VALUES ('admin', 'secret', 'individual')", 
     ] {
         handle.query(query, &[]).await.unwrap_or_else(|_| panic!("failed for {query}"));
@@ -286,6 +622,26 @@ pub async fn create_test_user_with_email(
     link_test_address(handle, login, login, "primary").await;
 }
 
+pub async fn set_test_totp_secret(handle: &dyn Directory, login: &str, totp_secret: &str) {
+    handle
+        .query(
+            "UPDATE accounts SET totp_secret = ? WHERE name = ?",
+            &[totp_secret.into(), login.into()],
+        )
+        .await
+        .unwrap();
+}
+
+pub async fn set_test_password_expired(handle: &dyn Directory, login: &str) {
+    handle
+        .query(
+            "UPDATE accounts SET password_expired = true WHERE name = ?",
+            &[login.into()],
+        )
+        .await
+        .unwrap();
+}
+
 pub async fn create_test_group(handle: &dyn Directory, login: &str, name: &str) {
     handle
         .query(
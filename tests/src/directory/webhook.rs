@@ -0,0 +1,237 @@
+/*
+ * Copyright (c) 2023 Stalwart Labs Ltd.
+ *
+ * This file is part of Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use std::time::Duration;
+
+use directory::config::ConfigDirectory;
+use mail_send::Credentials;
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::TcpListener,
+    sync::mpsc,
+};
+
+const CONFIG: &str = r#"
+[directory."hooked"]
+type = "memory"
+
+[directory."hooked".webhook]
+url = "http://127.0.0.1:9198/auth-events"
+headers = ["Authorization: Bearer s3cr3t"]
+
+[[directory."hooked".users]]
+name = "john"
+description = "John Doe"
+secret = "12345"
+email = "john@example.org"
+
+[directory."limited"]
+type = "memory"
+
+[directory."limited".webhook]
+url = "http://127.0.0.1:9198/auth-events"
+rate = "2/1h"
+
+[[directory."limited".users]]
+name = "jane"
+description = "Jane Doe"
+secret = "abcde"
+email = "jane@example.org"
+
+[directory."unreachable"]
+type = "memory"
+
+[directory."unreachable".webhook]
+url = "http://127.0.0.1:9199/auth-events"
+timeout = "1s"
+
+[[directory."unreachable".users]]
+name = "bill"
+description = "Bill Foobar"
+secret = "xyz"
+email = "bill@example.org"
+"#;
+
+#[tokio::test]
+async fn webhook_directory() {
+    // Enable logging
+    /*tracing::subscriber::set_global_default(
+        tracing_subscriber::FmtSubscriber::builder()
+            .with_max_level(tracing::Level::DEBUG)
+            .finish(),
+    )
+    .unwrap();*/
+
+    let mut events = spawn_mock_webhook_server().await;
+    let mut config = utils::config::Config::new(CONFIG)
+        .unwrap()
+        .parse_directory()
+        .unwrap();
+    let hooked = config.directories.remove("hooked").unwrap();
+    let limited = config.directories.remove("limited").unwrap();
+    let unreachable = config.directories.remove("unreachable").unwrap();
+
+    // Successful authentication
+    assert_eq!(
+        authenticate(&hooked, "john", "12345").await,
+        Some("john".to_string())
+    );
+    let (headers, event) = next_event(&mut events).await;
+    assert!(
+        headers.contains("authorization: bearer s3cr3t"),
+        "missing auth header: {headers}"
+    );
+    assert_eq!(event["principal"], "john");
+    assert_eq!(event["source"], "hooked");
+    assert_eq!(event["mechanism"], "plain");
+    assert_eq!(event["outcome"], "success");
+    assert!(event["reason"].is_null());
+    assert!(event["timestamp"].as_u64().unwrap() > 0);
+
+    // Failed authentication reports the attempted username
+    for (username, secret) in [("john", "wrong"), ("nobody", "12345")] {
+        assert_eq!(authenticate(&hooked, username, secret).await, None);
+        let (_, event) = next_event(&mut events).await;
+        assert_eq!(event["principal"], username);
+        assert_eq!(event["outcome"], "failure");
+        assert_eq!(event["reason"], "Invalid credentials");
+    }
+
+    // Other mechanisms are reported as well
+    assert_eq!(
+        hooked
+            .authenticate(&Credentials::XOauth2 {
+                username: "john".to_string(),
+                secret: "invalid-token".to_string(),
+            })
+            .await
+            .unwrap()
+            .map(|p| p.name),
+        None
+    );
+    let (_, event) = next_event(&mut events).await;
+    assert_eq!(event["mechanism"], "xoauth2");
+    assert_eq!(event["principal"], "john");
+
+    // Events exceeding the rate limit are dropped, authentication is unaffected
+    for _ in 0..3 {
+        assert_eq!(
+            authenticate(&limited, "jane", "abcde").await,
+            Some("jane".to_string())
+        );
+    }
+    for _ in 0..2 {
+        let (headers, event) = next_event(&mut events).await;
+        assert!(!headers.contains("authorization:"));
+        assert_eq!(event["source"], "limited");
+        assert_eq!(event["principal"], "jane");
+    }
+    assert!(
+        tokio::time::timeout(Duration::from_millis(500), events.recv())
+            .await
+            .is_err(),
+        "rate limited event was delivered"
+    );
+
+    // An unreachable webhook does not delay or break authentication
+    let time = std::time::Instant::now();
+    assert_eq!(
+        authenticate(&unreachable, "bill", "xyz").await,
+        Some("bill".to_string())
+    );
+    assert!(time.elapsed() < Duration::from_millis(500));
+}
+
+async fn authenticate(
+    handle: &std::sync::Arc<dyn directory::Directory>,
+    username: &str,
+    secret: &str,
+) -> Option<String> {
+    handle
+        .authenticate(&Credentials::Plain {
+            username: username.to_string(),
+            secret: secret.to_string(),
+        })
+        .await
+        .unwrap()
+        .map(|p| p.name)
+}
+
+async fn next_event(
+    events: &mut mpsc::UnboundedReceiver<(String, serde_json::Value)>,
+) -> (String, serde_json::Value) {
+    tokio::time::timeout(Duration::from_secs(5), events.recv())
+        .await
+        .expect("timed out waiting for webhook event")
+        .unwrap()
+}
+
+pub async fn spawn_mock_webhook_server() -> mpsc::UnboundedReceiver<(String, serde_json::Value)> {
+    let (tx, rx) = mpsc::unbounded_channel();
+    let listener = TcpListener::bind("127.0.0.1:9198")
+        .await
+        .unwrap_or_else(|e| panic!("Failed to bind mock webhook server to 127.0.0.1:9198: {e}"));
+
+    tokio::spawn(async move {
+        while let Ok((mut stream, _)) = listener.accept().await {
+            let tx = tx.clone();
+            tokio::spawn(async move {
+                let mut buf = vec![0u8; 1024];
+                let mut request = Vec::new();
+                let header_end = loop {
+                    if let Some(pos) = request.windows(4).position(|w| w == b"\r\n\r\n") {
+                        break pos + 4;
+                    }
+                    match stream.read(&mut buf).await {
+                        Ok(0) | Err(_) => return,
+                        Ok(n) => request.extend_from_slice(&buf[..n]),
+                    }
+                };
+                let headers = String::from_utf8_lossy(&request[..header_end]).to_lowercase();
+                assert!(
+                    headers.starts_with("post /auth-events "),
+                    "unexpected request: {headers}"
+                );
+                let content_length = headers
+                    .lines()
+                    .find_map(|line| line.strip_prefix("content-length:"))
+                    .and_then(|value| value.trim().parse::<usize>().ok())
+                    .unwrap_or(0);
+                while request.len() < header_end + content_length {
+                    match stream.read(&mut buf).await {
+                        Ok(0) | Err(_) => return,
+                        Ok(n) => request.extend_from_slice(&buf[..n]),
+                    }
+                }
+                let event = serde_json::from_slice(&request[header_end..]).unwrap();
+                stream
+                    .write_all(b"HTTP/1.1 204 No Content\r\nConnection: close\r\n\r\n")
+                    .await
+                    .ok();
+                tx.send((headers, event)).ok();
+            });
+        }
+    });
+
+    rx
+}
@@ -87,7 +87,10 @@ pub async fn test(mut imap_john: &mut ImapConnection, _imap_check: &mut ImapConn
         .await
         .assert_count("Shared Folders", 0);
     imap_john.send("NAMESPACE").await;
-    imap_john.assert_read(Type::Tagged, ResponseType::Ok).await;
+    imap_john
+        .assert_read(Type::Tagged, ResponseType::Ok)
+        .await
+        .assert_equals("* NAMESPACE ((\"\" \"/\")) NIL NIL");
 
     // List rights
     imap_jane.send("LISTRIGHTS INBOX jdoe@example.com").await;
@@ -137,7 +140,7 @@ pub async fn test(mut imap_john: &mut ImapConnection, _imap_check: &mut ImapConn
     imap_john
         .assert_read(Type::Tagged, ResponseType::Ok)
         .await
-        .assert_equals("* NAMESPACE ((\"\" \"/\")) ((\"Shared Folders\" \"/\")) NIL");
+        .assert_equals("* NAMESPACE ((\"\" \"/\")) ((\"Shared Folders/\" \"/\")) NIL");
 
     // List John's right on Jane's Inbox
     imap_john
@@ -325,4 +328,33 @@ pub async fn test(mut imap_john: &mut ImapConnection, _imap_check: &mut ImapConn
         .assert_read(Type::Tagged, ResponseType::Ok)
         .await
         .assert_count("Shared Folders", 3);
+
+    // Accounts with an expired password are limited to read-only access
+    let mut imap_expired = ImapConnection::connect(b"_e ").await;
+    imap_expired
+        .assert_read(Type::Untagged, ResponseType::Ok)
+        .await;
+    imap_expired
+        .send("AUTHENTICATE PLAIN {36+}\r\nAGV4cGlyZWRAZXhhbXBsZS5jb20Ac2VjcmV0")
+        .await;
+    imap_expired
+        .assert_read(Type::Tagged, ResponseType::Ok)
+        .await;
+    imap_expired.send("SELECT INBOX").await;
+    imap_expired
+        .assert_read(Type::Tagged, ResponseType::Ok)
+        .await
+        .assert_contains("[READ-ONLY]");
+    for command in [
+        "CREATE \"New Folder\"",
+        "SUBSCRIBE INBOX",
+        "UNSUBSCRIBE INBOX",
+        "STORE 1:* +FLAGS (\\Seen)",
+    ] {
+        imap_expired.send(command).await;
+        imap_expired
+            .assert_read(Type::Tagged, ResponseType::No)
+            .await
+            .assert_contains("[NOPERM]");
+    }
 }
@@ -28,7 +28,7 @@ use mail_send::Credentials;
 
 use super::{AssertResult, ImapConnection, Type};
 
-pub async fn test(imap: &mut ImapConnection, _imap_check: &mut ImapConnection) {
+pub async fn test(imap: &mut ImapConnection, imap_check: &mut ImapConnection) {
     // Test CAPABILITY
     imap.send("CAPABILITY").await;
     imap.assert_read(Type::Tagged, ResponseType::Ok).await;
@@ -52,6 +52,16 @@ pub async fn test(imap: &mut ImapConnection, _imap_check: &mut ImapConnection) {
     imap.assert_read(Type::Continuation, ResponseType::Ok).await;
     imap.send_untagged("AGJvYXR5AG1jYm9hdGZhY2U=").await;
     imap.assert_read(Type::Tagged, ResponseType::No).await;
+
+    // Accounts requiring a second factor are rejected
+    imap_check.send("AUTHENTICATE PLAIN {32}").await;
+    imap_check
+        .assert_read(Type::Continuation, ResponseType::Ok)
+        .await;
+    imap_check
+        .send_untagged("AHRvdHBAZXhhbXBsZS5jb20Ac2VjcmV0")
+        .await;
+    imap_check.assert_read(Type::Tagged, ResponseType::No).await;
 }
 
 #[test]
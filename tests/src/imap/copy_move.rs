@@ -25,7 +25,7 @@ use imap_proto::ResponseType;
 
 use super::{AssertResult, ImapConnection, Type};
 
-pub async fn test(imap: &mut ImapConnection, _imap_check: &mut ImapConnection) {
+pub async fn test(imap: &mut ImapConnection, imap_check: &mut ImapConnection) {
     // Check status
     imap.send("LIST \"\" % RETURN (STATUS (UIDNEXT MESSAGES UNSEEN SIZE))")
         .await;
@@ -123,4 +123,39 @@ pub async fn test(imap: &mut ImapConnection, _imap_check: &mut ImapConnection) {
         .assert_contains("\"Burrata al Tartufo\" (UIDNEXT 5 MESSAGES 0 UNSEEN 0 SIZE 0)")
         .assert_contains("\"Scamorza Affumicata\" (UIDNEXT 9 MESSAGES 4 UNSEEN 4 SIZE 5851)")
         .assert_contains("\"INBOX\" (UIDNEXT 11 MESSAGES 10 UNSEEN 10 SIZE 12193)");
+
+    // Save a search and move its results, one of the saved messages
+    // is expunged by another session before the move.
+    imap.send("SELECT \"Scamorza Affumicata\"").await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok).await;
+    imap.send("UID SEARCH RETURN (SAVE) ALL").await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok).await;
+
+    imap_check.send("SELECT \"Scamorza Affumicata\"").await;
+    imap_check.assert_read(Type::Tagged, ResponseType::Ok).await;
+    imap_check.send("UID STORE 5 +FLAGS (\\Deleted)").await;
+    imap_check.assert_read(Type::Tagged, ResponseType::Ok).await;
+    imap_check.send("UID EXPUNGE 5").await;
+    imap_check.assert_read(Type::Tagged, ResponseType::Ok).await;
+
+    imap.send("UID MOVE $ \"Burrata al Tartufo\"").await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok)
+        .await
+        .assert_contains("* OK [COPYUID")
+        .assert_contains("6:8 5:7")
+        .assert_contains("* 1 EXPUNGE");
+
+    // All saved messages are gone now, moving them again is a no-op
+    imap.send("UID MOVE $ \"Burrata al Tartufo\"").await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok)
+        .await
+        .assert_count("COPYUID", 0);
+
+    // Check status
+    imap.send("STATUS \"Burrata al Tartufo\" (UIDNEXT MESSAGES)")
+        .await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok)
+        .await
+        .assert_contains("MESSAGES 3")
+        .assert_contains("UIDNEXT 8");
 }
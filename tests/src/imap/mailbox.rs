@@ -335,6 +335,60 @@ pub async fn test(mut imap: &mut ImapConnection, mut imap_check: &mut ImapConnec
         .await
         .assert_folders([("Fruit/Apple", [""])], true);
 
+    // Create enough folders to span several LIST windows
+    for num in 0..250 {
+        imap.send(&format!("CREATE \"Window/{:03}\"", num)).await;
+        imap.assert_read(Type::Tagged, ResponseType::Ok).await;
+    }
+    let expected_names = (0..250)
+        .map(|num| format!("\"Window/{:03}\"", num))
+        .collect::<Vec<_>>();
+    for imap in [&mut imap, &mut imap_check] {
+        // Every folder is listed exactly once, in order, followed by its status
+        imap.send("LIST \"\" \"*\" RETURN (STATUS (MESSAGES))")
+            .await;
+        let lines = imap.assert_read(Type::Tagged, ResponseType::Ok).await;
+        let mut listed_names = Vec::with_capacity(250);
+        for (pos, line) in lines.iter().enumerate() {
+            if let Some(name) = line
+                .strip_prefix("* LIST ")
+                .and_then(|line| line.rsplit_once(' '))
+                .map(|(_, name)| name)
+                .filter(|name| name.starts_with("\"Window/"))
+            {
+                assert_eq!(
+                    lines.get(pos + 1).map(|line| line.as_str()),
+                    Some(format!("* STATUS {} (MESSAGES 0)", name).as_str()),
+                    "missing status for {}",
+                    name
+                );
+                listed_names.push(name.to_string());
+            }
+        }
+        assert_eq!(listed_names, expected_names);
+        lines.assert_count("* LIST ", 250 + 14);
+
+        // Patterns still apply to folders past the first window
+        imap.send("LIST \"\" \"Window/2%\"").await;
+        imap.assert_read(Type::Tagged, ResponseType::Ok)
+            .await
+            .assert_count("* LIST ", 50)
+            .assert_contains("\"Window/200\"")
+            .assert_contains("\"Window/249\"");
+    }
+
+    // Delete window test folders
+    for num in 0..250 {
+        imap.send(&format!("DELETE \"Window/{:03}\"", num)).await;
+        imap.assert_read(Type::Tagged, ResponseType::Ok).await;
+    }
+    imap.send("DELETE \"Window\"").await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok).await;
+    imap.send("LIST \"\" \"Window*\"").await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok)
+        .await
+        .assert_count("* LIST", 0);
+
     // Restore Trash folder's original name
     imap.send("RENAME \"Recycle Bin\" \"Deleted Items\"").await;
     imap.assert_read(Type::Tagged, ResponseType::Ok).await;
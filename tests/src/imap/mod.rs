@@ -31,6 +31,7 @@ pub mod fetch;
 pub mod idle;
 pub mod mailbox;
 pub mod managesieve;
+pub mod saved_query;
 pub mod search;
 pub mod store;
 pub mod thread;
@@ -54,7 +55,7 @@ use crate::{
     add_test_certs,
     directory::sql::{
         add_to_group, create_test_directory, create_test_group_with_email, create_test_user,
-        create_test_user_with_email,
+        create_test_user_with_email, set_test_password_expired, set_test_totp_secret,
     },
     store::TempDir,
 };
@@ -185,16 +186,19 @@ attempts.interval = "500ms"
 type = "sql"
 address = "sqlite::memory:"
 
+[directory."sql".options]
+allow-plaintext = true
+
 [directory."sql".pool]
 max-connections = 1
 
 [directory."sql".query]
-name = "SELECT name, type, secret, description, quota FROM accounts WHERE name = ? AND active = true"
-members = "SELECT member_of FROM group_members WHERE name = ?"
+name = "SELECT name, type, secret, description, quota, totp_secret, password_expired FROM accounts WHERE name = ? AND active = true"
+members = "SELECT g.member_of, a.type FROM group_members AS g LEFT JOIN accounts AS a ON a.name = g.member_of WHERE g.name = ?"
 recipients = "SELECT name FROM emails WHERE address = ?"
 emails = "SELECT address FROM emails WHERE name = ? AND type != 'list' ORDER BY type DESC, address ASC"
 verify = "SELECT address FROM emails WHERE address LIKE '%' || ? || '%' AND type = 'primary' ORDER BY address LIMIT 5"
-expand = "SELECT p.address FROM emails AS p JOIN emails AS l ON p.name = l.name WHERE p.type = 'primary' AND l.address = ? AND l.type = 'list' ORDER BY p.address LIMIT 50"
+expand = "SELECT p.address, a.type FROM emails AS p JOIN emails AS l ON p.name = l.name JOIN accounts AS a ON a.name = p.name WHERE p.type = 'primary' AND l.address = ? AND l.type = 'list' ORDER BY p.address LIMIT 50"
 domains = "SELECT 1 FROM emails WHERE address LIKE '%@' || ? LIMIT 1"
 
 [directory."sql".columns]
@@ -204,6 +208,8 @@ secret = "secret"
 email = "address"
 quota = "quota"
 type = "type"
+totp-secret = "totp_secret"
+password-expired = "password_expired"
 
 [directory."local"]
 type = "memory"
@@ -212,6 +218,17 @@ type = "memory"
 domains = ["example.com"]
 remote-domains = ["remote.org", "foobar.com", "test.com", "other_domain.com"]
 
+[imap.search]
+any-header = true
+timezone = "+13:00"
+
+[imap.search.thread-size]
+max-threads = 3
+
+[imap.search.snippet]
+length = 30
+max-results = 1
+
 [oauth]
 key = "parerga_und_paralipomena"
 [oauth.auth]
@@ -299,6 +316,22 @@ async fn init_imap_tests(delete_if_exists: bool) -> IMAPTest {
         "Bill Foobar",
     )
     .await;
+    create_test_user_with_email(
+        jmap.directory.as_ref(),
+        "totp@example.com",
+        "secret",
+        "Two Factor",
+    )
+    .await;
+    set_test_totp_secret(jmap.directory.as_ref(), "totp@example.com", "totp/secret").await;
+    create_test_user_with_email(
+        jmap.directory.as_ref(),
+        "expired@example.com",
+        "secret",
+        "Expired Password",
+    )
+    .await;
+    set_test_password_expired(jmap.directory.as_ref(), "expired@example.com").await;
     create_test_group_with_email(
         jmap.directory.as_ref(),
         "support@example.com",
@@ -365,6 +398,7 @@ pub async fn imap_tests() {
 
     mailbox::test(&mut imap, &mut imap_check).await;
     append::test(&mut imap, &mut imap_check).await;
+    saved_query::test(&handle.jmap, &mut imap, &mut imap_check).await;
     search::test(&mut imap, &mut imap_check).await;
     fetch::test(&mut imap, &mut imap_check).await;
     store::test(&mut imap, &mut imap_check).await;
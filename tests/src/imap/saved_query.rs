@@ -0,0 +1,126 @@
+/*
+ * Copyright (c) 2020-2022, Stalwart Labs Ltd.
+ *
+ * This file is part of Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use imap_proto::ResponseType;
+use jmap::JMAP;
+use jmap_proto::{
+    object::Object,
+    types::{collection::Collection, property::Property, value::Value},
+};
+use store::write::{log::ChangeLogBuilder, BatchBuilder, F_CLEAR, F_VALUE};
+
+use super::{AssertResult, ImapConnection, Type};
+
+pub async fn test(jmap: &JMAP, imap: &mut ImapConnection, _imap_check: &mut ImapConnection) {
+    // Saved searches are not listed until one is created
+    imap.send("LIST \"\" \"Saved Searches*\"").await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok)
+        .await
+        .assert_count("* LIST", 0);
+
+    // Create a saved query matching the messages sent by Nathaniel
+    let account_id = jmap.get_account_id("jdoe@example.com").await.unwrap();
+    let document_id = jmap
+        .assign_document_id(account_id, Collection::SavedQuery)
+        .await
+        .unwrap();
+    let mut batch = BatchBuilder::new();
+    batch
+        .with_account_id(account_id)
+        .with_collection(Collection::SavedQuery)
+        .create_document(document_id)
+        .value(
+            Property::Value,
+            Object::with_capacity(2)
+                .with_property(Property::Name, Value::Text("Nathaniel".to_string()))
+                .with_property(
+                    Property::Filter,
+                    Value::Object(Object::with_capacity(1).with_property(
+                        Property::_T("from".to_string()),
+                        Value::Text("nathaniel".to_string()),
+                    )),
+                ),
+            F_VALUE,
+        );
+    jmap.write_batch(batch).await.unwrap();
+    let mut changes = ChangeLogBuilder::new();
+    changes.log_insert(Collection::SavedQuery, document_id);
+    jmap.commit_changes(account_id, changes).await.unwrap();
+
+    // Saved queries are listed as virtual mailboxes
+    imap.send("LIST \"\" \"*\"").await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok)
+        .await
+        .assert_folders(
+            [
+                ("Saved Searches", ["\\NoSelect"]),
+                ("Saved Searches/Nathaniel", ["\\NoInferiors"]),
+            ],
+            false,
+        );
+    imap.send("STATUS \"Saved Searches/Nathaniel\" (MESSAGES UIDNEXT)")
+        .await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok)
+        .await
+        .assert_contains("MESSAGES 3")
+        .assert_contains("UIDNEXT 4");
+
+    // Virtual mailboxes are always opened read-only
+    imap.send("SELECT \"Saved Searches/Nathaniel\"").await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok)
+        .await
+        .assert_contains("3 EXISTS")
+        .assert_response_code("READ-ONLY");
+    imap.send("UID SEARCH ALL").await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok)
+        .await
+        .assert_equals("* SEARCH 1 2 3");
+    imap.send("UID FETCH 2 BODY.PEEK[HEADER.FIELDS (SUBJECT)]")
+        .await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok)
+        .await
+        .assert_contains("Subject: Formatted text mail");
+    imap.send("UID STORE 2 +FLAGS (\\Seen)").await;
+    imap.assert_read(Type::Tagged, ResponseType::No).await;
+    imap.send("UNSELECT").await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok).await;
+
+    // Destroyed saved queries are removed from the mailbox list
+    let mut batch = BatchBuilder::new();
+    batch
+        .with_account_id(account_id)
+        .with_collection(Collection::SavedQuery)
+        .delete_document(document_id)
+        .value(Property::Value, (), F_VALUE | F_CLEAR)
+        .value(Property::EmailIds, (), F_VALUE | F_CLEAR);
+    jmap.write_batch(batch).await.unwrap();
+    let mut changes = ChangeLogBuilder::new();
+    changes.log_delete(Collection::SavedQuery, document_id);
+    jmap.commit_changes(account_id, changes).await.unwrap();
+    imap.send("LIST \"\" \"Saved Searches*\"").await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok)
+        .await
+        .assert_count("* LIST", 0);
+    imap.send("SELECT \"Saved Searches/Nathaniel\"").await;
+    imap.assert_read(Type::Tagged, ResponseType::No).await;
+}
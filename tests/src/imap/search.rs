@@ -23,7 +23,7 @@
 
 use imap_proto::ResponseType;
 
-use super::{AssertResult, ImapConnection, Type};
+use super::{append::assert_append_message, AssertResult, ImapConnection, Type};
 
 pub async fn test(imap: &mut ImapConnection, imap_check: &mut ImapConnection) {
     // Searches without selecting a mailbox should fail.
@@ -121,6 +121,44 @@ pub async fn test(imap: &mut ImapConnection, imap_check: &mut ImapConnection) {
         .await
         .assert_contains("MIN 2 MAX 9");
 
+    // Searches reaching the deadline return partial results with the LIMIT code,
+    // without MIN, MAX or COUNT and keeping the previously saved search
+    imap_check
+        .send("UID SEARCH RETURN (SAVE MIN MAX COUNT ALL X-DEADLINE 0) ALL")
+        .await;
+    imap_check
+        .assert_read(Type::Tagged, ResponseType::Ok)
+        .await
+        .assert_response_code("LIMIT")
+        .assert_count("COUNT", 0)
+        .assert_count(" MIN ", 0)
+        .assert_count(" MAX ", 0);
+    imap_check.send("UID SEARCH NOT $").await;
+    imap_check
+        .assert_read(Type::Tagged, ResponseType::Ok)
+        .await
+        .assert_equals("* SEARCH 2 5 7 9");
+    imap_check
+        .send("UID SEARCH RETURN (COUNT X-DEADLINE 60000) ALL")
+        .await;
+    imap_check
+        .assert_read(Type::Tagged, ResponseType::Ok)
+        .await
+        .assert_contains("COUNT 10")
+        .assert_count("[LIMIT]", 0);
+
+    // Matched terms
+    imap_check
+        .send("UID SEARCH RETURN (X-MATCHES) FROM nathaniel")
+        .await;
+    imap_check
+        .assert_read(Type::Tagged, ResponseType::Ok)
+        .await
+        .assert_contains(concat!(
+            "X-MATCHES (1 (FROM \"nathaniel\") ",
+            "4 (FROM \"nathaniel\") 6 (FROM \"nathaniel\"))"
+        ));
+
     // Sort
     imap_check
         .send("UID SORT (REVERSE SUBJECT REVERSE DATE) UTF-8 FROM Nathaniel")
@@ -135,4 +173,258 @@ pub async fn test(imap: &mut ImapConnection, imap_check: &mut ImapConnection) {
     imap.assert_read(Type::Tagged, ResponseType::Ok)
         .await
         .assert_contains("COUNT 10 ALL 6,4:5,1,10,9,3,7:8,2");
+
+    // Partial results
+    imap.send("UID SORT RETURN (PARTIAL 2:4) (DATE SUBJECT) UTF-8 ALL")
+        .await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok)
+        .await
+        .assert_contains("COUNT 10 PARTIAL (2:4 4:5,1)");
+    imap.send("UID SORT RETURN (PARTIAL -1:-3) (DATE SUBJECT) UTF-8 ALL")
+        .await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok)
+        .await
+        .assert_contains("COUNT 10 PARTIAL (-1:-3 7:8,2)");
+    imap.send("UID SEARCH RETURN (PARTIAL 20:30) ALL").await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok)
+        .await
+        .assert_contains("COUNT 10 PARTIAL (20:30 NIL)");
+
+    // Header searches match the decoded RFC 2047 value
+    imap.send("CREATE \"Encoded Headers\"").await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok).await;
+    assert_append_message(
+        imap,
+        "Encoded Headers",
+        concat!(
+            "From: =?UTF-8?Q?J=C3=BCrgen?= <jurgen@example.com>\r\n",
+            "Subject: =?UTF-8?B?R3LDvMOfZSBhdXMgQmVybGlu?=\r\n",
+            "\r\n",
+            "Test\r\n"
+        ),
+        ResponseType::Ok,
+    )
+    .await;
+    imap.send("SELECT \"Encoded Headers\"").await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok).await;
+    imap.send("UID SEARCH CHARSET UTF-8 HEADER SUBJECT \"Grüße\"")
+        .await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok)
+        .await
+        .assert_equals("* SEARCH 1");
+    imap.send("UID SEARCH CHARSET UTF-8 HEADER FROM \"jürgen\"")
+        .await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok)
+        .await
+        .assert_equals("* SEARCH 1");
+    imap.send("UID SEARCH HEADER SUBJECT R3LDvMOfZSBhdXMgQmVybGlu")
+        .await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok)
+        .await
+        .assert_equals("* SEARCH");
+
+    // Non-RFC headers are searchable when enabled
+    assert_append_message(
+        imap,
+        "Encoded Headers",
+        concat!(
+            "From: jane@example.com\r\n",
+            "Subject: Urgent\r\n",
+            "X-Priority: 1 (Highest)\r\n",
+            "\r\n",
+            "Test\r\n"
+        ),
+        ResponseType::Ok,
+    )
+    .await;
+    imap.send("NOOP").await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok).await;
+    imap.send("UID SEARCH HEADER X-Priority \"\"").await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok)
+        .await
+        .assert_equals("* SEARCH 2");
+    imap.send("UID SEARCH HEADER x-priority \"(highest)\"")
+        .await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok)
+        .await
+        .assert_equals("* SEARCH 2");
+    imap.send("UID SEARCH HEADER X-Priority 5").await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok)
+        .await
+        .assert_equals("* SEARCH");
+
+    // Date searches use the configured +13:00 offset, which moves this message
+    // to the following day
+    assert_append_message(
+        imap,
+        "Encoded Headers",
+        concat!(
+            "From: john@example.com\r\n",
+            "Subject: Late night\r\n",
+            "Date: Mon, 6 Feb 2023 20:30:00 +0000\r\n",
+            "\r\n",
+            "Test\r\n"
+        ),
+        ResponseType::Ok,
+    )
+    .await;
+    imap.send("NOOP").await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok).await;
+    imap.send("UID SEARCH SENTON 7-Feb-2023").await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok)
+        .await
+        .assert_equals("* SEARCH 3");
+    imap.send("UID SEARCH SENTON 6-Feb-2023").await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok)
+        .await
+        .assert_equals("* SEARCH");
+    imap.send("UID SEARCH SENTSINCE 7-Feb-2023 SENTBEFORE 8-Feb-2023")
+        .await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok)
+        .await
+        .assert_equals("* SEARCH 3");
+
+    // Clients can override the configured offset for their session
+    imap.send("ID (\"timezone\" \"+25:00\")").await;
+    imap.assert_read(Type::Tagged, ResponseType::Bad).await;
+    imap.send("ID (\"name\" \"test\" \"timezone\" \"-03:00\")")
+        .await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok).await;
+    imap.send("UID SEARCH SENTON 6-Feb-2023").await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok)
+        .await
+        .assert_equals("* SEARCH 3");
+    imap.send("ID (\"timezone\" \"+13:00\")").await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok).await;
+    imap.send("UID SEARCH SENTBEFORE 7-Feb-2023").await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok)
+        .await
+        .assert_equals("* SEARCH");
+
+    // Multi-mailbox search
+    imap.send("ESEARCH IN (mailboxes (INBOX \"Encoded Headers\")) FROM nathaniel")
+        .await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok)
+        .await
+        .assert_contains("MAILBOX \"INBOX\" UIDVALIDITY ")
+        .assert_contains(") UID ALL 1,4,6")
+        .assert_count("MAILBOX \"Encoded Headers\"", 0);
+    imap.send("ESEARCH IN (subtree \"Encoded Headers\") RETURN (COUNT) FROM john")
+        .await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok)
+        .await
+        .assert_contains("MAILBOX \"Encoded Headers\" UIDVALIDITY ")
+        .assert_contains(") UID COUNT 1")
+        .assert_count("MAILBOX \"INBOX\"", 0);
+
+    // Body excerpts are centered on the first matched term
+    assert_append_message(
+        imap,
+        "Encoded Headers",
+        concat!(
+            "From: jane@example.com\r\n",
+            "Subject: Pangram\r\n",
+            "\r\n",
+            "The quick brown fox jumps over the lazy dog\r\n",
+            "near the riverbank at dawn\r\n"
+        ),
+        ResponseType::Ok,
+    )
+    .await;
+    imap.send("NOOP").await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok).await;
+    imap.send("UID SEARCH RETURN (X-SNIPPETS) BODY lazy").await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok)
+        .await
+        .assert_contains("UID X-SNIPPETS (4 \"mps over the <mark>lazy</mark> dog near the\")");
+    imap.send("UID SEARCH RETURN (X-SNIPPETS PARTIAL 2:2) BODY lazy")
+        .await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok)
+        .await
+        .assert_count("X-SNIPPETS", 0);
+    // Messages sent at the same time are ordered by the next sort key
+    for (subject, date) in [
+        ("Tiebreak b", "Tue, 7 Mar 2023 10:00:00 +0000"),
+        ("Tiebreak a", "Tue, 7 Mar 2023 10:00:00 +0000"),
+        ("Tiebreak c", "Mon, 6 Mar 2023 10:00:00 +0000"),
+    ] {
+        assert_append_message(
+            imap,
+            "Encoded Headers",
+            &format!(
+                "From: jane@example.com\r\nSubject: {subject}\r\nDate: {date}\r\n\r\nTest\r\n"
+            ),
+            ResponseType::Ok,
+        )
+        .await;
+    }
+    imap.send("NOOP").await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok).await;
+    imap.send("UID SORT (DATE SUBJECT) UTF-8 SUBJECT tiebreak")
+        .await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok)
+        .await
+        .assert_equals("* SORT 7 6 5");
+    imap.send("UID SORT (DATE REVERSE SUBJECT) UTF-8 SUBJECT tiebreak")
+        .await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok)
+        .await
+        .assert_equals("* SORT 7 5 6");
+
+    // Only the configured number of results get a snippet
+    imap.send("UID SEARCH RETURN (X-SNIPPETS) SUBJECT tiebreak BODY test")
+        .await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok)
+        .await
+        .assert_contains("X-SNIPPETS (5 ")
+        .assert_count("<mark>", 1);
+
+    // Relevance is scored by term frequency rather than by matched conditions
+    for body in [
+        "zebra alpha beta gamma delta",
+        "zebra zebra zebra alpha beta",
+        "zebra zebra alpha beta gamma",
+    ] {
+        assert_append_message(
+            imap,
+            "Encoded Headers",
+            &format!("From: jane@example.com\r\nSubject: Relevance\r\n\r\n{body}\r\n"),
+            ResponseType::Ok,
+        )
+        .await;
+    }
+    imap.send("NOOP").await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok).await;
+    imap.send("UID SORT (REVERSE X-RELEVANCE) UTF-8 BODY zebra")
+        .await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok)
+        .await
+        .assert_equals("* SORT 9 10 8");
+    imap.send("UID SORT (X-RELEVANCE) UTF-8 BODY zebra").await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok)
+        .await
+        .assert_equals("* SORT 8 10 9");
+
+    // Change buckets count messages by the time the searched keyword was set
+    imap.send("UID STORE 8:9 +FLAGS (\\Seen)").await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok).await;
+    imap.send("UID SEARCH RETURN (X-CHANGEBUCKETS) SEEN SUBJECT relevance")
+        .await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok)
+        .await
+        .assert_contains("X-CHANGEBUCKETS (DAY 2 WEEK 0 MONTH 0 OLDER 0)");
+    imap.send("UID SEARCH RETURN (X-CHANGEBUCKETS) SUBJECT relevance")
+        .await;
+    imap.assert_read(Type::Tagged, ResponseType::Bad).await;
+
+    // Thread size searches give up once the thread limit is exceeded
+    imap.send("UID SEARCH X-THREADSIZE-MIN 1").await;
+    imap.assert_read(Type::Tagged, ResponseType::No)
+        .await
+        .assert_contains("[LIMIT]");
+
+    imap.send("UNSELECT").await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok).await;
+    imap.send("DELETE \"Encoded Headers\"").await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok).await;
 }
@@ -25,7 +25,10 @@ use imap_proto::ResponseType;
 
 use crate::imap::AssertResult;
 
-use super::{append::build_messages, ImapConnection, Type};
+use super::{
+    append::{assert_append_message, build_messages},
+    ImapConnection, Type,
+};
 
 pub async fn test(imap: &mut ImapConnection, _imap_check: &mut ImapConnection) {
     // Create test messages
@@ -83,20 +86,20 @@ pub async fn test(imap: &mut ImapConnection, _imap_check: &mut ImapConnection) {
     let email_id = email_id.expect("Missing EMAILID");
     let thread_id = thread_id.expect("Missing THREADID");
 
-    // 4 different threads are expected
+    // 3 different threads are expected
     imap.send("THREAD REFERENCES UTF-8 1:*").await;
     imap.assert_read(Type::Tagged, ResponseType::Ok)
         .await
-        .assert_contains("(1 2 3 4)")
-        .assert_contains("(5 6 7 8)")
-        .assert_contains("(9 10 11 12)");
+        .assert_contains("(1 (2)(3)(4))")
+        .assert_contains("(5 (6)(7)(8))")
+        .assert_contains("(9 (10)(11)(12))");
 
     imap.send("THREAD REFERENCES UTF-8 SUBJECT T1").await;
     imap.assert_read(Type::Tagged, ResponseType::Ok)
         .await
-        .assert_contains("(5 6 7 8)")
-        .assert_count("(1 2 3 4)", 0)
-        .assert_count("(9 10 11 12)", 0);
+        .assert_contains("(5 (6)(7)(8))")
+        .assert_count("(1 ", 0)
+        .assert_count("(9 ", 0);
 
     // Filter by threadId and messageId
     imap.send(&format!(
@@ -106,8 +109,8 @@ pub async fn test(imap: &mut ImapConnection, _imap_check: &mut ImapConnection) {
     .await;
     imap.assert_read(Type::Tagged, ResponseType::Ok)
         .await
-        .assert_contains("(1 2 3 4)")
-        .assert_count("(", 1);
+        .assert_contains("(1 (2)(3)(4))")
+        .assert_count("(", 4);
 
     imap.send(&format!("UID THREAD REFERENCES UTF-8 EMAILID {}", email_id))
         .await;
@@ -116,11 +119,40 @@ pub async fn test(imap: &mut ImapConnection, _imap_check: &mut ImapConnection) {
         .assert_contains("(1)")
         .assert_count("(", 1);
 
+    // Nested replies are grouped under their parent
+    for message in [
+        "Message-ID: <chain1@domain>\nSubject: Chain\n\nmsg\n",
+        concat!(
+            "Message-ID: <chain2@domain>\nReferences: <chain1@domain>\n",
+            "Subject: Re: Chain\n\nreply\n"
+        ),
+        concat!(
+            "Message-ID: <chain3@domain>\nReferences: <chain1@domain> <chain2@domain>\n",
+            "Subject: Re: Chain\n\nreply\n"
+        ),
+        concat!(
+            "Message-ID: <chain4@domain>\nIn-Reply-To: <chain1@domain>\n",
+            "Subject: Re: Chain\n\nreply\n"
+        ),
+    ] {
+        assert_append_message(imap, "Manchego", message, ResponseType::Ok).await;
+    }
+    imap.send("NOOP").await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok).await;
+    imap.send("UID THREAD REFERENCES UTF-8 SUBJECT Chain").await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok)
+        .await
+        .assert_equals("* THREAD (13 (14 15)(16))");
+    imap.send("THREAD ORDEREDSUBJECT UTF-8 SUBJECT Chain").await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok)
+        .await
+        .assert_equals("* THREAD (13 (14)(15)(16))");
+
     // Delete all messages
     imap.send("STORE 1:* +FLAGS.SILENT (\\Deleted)").await;
     imap.assert_read(Type::Tagged, ResponseType::Ok).await;
     imap.send("EXPUNGE").await;
     imap.assert_read(Type::Tagged, ResponseType::Ok)
         .await
-        .assert_count("EXPUNGE", 13);
+        .assert_count("EXPUNGE", 17);
 }
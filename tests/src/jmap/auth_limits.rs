@@ -23,7 +23,10 @@
 
 use std::{sync::Arc, time::Duration};
 
-use jmap::JMAP;
+use jmap::{
+    auth::{authenticate::AuthResult, rate_limit::RemoteAddress},
+    JMAP,
+};
 use jmap_client::{
     client::{Client, Credentials},
     core::set::{SetError, SetErrorType},
@@ -32,7 +35,7 @@ use jmap_client::{
 use jmap_proto::types::id::Id;
 
 use crate::{
-    directory::sql::{create_test_user_with_email, link_test_address},
+    directory::sql::{create_test_user_with_email, link_test_address, set_test_totp_secret},
     jmap::mailbox::destroy_all_mailboxes,
 };
 
@@ -64,6 +67,31 @@ pub async fn test(server: Arc<JMAP>, admin_client: &mut Client) {
                 .await,
             Err(jmap_client::Error::Problem(err)) if err.status() == Some(401)));
 
+    // Accounts requiring a second factor should be rejected without issuing an access token
+    create_test_user_with_email(directory, "totp@example.com", "secret", "Two Factor").await;
+    set_test_totp_secret(directory, "totp@example.com", "totp/secret").await;
+    let totp_account_id = server.get_account_id("totp@example.com").await.unwrap();
+    assert!(matches!(
+            Client::new()
+                .credentials(Credentials::basic("totp@example.com", "secret"))
+                .accept_invalid_certs(true)
+                .connect("https://127.0.0.1:8899")
+                .await,
+            Err(jmap_client::Error::Problem(err)) if err.status() == Some(401)));
+    assert!(matches!(
+        server
+            .verify_credentials(
+                &mail_send::Credentials::Plain {
+                    username: "totp@example.com".to_string(),
+                    secret: "secret".to_string(),
+                },
+                &RemoteAddress::IpAddress("127.0.0.1".parse().unwrap()),
+            )
+            .await,
+        AuthResult::SecondFactorRequired { name } if name == "totp@example.com"
+    ));
+    assert!(server.access_tokens.get(&totp_account_id).is_none());
+
     // Invalid authentication requests should be rate limited
     let mut n_401 = 0;
     let mut n_429 = 0;
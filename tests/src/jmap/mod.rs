@@ -58,6 +58,7 @@ pub mod event_source;
 pub mod mailbox;
 pub mod push_subscription;
 pub mod quota;
+pub mod saved_query;
 pub mod sieve_script;
 pub mod stress_test;
 pub mod thread_get;
@@ -172,6 +173,19 @@ throttle = "500ms"
 [jmap.web-sockets]
 throttle = "500ms"
 
+[jmap.web-socket]
+heartbeat = "1s"
+max-missed-heartbeats = 2
+max-queued-messages = 8
+token-check-interval = "1s"
+compression.level = 9
+compression.threshold = 32
+
+[jmap.web-socket.rate-limit]
+requests-per-second = 1
+burst = 20
+max-violations = 5
+
 [jmap.push]
 throttle = "500ms"
 attempts.interval = "500ms"
@@ -180,11 +194,14 @@ attempts.interval = "500ms"
 type = "sql"
 address = "sqlite::memory:"
 
+[directory."sql".options]
+allow-plaintext = true
+
 [directory."sql".pool]
 max-connections = 1
 
 [directory."sql".query]
-name = "SELECT name, type, secret, description, quota FROM accounts WHERE name = ? AND active = true"
+name = "SELECT name, type, secret, description, quota, totp_secret FROM accounts WHERE name = ? AND active = true"
 members = "SELECT member_of FROM group_members WHERE name = ?"
 recipients = "SELECT name FROM emails WHERE address = ?"
 emails = "SELECT address FROM emails WHERE name = ? AND type != 'list' ORDER BY type DESC, address ASC"
@@ -199,6 +216,7 @@ secret = "secret"
 email = "address"
 quota = "quota"
 type = "type"
+totp-secret = "totp_secret"
 
 [directory."local"]
 type = "memory"
@@ -255,6 +273,7 @@ pub async fn jmap_tests() {
     quota::test(params.server.clone(), &mut params.client).await;
     crypto::test(params.server.clone(), &mut params.client).await;
     blob::test(params.server.clone(), &mut params.client).await;
+    saved_query::test(params.server.clone(), &mut params.client).await;
 
     if delete {
         params.temp_dir.delete();
@@ -0,0 +1,207 @@
+/*
+ * Copyright (c) 2023 Stalwart Labs Ltd.
+ *
+ * This file is part of Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use std::sync::Arc;
+
+use jmap::JMAP;
+use jmap_client::{client::Client, mailbox::Role};
+use jmap_proto::types::id::Id;
+use serde_json::Value;
+
+use crate::{
+    directory::sql::create_test_user_with_email,
+    jmap::{jmap_json_request, mailbox::destroy_all_mailboxes, test_account_login},
+};
+
+pub async fn test(server: Arc<JMAP>, admin_client: &mut Client) {
+    println!("Running SavedQuery tests...");
+
+    // Create test account
+    let directory = server.directory.as_ref();
+    create_test_user_with_email(directory, "jdoe@example.com", "12345", "John Doe").await;
+    let account_id = Id::from(server.get_account_id("jdoe@example.com").await.unwrap());
+    let client = test_account_login("jdoe@example.com", "12345").await;
+
+    // Import test messages
+    let mailbox_id = client
+        .mailbox_create("Saved Query Test", None::<String>, Role::None)
+        .await
+        .unwrap()
+        .take_id();
+    for subject in ["Invoice #1", "Invoice #2", "Lunch on Friday"] {
+        client
+            .email_import(
+                format!(
+                    "From: bill@example.com\r\nTo: jdoe@example.com\r\nSubject: {subject}\r\n\r\nTest message\r\n"
+                )
+                .into_bytes(),
+                [&mailbox_id],
+                None::<Vec<&str>>,
+                None,
+            )
+            .await
+            .unwrap();
+    }
+    let old_state = saved_query_request(
+        &account_id,
+        r#"["SavedQuery/get", {"accountId": "$$", "ids": null}, "R1"]"#,
+    )
+    .await
+    .pointer("/methodResponses/0/1/state")
+    .and_then(|v| v.as_str())
+    .unwrap()
+    .to_string();
+
+    // Names must be unique and filters valid Email/query filters
+    let response = saved_query_request(
+        &account_id,
+        r#"["SavedQuery/set", {
+            "accountId": "$$",
+            "create": {
+                "a": {"name": "Invoices", "filter": {"subject": "invoice"}},
+                "b": {"name": "Invoices", "filter": {"subject": "lunch"}},
+                "c": {"name": "Invoices/2023", "filter": {"subject": "invoice"}},
+                "d": {"name": "Unknown", "filter": {"notAFilter": true}},
+                "e": {"name": "No filter"}
+            }
+        }, "R1"]"#,
+    )
+    .await;
+    let query_id = response
+        .pointer("/methodResponses/0/1/created/a/id")
+        .and_then(|v| v.as_str())
+        .unwrap_or_else(|| panic!("Response: {response:?}"))
+        .to_string();
+    assert_eq!(
+        response.pointer("/methodResponses/0/1/notCreated/b/type"),
+        Some(&Value::String("alreadyExists".to_string())),
+        "Response: {response:?}"
+    );
+    for id in ["c", "d", "e"] {
+        assert_eq!(
+            response.pointer(&format!("/methodResponses/0/1/notCreated/{id}/type")),
+            Some(&Value::String("invalidProperties".to_string())),
+            "Id {id}, Response: {response:?}"
+        );
+    }
+
+    // Saved queries are evaluated as Email/query filters
+    let response = saved_query_request(
+        &account_id,
+        &format!(
+            r#"["SavedQuery/get", {{"accountId": "$$", "ids": ["{query_id}"]}}, "R1"],
+               ["SavedQuery/changes", {{"accountId": "$$", "sinceState": "{old_state}"}}, "R2"]"#
+        ),
+    )
+    .await;
+    assert_eq!(
+        response.pointer("/methodResponses/0/1/list/0/name"),
+        Some(&Value::String("Invoices".to_string())),
+        "Response: {response:?}"
+    );
+    assert_eq!(
+        response.pointer("/methodResponses/0/1/list/0/filter/subject"),
+        Some(&Value::String("invoice".to_string())),
+        "Response: {response:?}"
+    );
+    assert_eq!(
+        response.pointer("/methodResponses/1/1/created/0"),
+        Some(&Value::String(query_id.clone())),
+        "Response: {response:?}"
+    );
+    let document_id = Id::from_bytes(query_id.as_bytes()).unwrap().document_id();
+    assert_eq!(
+        server
+            .saved_query_ids(account_id.document_id(), document_id)
+            .await
+            .unwrap()
+            .map(|ids| ids.len()),
+        Some(2)
+    );
+
+    // Updating a saved query changes the messages it matches
+    let response = saved_query_request(
+        &account_id,
+        &format!(
+            r#"["SavedQuery/set", {{
+                "accountId": "$$",
+                "update": {{"{query_id}": {{"name": "Lunch", "filter": {{"subject": "lunch"}}}}}}
+            }}, "R1"],
+            ["SavedQuery/get", {{"accountId": "$$", "ids": ["{query_id}"]}}, "R2"]"#
+        ),
+    )
+    .await;
+    assert_eq!(
+        response.pointer("/methodResponses/1/1/list/0/name"),
+        Some(&Value::String("Lunch".to_string())),
+        "Response: {response:?}"
+    );
+    assert_eq!(
+        server
+            .saved_query_ids(account_id.document_id(), document_id)
+            .await
+            .unwrap()
+            .map(|ids| ids.len()),
+        Some(1)
+    );
+
+    // Destroyed saved queries can no longer be evaluated
+    let response = saved_query_request(
+        &account_id,
+        &format!(
+            r#"["SavedQuery/set", {{"accountId": "$$", "destroy": ["{query_id}"]}}, "R1"],
+               ["SavedQuery/get", {{"accountId": "$$", "ids": ["{query_id}"]}}, "R2"]"#
+        ),
+    )
+    .await;
+    assert_eq!(
+        response.pointer("/methodResponses/0/1/destroyed/0"),
+        Some(&Value::String(query_id.clone())),
+        "Response: {response:?}"
+    );
+    assert_eq!(
+        response.pointer("/methodResponses/1/1/notFound/0"),
+        Some(&Value::String(query_id.clone())),
+        "Response: {response:?}"
+    );
+    assert_eq!(
+        server
+            .saved_query_ids(account_id.document_id(), document_id)
+            .await
+            .unwrap(),
+        None
+    );
+
+    admin_client.set_default_account_id(account_id.to_string());
+    destroy_all_mailboxes(admin_client).await;
+    server.store.assert_is_empty().await;
+}
+
+async fn saved_query_request(account_id: &Id, method_calls: &str) -> Value {
+    jmap_json_request(
+        format!("[{method_calls}]").replace("$$", &account_id.to_string()),
+        "jdoe@example.com",
+        "12345",
+    )
+    .await
+}
@@ -21,11 +21,25 @@
  * for more details.
 */
 
-use std::{sync::Arc, time::Duration};
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
 
 use ahash::AHashSet;
-use futures::StreamExt;
-use jmap::JMAP;
+use futures::{SinkExt, StreamExt};
+use hyper::header::AUTHORIZATION;
+use jmap::{
+    auth::{authenticate::BearerToken, oauth::TokenError},
+    websocket::{
+        deflate::{DeflateParams, DeflateStream},
+        encoding::WebSocketEncoding,
+        metrics::WebSocketMetricsSnapshot,
+        outbound::{OutboundQueue, QueueError},
+        throttle::ThrottledChanges,
+    },
+    JMAP,
+};
 use jmap_client::{
     client::Client,
     client_ws::WebSocketMessage,
@@ -33,19 +47,36 @@ use jmap_client::{
         response::{Response, TaggedMethodResponse},
         set::SetObject,
     },
+    mailbox::Role,
     TypeState,
 };
-use jmap_proto::types::id::Id;
-use tokio::sync::mpsc;
+use jmap_proto::types::{id::Id, state::State, type_state::DataType};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt, DuplexStream},
+    sync::{mpsc, watch},
+    task::JoinHandle,
+};
+use tokio_tungstenite::WebSocketStream;
+use tungstenite::{
+    protocol::{frame::coding::CloseCode, Role},
+    Message,
+};
+use utils::listener::{limiter::TokenBucket, ServerInstance};
 
 use crate::{
     directory::sql::create_test_user_with_email,
     jmap::{mailbox::destroy_all_mailboxes, test_account_login},
+    smtp::session::TestServerInstance,
 };
 
 pub async fn test(server: Arc<JMAP>, admin_client: &mut Client) {
     println!("Running WebSockets tests...");
 
+    // Compression settings are read from the configuration
+    assert!(server.config.web_socket_compression);
+    assert_eq!(server.config.web_socket_compression_level, 9);
+    assert_eq!(server.config.web_socket_compression_threshold, 32);
+
     // Authenticate all accounts
     let directory = server.directory.as_ref();
     create_test_user_with_email(directory, "jdoe@example.com", "12345", "John Doe").await;
@@ -123,12 +154,740 @@ pub async fn test(server: Arc<JMAP>, admin_client: &mut Client) {
         .unwrap();
     expect_nothing(&mut stream_rx).await;
 
+    // Clients that stop answering pings are disconnected
+    let (shutdown_tx, shutdown_rx) = watch::channel(false);
+    let (mut client_io, handle) = spawn_session(
+        &server,
+        "jdoe@example.com",
+        &shutdown_rx,
+        WebSocketEncoding::Json,
+        1024,
+    )
+    .await;
+    let mut pings = 0;
+    let mut unanswered = 0;
+    let start = Instant::now();
+    while let Some(opcode) =
+        tokio::time::timeout(Duration::from_secs(10), read_frame(&mut client_io))
+            .await
+            .unwrap()
+    {
+        assert_eq!(opcode, 0x09, "Expected ping, got opcode {opcode}");
+        pings += 1;
+        if pings <= 3 {
+            // Empty masked pong frame
+            client_io
+                .write_all(&[0x80 | 0x0a, 0x80, 0, 0, 0, 0])
+                .await
+                .unwrap();
+        } else {
+            unanswered += 1;
+        }
+    }
+    assert_eq!(unanswered, 2);
+    assert!(start.elapsed() >= Duration::from_secs(5));
+    handle.await.unwrap();
+
+    // Push and heartbeat statistics are exported through the admin API
+    let metrics = server.web_socket_metrics.snapshot();
+    assert!(metrics.connections >= 2, "{metrics:?}");
+    assert!(metrics.active >= 1, "{metrics:?}");
+    assert!(metrics.pushes >= 2, "{metrics:?}");
+    assert!(metrics.heartbeats >= 5, "{metrics:?}");
+    let exported = serde_json::from_slice::<WebSocketMetricsSnapshot>(
+        &reqwest::Client::builder()
+            .timeout(Duration::from_millis(500))
+            .danger_accept_invalid_certs(true)
+            .build()
+            .unwrap()
+            .get("https://127.0.0.1:8899/admin/websocket/metrics")
+            .header(AUTHORIZATION, "Basic YWRtaW46c2VjcmV0")
+            .send()
+            .await
+            .unwrap()
+            .bytes()
+            .await
+            .unwrap(),
+    )
+    .unwrap();
+    assert!(exported.connections >= metrics.connections);
+    assert!(exported.pushes >= metrics.pushes);
+    assert!(exported.heartbeats >= metrics.heartbeats);
+
+    // Clients that stop reading are disconnected once the outbound queue is full
+    let (mut client_io, handle) = spawn_session(
+        &server,
+        "jdoe@example.com",
+        &shutdown_rx,
+        WebSocketEncoding::Json,
+        2048,
+    )
+    .await;
+    for _ in 0..200 {
+        // Masked text frames, each one is answered with an error response
+        client_io
+            .write_all(&[0x80 | 0x01, 0x80 | 0x01, 0, 0, 0, 0, b'x'])
+            .await
+            .unwrap();
+    }
+    tokio::time::timeout(Duration::from_secs(5), handle)
+        .await
+        .expect("Stalled client was not disconnected")
+        .unwrap();
+    let mut buf = Vec::new();
+    tokio::time::timeout(Duration::from_secs(5), client_io.read_to_end(&mut buf))
+        .await
+        .unwrap()
+        .unwrap();
+    assert!(!buf.is_empty());
+
+    // MessagePack encoding is negotiated using the WebSocket subprotocol
+    assert_eq!(
+        WebSocketEncoding::negotiate(["jmap+msgpack, jmap"]),
+        WebSocketEncoding::MsgPack
+    );
+    assert_eq!(
+        WebSocketEncoding::negotiate(["other", "jmap, jmap+msgpack"]),
+        WebSocketEncoding::Json
+    );
+    assert_eq!(WebSocketEncoding::negotiate([]), WebSocketEncoding::Json);
+
+    // Binary requests are answered with MessagePack, text requests with JSON
+    let (client_io, handle) = spawn_session(
+        &server,
+        "jdoe@example.com",
+        &shutdown_rx,
+        WebSocketEncoding::MsgPack,
+        1024 * 1024,
+    )
+    .await;
+    let mut ws_client = client_stream(client_io).await;
+    let request = serde_json::json!({
+        "@type": "Request",
+        "id": "r1",
+        "using": ["urn:ietf:params:jmap:core"],
+        "methodCalls": [["Core/echo", {"hello": true}, "c1"]]
+    });
+    ws_client
+        .send(Message::Binary(rmp_serde::to_vec(&request).unwrap()))
+        .await
+        .unwrap();
+    let response = match ws_client.next().await.unwrap().unwrap() {
+        Message::Binary(bytes) => rmp_serde::from_slice::<serde_json::Value>(&bytes).unwrap(),
+        message => panic!("Expected binary message, got: {:?}", message),
+    };
+    assert_eq!(response["@type"], "Response");
+    assert_eq!(response["requestId"], "r1");
+    assert_eq!(
+        response["methodResponses"],
+        serde_json::json!([["Core/echo", {"hello": true}, "c1"]])
+    );
+    ws_client
+        .send(Message::Text(request.to_string()))
+        .await
+        .unwrap();
+    match ws_client.next().await.unwrap().unwrap() {
+        Message::Text(text) => {
+            let response = serde_json::from_str::<serde_json::Value>(&text).unwrap();
+            assert_eq!(response["requestId"], "r1");
+        }
+        message => panic!("Expected text message, got: {:?}", message),
+    }
+
+    // Invalid MessagePack is rejected with a binary error response
+    ws_client.send(Message::Binary(vec![0xc1])).await.unwrap();
+    match ws_client.next().await.unwrap().unwrap() {
+        Message::Binary(bytes) => {
+            let response = rmp_serde::from_slice::<serde_json::Value>(&bytes).unwrap();
+            assert_eq!(response["type"], "urn:ietf:params:jmap:error:notRequest");
+        }
+        message => panic!("Expected binary message, got: {:?}", message),
+    }
+    ws_client.close(None).await.unwrap();
+    handle.await.unwrap();
+
+    // Several requests can be sent in a single frame, failures don't abort the batch
+    let (client_io, handle) = spawn_session(
+        &server,
+        "jdoe@example.com",
+        &shutdown_rx,
+        WebSocketEncoding::Json,
+        1024 * 1024,
+    )
+    .await;
+    let mut ws_client = client_stream(client_io).await;
+    ws_client
+        .send(Message::Text(
+            serde_json::json!([
+                request,
+                {
+                    "@type": "Request",
+                    "id": "r2",
+                    "using": ["urn:ietf:params:jmap:core"],
+                    "methodCalls": "not-a-list"
+                },
+                {"@type": "WebSocketPushDisable"},
+                {
+                    "@type": "Request",
+                    "id": "r4",
+                    "using": ["urn:ietf:params:jmap:core"],
+                    "methodCalls": [["Core/echo", {"batch": 4}, "c4"]]
+                }
+            ])
+            .to_string(),
+        ))
+        .await
+        .unwrap();
+    let responses = next_text_message(&mut ws_client).await;
+    let responses = responses.as_array().unwrap();
+    assert_eq!(responses.len(), 4, "{responses:?}");
+    assert_eq!(responses[0]["@type"], "Response", "{responses:?}");
+    assert_eq!(responses[0]["requestId"], "r1", "{responses:?}");
+    assert_eq!(responses[1]["@type"], "RequestError", "{responses:?}");
+    assert_eq!(responses[2]["@type"], "RequestError", "{responses:?}");
+    assert_eq!(
+        responses[2]["type"], "urn:ietf:params:jmap:error:notRequest",
+        "{responses:?}"
+    );
+    assert_eq!(responses[3]["requestId"], "r4", "{responses:?}");
+    assert_eq!(
+        responses[3]["methodResponses"],
+        serde_json::json!([["Core/echo", {"batch": 4}, "c4"]])
+    );
+
+    // Empty batches are rejected
+    ws_client
+        .send(Message::Text("[]".to_string()))
+        .await
+        .unwrap();
+    let response = next_text_message(&mut ws_client).await;
+    assert_eq!(response["@type"], "RequestError", "{response}");
+    ws_client.close(None).await.unwrap();
+    tokio::time::timeout(Duration::from_secs(5), handle)
+        .await
+        .unwrap()
+        .unwrap();
+
+    // Requests over the rate limit are rejected until the connection is closed
+    let (client_io, handle) = spawn_session(
+        &server,
+        "jdoe@example.com",
+        &shutdown_rx,
+        WebSocketEncoding::Json,
+        1024 * 1024,
+    )
+    .await;
+    let mut ws_client = client_stream(client_io).await;
+    for _ in 0..15 {
+        ws_client
+            .send(Message::Text(request.to_string()))
+            .await
+            .unwrap();
+        let response = next_text_message(&mut ws_client).await;
+        assert_eq!(response["@type"], "Response", "{response}");
+    }
+
+    // Each request in a batch takes a token, batches are rejected as a whole
+    ws_client
+        .send(Message::Text(
+            serde_json::Value::Array(vec![request.clone(); 6]).to_string(),
+        ))
+        .await
+        .unwrap();
+    let response = next_text_message(&mut ws_client).await;
+    assert_eq!(response["status"], 429, "{response}");
+    ws_client
+        .send(Message::Text(
+            serde_json::Value::Array(vec![request.clone(); 5]).to_string(),
+        ))
+        .await
+        .unwrap();
+    let responses = next_text_message(&mut ws_client).await;
+    assert_eq!(responses.as_array().unwrap().len(), 5, "{responses}");
+    for num in 20..26 {
+        ws_client
+            .send(Message::Text(request.to_string()))
+            .await
+            .unwrap();
+        let response = match ws_client.next().await.unwrap().unwrap() {
+            Message::Text(text) => serde_json::from_str::<serde_json::Value>(&text).unwrap(),
+            Message::Close(Some(frame)) if num == 25 => {
+                assert_eq!(frame.code, CloseCode::Policy);
+                break;
+            }
+            message => panic!("Unexpected message {num}: {:?}", message),
+        };
+        assert_eq!(response["@type"], "RequestError", "{num}: {response}");
+        assert_eq!(response["status"], 429, "{num}: {response}");
+    }
+    tokio::time::timeout(Duration::from_secs(5), handle)
+        .await
+        .unwrap()
+        .unwrap();
+
+    // Tokens are refilled at the configured rate
+    let mut bucket = TokenBucket::new(2, 3);
+    let start = Instant::now();
+    for _ in 0..3 {
+        assert!(bucket.is_allowed_at(start));
+    }
+    assert!(!bucket.is_allowed_at(start));
+    assert!(!bucket.is_allowed_at(start + Duration::from_millis(400)));
+    assert!(bucket.is_allowed_at(start + Duration::from_millis(600)));
+    assert!(!bucket.is_allowed_at(start + Duration::from_millis(600)));
+    for _ in 0..3 {
+        assert!(bucket.is_allowed_at(start + Duration::from_secs(10)));
+    }
+    assert!(!bucket.is_allowed_at(start + Duration::from_secs(10)));
+    assert!(!bucket.is_allowed_n_at(2, start + Duration::from_millis(10_500)));
+    assert!(bucket.is_allowed_at(start + Duration::from_millis(10_500)));
+    assert!(!bucket.is_allowed_n_at(4, start + Duration::from_secs(20)));
+    assert!(bucket.is_allowed_n_at(3, start + Duration::from_secs(20)));
+
+    // Connections are terminated once their access token is revoked
+    create_test_user_with_email(directory, "jane@example.com", "abcde", "Jane Smith").await;
+    let (client_io, handle) = spawn_session(
+        &server,
+        "jane@example.com",
+        &shutdown_rx,
+        WebSocketEncoding::Json,
+        1024 * 1024,
+    )
+    .await;
+    let mut ws_client = client_stream(client_io).await;
+    ws_client
+        .send(Message::Text(request.to_string()))
+        .await
+        .unwrap();
+    match ws_client.next().await.unwrap().unwrap() {
+        Message::Text(text) => {
+            let response = serde_json::from_str::<serde_json::Value>(&text).unwrap();
+            assert_eq!(response["@type"], "Response");
+        }
+        message => panic!("Expected text message, got: {:?}", message),
+    }
+    directory
+        .query(
+            "UPDATE accounts SET active = false WHERE name = ?",
+            &["jane@example.com".into()],
+        )
+        .await
+        .unwrap();
+    let mut got_error = false;
+    loop {
+        match tokio::time::timeout(Duration::from_secs(5), ws_client.next())
+            .await
+            .expect("Connection was not terminated")
+            .unwrap()
+            .unwrap()
+        {
+            Message::Text(text) => {
+                let response = serde_json::from_str::<serde_json::Value>(&text).unwrap();
+                assert_eq!(response["@type"], "RequestError", "{response}");
+                assert_eq!(response["status"], 401, "{response}");
+                got_error = true;
+            }
+            Message::Close(Some(frame)) => {
+                assert_eq!(frame.code, CloseCode::Policy);
+                break;
+            }
+            Message::Ping(_) | Message::Pong(_) => (),
+            message => panic!("Unexpected message: {:?}", message),
+        }
+    }
+    assert!(got_error);
+    tokio::time::timeout(Duration::from_secs(5), handle)
+        .await
+        .unwrap()
+        .unwrap();
+
+    // Bearer tokens are verified again by their issuer
+    let jdoe_id = server.get_account_id("jdoe@example.com").await.unwrap();
+    assert_eq!(
+        server
+            .validate_access_token("access_token", "bm90LWEtdG9rZW4")
+            .await
+            .unwrap_err(),
+        TokenError::Malformed
+    );
+    assert!(server
+        .revalidate_access_token(jdoe_id, None)
+        .await
+        .is_some());
+    for token in [
+        BearerToken::Issued("bm90LWEtdG9rZW4".to_string()),
+        BearerToken::Directory("not-a-token".to_string()),
+    ] {
+        assert!(
+            server
+                .revalidate_access_token(jdoe_id, Some(&token))
+                .await
+                .is_none(),
+            "{token:?}"
+        );
+    }
+
+    // Upload tickets can be redeemed once without credentials
+    let (client_io, handle) = spawn_session(
+        &server,
+        "jdoe@example.com",
+        &shutdown_rx,
+        WebSocketEncoding::Json,
+        1024 * 1024,
+    )
+    .await;
+    let mut ws_client = client_stream(client_io).await;
+    let jane_account_id = Id::from(server.get_account_id("jane@example.com").await.unwrap());
+    ws_client
+        .send(Message::Text(
+            serde_json::json!({
+                "@type": "WebSocketUploadTicket",
+                "id": "t1",
+                "accountId": jane_account_id.to_string()
+            })
+            .to_string(),
+        ))
+        .await
+        .unwrap();
+    let response = next_text_message(&mut ws_client).await;
+    assert_eq!(response["@type"], "RequestError", "{response}");
+    assert_eq!(response["status"], 403, "{response}");
+    assert_eq!(response["requestId"], "t1", "{response}");
+    ws_client
+        .send(Message::Text(
+            serde_json::json!({
+                "@type": "WebSocketUploadTicket",
+                "id": "t2",
+                "accountId": account_id
+            })
+            .to_string(),
+        ))
+        .await
+        .unwrap();
+    let response = next_text_message(&mut ws_client).await;
+    assert_eq!(response["@type"], "UploadTicket", "{response}");
+    assert_eq!(response["accountId"], account_id, "{response}");
+    assert_eq!(response["requestId"], "t2", "{response}");
+    let ticket = response["ticket"].as_str().unwrap().to_string();
+    assert!(response["uploadUrl"]
+        .as_str()
+        .unwrap()
+        .ends_with(&format!("/jmap/upload/{account_id}/?ticket={ticket}")));
+
+    // Tickets are bound to the account they were issued for
+    assert_eq!(
+        upload_with_ticket(&jane_account_id.to_string(), &ticket, b"hello").await,
+        (401, serde_json::Value::Null)
+    );
+    assert_eq!(
+        upload_with_ticket(&account_id, "not-a-ticket", b"hello").await,
+        (401, serde_json::Value::Null)
+    );
+
+    // A second ticket is needed since redemption attempts consume the ticket
+    ws_client
+        .send(Message::Text(
+            serde_json::json!({
+                "@type": "WebSocketUploadTicket",
+                "id": "t3",
+                "accountId": account_id
+            })
+            .to_string(),
+        ))
+        .await
+        .unwrap();
+    let ticket = next_text_message(&mut ws_client).await["ticket"]
+        .as_str()
+        .unwrap()
+        .to_string();
+    let (status, upload) = upload_with_ticket(&account_id, &ticket, b"hello world").await;
+    assert_eq!(status, 200, "{upload}");
+    assert_eq!(upload["accountId"], account_id, "{upload}");
+    assert_eq!(upload["size"], 11, "{upload}");
+
+    // The connection is notified once the blob is available
+    let available = next_text_message(&mut ws_client).await;
+    assert_eq!(available["@type"], "BlobAvailable", "{available}");
+    assert_eq!(available["blobId"], upload["blobId"], "{available}");
+    assert_eq!(available["size"], 11, "{available}");
+    assert_eq!(available["requestId"], "t3", "{available}");
+    assert!(available["downloadUrl"]
+        .as_str()
+        .unwrap()
+        .ends_with(&format!(
+            "/jmap/download/{account_id}/{}/blob",
+            upload["blobId"].as_str().unwrap()
+        )));
+
+    // Tickets can't be reused
+    assert_eq!(
+        upload_with_ticket(&account_id, &ticket, b"hello world").await,
+        (401, serde_json::Value::Null)
+    );
+    ws_client.close(None).await.unwrap();
+    tokio::time::timeout(Duration::from_secs(5), handle)
+        .await
+        .unwrap()
+        .unwrap();
+
+    // Acknowledged state changes are numbered, unacknowledged ones are re-sent on reconnect
+    let mailbox_id = client
+        .mailbox_create("WebSocket Ack Test", None::<String>, Role::None)
+        .await
+        .unwrap()
+        .take_id();
+    let push_enable = serde_json::json!({
+        "@type": "WebSocketPushEnable",
+        "dataTypes": ["Mailbox"],
+        "ackRequired": true,
+        "clientId": "ack-test"
+    })
+    .to_string();
+    let (client_io, handle) = spawn_session(
+        &server,
+        "jdoe@example.com",
+        &shutdown_rx,
+        WebSocketEncoding::Json,
+        1024 * 1024,
+    )
+    .await;
+    let mut ws_client = client_stream(client_io).await;
+    ws_client
+        .send(Message::Text(push_enable.clone()))
+        .await
+        .unwrap();
+    client
+        .mailbox_update_sort_order(&mailbox_id, 1)
+        .await
+        .unwrap();
+    let change = next_text_message(&mut ws_client).await;
+    assert_eq!(change["@type"], "StateChange", "{change}");
+    assert_eq!(change["seqId"], 0, "{change}");
+    ws_client
+        .send(Message::Text(
+            serde_json::json!({"@type": "WebSocketPushAck", "seqId": 0}).to_string(),
+        ))
+        .await
+        .unwrap();
+    client
+        .mailbox_update_sort_order(&mailbox_id, 2)
+        .await
+        .unwrap();
+    let unacknowledged = next_text_message(&mut ws_client).await;
+    assert_eq!(unacknowledged["seqId"], 1, "{unacknowledged}");
+    ws_client.close(None).await.unwrap();
+    tokio::time::timeout(Duration::from_secs(5), handle)
+        .await
+        .unwrap()
+        .unwrap();
+
+    // Only the unacknowledged change is delivered again
+    let (client_io, handle) = spawn_session(
+        &server,
+        "jdoe@example.com",
+        &shutdown_rx,
+        WebSocketEncoding::Json,
+        1024 * 1024,
+    )
+    .await;
+    let mut ws_client = client_stream(client_io).await;
+    ws_client
+        .send(Message::Text(push_enable.clone()))
+        .await
+        .unwrap();
+    assert_eq!(next_text_message(&mut ws_client).await, unacknowledged);
+    ws_client
+        .send(Message::Text(
+            serde_json::json!({"@type": "WebSocketPushAck", "seqId": 1}).to_string(),
+        ))
+        .await
+        .unwrap();
+    ws_client.close(None).await.unwrap();
+    tokio::time::timeout(Duration::from_secs(5), handle)
+        .await
+        .unwrap()
+        .unwrap();
+    assert!(server
+        .web_socket_pending
+        .get(&(
+            server.get_account_id("jdoe@example.com").await.unwrap(),
+            "ack-test".to_string()
+        ))
+        .is_none());
+    client.mailbox_destroy(&mailbox_id, true).await.unwrap();
+
+    // Heartbeats carry the server time in a message, pings have an empty payload
+    let (client_io, handle) = spawn_session(
+        &server,
+        "jdoe@example.com",
+        &shutdown_rx,
+        WebSocketEncoding::Json,
+        1024 * 1024,
+    )
+    .await;
+    let mut ws_client = client_stream(client_io).await;
+    ws_client
+        .send(Message::Text(
+            serde_json::json!({
+                "@type": "WebSocketPushEnable",
+                "dataTypes": null,
+                "serverTime": true
+            })
+            .to_string(),
+        ))
+        .await
+        .unwrap();
+    let mut server_time = None;
+    let mut pings = 0;
+    while pings < 2 {
+        match tokio::time::timeout(Duration::from_secs(5), ws_client.next())
+            .await
+            .expect("No heartbeat received")
+            .unwrap()
+            .unwrap()
+        {
+            Message::Text(text) => {
+                let message = serde_json::from_str::<serde_json::Value>(&text).unwrap();
+                assert_eq!(message["@type"], "ServerTime", "{message}");
+                server_time = message["serverTime"].as_str().map(|s| s.to_string());
+            }
+            Message::Ping(payload) => {
+                assert!(payload.is_empty(), "Unexpected ping payload {payload:?}");
+                pings += 1;
+            }
+            message => panic!("Unexpected message: {:?}", message),
+        }
+    }
+    assert!(server_time.is_some());
+    ws_client.close(None).await.unwrap();
+    tokio::time::timeout(Duration::from_secs(5), handle)
+        .await
+        .unwrap()
+        .unwrap();
+
+    // Clients are sent a close frame when the server shuts down
+    let (client_io, handle) = spawn_session(
+        &server,
+        "jdoe@example.com",
+        &shutdown_rx,
+        WebSocketEncoding::Json,
+        1024 * 1024,
+    )
+    .await;
+    let mut ws_client = client_stream(client_io).await;
+    shutdown_tx.send(true).unwrap();
+    match tokio::time::timeout(Duration::from_secs(5), ws_client.next())
+        .await
+        .unwrap()
+        .unwrap()
+        .unwrap()
+    {
+        Message::Close(Some(frame)) => {
+            assert_eq!(frame.code, CloseCode::Away);
+            assert_eq!(frame.reason, "Server shutting down");
+        }
+        message => panic!("Expected close frame, got: {:?}", message),
+    }
+    tokio::time::timeout(Duration::from_secs(5), handle)
+        .await
+        .unwrap()
+        .unwrap();
+
     admin_client.set_default_account_id(account_id);
     destroy_all_mailboxes(admin_client).await;
 
     server.store.assert_is_empty().await;
 }
 
+// Runs a WebSocket session for an account over an in-memory stream, returning
+// the client end of the stream along with the task serving the session.
+async fn spawn_session(
+    server: &Arc<JMAP>,
+    account: &str,
+    shutdown_rx: &watch::Receiver<bool>,
+    encoding: WebSocketEncoding,
+    max_buf_size: usize,
+) -> (DuplexStream, JoinHandle<()>) {
+    let access_token = server
+        .get_access_token(server.get_account_id(account).await.unwrap())
+        .await
+        .unwrap();
+    let (client_io, server_io) = tokio::io::duplex(max_buf_size);
+    let stream =
+        WebSocketStream::from_raw_socket(DeflateStream::new(server_io, None), Role::Server, None)
+            .await;
+    let instance = Arc::new(ServerInstance::test_with_shutdown(shutdown_rx.clone()));
+    let server = server.clone();
+    let handle = tokio::spawn(async move {
+        server
+            .handle_websocket_stream(stream, Arc::new(access_token), instance, encoding, None)
+            .await
+    });
+
+    (client_io, handle)
+}
+
+// Returns the next text message received over a WebSocket, skipping heartbeats.
+async fn next_text_message(
+    ws_client: &mut WebSocketStream<DeflateStream<DuplexStream>>,
+) -> serde_json::Value {
+    loop {
+        match tokio::time::timeout(Duration::from_secs(5), ws_client.next())
+            .await
+            .expect("No message received")
+            .unwrap()
+            .unwrap()
+        {
+            Message::Text(text) => return serde_json::from_str(&text).unwrap(),
+            Message::Ping(_) | Message::Pong(_) => (),
+            message => panic!("Expected text message, got: {:?}", message),
+        }
+    }
+}
+
+// Uploads a blob using a ticket, returning the status code and the JSON response
+// of successful uploads.
+async fn upload_with_ticket(
+    account_id: &str,
+    ticket: &str,
+    bytes: &[u8],
+) -> (u16, serde_json::Value) {
+    let response = reqwest::Client::builder()
+        .timeout(Duration::from_millis(500))
+        .danger_accept_invalid_certs(true)
+        .build()
+        .unwrap()
+        .post(format!(
+            "https://127.0.0.1:8899/jmap/upload/{account_id}/?ticket={ticket}"
+        ))
+        .body(bytes.to_vec())
+        .send()
+        .await
+        .unwrap();
+    let status = response.status().as_u16();
+    if status == 200 {
+        (
+            status,
+            serde_json::from_slice(&response.bytes().await.unwrap()).unwrap(),
+        )
+    } else {
+        (status, serde_json::Value::Null)
+    }
+}
+
+async fn client_stream(io: DuplexStream) -> WebSocketStream<DeflateStream<DuplexStream>> {
+    WebSocketStream::from_raw_socket(DeflateStream::new(io, None), Role::Client, None).await
+}
+
+async fn read_frame(io: &mut DuplexStream) -> Option<u8> {
+    let mut header = [0u8; 2];
+    io.read_exact(&mut header).await.ok()?;
+    assert!(header[1] < 126, "Unexpected frame length");
+    let mut payload = vec![0u8; header[1] as usize];
+    io.read_exact(&mut payload).await.ok()?;
+    Some(header[0] & 0x0f)
+}
+
 async fn expect_response(
     stream_rx: &mut mpsc::Receiver<WebSocketMessage>,
 ) -> Response<TaggedMethodResponse> {
@@ -176,3 +935,314 @@ async fn expect_nothing(stream_rx: &mut mpsc::Receiver<WebSocketMessage>) {
         }
     }
 }
+
+#[tokio::test]
+async fn websocket_compression() {
+    // Only offers with parameters that can be honoured are accepted
+    let params = DeflateParams::negotiate([
+        "x-webkit-deflate-frame, permessage-deflate; client_max_window_bits",
+    ])
+    .unwrap();
+    assert_eq!(params.to_header(), "permessage-deflate");
+    let no_context_takeover = DeflateParams::negotiate([
+        "permessage-deflate; server_max_window_bits=10",
+        "permessage-deflate; server_no_context_takeover; server_max_window_bits=15",
+    ])
+    .unwrap();
+    assert_eq!(
+        no_context_takeover.to_header(),
+        "permessage-deflate; server_no_context_takeover; server_max_window_bits=15"
+    );
+    assert_eq!(
+        DeflateParams::negotiate(["permessage-deflate; server_max_window_bits=10"]),
+        None
+    );
+    assert_eq!(
+        DeflateParams::negotiate(["permessage-deflate; unknown_param"]),
+        None
+    );
+    assert_eq!(DeflateParams::negotiate(["x-webkit-deflate-frame"]), None);
+    assert_eq!(DeflateParams::negotiate([]), None);
+
+    let message = format!(
+        "{{\"@type\":\"StateChange\",\"changed\":{{{}}}}}",
+        (0..100)
+            .map(|i| format!("\"a{i}\":{{\"Email\":\"s{i}\",\"Mailbox\":\"s{i}\"}}"))
+            .collect::<Vec<_>>()
+            .join(",")
+    );
+
+    // Messages round-trip in both directions, falling back to plain frames without the extension
+    for params in [Some(params), Some(no_context_takeover), None] {
+        let (client_io, server_io) = tokio::io::duplex(1024 * 1024);
+        let mut server = WebSocketStream::from_raw_socket(
+            DeflateStream::new(server_io, params),
+            Role::Server,
+            None,
+        )
+        .await;
+        let mut client = WebSocketStream::from_raw_socket(
+            DeflateStream::new(client_io, params),
+            Role::Client,
+            None,
+        )
+        .await;
+        assert_eq!(server.get_ref().is_compressed(), params.is_some());
+
+        for text in [message.as_str(), message.as_str(), "", "{}"] {
+            client.send(Message::Text(text.to_string())).await.unwrap();
+            assert_eq!(
+                server.next().await.unwrap().unwrap(),
+                Message::Text(text.to_string()),
+                "{params:?}"
+            );
+            server.send(Message::Text(text.to_string())).await.unwrap();
+            assert_eq!(
+                client.next().await.unwrap().unwrap(),
+                Message::Text(text.to_string()),
+                "{params:?}"
+            );
+        }
+
+        // Control frames are not compressed
+        client.send(Message::Ping(b"ping".to_vec())).await.unwrap();
+        assert_eq!(
+            server.next().await.unwrap().unwrap(),
+            Message::Ping(b"ping".to_vec())
+        );
+    }
+
+    // Server frames are sent compressed with the RSV1 bit set
+    let (mut raw_server, server_io) = tokio::io::duplex(1024 * 1024);
+    let mut server = WebSocketStream::from_raw_socket(
+        DeflateStream::new(server_io, Some(params)),
+        Role::Server,
+        None,
+    )
+    .await;
+    server.send(Message::Text(message.clone())).await.unwrap();
+    let mut frame = vec![0u8; 1024 * 1024];
+    let frame_len = raw_server.read(&mut frame).await.unwrap();
+    assert_eq!(frame[0], 0x80 | 0x40 | 0x01);
+    assert!(
+        frame_len < message.len() / 2,
+        "{frame_len} >= {}",
+        message.len() / 2
+    );
+
+    // The compressed frame inflates back to the original message
+    let (client_io, mut raw_client) = tokio::io::duplex(1024 * 1024);
+    raw_client.write_all(&frame[..frame_len]).await.unwrap();
+    let mut client = WebSocketStream::from_raw_socket(
+        DeflateStream::new(client_io, Some(params)),
+        Role::Client,
+        None,
+    )
+    .await;
+    assert_eq!(
+        client.next().await.unwrap().unwrap(),
+        Message::Text(message.clone())
+    );
+
+    // The compression level is applied to outgoing messages
+    let stored = server_frame(params, 0, 0, &message).await;
+    assert_eq!(stored[0], 0x80 | 0x40 | 0x01);
+    assert!(stored.len() > message.len(), "{}", stored.len());
+    let best = server_frame(params, 9, 0, &message).await;
+    assert_eq!(best[0], 0x80 | 0x40 | 0x01);
+    assert!(best.len() <= frame_len, "{} > {frame_len}", best.len());
+
+    // Messages below the threshold are sent uncompressed with the RSV1 bit clear
+    let small = server_frame(params, 6, 32, "{}").await;
+    assert_eq!(small, [0x80 | 0x01, 2, b'{', b'}']);
+    let large = server_frame(params, 6, 32, &message).await;
+    assert_eq!(large[0], 0x80 | 0x40 | 0x01);
+    assert_eq!(
+        server_frame(params, 6, message.len() + 1, &message).await[0],
+        0x80 | 0x01
+    );
+}
+
+// Sends a text message through a compressing server stream, returning the raw frame
+async fn server_frame(params: DeflateParams, level: u32, threshold: usize, text: &str) -> Vec<u8> {
+    let (mut raw_server, server_io) = tokio::io::duplex(1024 * 1024);
+    let mut server = WebSocketStream::from_raw_socket(
+        DeflateStream::new(server_io, Some(params)).with_compression(level, threshold),
+        Role::Server,
+        None,
+    )
+    .await;
+    server.send(Message::Text(text.to_string())).await.unwrap();
+    let mut frame = vec![0u8; 1024 * 1024];
+    let frame_len = raw_server.read(&mut frame).await.unwrap();
+    frame.truncate(frame_len);
+    frame
+}
+
+#[tokio::test]
+async fn websocket_outbound_backpressure() {
+    let (client_io, server_io) = tokio::io::duplex(1024);
+    let (sink, _stream) =
+        WebSocketStream::from_raw_socket(DeflateStream::new(server_io, None), Role::Server, None)
+            .await
+            .split();
+    let mut outbound = OutboundQueue::new(sink, 2);
+
+    // Messages are queued until the client stops reading and the queue fills up
+    let mut result = Ok(());
+    for _ in 0..8 {
+        result = outbound.send(Message::Text("x".repeat(4096)));
+        if result.is_err() {
+            break;
+        }
+    }
+    assert_eq!(result, Err(QueueError::Full));
+    assert!(outbound.send(Message::Text("x".to_string())).is_err());
+
+    // The client is sent a close frame once it reads again
+    let mut ws_client = client_stream(client_io).await;
+    loop {
+        match tokio::time::timeout(Duration::from_secs(5), ws_client.next())
+            .await
+            .expect("No close frame received")
+            .unwrap()
+            .unwrap()
+        {
+            Message::Text(text) => assert_eq!(text.len(), 4096),
+            Message::Close(Some(frame)) => {
+                assert_eq!(frame.code, CloseCode::Policy);
+                assert_eq!(frame.reason, "Client is not reading messages fast enough");
+                break;
+            }
+            message => panic!("Unexpected message: {:?}", message),
+        }
+    }
+    tokio::time::timeout(Duration::from_secs(5), outbound.shutdown())
+        .await
+        .unwrap();
+}
+
+#[test]
+fn websocket_type_throttle() {
+    let mut changes = ThrottledChanges::new(
+        Duration::from_secs(1),
+        [
+            (DataType::Email, Duration::from_millis(100)),
+            (DataType::EmailDelivery, Duration::from_secs(5)),
+        ]
+        .into_iter()
+        .collect(),
+    );
+    let account_id = Id::from(1u32);
+    let start = Instant::now();
+    let at = |ms: u64| start + Duration::from_millis(ms);
+    let pushed_types = |changes: &mut ThrottledChanges, ms: u64| {
+        changes.take_due(at(ms)).map(|push| {
+            push.changes
+                .changed
+                .get(&account_id)
+                .unwrap()
+                .iter()
+                .map(|(data_type, state)| (*data_type, state.clone()))
+                .collect::<Vec<_>>()
+        })
+    };
+
+    // Nothing was pushed yet, all types are sent right away
+    for data_type in [DataType::Email, DataType::EmailDelivery, DataType::Mailbox] {
+        changes.insert(account_id, data_type, State::from(1), at(0));
+    }
+    assert_eq!(
+        pushed_types(&mut changes, 0).unwrap(),
+        vec![
+            (DataType::Email, State::from(1)),
+            (DataType::EmailDelivery, State::from(1)),
+            (DataType::Mailbox, State::from(1)),
+        ]
+    );
+    assert!(changes.is_empty());
+    assert_eq!(changes.next_deadline(at(0)), None);
+
+    // Each type waits for its own interval
+    changes.insert(account_id, DataType::Email, State::from(2), at(10));
+    changes.insert(account_id, DataType::EmailDelivery, State::from(2), at(10));
+    assert_eq!(
+        changes.next_deadline(at(10)),
+        Some(Duration::from_millis(90))
+    );
+    assert_eq!(pushed_types(&mut changes, 10), None);
+    let push = changes.take_due(at(100)).unwrap();
+    assert_eq!(push.latency, Duration::from_millis(90));
+    assert_eq!(push.coalesced, 0);
+    assert_eq!(
+        push.changes
+            .changed
+            .get(&account_id)
+            .unwrap()
+            .keys()
+            .collect::<Vec<_>>(),
+        vec![&DataType::Email]
+    );
+    assert_eq!(
+        changes.next_deadline(at(100)),
+        Some(Duration::from_millis(4900))
+    );
+
+    // Types without a configured throttle use the default interval
+    changes.insert(account_id, DataType::EmailDelivery, State::from(3), at(150));
+    changes.insert(account_id, DataType::Mailbox, State::from(2), at(150));
+    assert_eq!(
+        changes.next_deadline(at(150)),
+        Some(Duration::from_millis(850))
+    );
+    assert_eq!(pushed_types(&mut changes, 999), None);
+    assert_eq!(
+        pushed_types(&mut changes, 1000).unwrap(),
+        vec![(DataType::Mailbox, State::from(2))]
+    );
+    assert_eq!(
+        changes.next_deadline(at(1000)),
+        Some(Duration::from_millis(4000))
+    );
+
+    // Delivery changes are coalesced until their interval elapses
+    let push = changes.take_due(at(5000)).unwrap();
+    assert_eq!(push.coalesced, 1);
+    assert_eq!(push.latency, Duration::from_millis(4990));
+    assert_eq!(
+        push.changes
+            .changed
+            .get(&account_id)
+            .unwrap()
+            .get(&DataType::EmailDelivery),
+        Some(&State::from(3))
+    );
+    assert!(changes.is_empty());
+
+    // A client requested throttle replaces the default interval only
+    changes.set_throttle(Duration::from_millis(200));
+    changes.insert(account_id, DataType::Mailbox, State::from(3), at(5050));
+    changes.insert(
+        account_id,
+        DataType::EmailDelivery,
+        State::from(4),
+        at(5050),
+    );
+    assert_eq!(
+        pushed_types(&mut changes, 5050).unwrap(),
+        vec![(DataType::Mailbox, State::from(3))]
+    );
+    changes.insert(account_id, DataType::Mailbox, State::from(4), at(5100));
+    assert_eq!(
+        changes.next_deadline(at(5100)),
+        Some(Duration::from_millis(150))
+    );
+    assert_eq!(
+        pushed_types(&mut changes, 5250).unwrap(),
+        vec![(DataType::Mailbox, State::from(4))]
+    );
+    assert_eq!(
+        changes.next_deadline(at(5250)),
+        Some(Duration::from_millis(4750))
+    );
+}
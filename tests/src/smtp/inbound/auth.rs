@@ -53,6 +53,13 @@ secret = "p4ssw0rd"
 email = "jane@example.org"
 email-list = ["info@example.org"]
 member-of = ["sales", "support"]
+
+[[directory."local".users]]
+name = "bill"
+description = "Bill Foobar"
+secret = "secret"
+totp-secret = "totp/bill"
+email = "bill@example.org"
 "#;
 
 #[tokio::test]
@@ -152,6 +159,13 @@ async fn auth() {
     session.cmd("amFuZQ==", "334").await;
     session.cmd("cDRzc3cwcmQ=", "235 2.7.0").await;
 
+    // Accounts requiring a second factor should be rejected
+    session.data.authenticated_as.clear();
+    session.data.auth_errors = 0;
+    session
+        .cmd("AUTH PLAIN AGJpbGwAc2VjcmV0", "535 5.7.8")
+        .await;
+
     // Login should not be advertised to 10.0.0.2
     session.data.remote_ip = "10.0.0.2".parse().unwrap();
     session.eval_session_params().await;
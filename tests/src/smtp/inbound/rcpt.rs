@@ -34,6 +34,7 @@ use crate::smtp::{
 use smtp::{
     config::{ConfigContext, IfBlock, MaybeDynValue},
     core::{Session, State, SMTP},
+    inbound::srs::srs_encode,
 };
 
 const DIRECTORY: &str = r#"
@@ -171,3 +172,67 @@ async fn rcpt() {
     assert!((rcpt.flags & (RCPT_NOTIFY_DELAY | RCPT_NOTIFY_SUCCESS | RCPT_NOTIFY_FAILURE)) != 0);
     assert_eq!(rcpt.dsn_info.as_ref().unwrap(), "Jane.Doe@Foobar.org");
 }
+
+#[tokio::test]
+async fn rcpt_srs() {
+    let mut core = SMTP::test();
+
+    let directory = Config::new(DIRECTORY).unwrap().parse_directory().unwrap();
+    let config = &mut core.session.config.rcpt;
+    config.directory = IfBlock::new(Some(MaybeDynValue::Static(
+        directory.directories.get("local").unwrap().clone(),
+    )));
+    config.relay = IfBlock::new(false);
+    config.errors_max = IfBlock::new(10);
+    config.errors_wait = IfBlock::new(Duration::from_millis(5));
+    core.session.config.data.srs_secret = Some("srs-secret".to_string());
+
+    let mut session = Session::test(core);
+    session.data.remote_ip = "10.0.0.1".parse().unwrap();
+    session.eval_session_params().await;
+    session.ehlo("mx1.remote.org").await;
+    session.mail_from("<>", "250").await;
+
+    // Bounces to a rewritten sender are relayed to the original address
+    let srs_address = srs_encode("srs-secret", "Sender@Remote.org", "mx.example.org");
+    assert!(srs_address.starts_with("SRS0="), "{srs_address}");
+    assert!(srs_address.ends_with("=Remote.org=Sender@mx.example.org"));
+    session.rcpt_to(&srs_address, "250").await;
+    let rcpt = session.data.rcpt_to.last().unwrap();
+    assert_eq!(rcpt.address, "Sender@Remote.org");
+    assert_eq!(rcpt.domain, "remote.org");
+
+    // The hash is case insensitive
+    session.rcpt_to(&srs_address.to_lowercase(), "250").await;
+    assert_eq!(session.data.rcpt_to.len(), 1);
+
+    // Forwarded SRS0 addresses are rewritten as SRS1 and reversed to the first hop
+    let srs_address = srs_encode(
+        "srs-secret",
+        "SRS0=HHHH=TT=other.org=user@forwarder.net",
+        "mx.example.org",
+    );
+    assert!(srs_address.starts_with("SRS1="), "{srs_address}");
+    assert!(srs_address.ends_with("=forwarder.net==HHHH=TT=other.org=user@mx.example.org"));
+    session.rcpt_to(&srs_address, "250").await;
+    assert_eq!(
+        session.data.rcpt_to.last().unwrap().address,
+        "SRS0=HHHH=TT=other.org=user@forwarder.net"
+    );
+
+    // Forged or tampered addresses are rejected
+    session
+        .rcpt_to("SRS0=AAAA=AA=remote.org=sender@mx.example.org", "550 5.1.1")
+        .await;
+    let srs_address = srs_encode("other-secret", "sender@remote.org", "mx.example.org");
+    session.rcpt_to(&srs_address, "550 5.1.1").await;
+    let srs_address = srs_encode("srs-secret", "sender@remote.org", "mx.example.org")
+        .replace("=sender@", "=admin@");
+    session.rcpt_to(&srs_address, "550 5.1.1").await;
+
+    // SRS addresses at other domains are not reversed
+    session
+        .rcpt_to("SRS0=AAAA=AA=remote.org=sender@other.org", "550 5.1.2")
+        .await;
+    assert_eq!(session.data.rcpt_to.len(), 2);
+}
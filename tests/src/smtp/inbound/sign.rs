@@ -21,9 +21,12 @@
  * for more details.
 */
 
-use std::time::{Duration, Instant};
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
 
-use directory::config::ConfigDirectory;
+use directory::{config::ConfigDirectory, Domain};
 use mail_auth::{
     common::{parse::TxtRecordParser, verify::DomainKey},
     spf::Spf,
@@ -219,6 +222,40 @@ async fn sign_and_seal() {
         );
 }
 
+#[test]
+fn domain_signer() {
+    let ctx = ConfigContext::new(&[]).parse_signatures();
+    let config = Config::new(SIGNATURES)
+        .unwrap()
+        .parse_mail_auth(&ctx)
+        .unwrap()
+        .dkim;
+
+    for (key, selector, domain, expected) in [
+        // Signers are resolved by key id, the selector has to match
+        (Some("rsa"), None, "example.com", Some("rsa")),
+        (Some("ed"), Some("ed"), "example.com", Some("ed")),
+        (Some("rsa"), Some("ed"), "example.com", None),
+        (Some("unknown"), Some("rsa"), "example.com", None),
+        // Selectors are never used as a key id
+        (None, Some("rsa"), "example.com", Some("rsa")),
+        (None, Some("rsa"), "example.org", None),
+        (None, Some("unknown"), "example.com", None),
+        (None, None, "example.com", None),
+    ] {
+        let mut domain = Domain::new(domain);
+        domain.dkim_key = key.map(|key| key.to_string());
+        domain.dkim_selector = selector.map(|selector| selector.to_string());
+        let signer = config.domain_signer(&domain);
+        assert_eq!(
+            signer.map(|signer| Arc::as_ptr(&signer)),
+            expected.map(|id| Arc::as_ptr(&ctx.signers[id])),
+            "Failed for key {key:?}, selector {selector:?}, domain {:?}",
+            domain.name
+        );
+    }
+}
+
 pub trait TextConfigContext<'x> {
     fn parse_signatures(self) -> ConfigContext<'x>;
 }
@@ -82,6 +82,7 @@ async fn throttle_inbound() {
         domain: "test.org".to_string(),
         flags: 0,
         dsn_info: None,
+        relay: None,
     }
     .into();
     assert!(session.is_allowed().await, "Rate limiter too strict.");
@@ -93,6 +94,7 @@ async fn throttle_inbound() {
         domain: "test.org".to_string(),
         flags: 0,
         dsn_info: None,
+        relay: None,
     }
     .into();
     assert!(session.is_allowed().await, "Rate limiter failed.");
@@ -104,6 +106,7 @@ async fn throttle_inbound() {
         domain: "example.org".to_string(),
         flags: 0,
         dsn_info: None,
+        relay: None,
     });
     assert!(session.is_allowed().await, "Rate limiter too strict.");
     assert!(session.is_allowed().await, "Rate limiter too strict.");
@@ -44,6 +44,9 @@ const CONFIG: &str = r#"
 type = "sql"
 address = "sqlite::memory:"
 
+[directory."sql".options]
+allow-plaintext = true
+
 [directory."sql".pool]
 max-connections = 1
 
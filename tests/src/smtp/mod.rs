@@ -263,6 +263,8 @@ impl TestConfig for SessionConfig {
                 add_auth_results: IfBlock::new(true),
                 add_message_id: IfBlock::new(true),
                 add_date: IfBlock::new(true),
+                srs_secret: None,
+                srs_max_age: Duration::from_secs(21 * 86400),
                 pipe_commands: vec![],
                 milters: vec![],
             },
@@ -304,6 +306,7 @@ impl TestConfig for QueueConfig {
             expire: IfBlock::new(Duration::from_secs(10)),
             hostname: IfBlock::new("mx.example.org".to_string()),
             next_hop: Default::default(),
+            relay_hosts: Default::default(),
             max_mx: IfBlock::new(5),
             max_multihomed: IfBlock::new(5),
             source_ip: QueueOutboundSourceIp {
@@ -353,6 +356,7 @@ impl TestConfig for MailAuthConfig {
             dkim: DkimAuthConfig {
                 verify: IfBlock::new(VerifyStrategy::Relaxed),
                 sign: IfBlock::default(),
+                signers: AHashMap::new(),
             },
             arc: ArcAuthConfig {
                 verify: IfBlock::new(VerifyStrategy::Relaxed),
@@ -92,6 +92,7 @@ async fn generate_dsn() {
                 details: "Connection timeout".to_string(),
             })),
             disable_tls: false,
+            relay: None,
             changed: false,
         }],
         flags: 0,
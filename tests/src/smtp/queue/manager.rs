@@ -153,6 +153,7 @@ fn domain(domain: &str, retry: u64, notify: u64, expires: u64) -> Domain {
         expires: Instant::now() + Duration::from_secs(expires),
         status: Status::Scheduled,
         disable_tls: false,
+        relay: None,
         changed: false,
     }
 }
@@ -80,6 +80,7 @@ async fn queue_serialize() {
                 expires: Instant::now() + Duration::from_secs(10),
                 status: Status::Scheduled,
                 disable_tls: false,
+                relay: None,
                 changed: false,
             },
             Domain {
@@ -89,6 +90,7 @@ async fn queue_serialize() {
                 expires: Instant::now() + Duration::from_secs(10),
                 status: Status::Scheduled,
                 disable_tls: false,
+                relay: "mx.relay.example.com".to_string().into(),
                 changed: false,
             },
         ],
@@ -192,6 +194,7 @@ fn assert_msg_eq(msg: &Message, other: &Message) {
         assert_eq!(domain.retry.inner, other.retry.inner);
         assert_eq!(domain.notify.inner, other.notify.inner);
         assert_eq!(domain.status, other.status);
+        assert_eq!(domain.relay, other.relay);
         assert_instant_eq(domain.expires, other.expires);
         assert_instant_eq(domain.retry.due, other.retry.due);
         assert_instant_eq(domain.notify.due, other.notify.due);
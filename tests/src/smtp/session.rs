@@ -247,6 +247,7 @@ impl TestSession for Session<DummyIo> {
                     domain: "foobar.org".to_string(),
                     flags: 123,
                     dsn_info: "envelope1".to_string().into(),
+                    relay: None,
                 },
                 vec![
                     SessionAddress {
@@ -255,6 +256,7 @@ impl TestSession for Session<DummyIo> {
                         domain: "foobar.org".to_string(),
                         flags: 1,
                         dsn_info: None,
+                        relay: None,
                     },
                     SessionAddress {
                         address: "b@test.net".to_string(),
@@ -262,6 +264,7 @@ impl TestSession for Session<DummyIo> {
                         domain: "test.net".to_string(),
                         flags: 2,
                         dsn_info: None,
+                        relay: None,
                     },
                     SessionAddress {
                         address: "c@foobar.org".to_string(),
@@ -269,6 +272,7 @@ impl TestSession for Session<DummyIo> {
                         domain: "foobar.org".to_string(),
                         flags: 3,
                         dsn_info: None,
+                        relay: None,
                     },
                     SessionAddress {
                         address: "d@test.net".to_string(),
@@ -276,6 +280,15 @@ impl TestSession for Session<DummyIo> {
                         domain: "test.net".to_string(),
                         flags: 4,
                         dsn_info: None,
+                        relay: None,
+                    },
+                    SessionAddress {
+                        address: "e@foobar.org".to_string(),
+                        address_lcase: "e@foobar.org".to_string(),
+                        domain: "foobar.org".to_string(),
+                        flags: 5,
+                        dsn_info: None,
+                        relay: Some("mx.relay.org".to_string()),
                     },
                 ],
             )
@@ -284,12 +297,22 @@ impl TestSession for Session<DummyIo> {
             message
                 .domains
                 .iter()
-                .map(|d| d.domain.clone())
+                .map(|d| (d.domain.as_str(), d.relay.as_deref()))
                 .collect::<Vec<_>>(),
-            vec!["foobar.org".to_string(), "test.net".to_string()]
+            vec![
+                ("foobar.org", None),
+                ("foobar.org", Some("mx.relay.org")),
+                ("test.net", None)
+            ]
         );
-        let rcpts = ["a@foobar.org", "b@test.net", "c@foobar.org", "d@test.net"];
-        let domain_idx = [0, 1, 0, 1];
+        let rcpts = [
+            "a@foobar.org",
+            "b@test.net",
+            "c@foobar.org",
+            "d@test.net",
+            "e@foobar.org",
+        ];
+        let domain_idx = [0, 2, 0, 2, 1];
         for rcpt in &message.recipients {
             let idx = (rcpt.flags - 1) as usize;
             assert_eq!(rcpts[idx], rcpt.address);
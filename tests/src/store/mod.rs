@@ -62,6 +62,494 @@ pub async fn store_tests() {
     temp_dir.delete();
 }
 
+#[cfg(feature = "foundationdb")]
+#[tokio::test]
+pub async fn store_refresh_interval() {
+    let temp_dir = TempDir::new("store_refresh_interval", true);
+    let config_file = format!(
+        concat!(
+            "store.blob.type = \"local\"\n",
+            "store.blob.local.path = \"{}\"\n",
+            "store.db.refresh-interval = \"300ms\"\n"
+        ),
+        temp_dir.path.display(),
+    );
+    let db = Store::open(&Config::new(&config_file).unwrap())
+        .await
+        .unwrap();
+
+    // The transaction is kept until it is older than the configured interval
+    let mut trx = db.read_transaction().await.unwrap();
+    let read_version = trx.trx.get_read_version().await.unwrap();
+    tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+    trx.refresh_if_old().await.unwrap();
+    assert_eq!(trx.trx.get_read_version().await.unwrap(), read_version);
+
+    tokio::time::sleep(std::time::Duration::from_millis(300)).await;
+    trx.refresh_if_old().await.unwrap();
+    assert!(trx.trx.get_read_version().await.unwrap() > read_version);
+
+    temp_dir.delete();
+}
+
+#[cfg(feature = "foundationdb")]
+#[tokio::test]
+pub async fn store_serializable_reads() {
+    use store::{
+        write::{BatchBuilder, F_CLEAR, F_VALUE},
+        Serialize, ValueKey,
+    };
+
+    let temp_dir = TempDir::new("store_serializable_reads", true);
+    let config_file = format!(
+        concat!(
+            "store.blob.type = \"local\"\n",
+            "store.blob.local.path = \"{}\"\n",
+        ),
+        temp_dir.path.display(),
+    );
+    let db = Store::open(&Config::new(&config_file).unwrap())
+        .await
+        .unwrap();
+    let key = ValueKey {
+        account_id: 0,
+        collection: 0,
+        document_id: 0,
+        family: 0,
+        field: 0,
+    };
+    let set_value = |value: &'static str| {
+        let mut batch = BatchBuilder::new();
+        batch
+            .with_account_id(0)
+            .with_collection(0u8)
+            .update_document(0)
+            .value(0u8, value, F_VALUE);
+        batch.build()
+    };
+
+    for is_snapshot in [true, false] {
+        db.write(set_value("read")).await.unwrap();
+        let trx = db.read_transaction().await.unwrap();
+        let trx = if is_snapshot { trx } else { trx.serializable() };
+        assert_eq!(
+            trx.get_value::<String>(key).await.unwrap().as_deref(),
+            Some("read")
+        );
+
+        // Modify the value after it was read, committing a write based on
+        // the stale value only fails when the read registered a conflict range
+        db.write(set_value("modified")).await.unwrap();
+        trx.trx
+            .set(&key.serialize(), "updated".serialize().as_slice());
+        assert_eq!(
+            trx.trx.commit().await.is_err(),
+            !is_snapshot,
+            "snapshot: {is_snapshot}"
+        );
+    }
+
+    let mut batch = BatchBuilder::new();
+    batch
+        .with_account_id(0)
+        .with_collection(0u8)
+        .update_document(0)
+        .value(0u8, (), F_VALUE | F_CLEAR);
+    db.write(batch.build()).await.unwrap();
+    temp_dir.delete();
+}
+
+#[cfg(feature = "foundationdb")]
+#[tokio::test]
+pub async fn store_purge_checksummed_bitmaps() {
+    use store::{
+        write::{BatchBuilder, F_CLEAR},
+        BitmapKey, Serialize, BM_TAG, TAG_STATIC,
+    };
+
+    let temp_dir = TempDir::new("store_purge_checksummed_bitmaps", true);
+    let config_file = format!(
+        concat!(
+            "store.blob.type = \"local\"\n",
+            "store.blob.local.path = \"{}\"\n",
+            "store.db.bitmap.checksum = true\n",
+        ),
+        temp_dir.path.display(),
+    );
+    let db = Store::open(&Config::new(&config_file).unwrap())
+        .await
+        .unwrap();
+    db.destroy().await;
+    let key = |field: u8| {
+        BitmapKey {
+            account_id: 0,
+            collection: 0,
+            family: BM_TAG | TAG_STATIC,
+            field,
+            block_num: 0,
+            key: Vec::<u8>::new(),
+        }
+        .serialize()
+    };
+
+    // Set a bit on two bitmaps, then clear it on the first one
+    let mut batch = BatchBuilder::new();
+    batch
+        .with_account_id(0)
+        .with_collection(0u8)
+        .update_document(5)
+        .bitmap(0u8, (), 0)
+        .update_document(7)
+        .bitmap(1u8, (), 0);
+    db.write(batch.build()).await.unwrap();
+    let mut batch = BatchBuilder::new();
+    batch
+        .with_account_id(0)
+        .with_collection(0u8)
+        .update_document(5)
+        .bitmap(0u8, (), F_CLEAR);
+    db.write(batch.build()).await.unwrap();
+
+    // The empty block is stored along with its checksum
+    let trx = db.read_transaction().await.unwrap();
+    let empty = trx.trx.get(&key(0), false).await.unwrap().unwrap();
+    let (block, checksum) = empty.split_at(empty.len() - 4);
+    assert!(block.iter().all(|byte| *byte == 0));
+    assert!(checksum.iter().any(|byte| *byte != 0));
+    drop(trx);
+
+    // Only the empty block is purged
+    db.purge_bitmaps().await.unwrap();
+    let trx = db.read_transaction().await.unwrap();
+    assert!(trx.trx.get(&key(0), false).await.unwrap().is_none());
+    assert!(trx.trx.get(&key(1), false).await.unwrap().is_some());
+    drop(trx);
+
+    db.destroy().await;
+    temp_dir.delete();
+}
+
+#[cfg(feature = "foundationdb")]
+#[tokio::test]
+pub async fn store_repair_bitmap() {
+    use store::roaring::RoaringBitmap;
+    use store::{
+        write::{BatchBuilder, F_INDEX},
+        BitmapKey, Serialize,
+    };
+
+    let temp_dir = TempDir::new("store_repair_bitmap", true);
+    let config_file = format!(
+        concat!(
+            "store.blob.type = \"local\"\n",
+            "store.blob.local.path = \"{}\"\n",
+        ),
+        temp_dir.path.display(),
+    );
+    let db = Store::open(&Config::new(&config_file).unwrap())
+        .await
+        .unwrap();
+    db.destroy().await;
+
+    // Index more documents than are read in a single transaction, plus a few
+    // entries on the last field number
+    let document_ids = (0..12_000).collect::<RoaringBitmap>();
+    for chunk in (0..12_000u32).collect::<Vec<_>>().chunks(1_000) {
+        let mut batch = BatchBuilder::new();
+        batch.with_account_id(0).with_collection(0u8);
+        for document_id in chunk {
+            batch
+                .create_document(*document_id)
+                .value(0u8, *document_id, F_INDEX);
+        }
+        db.write(batch.build()).await.unwrap();
+    }
+    let mut batch = BatchBuilder::new();
+    batch.with_account_id(0).with_collection(0u8);
+    for document_id in 0..5 {
+        batch
+            .update_document(document_id)
+            .value(u8::MAX - 1, document_id, F_INDEX);
+    }
+    db.write(batch.build()).await.unwrap();
+    assert_eq!(
+        db.document_ids_from_index(0, 0u8, 0u8).await.unwrap(),
+        document_ids
+    );
+    assert_eq!(
+        db.document_ids_from_index(0, 0u8, u8::MAX - 1)
+            .await
+            .unwrap(),
+        (0..5).collect::<RoaringBitmap>()
+    );
+    assert!(db
+        .document_ids_from_index(0, 0u8, u8::MAX)
+        .await
+        .unwrap()
+        .is_empty());
+    assert_eq!(db.verify_document_ids(0, 0u8, 0u8).await.unwrap(), 0);
+
+    // Corrupt the first block of the document ids bitmap
+    let trx = db.read_transaction().await.unwrap();
+    trx.trx
+        .set(&BitmapKey::document_ids(0, 0u8).serialize(), &[0u8; 128]);
+    trx.trx.commit().await.unwrap();
+    assert_eq!(db.verify_document_ids(0, 0u8, 0u8).await.unwrap(), 1024);
+
+    // The block is rebuilt from the index, repairing again is a no-op
+    assert_eq!(
+        db.repair_bitmap(BitmapKey::document_ids(0, 0u8), &document_ids)
+            .await
+            .unwrap(),
+        1
+    );
+    assert_eq!(db.verify_document_ids(0, 0u8, 0u8).await.unwrap(), 0);
+    assert_eq!(
+        db.repair_bitmap(BitmapKey::document_ids(0, 0u8), &document_ids)
+            .await
+            .unwrap(),
+        0
+    );
+
+    db.destroy().await;
+    temp_dir.delete();
+}
+
+#[cfg(feature = "foundationdb")]
+#[tokio::test]
+pub async fn store_chunked_values() {
+    use store::{
+        write::{BatchBuilder, F_CLEAR, F_VALUE},
+        Serialize, ValueKey,
+    };
+
+    let temp_dir = TempDir::new("store_chunked_values", true);
+    let config_file = format!(
+        concat!(
+            "store.blob.type = \"local\"\n",
+            "store.blob.local.path = \"{}\"\n",
+            "store.db.value-chunk-size = 1024\n",
+        ),
+        temp_dir.path.display(),
+    );
+    let db = Store::open(&Config::new(&config_file).unwrap())
+        .await
+        .unwrap();
+    db.destroy().await;
+    let key = |document_id: u32| (&ValueKey::new(0, 0u8, document_id, 0u8)).serialize();
+    let chunk_key = |document_id: u32, chunk_num: u8| {
+        let mut key = key(document_id);
+        key.push(chunk_num);
+        key
+    };
+    let value = |len: usize| {
+        (0..len)
+            .map(|n| char::from(b'a' + (n % 26) as u8))
+            .collect::<String>()
+    };
+
+    // Values up to the chunk size are stored as is, larger ones are split
+    let mut batch = BatchBuilder::new();
+    batch.with_account_id(0).with_collection(0u8);
+    for (document_id, len) in [(0, 500), (1, 1024), (2, 1025), (3, 3000)] {
+        batch
+            .create_document(document_id)
+            .value(0u8, value(len), F_VALUE);
+    }
+    db.write(batch.build()).await.unwrap();
+    for (document_id, len) in [(0, 500), (1, 1024), (2, 1025), (3, 3000)] {
+        assert_eq!(
+            db.get_value::<String>(ValueKey::new(0, 0u8, document_id, 0u8))
+                .await
+                .unwrap(),
+            Some(value(len)),
+            "document {document_id}"
+        );
+    }
+    let trx = db.read_transaction().await.unwrap();
+    assert_eq!(
+        trx.trx.get(&key(3), false).await.unwrap().unwrap().len(),
+        1024
+    );
+    assert!(trx
+        .trx
+        .get(&chunk_key(1, 0), false)
+        .await
+        .unwrap()
+        .is_none());
+    for chunk_num in 0..=2 {
+        assert!(trx
+            .trx
+            .get(&chunk_key(3, chunk_num), false)
+            .await
+            .unwrap()
+            .is_some());
+    }
+    drop(trx);
+
+    // Overwriting or clearing a chunked value removes its chunks
+    let mut batch = BatchBuilder::new();
+    batch
+        .with_account_id(0)
+        .with_collection(0u8)
+        .update_document(2)
+        .value(0u8, value(10), F_VALUE)
+        .update_document(3)
+        .value(0u8, value(3000), F_VALUE | F_CLEAR);
+    db.write(batch.build()).await.unwrap();
+    assert_eq!(
+        db.get_value::<String>(ValueKey::new(0, 0u8, 2, 0u8))
+            .await
+            .unwrap(),
+        Some(value(10))
+    );
+    assert_eq!(
+        db.get_value::<String>(ValueKey::new(0, 0u8, 3, 0u8))
+            .await
+            .unwrap(),
+        None
+    );
+    let trx = db.read_transaction().await.unwrap();
+    for document_id in [2, 3] {
+        for chunk_num in 0..=2 {
+            assert!(trx
+                .trx
+                .get(&chunk_key(document_id, chunk_num), false)
+                .await
+                .unwrap()
+                .is_none());
+        }
+    }
+    drop(trx);
+
+    // Missing or truncated chunks are reported as corrupted
+    let mut batch = BatchBuilder::new();
+    batch
+        .with_account_id(0)
+        .with_collection(0u8)
+        .update_document(2)
+        .value(0u8, value(3000), F_VALUE)
+        .update_document(3)
+        .value(0u8, value(3000), F_VALUE);
+    db.write(batch.build()).await.unwrap();
+    let trx = db.read_transaction().await.unwrap();
+    trx.trx.clear(&chunk_key(2, 1));
+    trx.trx.set(&chunk_key(3, 2), b"truncated");
+    trx.trx.commit().await.unwrap();
+    for document_id in [2, 3] {
+        assert!(
+            db.get_value::<String>(ValueKey::new(0, 0u8, document_id, 0u8))
+                .await
+                .is_err(),
+            "document {document_id}"
+        );
+    }
+
+    // Values that don't fit in the maximum number of chunks are rejected
+    let mut batch = BatchBuilder::new();
+    batch
+        .with_account_id(0)
+        .with_collection(0u8)
+        .update_document(0)
+        .value(0u8, value(1024 * 256), F_VALUE);
+    assert!(db.write(batch.build()).await.is_err());
+    assert_eq!(
+        db.get_value::<String>(ValueKey::new(0, 0u8, 0, 0u8))
+            .await
+            .unwrap(),
+        Some(value(500))
+    );
+
+    db.destroy().await;
+    temp_dir.delete();
+}
+
+#[cfg(feature = "foundationdb")]
+#[tokio::test]
+pub async fn store_bitmaps_intersection() {
+    use nlp::language::Language;
+    use store::roaring::RoaringBitmap;
+    use store::{
+        query::Filter,
+        write::{BatchBuilder, F_BITMAP},
+    };
+
+    let temp_dir = TempDir::new("store_bitmaps_intersection", true);
+    let config_file = format!(
+        concat!(
+            "store.blob.type = \"local\"\n",
+            "store.blob.local.path = \"{}\"\n",
+            "store.db.bitmap.checksum = true\n",
+        ),
+        temp_dir.path.display(),
+    );
+    let db = Store::open(&Config::new(&config_file).unwrap())
+        .await
+        .unwrap();
+    db.destroy().await;
+
+    // Two broad terms stored over many blocks and a few selective ones
+    let broad = (0..20_000)
+        .filter(|id| id % 3 != 0)
+        .collect::<RoaringBitmap>();
+    let even = (0..20_000)
+        .filter(|id| id % 2 == 0)
+        .collect::<RoaringBitmap>();
+    let near = [2_050u32, 2_051, 2_100, 2_500]
+        .into_iter()
+        .collect::<RoaringBitmap>();
+    let far = [10u32, 1_500, 9_000, 19_998, 19_999]
+        .into_iter()
+        .collect::<RoaringBitmap>();
+    for chunk in (0..20_000u32).collect::<Vec<_>>().chunks(1_000) {
+        let mut batch = BatchBuilder::new();
+        batch.with_account_id(0).with_collection(0u8);
+        for document_id in chunk {
+            let text = [
+                ("broad", &broad),
+                ("even", &even),
+                ("near", &near),
+                ("far", &far),
+            ]
+            .into_iter()
+            .filter(|(_, bitmap)| bitmap.contains(*document_id))
+            .map(|(word, _)| word)
+            .collect::<Vec<_>>()
+            .join(" ");
+            batch.create_document(*document_id);
+            if !text.is_empty() {
+                batch.value(0u8, text, F_BITMAP);
+            }
+        }
+        db.write(batch.build()).await.unwrap();
+    }
+
+    // Intersections are read through the block span of the running result,
+    // which must not drop documents from any of the bitmaps
+    for (text, expected) in [
+        ("broad even", &broad & &even),
+        ("broad near", &broad & &near),
+        ("broad far", &broad & &far),
+        ("even far", &even & &far),
+        ("broad even far", &(&broad & &even) & &far),
+        ("broad even near", &(&broad & &even) & &near),
+        ("near far", RoaringBitmap::new()),
+        ("broad missing", RoaringBitmap::new()),
+    ] {
+        assert_eq!(
+            db.filter(0, 0u8, vec![Filter::has_text(0u8, text, Language::None)])
+                .await
+                .unwrap()
+                .results,
+            expected,
+            "failed for {text:?}"
+        );
+    }
+
+    db.destroy().await;
+    temp_dir.delete();
+}
+
 pub fn deflate_artwork_data() -> Vec<u8> {
     let mut csv_path = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR"));
     csv_path.push("resources");
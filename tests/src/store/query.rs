@@ -28,13 +28,18 @@ use std::{
 
 use jmap_proto::types::keyword::Keyword;
 use nlp::language::Language;
-use store::{ahash::AHashMap, query::sort::Pagination};
+use store::{
+    ahash::AHashMap,
+    query::{sort::Pagination, Operator},
+    roaring::RoaringBitmap,
+    Serialize,
+};
 
 use store::{
     fts::builder::FtsIndexBuilder,
     query::{Comparator, Filter},
     write::{BatchBuilder, F_BITMAP, F_INDEX, F_VALUE},
-    Store, ValueKey,
+    BitmapKey, Store, ValueKey,
 };
 
 use crate::store::deflate_artwork_data;
@@ -213,7 +218,16 @@ pub async fn test(db: Arc<Store>, do_insert: bool) {
     test_filter(db.clone()).await;
 
     println!("Running sort tests...");
-    test_sort(db).await;
+    test_sort(db.clone()).await;
+
+    println!("Running get_values tests...");
+    test_get_values(db.clone()).await;
+
+    println!("Running bitmap count tests...");
+    test_bitmap_count(db.clone()).await;
+
+    println!("Running limited range tests...");
+    test_range_ids(db).await;
 }
 
 pub async fn test_filter(db: Arc<Store>) {
@@ -454,3 +468,130 @@ pub async fn test_sort(db: Arc<Store>) {
         );
     }
 }
+
+pub async fn test_get_values(db: Arc<Store>) {
+    let field = FIELDS
+        .iter()
+        .position(|field| *field == "accession_number")
+        .unwrap() as u8;
+
+    // Keys are requested in descending order with missing documents in between
+    let keys = (0..100u32)
+        .rev()
+        .map(|document_id| ValueKey {
+            account_id: 0,
+            collection: COLLECTION_ID,
+            document_id: if document_id % 10 == 0 {
+                u32::MAX - document_id
+            } else {
+                document_id
+            },
+            family: 0,
+            field,
+        })
+        .collect::<Vec<_>>();
+
+    let mut expected_values = Vec::with_capacity(keys.len());
+    for key in &keys {
+        let value = db.get_value::<String>(*key).await.unwrap();
+        assert_eq!(db.exists(*key).await.unwrap(), value.is_some());
+        expected_values.push(value);
+    }
+    let values = db.get_values::<String>(keys).await.unwrap();
+
+    assert_eq!(values.len(), 100);
+    assert_eq!(values.iter().filter(|value| value.is_none()).count(), 10);
+    assert_eq!(values, expected_values);
+}
+
+pub async fn test_bitmap_count(db: Arc<Store>) {
+    let field = FIELDS.iter().position(|field| *field == "artist").unwrap() as u8;
+
+    let document_ids = BitmapKey::document_ids(0, COLLECTION_ID);
+    let mut keys = vec![BitmapKey {
+        account_id: document_ids.account_id,
+        collection: document_ids.collection,
+        family: document_ids.family,
+        field: document_ids.field,
+        block_num: document_ids.block_num,
+        key: document_ids.key.to_vec(),
+    }];
+    for word in [
+        "turner",
+        "joseph",
+        "mallord",
+        "william",
+        "blake",
+        "nonexistentartist",
+    ] {
+        keys.push(BitmapKey::hash(word, 0, COLLECTION_ID, 0, field));
+    }
+
+    for key in keys {
+        let expected_count = db
+            .get_bitmap(key.clone())
+            .await
+            .unwrap()
+            .map_or(0, |bitmap| bitmap.len());
+        assert_eq!(
+            db.get_bitmap_count(key).await.unwrap(),
+            expected_count,
+            "count mismatch"
+        );
+    }
+}
+
+pub async fn test_range_ids(db: Arc<Store>) {
+    let field = FIELDS.iter().position(|field| *field == "year").unwrap() as u8;
+
+    for (op, value) in [
+        (Operator::GreaterEqualThan, 2000u32),
+        (Operator::LowerThan, 1800u32),
+        (Operator::Equal, 1979u32),
+    ] {
+        // Full range in index order, matching the range filter
+        let all_ids = db
+            .range_to_ids(
+                0,
+                COLLECTION_ID,
+                field,
+                value.serialize(),
+                op,
+                usize::MAX,
+                false,
+            )
+            .await
+            .unwrap();
+        assert!(all_ids.len() > 10, "{op:?} {value}: {}", all_ids.len());
+        assert_eq!(
+            all_ids.iter().copied().collect::<RoaringBitmap>(),
+            db.filter(0, COLLECTION_ID, vec![Filter::cond(field, op, value)])
+                .await
+                .unwrap()
+                .results
+        );
+
+        // Limited reads return the first entries in ascending or descending order
+        for limit in [1, 5, 10] {
+            assert_eq!(
+                db.range_to_ids(0, COLLECTION_ID, field, value.serialize(), op, limit, false)
+                    .await
+                    .unwrap(),
+                all_ids[..limit],
+                "{op:?} {value} ascending limit {limit}"
+            );
+            assert_eq!(
+                db.range_to_ids(0, COLLECTION_ID, field, value.serialize(), op, limit, true)
+                    .await
+                    .unwrap(),
+                all_ids
+                    .iter()
+                    .rev()
+                    .take(limit)
+                    .copied()
+                    .collect::<Vec<_>>(),
+                "{op:?} {value} descending limit {limit}"
+            );
+        }
+    }
+}